@@ -0,0 +1,74 @@
+use std::fmt;
+use gfx;
+
+// Which adapter --gpu asks for: its place in the list or part of its name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdapterChoice {
+    Index(usize),
+    // matched case-insensitively anywhere in the adapter's name
+    Name(String),
+}
+
+impl AdapterChoice {
+    pub fn parse(s: &str) -> AdapterChoice {
+        match s.parse::<usize>() {
+            Ok(index) => AdapterChoice::Index(index),
+            Err(_) => AdapterChoice::Name(s.to_lowercase()),
+        }
+    }
+    fn matches(&self, adapter: &AdapterSummary) -> bool {
+        match *self {
+            AdapterChoice::Index(index) => adapter.index == index,
+            AdapterChoice::Name(ref name) => adapter.name.to_lowercase().contains(name.as_str()),
+        }
+    }
+}
+
+// What the backend tells about an adapter, kept once the adapters themselves are gone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterSummary {
+    pub index: usize,
+    pub name: String,
+    pub vendor: usize,
+    pub device: usize,
+    pub software: bool,
+}
+
+impl AdapterSummary {
+    pub fn from_info(index: usize, info: &gfx::AdapterInfo) -> Self {
+        AdapterSummary {
+            index,
+            name: info.name.clone(),
+            vendor: info.vendor,
+            device: info.device,
+            software: info.software_rendering,
+        }
+    }
+    pub fn kind(&self) -> &'static str {
+        if self.software { "software" } else { "hardware" }
+    }
+}
+
+impl fmt::Display for AdapterSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} ({}, {:04x}:{:04x})", self.index, self.name, self.kind(), self.vendor, self.device)
+    }
+}
+
+// The order to try opening adapters in: the chosen one first, then the rest with
+// hardware before software, each as the backend listed them.
+pub fn adapter_order(adapters: &[AdapterSummary], choice: Option<&AdapterChoice>) -> Vec<usize> {
+    let chosen = choice.and_then(|choice| {
+        let found = adapters.iter().find(|adapter| choice.matches(adapter)).map(|adapter| adapter.index);
+        if found.is_none() {
+            warn!("no adapter matches {:?}; picking one instead", choice);
+        }
+        found
+    });
+    let mut order: Vec<usize> = chosen.into_iter().collect();
+    let mut rest: Vec<&AdapterSummary> = adapters.iter().filter(|adapter| Some(adapter.index) != chosen).collect();
+    // stable, so the backend's order holds within each kind
+    rest.sort_by_key(|adapter| adapter.software);
+    order.extend(rest.into_iter().map(|adapter| adapter.index));
+    order
+}
@@ -0,0 +1,403 @@
+use std::sync::Arc;
+use fnv::FnvHashMap as HashMap;
+use rusqlite::Connection;
+use cgmath::{Matrix4, SquareMatrix};
+
+use models::*;
+use locomotion::LocomotionClips;
+
+// largest per-element difference of a pose matrix still treated as "on the line"
+const REDUCTION_EPSILON: f32 = 1e-4;
+
+// Per-joint samples of one object's animation, shared by all its instances.
+pub struct AnimationClip {
+    // indexed by joint index, samples ordered by time
+    pub tracks: Vec<Vec<(f32, Animation)>>,
+    // length in seconds
+    pub duration: f32,
+    // whether sample times are seconds rather than an evenly spaced ordering
+    pub timestamped: bool,
+}
+
+impl AnimationClip {
+    pub fn sample_count(&self) -> usize {
+        self.tracks.iter().map(|v| v.len()).max().unwrap_or(0)
+    }
+    // Indices of the samples around t and the blend factor between them.
+    pub fn sample_span(&self, v: &[(f32, Animation)], t: f32) -> (usize, usize, f32) {
+        let length = v.len();
+        if !self.timestamped {
+            let sample_per_second = length as f32 / self.duration;
+            let t = t * sample_per_second;
+            let index_1 = (t.floor() as usize).min(length - 1);
+            let index_2 = if index_1 + 1 == length { 0 } else { index_1 + 1 };
+            return (index_1, index_2, t - index_1 as f32);
+        }
+        // last sample at or before t; before the first one we wrap from the last
+        let index_1 = match v.iter().rposition(|s| s.0 <= t) {
+            Some(i) => i,
+            None => length - 1,
+        };
+        let index_2 = if index_1 + 1 == length { 0 } else { index_1 + 1 };
+        let (t_1, mut t_2) = (v[index_1].0, v[index_2].0);
+        let mut t = t;
+        if index_2 <= index_1 {
+            t_2 += self.duration;
+            if t < t_1 {
+                t += self.duration;
+            }
+        }
+        let span = t_2 - t_1;
+        let blend_factor = if span > 0.0 { ((t - t_1) / span).max(0.0).min(1.0) } else { 0.0 };
+        (index_1, index_2, blend_factor)
+    }
+    // Where the last sample sits, so a clip played once can hold on its final pose
+    // instead of blending back to the first.
+    pub fn end_time(&self) -> f32 {
+        if self.timestamped {
+            return self.tracks.iter().filter_map(|v| v.last()).map(|s| s.0).fold(0.0, f32::max);
+        }
+        match self.sample_count() {
+            0 => 0.0,
+            count => self.duration * (count - 1) as f32 / count as f32,
+        }
+    }
+    // Interpolated pose of a joint at t seconds into the clip; None without samples.
+    pub fn pose_at(&self, joint_index: usize, t: f32) -> Option<Matrix4<f32>> {
+        match self.tracks.get(joint_index) {
+            Some(v) if v.len() > 0 => {
+                let (index_1, index_2, blend_factor) = self.sample_span(v, t);
+                let pose_1: Matrix4<f32> = v[index_1].1.pose;
+                let pose_2: Matrix4<f32> = v[index_2].1.pose;
+                Some(pose_1 + (pose_2 - pose_1) * blend_factor)
+            },
+            _ => None,
+        }
+    }
+    fn sample_total(&self) -> usize {
+        self.tracks.iter().map(|v| v.len()).sum()
+    }
+    // Drops samples that linear interpolation between their neighbours reproduces.
+    // Evenly spaced clips get explicit times first, since dropping samples breaks the spacing.
+    pub fn reduce_keyframes(&mut self) {
+        if !self.timestamped {
+            for v in &mut self.tracks {
+                let step = self.duration / v.len().max(1) as f32;
+                for (i, sample) in v.iter_mut().enumerate() {
+                    sample.0 = i as f32 * step;
+                    sample.1.time = sample.0;
+                }
+            }
+            self.timestamped = true;
+        }
+        for v in &mut self.tracks {
+            reduce_track(v);
+        }
+    }
+}
+
+fn reduce_track(v: &mut Vec<(f32, Animation)>) {
+    if v.len() < 3 {
+        return;
+    }
+    let mut keep = vec!(true; v.len());
+    let mut last_kept = 0;
+    for i in 1..v.len() - 1 {
+        let (t_0, t_1, t_2) = (v[last_kept].0, v[i].0, v[i + 1].0);
+        let span = t_2 - t_0;
+        let f = if span > 0.0 { (t_1 - t_0) / span } else { 0.0 };
+        let lerp = v[last_kept].1.pose + (v[i + 1].1.pose - v[last_kept].1.pose) * f;
+        if max_difference(&lerp, &v[i].1.pose) < REDUCTION_EPSILON {
+            keep[i] = false;
+        } else {
+            last_kept = i;
+        }
+    }
+    let mut i = 0;
+    v.retain(|_| {
+        i += 1;
+        keep[i - 1]
+    });
+}
+
+fn max_difference(a: &Matrix4<f32>, b: &Matrix4<f32>) -> f32 {
+    let a: &[[f32; 4]; 4] = a.as_ref();
+    let b: &[[f32; 4]; 4] = b.as_ref();
+    let mut max = 0.0f32;
+    for c in 0..4 {
+        for r in 0..4 {
+            max = max.max((a[c][r] - b[c][r]).abs());
+        }
+    }
+    max
+}
+
+// Plays clips authored for one skeleton on another, pairing joints by name.
+// Poses are taken relative to the source's bind pose and put on the target's,
+// so bones that rest differently still move the same way from where they rest.
+pub struct Retarget {
+    pub source_object: i32,
+    // by target joint index, the source joint index and the matrix taking its poses over
+    joints: Vec<Option<(usize, Matrix4<f32>)>>,
+}
+
+impl Retarget {
+    // names are by joint index; joints without a name, or with one the other skeleton
+    // lacks, keep their bind pose.
+    pub fn new(
+        source_object: i32,
+        source: &[Joint],
+        source_names: &HashMap<i32, String>,
+        target: &[Joint],
+        target_names: &HashMap<i32, String>,
+    ) -> Self {
+        let by_name: HashMap<&str, &Joint> = source.iter()
+            .filter_map(|j| source_names.get(&j.joint_index).map(|name| (name.as_str(), j)))
+            .collect();
+        let size = target.iter().map(|j| j.joint_index as usize + 1).max().unwrap_or(0);
+        let mut joints = vec!(None; size);
+        for j in target {
+            let matched = target_names.get(&j.joint_index).and_then(|name| by_name.get(name.as_str()));
+            if let Some(s) = matched {
+                // a source bind that cannot be inverted is taken as is
+                let compensation = s.bind.invert().map(|inverse| j.bind * inverse).unwrap_or_else(Matrix4::identity);
+                joints[j.joint_index as usize] = Some((s.joint_index as usize, compensation));
+            }
+        }
+        Retarget {
+            source_object,
+            joints,
+        }
+    }
+    pub fn matched(&self) -> usize {
+        self.joints.iter().filter(|j| j.is_some()).count()
+    }
+    pub fn apply(&self, clip: AnimationClip) -> AnimationClip {
+        let tracks = self.joints.iter().enumerate().map(|(joint_index, joint)| match *joint {
+            Some((source_index, compensation)) => match clip.tracks.get(source_index) {
+                Some(v) => v.iter().map(|&(t, ref sample)| (t, Animation {
+                    joint_index: joint_index as i32,
+                    time: sample.time,
+                    pose: compensation * sample.pose,
+                })).collect(),
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        }).collect();
+        AnimationClip {
+            tracks,
+            ..clip
+        }
+    }
+}
+
+// With retarget, the clip is read from its source object and fitted to this one.
+fn load_clip(conn: &Connection, object_id: i32, name: Option<&str>, default_duration: f32, retarget: Option<&Retarget>) -> RusqliteResult<AnimationClip> {
+    let source_id = retarget.map_or(object_id, |r| r.source_object);
+    let duration = query_animation_duration(conn, &source_id, name)?.and_then(|d| if d > 0.0 { Some(d) } else { None });
+    let mut clip = AnimationClip {
+        tracks: query_animation(conn, &source_id, name)?,
+        duration: duration.unwrap_or(default_duration),
+        timestamped: duration.is_some(),
+    };
+    if let Some(retarget) = retarget {
+        clip = retarget.apply(clip);
+    }
+    let before = clip.sample_total();
+    clip.reduce_keyframes();
+    if before > 0 {
+        debug!("object {} {}: kept {} of {} animation samples", object_id, name.unwrap_or("*"), clip.sample_total(), before);
+    }
+    Ok(clip)
+}
+
+// A clip played over the pose below it on part of the skeleton, like a head turn or
+// breathing. It adds how far each joint has moved from the clip's first sample, so
+// it fits whatever the base clip or locomotion blend is doing.
+pub struct AdditiveClip {
+    clip: AnimationClip,
+    // by joint index, the first sample inverted; None for joints without samples
+    reference: Vec<Option<Matrix4<f32>>>,
+    // by joint index, whether the layer moves the joint; None moves every joint
+    mask: Option<Vec<bool>>,
+    // what a body starts playing it at
+    pub weight: f32,
+}
+
+impl AdditiveClip {
+    pub fn new(clip: AnimationClip, joints: &[Joint], root_joint: Option<i32>, weight: f32) -> Self {
+        let reference = clip.tracks.iter()
+            .map(|v| v.first().and_then(|s| s.1.pose.invert()))
+            .collect();
+        AdditiveClip {
+            clip,
+            reference,
+            mask: root_joint.map(|root| subtree_mask(joints, root)),
+            weight,
+        }
+    }
+    // The change to a joint's local pose at a world time, looping the clip.
+    fn delta(&self, joint_index: usize, time: f64) -> Option<Matrix4<f32>> {
+        if let Some(ref mask) = self.mask {
+            if mask.get(joint_index) != Some(&true) {
+                return None;
+            }
+        }
+        let reference = match self.reference.get(joint_index) {
+            Some(&Some(reference)) => reference,
+            _ => return None,
+        };
+        let duration = self.clip.duration as f64;
+        let t = time % duration;
+        let t = (if t < 0.0 { t + duration } else { t }) as f32;
+        self.clip.pose_at(joint_index, t).map(|pose| reference * pose)
+    }
+}
+
+// By joint index, whether the joint is root or one of its descendants.
+fn subtree_mask(joints: &[Joint], root: i32) -> Vec<bool> {
+    let parents: HashMap<i32, i32> = joints.iter().map(|j| (j.joint_index, j.parent)).collect();
+    let size = joints.iter().map(|j| j.joint_index as usize + 1).max().unwrap_or(0);
+    let mut mask = vec![false; size];
+    for joint in joints {
+        let mut index = joint.joint_index;
+        // bounded, so a cycle cannot hang it
+        for _ in 0..joints.len() {
+            if index == root {
+                mask[joint.joint_index as usize] = true;
+                break;
+            }
+            match parents.get(&index) {
+                Some(&parent) => index = parent,
+                None => break,
+            }
+        }
+    }
+    mask
+}
+
+// An additive clip as one body plays it. The weight can change while it plays,
+// fading an overlay like aiming in and out.
+#[derive(Clone)]
+pub struct AdditiveLayer {
+    pub clip: Arc<AdditiveClip>,
+    pub weight: f32,
+}
+
+impl AdditiveLayer {
+    pub fn new(clip: Arc<AdditiveClip>) -> Self {
+        let weight = clip.weight;
+        AdditiveLayer { clip, weight }
+    }
+    // What to multiply a joint's local pose by, scaled by the weight;
+    // None when the layer leaves the joint alone.
+    pub fn delta(&self, joint_index: usize, time: f64) -> Option<Matrix4<f32>> {
+        if self.weight == 0.0 {
+            return None;
+        }
+        self.clip.delta(joint_index, time).map(|delta| {
+            let identity = Matrix4::identity();
+            identity + (delta - identity) * self.weight
+        })
+    }
+}
+
+// Clips keyed by ObjectId, loaded once and shared between instances.
+// A retargeted object's clips are kept under its own id, already fitted to its skeleton.
+pub struct AnimationStore {
+    clips: HashMap<i32, Arc<AnimationClip>>,
+    // None for objects without idle and walk clips
+    locomotion: HashMap<i32, Option<Arc<LocomotionClips>>>,
+    // None for objects without a death clip
+    deaths: HashMap<i32, Option<Arc<AnimationClip>>>,
+    // empty for objects without AnimationLayer rows
+    layers: HashMap<i32, Vec<Arc<AdditiveClip>>>,
+}
+
+impl AnimationStore {
+    pub fn new() -> Self {
+        AnimationStore {
+            clips: HashMap::default(),
+            locomotion: HashMap::default(),
+            deaths: HashMap::default(),
+            layers: HashMap::default(),
+        }
+    }
+    pub fn get_or_load(&mut self, conn: &Connection, object_id: i32, default_duration: f32, retarget: Option<&Retarget>) -> RusqliteResult<Arc<AnimationClip>> {
+        if let Some(clip) = self.clips.get(&object_id) {
+            return Ok(clip.clone());
+        }
+        let clip = Arc::new(load_clip(conn, object_id, None, default_duration, retarget)?);
+        self.clips.insert(object_id, clip.clone());
+        Ok(clip)
+    }
+    // The idle, walk and optional run clips, from Animation rows of those names.
+    pub fn get_or_load_locomotion(&mut self, conn: &Connection, object_id: i32, default_duration: f32, retarget: Option<&Retarget>) -> RusqliteResult<Option<Arc<LocomotionClips>>> {
+        if let Some(clips) = self.locomotion.get(&object_id) {
+            return Ok(clips.clone());
+        }
+        let idle = load_clip(conn, object_id, Some("idle"), default_duration, retarget)?;
+        let walk = load_clip(conn, object_id, Some("walk"), default_duration, retarget)?;
+        let run = load_clip(conn, object_id, Some("run"), default_duration, retarget)?;
+        let clips = if idle.sample_count() > 0 && walk.sample_count() > 0 {
+            Some(Arc::new(LocomotionClips {
+                idle,
+                walk,
+                run: if run.sample_count() > 0 { Some(run) } else { None },
+            }))
+        } else {
+            None
+        };
+        self.locomotion.insert(object_id, clips.clone());
+        Ok(clips)
+    }
+    // The clip played once when the object dies, from Animation rows named death.
+    pub fn get_or_load_death(&mut self, conn: &Connection, object_id: i32, default_duration: f32, retarget: Option<&Retarget>) -> RusqliteResult<Option<Arc<AnimationClip>>> {
+        if let Some(clip) = self.deaths.get(&object_id) {
+            return Ok(clip.clone());
+        }
+        let clip = load_clip(conn, object_id, Some("death"), default_duration, retarget)?;
+        let clip = if clip.sample_count() > 0 { Some(Arc::new(clip)) } else { None };
+        self.deaths.insert(object_id, clip.clone());
+        Ok(clip)
+    }
+    // The additive clips listed in AnimationLayer, each from the Animation rows of its name.
+    // joints are the object's own, which root joints are looked up in.
+    pub fn get_or_load_layers(&mut self, conn: &Connection, object_id: i32, default_duration: f32, retarget: Option<&Retarget>, joints: &[Joint]) -> RusqliteResult<Vec<Arc<AdditiveClip>>> {
+        if let Some(layers) = self.layers.get(&object_id) {
+            return Ok(layers.clone());
+        }
+        let mut layers = Vec::new();
+        for (name, root_joint, weight) in query_animation_layers(conn, &object_id)? {
+            let clip = load_clip(conn, object_id, Some(&name), default_duration, retarget)?;
+            if clip.sample_count() == 0 {
+                warn!("object {}: layer clip {} has no samples", object_id, name);
+                continue;
+            }
+            layers.push(Arc::new(AdditiveClip::new(clip, joints, root_joint, weight)));
+        }
+        self.layers.insert(object_id, layers.clone());
+        Ok(layers)
+    }
+    // Read again on the next load, as after the object's rows changed.
+    // Objects holding the old clips keep them.
+    pub fn forget(&mut self, object_id: i32) {
+        self.clips.remove(&object_id);
+        self.locomotion.remove(&object_id);
+        self.deaths.remove(&object_id);
+        self.layers.remove(&object_id);
+    }
+    // Forgets clips no GameObject holds any more.
+    pub fn collect_unused(&mut self) {
+        self.clips.retain(|_, clip| Arc::strong_count(clip) > 1);
+        self.locomotion.retain(|_, clips| match *clips {
+            Some(ref clips) => Arc::strong_count(clips) > 1,
+            None => false,
+        });
+        self.deaths.retain(|_, clip| match *clip {
+            Some(ref clip) => Arc::strong_count(clip) > 1,
+            None => false,
+        });
+        self.layers.retain(|_, layers| layers.iter().any(|clip| Arc::strong_count(clip) > 1));
+    }
+}
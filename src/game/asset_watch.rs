@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use fnv::FnvHashMap as HashMap;
+
+// how often the database's modification time is read
+const POLL_SECONDS: u64 = 1;
+
+// Notices an outside tool writing the database while the game runs, by polling the file's
+// modification time; SQLite tells nothing across processes. The game writes it too, for
+// settings and saves, so a change only says which objects to compare, by their fingerprints.
+pub struct AssetWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+    // by ObjectId, query_asset_fingerprint as of the last load
+    fingerprints: HashMap<i32, String>,
+}
+
+impl AssetWatch {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified_time(&path);
+        AssetWatch {
+            path,
+            modified,
+            last_poll: Instant::now(),
+            fingerprints: HashMap::default(),
+        }
+    }
+    // True once for each change seen, looking at most every POLL_SECONDS.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < Duration::from_secs(POLL_SECONDS) {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+    pub fn remember(&mut self, object_id: i32, fingerprint: String) {
+        self.fingerprints.insert(object_id, fingerprint);
+    }
+    // Whether the fingerprint differs from the one remembered, which it replaces.
+    // An object not seen before only has its fingerprint remembered.
+    pub fn changed(&mut self, object_id: i32, fingerprint: String) -> bool {
+        match self.fingerprints.insert(object_id, fingerprint.clone()) {
+            Some(last) => last != fingerprint,
+            None => false,
+        }
+    }
+}
+
+// The later of the database's and its write-ahead log's, since in WAL mode
+// a write only reaches the database itself at a checkpoint.
+fn modified_time(path: &PathBuf) -> Option<SystemTime> {
+    let mut wal = path.clone().into_os_string();
+    wal.push("-wal");
+    let paths = [path.clone(), PathBuf::from(wal)];
+    paths.iter()
+        .filter_map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .max()
+}
@@ -0,0 +1,82 @@
+use cgmath::{Matrix, Matrix3, Matrix4, Vector3};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Up {
+    Y,
+    Z,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+// A coordinate convention: which axis points up and which way the third axis turns.
+// x always points right.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Axes {
+    pub up: Up,
+    pub handedness: Handedness,
+}
+
+// What the World works in. The ground, the navigation grid and click-to-move all
+// take x and y as the ground plane, and cgmath's look_at and perspective are right handed.
+pub const WORLD: Axes = Axes { up: Up::Z, handedness: Handedness::Right };
+
+impl Default for Axes {
+    fn default() -> Axes {
+        WORLD
+    }
+}
+
+impl Axes {
+    // As in "z-up" or "y-up-left"; handedness defaults to right.
+    pub fn from_name(name: &str) -> Option<Axes> {
+        let (up, handedness) = match name {
+            "z-up" | "z-up-right" => (Up::Z, Handedness::Right),
+            "z-up-left" => (Up::Z, Handedness::Left),
+            "y-up" | "y-up-right" => (Up::Y, Handedness::Right),
+            "y-up-left" => (Up::Y, Handedness::Left),
+            _ => return None,
+        };
+        Some(Axes { up, handedness })
+    }
+    pub fn right(&self) -> Vector3<f32> {
+        Vector3::new(1.0, 0.0, 0.0)
+    }
+    pub fn up(&self) -> Vector3<f32> {
+        match self.up {
+            Up::Y => Vector3::new(0.0, 1.0, 0.0),
+            Up::Z => Vector3::new(0.0, 0.0, 1.0),
+        }
+    }
+    // Toward a viewer looking ahead; with right and up it spans the ground plane.
+    pub fn back(&self) -> Vector3<f32> {
+        let back = self.right().cross(self.up());
+        match self.handedness {
+            Handedness::Right => back,
+            Handedness::Left => -back,
+        }
+    }
+    // Columns are right, up and back, so it maps a screen-like layout with x right,
+    // y up and z toward the viewer into this convention.
+    pub fn basis(&self) -> Matrix3<f32> {
+        Matrix3::from_cols(self.right(), self.up(), self.back())
+    }
+    pub fn layout_plane(&self) -> Matrix4<f32> {
+        Matrix4::from(self.basis())
+    }
+    // Takes positions and normals authored in this convention into other.
+    // Both bases are signed permutations, so the inverse is the transpose.
+    pub fn to(&self, other: Axes) -> Matrix3<f32> {
+        other.basis() * self.basis().transpose()
+    }
+    pub fn to_world(&self) -> Matrix3<f32> {
+        self.to(WORLD)
+    }
+    // A mirror between the two turns front faces into back faces.
+    pub fn flips_winding(&self, other: Axes) -> bool {
+        self.handedness != other.handedness
+    }
+}
@@ -0,0 +1,130 @@
+use std::f32::consts::PI;
+use fnv::FnvHashMap as HashMap;
+use cgmath::{InnerSpace, Point3, Vector3};
+
+// within this distance a wander goal counts as reached
+const ARRIVAL_RADIUS: f32 = 0.05;
+// seconds a wanderer rests at each goal, at least and at most
+const REST_MIN: f32 = 1.0;
+const REST_MAX: f32 = 4.0;
+const FOLLOW_DISTANCE: f32 = 1.5;
+
+// Decides where an NPC walks, one fixed step at a time.
+// positions holds every object by key as of the latest snapshot, the NPC's own included,
+// and speed is in units per second. None stands still.
+pub trait Behavior {
+    fn step(&mut self, position: Point3<f32>, positions: &HashMap<i32, Point3<f32>>, speed: f32, dt: f32) -> Option<Vector3<f32>>;
+}
+
+// The behaviors the console can hand out; see build.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BehaviorKind {
+    Idle,
+    Wander { radius: f32 },
+    // distance defaults to FOLLOW_DISTANCE
+    Follow { target: i32, distance: Option<f32> },
+}
+
+impl BehaviorKind {
+    // home is where a wanderer stays around; seed varies its walk from other wanderers'.
+    pub fn build(&self, home: Point3<f32>, seed: u32) -> Box<Behavior> {
+        match *self {
+            BehaviorKind::Idle => Box::new(Idle),
+            BehaviorKind::Wander { radius } => Box::new(Wander::new(home, radius, seed)),
+            BehaviorKind::Follow { target, distance } => Box::new(Follow {
+                target,
+                distance: distance.unwrap_or(FOLLOW_DISTANCE),
+            }),
+        }
+    }
+}
+
+pub struct Idle;
+
+impl Behavior for Idle {
+    fn step(&mut self, _: Point3<f32>, _: &HashMap<i32, Point3<f32>>, _: f32, _: f32) -> Option<Vector3<f32>> {
+        None
+    }
+}
+
+// Walks to random points within radius of home, resting a while at each.
+pub struct Wander {
+    home: Point3<f32>,
+    radius: f32,
+    goal: Option<Point3<f32>>,
+    // seconds left before walking on
+    rest: f32,
+    random: u32,
+}
+
+impl Wander {
+    pub fn new(home: Point3<f32>, radius: f32, seed: u32) -> Self {
+        Wander {
+            home,
+            radius: radius.max(0.0),
+            goal: None,
+            rest: 0.0,
+            // xorshift sticks at zero
+            random: seed.wrapping_mul(0x9e37_79b9) | 1,
+        }
+    }
+    // In 0..1.
+    fn next_random(&mut self) -> f32 {
+        let mut x = self.random;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random = x;
+        (x >> 8) as f32 / (1u32 << 24) as f32
+    }
+    // Evenly spread over the disc around home.
+    fn pick_goal(&mut self, z: f32) -> Point3<f32> {
+        let angle = 2.0 * PI * self.next_random();
+        let distance = self.radius * self.next_random().sqrt();
+        Point3::new(self.home.x + distance * angle.cos(), self.home.y + distance * angle.sin(), z)
+    }
+}
+
+impl Behavior for Wander {
+    fn step(&mut self, position: Point3<f32>, _: &HashMap<i32, Point3<f32>>, speed: f32, dt: f32) -> Option<Vector3<f32>> {
+        if self.rest > 0.0 {
+            self.rest -= dt;
+            return None;
+        }
+        let goal = match self.goal {
+            Some(goal) => goal,
+            None => {
+                let goal = self.pick_goal(position.z);
+                self.goal = Some(goal);
+                goal
+            },
+        };
+        let to_goal = Vector3::new(goal.x - position.x, goal.y - position.y, 0.0);
+        let distance = to_goal.magnitude();
+        let reach = speed * dt;
+        if distance <= reach.max(ARRIVAL_RADIUS) {
+            self.goal = None;
+            self.rest = REST_MIN + (REST_MAX - REST_MIN) * self.next_random();
+            return if distance > 0.0 { Some(to_goal) } else { None };
+        }
+        Some(to_goal * (reach / distance))
+    }
+}
+
+// Keeps within distance of another object; stands still while it is gone.
+pub struct Follow {
+    target: i32,
+    distance: f32,
+}
+
+impl Behavior for Follow {
+    fn step(&mut self, position: Point3<f32>, positions: &HashMap<i32, Point3<f32>>, speed: f32, dt: f32) -> Option<Vector3<f32>> {
+        let target = positions.get(&self.target)?;
+        let to_target = Vector3::new(target.x - position.x, target.y - position.y, 0.0);
+        let distance = to_target.magnitude();
+        if distance <= self.distance {
+            return None;
+        }
+        Some(to_target * ((speed * dt).min(distance - self.distance) / distance))
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::time::Instant;
+use cgmath::{Matrix4, One, Point3, Rad, Vector3};
+
+use animation::AnimationClip;
+use color::WHITE;
+use markup::TextRun;
+use models::{query_asset_summaries, Animation, Joint};
+use sim::{AnimationClock, Body};
+use validation::{joint_order, ROOT_PARENT};
+use {load_font, load_locale, open_connection, query_mesh, text_quads, Skinning, Vertex, DEFAULT_LANGUAGE};
+
+// Each case runs for about this long after warming up, and at least MIN_RUNS times.
+const BENCH_SECONDS: f64 = 1.0;
+const WARMUP_RUNS: usize = 10;
+const MIN_RUNS: usize = 10;
+
+const SKELETON_SIZES: [usize; 3] = [64, 128, 256];
+// samples per joint in the generated clip, as in a second of a 30 fps export
+const CLIP_SAMPLES: usize = 30;
+const TEXT_LENGTHS: [usize; 2] = [1_000, 10_000];
+
+// Times the hot paths a refactor is likely to touch and prints a line per case,
+// so runs before and after a change can be compared. Run from where the game
+// runs, since the text cases need its fonts and the mesh case its database.
+pub fn run_benchmarks() {
+    for &joints in SKELETON_SIZES.iter() {
+        bench_skinning(joints);
+    }
+    bench_text();
+    bench_mesh_loading();
+}
+
+fn measure<F: FnMut()>(name: &str, mut f: F) {
+    for _ in 0..WARMUP_RUNS {
+        f();
+    }
+    let start = Instant::now();
+    let mut runs = 0;
+    let mut seconds = 0.0;
+    while runs < MIN_RUNS || seconds < BENCH_SECONDS {
+        f();
+        runs += 1;
+        let elapsed = start.elapsed();
+        seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+    }
+    println!("{:<36} {:>12.1} us  ({} runs)", name, seconds / runs as f64 * 1e6, runs);
+}
+
+// A chain of joints, each bent a little further along the clip, so every joint
+// is animated and depends on the one before.
+fn chain_body(count: usize) -> Body {
+    let identity: Matrix4<f32> = One::one();
+    let step = Matrix4::from_translation(Vector3::new(0.0, 0.0, 0.1));
+    let joints: Vec<Joint> = (0..count).map(|i| Joint {
+        joint_index: i as i32,
+        global: identity,
+        bind: step,
+        parent: if i == 0 { ROOT_PARENT } else { i as i32 - 1 },
+        inverse: identity,
+    }).collect();
+    let tracks: Vec<Vec<(f32, Animation)>> = (0..count).map(|i| (0..CLIP_SAMPLES).map(|s| {
+        let time = s as f32 / CLIP_SAMPLES as f32;
+        (time, Animation {
+            joint_index: i as i32,
+            time,
+            pose: step * Matrix4::from_angle_x(Rad(time * 0.2)),
+        })
+    }).collect()).collect();
+    let order = joint_order(&joints).expect("a chain has no cycles");
+    Body {
+        position: Point3::new(0.0, 0.0, 0.0),
+        joints: Arc::new(joints),
+        joint_order: Arc::new(order),
+        clip: Arc::new(AnimationClip {
+            tracks,
+            duration: 1.0,
+            timestamped: false,
+        }),
+        speed: 1.0,
+        phase: 0.0,
+        phase_time: 0.0,
+        locomotion: None,
+        once_from: None,
+        layers: Vec::new(),
+    }
+}
+
+fn bench_skinning(joints: usize) {
+    let body = chain_body(joints);
+    let mut local = Vec::new();
+    let mut palette: Vec<Skinning> = Vec::new();
+    // a new time every run, so no two samples land on the same pose
+    let mut time = 0.0;
+    measure(&format!("skinning {} joints", joints), || {
+        time += 1.0 / 60.0;
+        body.skinning_into(AnimationClock::Time(time), &mut local, &mut palette);
+    });
+}
+
+fn bench_text() {
+    let font = match load_font(&load_locale(DEFAULT_LANGUAGE)) {
+        Ok(font) => font,
+        Err(e) => {
+            println!("skipping text: failed to load the font: {:?}", e);
+            return;
+        },
+    };
+    let line = "The quick brown fox jumps over the lazy dog 0123456789.\n";
+    let mut vertex_data: Vec<Vertex> = Vec::new();
+    let mut index_data = Vec::new();
+    for &length in TEXT_LENGTHS.iter() {
+        let text: String = line.chars().cycle().take(length).collect();
+        let runs = [TextRun::plain(&text)];
+        measure(&format!("text quads {} chars", length), || {
+            text_quads(&font, &runs, [0.0, 0.0], WHITE.into(), 1.0, &[], 0.0, &mut vertex_data, &mut index_data);
+        });
+    }
+}
+
+fn bench_mesh_loading() {
+    let conn = open_connection();
+    let ids: Vec<i32> = match query_asset_summaries(&conn) {
+        Ok(summaries) => summaries.into_iter()
+            .filter(|summary| summary.meshes > 0)
+            .map(|summary| summary.object_id)
+            .collect(),
+        Err(e) => {
+            println!("skipping mesh loading: failed to list the objects: {}", e);
+            return;
+        },
+    };
+    if ids.is_empty() {
+        println!("skipping mesh loading: the database has no meshes");
+        return;
+    }
+    measure(&format!("mesh loading {} objects", ids.len()), || {
+        for id in &ids {
+            if let Err(e) = query_mesh(&conn, id) {
+                println!("failed to load the meshes of object {}: {}", id, e);
+            }
+        }
+    });
+}
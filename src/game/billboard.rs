@@ -0,0 +1,55 @@
+use std;
+use cgmath::Point3;
+
+use models::Image;
+use color::Color;
+
+pub struct Billboard {
+    pub position: Point3<f32>,
+    // world-space width and height
+    pub size: [f32; 2],
+    pub color: Color,
+    // on-screen height is clamped to this range in pixels
+    pub min_pixels: f32,
+    pub max_pixels: f32,
+}
+
+impl Billboard {
+    pub fn new(position: Point3<f32>, size: [f32; 2], color: Color) -> Self {
+        Billboard {
+            position,
+            size,
+            color,
+            min_pixels: 8.0,
+            max_pixels: 64.0,
+        }
+    }
+    pub fn with_pixel_range(self, min_pixels: f32, max_pixels: f32) -> Self {
+        Billboard {
+            min_pixels,
+            max_pixels,
+            ..self
+        }
+    }
+}
+
+// A white disc with a soft edge, tinted per billboard.
+pub fn marker_image<T>(size: u16) -> Image<T> {
+    let mut data = Vec::with_capacity(size as usize * size as usize * 4);
+    let radius = size as f32 / 2.0;
+    for y in 0..size {
+        for x in 0..size {
+            let dx = x as f32 + 0.5 - radius;
+            let dy = y as f32 + 0.5 - radius;
+            let d = (dx * dx + dy * dy).sqrt();
+            let alpha = (radius - d).max(0.0).min(1.0);
+            data.extend_from_slice(&[255, 255, 255, (alpha * 255.0) as u8]);
+        }
+    }
+    Image {
+        data,
+        width: size,
+        height: size,
+        format: std::marker::PhantomData::<T>
+    }
+}
@@ -0,0 +1,64 @@
+use cgmath::{EuclideanSpace, Point3, Vector3};
+
+// default spacing of recorded keys, in seconds
+pub const PATH_KEY_INTERVAL: f32 = 2.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraKey {
+    // seconds from the start of the path
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub target: Point3<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PathPlayback {
+    pub keys: Vec<CameraKey>,
+    // world time of the first frame, set once playback advances
+    pub started: Option<f64>,
+}
+
+impl PathPlayback {
+    pub fn new(keys: Vec<CameraKey>) -> Self {
+        PathPlayback {
+            keys,
+            started: None,
+        }
+    }
+    // Position and target at world time now, or None once the path has finished.
+    pub fn advance(&mut self, now: f64) -> Option<(Point3<f32>, Point3<f32>)> {
+        let started = *self.started.get_or_insert(now);
+        sample(&self.keys, (now - started) as f32)
+    }
+}
+
+fn catmull_rom(p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>, p3: Vector3<f32>, u: f32) -> Vector3<f32> {
+    let u2 = u * u;
+    let u3 = u2 * u;
+    (p1 * 2.0
+        + (p2 - p0) * u
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * u2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * u3) * 0.5
+}
+
+// Keys must be sorted by time; the end points are repeated to close the spline.
+pub fn sample(keys: &[CameraKey], t: f32) -> Option<(Point3<f32>, Point3<f32>)> {
+    let last = match keys.last() {
+        Some(k) => k,
+        None => return None,
+    };
+    if t > last.time {
+        return None;
+    }
+    if keys.len() == 1 || t <= keys[0].time {
+        return Some((keys[0].position, keys[0].target));
+    }
+    let i = keys.iter().rposition(|k| k.time <= t).unwrap_or(0).min(keys.len() - 2);
+    let at = |j: isize| keys[j.max(0).min(keys.len() as isize - 1) as usize];
+    let (k0, k1, k2, k3) = (at(i as isize - 1), at(i as isize), at(i as isize + 1), at(i as isize + 2));
+    let span = k2.time - k1.time;
+    let u = if span > 0.0 { (t - k1.time) / span } else { 0.0 };
+    let position = catmull_rom(k0.position.to_vec(), k1.position.to_vec(), k2.position.to_vec(), k3.position.to_vec(), u);
+    let target = catmull_rom(k0.target.to_vec(), k1.target.to_vec(), k2.target.to_vec(), k3.target.to_vec(), u);
+    Some((Point3::from_vec(position), Point3::from_vec(target)))
+}
@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use picking::Ray;
+
+// within this distance a waypoint counts as reached
+const ARRIVAL_RADIUS: f32 = 0.05;
+
+// Where a ray meets the ground, z = ground.
+// None when the ray runs parallel to the ground or meets it behind its origin.
+// There is no terrain yet, so the ground is flat.
+pub fn ground_point(ray: &Ray, ground: f32) -> Option<Point3<f32>> {
+    if ray.direction.z.abs() < 1e-6 {
+        return None;
+    }
+    let t = (ground - ray.origin.z) / ray.direction.z;
+    if t < 0.0 {
+        return None;
+    }
+    Some(ray.at(t))
+}
+
+// Waypoints the avator walks to one after another at a steady speed.
+// The plan keeps its own idea of where the avator is, since the body's position
+// comes back from the update thread a frame or more after a move is sent.
+pub struct MovePlan {
+    waypoints: VecDeque<Point3<f32>>,
+    position: Option<Point3<f32>>,
+    // units per second
+    pub speed: f32,
+}
+
+impl MovePlan {
+    pub fn new(speed: f32) -> Self {
+        MovePlan {
+            waypoints: VecDeque::new(),
+            position: None,
+            speed,
+        }
+    }
+    // Drops what was planned and walks through points from position.
+    pub fn go_to(&mut self, position: Point3<f32>, points: Vec<Point3<f32>>) {
+        self.waypoints.clear();
+        self.position = Some(position);
+        self.queue(position, points);
+    }
+    // Adds points after the waypoints already planned; position is used when there are none.
+    pub fn queue(&mut self, position: Point3<f32>, points: Vec<Point3<f32>>) {
+        if self.position.is_none() {
+            self.position = Some(position);
+        }
+        self.waypoints.extend(points);
+    }
+    // Where the avator will be once the plan is walked, if anything is planned.
+    pub fn end(&self) -> Option<Point3<f32>> {
+        self.waypoints.back().cloned()
+    }
+    // The avator's expected position followed by the waypoints still ahead.
+    pub fn path(&self) -> Vec<Point3<f32>> {
+        self.position.into_iter().chain(self.waypoints.iter().cloned()).collect()
+    }
+    pub fn clear(&mut self) {
+        self.waypoints.clear();
+        self.position = None;
+    }
+    // The move for this step toward the current waypoint, on the avator's own height.
+    // A waypoint reached within the step is dropped and the rest of the step is not carried over.
+    pub fn step(&mut self, dt: f32) -> Option<Vector3<f32>> {
+        let position = self.position?;
+        let target = match self.waypoints.front() {
+            Some(target) => Point3::new(target.x, target.y, position.z),
+            None => {
+                self.position = None;
+                return None;
+            },
+        };
+        let to_target = target - position;
+        let distance = to_target.magnitude();
+        let reach = self.speed * dt;
+        let v = if distance <= reach.max(ARRIVAL_RADIUS) {
+            self.waypoints.pop_front();
+            to_target
+        } else {
+            to_target * (reach / distance)
+        };
+        self.position = Some(position + v);
+        if self.waypoints.is_empty() {
+            self.position = None;
+        }
+        Some(v)
+    }
+}
@@ -0,0 +1,74 @@
+// A color in linear RGB with straight alpha, as the shaders blend it. The surfaces are
+// Srgba8, so what the shaders write is encoded to sRGB on the way out. Values picked in
+// a paint program or written as hex are sRGB already and go through from_srgb first,
+// or they come out lighter than picked. The constants here are linear.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+pub const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+pub const TRANSPARENT: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+
+// The UI's palette.
+// body text, and the plain lines of the console and log
+pub const TEXT: Color = Color { r: 0.8, g: 0.8, b: 0.8, a: 1.0 };
+pub const TITLE: Color = Color { r: 0.9, g: 0.9, b: 0.9, a: 1.0 };
+// notices that want a look: the interaction hint, an active trigger
+pub const HIGHLIGHT: Color = Color { r: 1.0, g: 0.9, b: 0.3, a: 1.0 };
+// the console's prompt mark
+pub const PROMPT: Color = Color { r: 0.9, g: 0.9, b: 0.3, a: 1.0 };
+// what a character says
+pub const SPEECH: Color = Color { r: 1.0, g: 0.9, b: 0.6, a: 1.0 };
+pub const ERROR: Color = Color { r: 1.0, g: 0.35, b: 0.3, a: 1.0 };
+pub const WARNING: Color = Color { r: 1.0, g: 0.8, b: 0.3, a: 1.0 };
+pub const MUTED: Color = Color { r: 0.55, g: 0.6, b: 0.7, a: 1.0 };
+// panels under menus and browsers, given an alpha of their own
+pub const PANEL: Color = Color { r: 0.02, g: 0.02, b: 0.04, a: 1.0 };
+// selected text in an input
+pub const SELECTION: Color = Color { r: 0.2, g: 0.3, b: 0.6, a: 0.9 };
+// the local player, on the map and in markers
+pub const PLAYER: Color = Color { r: 1.0, g: 0.8, b: 0.2, a: 1.0 };
+pub const REMOTE_PLAYER: Color = Color { r: 0.3, g: 0.6, b: 1.0, a: 1.0 };
+pub const OTHERS: Color = Color { r: 0.9, g: 0.9, b: 0.9, a: 1.0 };
+// the hull drawn around the selected object
+pub const SELECTED_OUTLINE: Color = Color { r: 1.0, g: 0.6, b: 0.1, a: 1.0 };
+
+impl Color {
+    pub fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color { r, g, b, a }
+    }
+    pub fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Color::rgba(r, g, b, 1.0)
+    }
+    // Alpha is linear either way.
+    pub fn from_srgb(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color::rgba(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+    pub fn from_srgb8(rgba: [u8; 4]) -> Self {
+        let channel = |v: u8| v as f32 / 255.0;
+        Color::from_srgb(channel(rgba[0]), channel(rgba[1]), channel(rgba[2]), channel(rgba[3]))
+    }
+    pub fn with_alpha(self, a: f32) -> Self {
+        Color { a, ..self }
+    }
+    // Blended in linear space, as the GPU blends.
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Color::rgba(mix(self.r, other.r), mix(self.g, other.g), mix(self.b, other.b), mix(self.a, other.a))
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(c: Color) -> [f32; 4] {
+        [c.r, c.g, c.b, c.a]
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
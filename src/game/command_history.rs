@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+use std::collections::vec_deque::Iter;
+
+// Which Invoker a recorded command went to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HistoryTarget {
+    Camera,
+    Observer,
+    Avator,
+}
+
+impl HistoryTarget {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            HistoryTarget::Camera => "camera",
+            HistoryTarget::Observer => "observer",
+            HistoryTarget::Avator => "avator",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<HistoryTarget> {
+        match name {
+            "camera" => Some(HistoryTarget::Camera),
+            "observer" => Some(HistoryTarget::Observer),
+            "avator" => Some(HistoryTarget::Avator),
+            _ => None,
+        }
+    }
+}
+
+// A command as it is kept in the database: a name and its numbers.
+pub(crate) trait Recordable: Sized {
+    // None for commands that make no sense run again, like those started by the clock
+    fn record(&self) -> Option<(&'static str, Vec<f32>)>;
+    fn replay(kind: &str, args: &[f32]) -> Option<Self>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    // animation time it ran at, in seconds
+    pub time: f64,
+    pub target: HistoryTarget,
+    pub kind: String,
+    pub args: Vec<f32>,
+}
+
+// The commands run so far, oldest first, keeping at most capacity of them.
+pub struct CommandHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl CommandHistory {
+    pub fn new(capacity: usize) -> Self {
+        CommandHistory {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+    pub(crate) fn record<C: Recordable>(&mut self, time: f64, target: HistoryTarget, command: &C) {
+        if let Some((kind, args)) = command.record() {
+            self.push(HistoryEntry { time, target, kind: kind.to_string(), args });
+        }
+    }
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.push_back(entry);
+        self.trim();
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    // Drops the oldest entries past the new capacity.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn iter(&self) -> Iter<HistoryEntry> {
+        self.entries.iter()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    fn trim(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use rusqlite::{Connection, SQLITE_OPEN_NO_MUTEX, SQLITE_OPEN_READ_ONLY};
+use rusqlite::Result as RusqliteResult;
+
+// connections kept open once handed back; more are opened while these are out
+const MAX_IDLE: usize = 4;
+
+// Read-only connections to the asset database for whatever thread loads meshes and
+// textures. A Connection is used by one thread at a time, so each borrower gets its own,
+// and in WAL mode they read alongside the game's writes without waiting. Clones share
+// the idle connections.
+#[derive(Clone)]
+pub struct ConnectionPool {
+    path: PathBuf,
+    idle: Arc<Mutex<Vec<Connection>>>,
+}
+
+impl ConnectionPool {
+    pub fn new(path: PathBuf) -> Self {
+        ConnectionPool {
+            path,
+            idle: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    // An idle connection, or a new one when all are out.
+    pub fn get(&self) -> RusqliteResult<PooledConnection> {
+        let idle = self.idle.lock().ok().and_then(|mut idle| idle.pop());
+        let conn = match idle {
+            Some(conn) => conn,
+            None => Connection::open_with_flags(&self.path, SQLITE_OPEN_READ_ONLY | SQLITE_OPEN_NO_MUTEX)?,
+        };
+        Ok(PooledConnection {
+            conn: Some(conn),
+            idle: self.idle.clone(),
+        })
+    }
+}
+
+// Goes back to the pool when dropped.
+pub struct PooledConnection {
+    conn: Option<Connection>,
+    idle: Arc<Mutex<Vec<Connection>>>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("pooled connection used after release")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let (Some(conn), Ok(mut idle)) = (self.conn.take(), self.idle.lock()) {
+            if idle.len() < MAX_IDLE {
+                idle.push(conn);
+            }
+        }
+    }
+}
+
+// Readers then never block the writer nor it them. The mode is kept in the file,
+// so this only has to reach it once.
+pub fn enable_wal(conn: &Connection) -> RusqliteResult<()> {
+    let mode = conn.query_row("PRAGMA journal_mode = WAL", &[], |r| r.get::<i32, String>(0))?;
+    if !mode.eq_ignore_ascii_case("wal") {
+        warn!("the database stays in {} mode; loading waits on writes", mode);
+    }
+    Ok(())
+}
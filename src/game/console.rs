@@ -0,0 +1,574 @@
+use cgmath::{
+    Point3,
+    Vector3,
+};
+
+use text_input::*;
+use input::*;
+use viewport::ViewportLayout;
+use debug_draw::DebugCategory;
+use fog::FogMode;
+use graphics_settings::LightingSpace;
+use color::Color;
+use scene_transition::TransitionKind;
+use sprite::SpriteSheet;
+use text_effect::TextEffect;
+use behavior::BehaviorKind;
+use spawn_palette::{SpawnTemplate, PALETTE_SLOTS};
+use picking::PickMode;
+use log::LogLevelFilter;
+use logging::parse_level;
+use AvatorTarget;
+use {FADE_SECONDS, FLASH_SECONDS};
+use movement::MovementModel;
+use layer::LayerMask;
+use viewport::ViewCamera;
+
+const MAX_LINES: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleCommand {
+    Help,
+    // lists the adapters, marking the one in use
+    Gpus,
+    // how many commands the history holds and whether it replays
+    History,
+    HistorySize(usize),
+    // whether the next startup runs the camera history again
+    HistoryReplay(bool),
+    HistoryClear,
+    Movement,
+    SetMovement(MovementModel),
+    // object id, joint index and its name; None clears the name
+    JointName(i32, i32, Option<String>),
+    // object id and the object whose clips it plays; None plays its own
+    Retarget(i32, Option<i32>),
+    Clear,
+    CameraMove(Vector3<f32>),
+    CameraLookAt(Point3<f32>),
+    // degrees, clockwise as seen through the camera
+    CameraRoll(f32),
+    CameraUp(Vector3<f32>),
+    // near and far planes
+    CameraClip(f32, f32),
+    // false goes back to perspective
+    CameraOrtho(bool),
+    // orthographic, straight down at the target
+    CameraTop,
+    ObserverMove(Vector3<f32>),
+    ObserverLookAt(Point3<f32>),
+    Layout(ViewportLayout),
+    Spawn(i32, Point3<f32>),
+    Despawn(i32),
+    Damage(i32, f32),
+    Heal(i32, f32),
+    Npc(i32, BehaviorKind),
+    PaletteList,
+    // None clears the slot
+    PaletteSet(usize, Option<SpawnTemplate>),
+    PathKey(i32),
+    PathPlay(i32),
+    PathStop,
+    PathClear(i32),
+    DebugDraw(DebugCategory, bool),
+    AvatorMove(AvatorTarget, Vector3<f32>),
+    // object id, tag, and whether it is added or taken off
+    Tag(i32, String, bool),
+    AvatorSpeed(i32, f32),
+    // avator id, additive layer in LayerNo order and its weight
+    LayerWeight(i32, usize, f32),
+    Save(String),
+    PresetSave(String),
+    PresetLoad(String),
+    PresetList,
+    Language(String),
+    MigrateIndices(i32),
+    // None loads every live object again
+    Reload(Option<i32>),
+    // whether objects load again by themselves when their rows change
+    ReloadWatch(bool),
+    AddMarker(Point3<f32>),
+    ClearMarkers,
+    AvatorMarkers(bool),
+    TimeShow,
+    TimeSet(f32),
+    TimeFreeze(bool),
+    TimeCycle(f64),
+    // None clears the selection
+    Select(Option<i32>),
+    Pick(PickMode),
+    Lighting(LightingSpace),
+    // object, MeshId, shown
+    MeshVisible(i32, usize, bool),
+    Layers,
+    CameraLayer(ViewCamera, LayerMask, bool),
+    Fog(FogMode),
+    // None follows the sky color
+    FogColor(Option<Color>),
+    Scene(i32, TransitionKind),
+    // color, seconds
+    Flash(Color, f32),
+    // seconds; true fades out
+    Fade(bool, f32),
+    // [left, bottom, right, top] in pixels
+    SpriteScreen(SpriteSheet, [f32; 4]),
+    SpriteWorld(SpriteSheet, Point3<f32>),
+    SpriteClear,
+    // empty text clears the line
+    Say(Vec<TextEffect>, String),
+    // writes the next frame's draws to a file; None writes the default one
+    DumpFrame(Option<String>),
+    // switches between command output and the log
+    LogPage,
+    // None sets the default level
+    LogLevel(Option<String>, LogLevelFilter),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConsolePage {
+    Output,
+    Log,
+}
+
+pub struct Console {
+    pub visible: bool,
+    pub page: ConsolePage,
+    pub input: TextInput,
+    pub lines: Vec<String>,
+    history: Vec<String>,
+    history_index: Option<usize>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            visible: false,
+            page: ConsolePage::Output,
+            input: TextInput::new(),
+            lines: Vec::new(),
+            history: Vec::new(),
+            history_index: None,
+        }
+    }
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.input.clear();
+        self.history_index = None;
+    }
+    pub fn print<S: Into<String>>(&mut self, line: S) {
+        self.lines.push(line.into());
+        if self.lines.len() > MAX_LINES {
+            let overflow = self.lines.len() - MAX_LINES;
+            self.lines.drain(0..overflow);
+        }
+    }
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    // Returns the parsed command when a line is submitted.
+    pub fn handle_input(&mut self, ev: &InputEvent) -> Option<ConsoleCommand> {
+        // the toggle key also produces a character
+        if *ev == InputEvent::Character('`') {
+            return None;
+        }
+        // while composing, the arrows pick the input method's candidates
+        let composing = self.input.is_composing();
+        if ev.is_pressed(Key::Up) && !composing {
+            self.recall(-1);
+            return None;
+        }
+        if ev.is_pressed(Key::Down) && !composing {
+            self.recall(1);
+            return None;
+        }
+        match self.input.handle_input(ev) {
+            Some(TextInputEvent::Submit(line)) => {
+                self.history_index = None;
+                if line.trim().is_empty() {
+                    return None;
+                }
+                self.print(format!("> {}", line));
+                self.history.push(line.clone());
+                match parse(&line) {
+                    Ok(c) => Some(c),
+                    Err(e) => {
+                        self.print(e);
+                        None
+                    }
+                }
+            },
+            Some(TextInputEvent::Cancel) => {
+                self.toggle();
+                None
+            },
+            None => None,
+        }
+    }
+
+    fn recall(&mut self, step: isize) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() as isize - 1;
+        let index = match self.history_index {
+            Some(i) => i as isize + step,
+            None if step < 0 => last,
+            None => return,
+        };
+        if index < 0 {
+            return;
+        }
+        if index > last {
+            self.history_index = None;
+            self.input.clear();
+            return;
+        }
+        self.history_index = Some(index as usize);
+        let line = self.history[index as usize].clone();
+        self.input.set_text(&line);
+    }
+}
+
+pub fn parse(line: &str) -> Result<ConsoleCommand, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    // the text keeps its own spacing, so it is cut from the line rather than the words
+    if words.first() == Some(&"say") {
+        return parse_say(line);
+    }
+    match words.as_slice() {
+        &["help"] => Ok(ConsoleCommand::Help),
+        &["gpus"] => Ok(ConsoleCommand::Gpus),
+        &["history"] => Ok(ConsoleCommand::History),
+        &["history", "size", size] => size.parse::<usize>()
+            .map(ConsoleCommand::HistorySize)
+            .map_err(|_| format!("not a size: {}", size)),
+        &["history", "replay", "on"] => Ok(ConsoleCommand::HistoryReplay(true)),
+        &["history", "replay", "off"] => Ok(ConsoleCommand::HistoryReplay(false)),
+        &["history", "clear"] => Ok(ConsoleCommand::HistoryClear),
+        &["movement"] => Ok(ConsoleCommand::Movement),
+        &["movement", acceleration, friction, max_speed] => {
+            let value = format!("{} {} {}", acceleration, friction, max_speed);
+            MovementModel::from_setting(&value)
+                .map(ConsoleCommand::SetMovement)
+                .ok_or_else(|| format!("not a movement model: {}", value))
+        },
+        &["joint", "name", id, joint, "none"] => Ok(ConsoleCommand::JointName(parse_id(id)?, parse_id(joint)?, None)),
+        &["joint", "name", id, joint, name] => Ok(ConsoleCommand::JointName(parse_id(id)?, parse_id(joint)?, Some(name.to_string()))),
+        &["retarget", id, "none"] => parse_id(id).map(|id| ConsoleCommand::Retarget(id, None)),
+        &["retarget", id, source] => Ok(ConsoleCommand::Retarget(parse_id(id)?, Some(parse_id(source)?))),
+        &["clear"] => Ok(ConsoleCommand::Clear),
+        &["camera", "move", x, y, z] => parse_vector(x, y, z).map(ConsoleCommand::CameraMove),
+        &["camera", "lookat", x, y, z] => parse_vector(x, y, z).map(|v| ConsoleCommand::CameraLookAt(Point3::new(v.x, v.y, v.z))),
+        &["camera", "ortho"] => Ok(ConsoleCommand::CameraOrtho(true)),
+        &["camera", "perspective"] => Ok(ConsoleCommand::CameraOrtho(false)),
+        &["camera", "top"] => Ok(ConsoleCommand::CameraTop),
+        &["camera", "clip", near, far] => {
+            let (near, far) = (parse_number(near)?, parse_number(far)?);
+            if near <= 0.0 || far <= near {
+                return Err(format!("expected 0 < near < far: {} {}", near, far));
+            }
+            Ok(ConsoleCommand::CameraClip(near, far))
+        },
+        &["camera", "roll", degrees] => parse_number(degrees).map(ConsoleCommand::CameraRoll),
+        &["camera", "up", x, y, z] => {
+            let up = parse_vector(x, y, z)?;
+            if up == Vector3::new(0.0, 0.0, 0.0) {
+                return Err("up cannot be zero".to_string());
+            }
+            Ok(ConsoleCommand::CameraUp(up))
+        },
+        &["observer", "move", x, y, z] => parse_vector(x, y, z).map(ConsoleCommand::ObserverMove),
+        &["observer", "lookat", x, y, z] => parse_vector(x, y, z).map(|v| ConsoleCommand::ObserverLookAt(Point3::new(v.x, v.y, v.z))),
+        &["view", name] => ViewportLayout::from_name(name)
+            .map(ConsoleCommand::Layout)
+            .ok_or_else(|| format!("unknown layout: {}", name)),
+        &["debug", category, state] => {
+            let category = DebugCategory::from_name(category)
+                .ok_or_else(|| format!("unknown debug category: {}", category))?;
+            match state {
+                "on" => Ok(ConsoleCommand::DebugDraw(category, true)),
+                "off" => Ok(ConsoleCommand::DebugDraw(category, false)),
+                _ => Err(format!("expected on or off: {}", state)),
+            }
+        },
+        &["path", "key", id] => parse_id(id).map(ConsoleCommand::PathKey),
+        &["path", "play", id] => parse_id(id).map(ConsoleCommand::PathPlay),
+        &["path", "stop"] => Ok(ConsoleCommand::PathStop),
+        &["path", "clear", id] => parse_id(id).map(ConsoleCommand::PathClear),
+        &["spawn", id] => parse_id(id).map(|id| ConsoleCommand::Spawn(id, Point3::new(0.0, 0.0, 0.0))),
+        &["spawn", id, x, y, z] => {
+            let id = parse_id(id)?;
+            parse_vector(x, y, z).map(|v| ConsoleCommand::Spawn(id, Point3::new(v.x, v.y, v.z)))
+        },
+        &["despawn", id] => parse_id(id).map(ConsoleCommand::Despawn),
+        &["palette"] => Ok(ConsoleCommand::PaletteList),
+        &["palette", slot, "clear"] => parse_slot(slot).map(|slot| ConsoleCommand::PaletteSet(slot, None)),
+        &["palette", slot, id] => {
+            let (slot, object_id) = (parse_slot(slot)?, parse_id(id)?);
+            Ok(ConsoleCommand::PaletteSet(slot, Some(SpawnTemplate { object_id, scale: 1.0 })))
+        },
+        &["palette", slot, id, scale] => {
+            let (slot, object_id) = (parse_slot(slot)?, parse_id(id)?);
+            match parse_number(scale)? {
+                scale if scale > 0.0 => Ok(ConsoleCommand::PaletteSet(slot, Some(SpawnTemplate { object_id, scale }))),
+                _ => Err(format!("not a positive scale: {}", scale)),
+            }
+        },
+        &["damage", id, amount] => Ok(ConsoleCommand::Damage(parse_id(id)?, parse_amount(amount)?)),
+        &["heal", id, amount] => Ok(ConsoleCommand::Heal(parse_id(id)?, parse_amount(amount)?)),
+        &["npc", id, "idle"] => parse_id(id).map(|id| ConsoleCommand::Npc(id, BehaviorKind::Idle)),
+        &["npc", id, "wander", radius] => Ok(ConsoleCommand::Npc(parse_id(id)?, BehaviorKind::Wander { radius: parse_amount(radius)? })),
+        &["npc", id, "follow", target] => Ok(ConsoleCommand::Npc(parse_id(id)?, BehaviorKind::Follow { target: parse_id(target)?, distance: None })),
+        &["npc", id, "follow", target, distance] => {
+            let (id, target) = (parse_id(id)?, parse_id(target)?);
+            Ok(ConsoleCommand::Npc(id, BehaviorKind::Follow { target, distance: Some(parse_amount(distance)?) }))
+        },
+        &["avator", "move", x, y, z] => parse_vector(x, y, z).map(|v| ConsoleCommand::AvatorMove(AvatorTarget::One(1), v)),
+        &["avator", "move", "group", tag, x, y, z] => parse_vector(x, y, z).map(|v| ConsoleCommand::AvatorMove(AvatorTarget::Group(tag.to_string()), v)),
+        &["avator", "move", target, x, y, z] => {
+            let target = match target {
+                "all" => AvatorTarget::All,
+                "selected" => AvatorTarget::Selected,
+                id => AvatorTarget::One(parse_id(id)?),
+            };
+            Ok(ConsoleCommand::AvatorMove(target, parse_vector(x, y, z)?))
+        },
+        &["tag", id, tag] => Ok(ConsoleCommand::Tag(parse_id(id)?, tag.to_string(), true)),
+        &["untag", id, tag] => Ok(ConsoleCommand::Tag(parse_id(id)?, tag.to_string(), false)),
+        &["avator", "speed", id, speed] => {
+            let id = id.parse::<i32>().map_err(|_| format!("not an object id: {}", id))?;
+            let speed = speed.parse::<f32>().map_err(|_| format!("not a number: {}", speed))?;
+            Ok(ConsoleCommand::AvatorSpeed(id, speed))
+        },
+        &["avator", "layer", id, layer, weight] => {
+            let id = parse_id(id)?;
+            let layer = layer.parse::<usize>().map_err(|_| format!("not a layer: {}", layer))?;
+            Ok(ConsoleCommand::LayerWeight(id, layer, parse_number(weight)?))
+        },
+        &["save", name] => Ok(ConsoleCommand::Save(name.to_string())),
+        &["preset", "save", name] => Ok(ConsoleCommand::PresetSave(name.to_string())),
+        &["preset", "load", name] => Ok(ConsoleCommand::PresetLoad(name.to_string())),
+        &["preset", "list"] => Ok(ConsoleCommand::PresetList),
+        &["marker", "clear"] => Ok(ConsoleCommand::ClearMarkers),
+        &["marker", x, y, z] => parse_vector(x, y, z).map(|v| ConsoleCommand::AddMarker(Point3::new(v.x, v.y, v.z))),
+        &["markers", "on"] => Ok(ConsoleCommand::AvatorMarkers(true)),
+        &["markers", "off"] => Ok(ConsoleCommand::AvatorMarkers(false)),
+        &["reload"] => Ok(ConsoleCommand::Reload(None)),
+        &["reload", "watch", "on"] => Ok(ConsoleCommand::ReloadWatch(true)),
+        &["reload", "watch", "off"] => Ok(ConsoleCommand::ReloadWatch(false)),
+        &["reload", id] => parse_id(id).map(|id| ConsoleCommand::Reload(Some(id))),
+        &["migrate", "indices", id] => id.parse::<i32>()
+            .map(ConsoleCommand::MigrateIndices)
+            .map_err(|_| format!("not an object id: {}", id)),
+        &["time"] => Ok(ConsoleCommand::TimeShow),
+        &["time", "freeze"] => Ok(ConsoleCommand::TimeFreeze(true)),
+        &["time", "resume"] => Ok(ConsoleCommand::TimeFreeze(false)),
+        &["time", "cycle", seconds] => match seconds.parse::<f64>() {
+            Ok(s) if s > 0.0 => Ok(ConsoleCommand::TimeCycle(s)),
+            _ => Err(format!("not a positive number: {}", seconds)),
+        },
+        &["time", hour] => hour.parse::<f32>()
+            .map(ConsoleCommand::TimeSet)
+            .map_err(|_| format!("not an hour: {}", hour)),
+        &["select", "none"] => Ok(ConsoleCommand::Select(None)),
+        &["select", id] => parse_id(id).map(|id| ConsoleCommand::Select(Some(id))),
+        &["mesh", id, mesh, "show"] => Ok(ConsoleCommand::MeshVisible(parse_id(id)?, parse_mesh_id(mesh)?, true)),
+        &["mesh", id, mesh, "hide"] => Ok(ConsoleCommand::MeshVisible(parse_id(id)?, parse_mesh_id(mesh)?, false)),
+        &["layers"] => Ok(ConsoleCommand::Layers),
+        &["layers", camera, layer, on] => {
+            let camera = match camera {
+                "main" => ViewCamera::Main,
+                "observer" => ViewCamera::Observer,
+                _ => return Err(format!("unknown camera: {}", camera)),
+            };
+            let layer = LayerMask::from_name(layer).ok_or_else(|| format!("unknown layer: {}", layer))?;
+            match on {
+                "on" => Ok(ConsoleCommand::CameraLayer(camera, layer, true)),
+                "off" => Ok(ConsoleCommand::CameraLayer(camera, layer, false)),
+                _ => Err(format!("not on or off: {}", on)),
+            }
+        },
+        &["lighting", space] => LightingSpace::from_name(space)
+            .map(ConsoleCommand::Lighting)
+            .ok_or_else(|| format!("expected linear or legacy: {}", space)),
+        &["pick", mode] => PickMode::from_name(mode)
+            .map(ConsoleCommand::Pick)
+            .ok_or_else(|| format!("expected bounds or mesh: {}", mode)),
+        &["fog", "off"] => Ok(ConsoleCommand::Fog(FogMode::Off)),
+        &["fog", "linear", start, end] => {
+            let (start, end) = (parse_number(start)?, parse_number(end)?);
+            if end <= start {
+                return Err(format!("fog end must be past start: {} {}", start, end));
+            }
+            Ok(ConsoleCommand::Fog(FogMode::Linear { start, end }))
+        },
+        &["fog", "exp", start, density] => {
+            let (start, density) = (parse_number(start)?, parse_number(density)?);
+            Ok(ConsoleCommand::Fog(FogMode::Exp { start, density }))
+        },
+        &["flash", r, g, b] => parse_vector(r, g, b).map(|c| ConsoleCommand::Flash(Color::rgb(c.x, c.y, c.z), FLASH_SECONDS)),
+        &["flash", r, g, b, seconds] => {
+            let c = parse_vector(r, g, b)?;
+            Ok(ConsoleCommand::Flash(Color::rgb(c.x, c.y, c.z), parse_number(seconds)?))
+        },
+        &["fade", "out"] => Ok(ConsoleCommand::Fade(true, FADE_SECONDS)),
+        &["fade", "in"] => Ok(ConsoleCommand::Fade(false, FADE_SECONDS)),
+        &["fade", "out", seconds] => parse_number(seconds).map(|s| ConsoleCommand::Fade(true, s)),
+        &["fade", "in", seconds] => parse_number(seconds).map(|s| ConsoleCommand::Fade(false, s)),
+        &["fog", "color", "sky"] => Ok(ConsoleCommand::FogColor(None)),
+        &["fog", "color", r, g, b] => parse_vector(r, g, b).map(|c| ConsoleCommand::FogColor(Some(Color::rgb(c.x, c.y, c.z)))),
+        &["scene", id] => parse_id(id).map(|id| ConsoleCommand::Scene(id, TransitionKind::CrossDissolve)),
+        &["scene", id, kind] => {
+            let id = parse_id(id)?;
+            TransitionKind::from_name(kind)
+                .map(|kind| ConsoleCommand::Scene(id, kind))
+                .ok_or_else(|| format!("unknown transition: {}", kind))
+        },
+        &["sprite", "ui", texture, columns, rows, fps, x, y, width, height] => {
+            let sheet = parse_sheet(texture, columns, rows, fps)?;
+            let (x, y) = (parse_number(x)?, parse_number(y)?);
+            let (width, height) = (parse_number(width)?, parse_number(height)?);
+            Ok(ConsoleCommand::SpriteScreen(sheet, [x, y, x + width, y + height]))
+        },
+        &["sprite", "world", texture, columns, rows, fps, x, y, z] => {
+            let sheet = parse_sheet(texture, columns, rows, fps)?;
+            parse_vector(x, y, z).map(|v| ConsoleCommand::SpriteWorld(sheet, Point3::new(v.x, v.y, v.z)))
+        },
+        &["sprite", "clear"] => Ok(ConsoleCommand::SpriteClear),
+        &["dumpframe"] => Ok(ConsoleCommand::DumpFrame(None)),
+        &["dumpframe", path] => Ok(ConsoleCommand::DumpFrame(Some(path.to_string()))),
+        &["log"] => Ok(ConsoleCommand::LogPage),
+        &["log", "level", level] => parse_level(level).map(|level| ConsoleCommand::LogLevel(None, level)),
+        &["log", "level", module, level] => parse_level(level).map(|level| ConsoleCommand::LogLevel(Some(module.to_string()), level)),
+        &["lang", language] => Ok(ConsoleCommand::Language(language.to_string())),
+        _ => Err(format!("unknown command: {}", line)),
+    }
+}
+
+pub const HELP: &'static [&'static str] = &[
+    "help",
+    "gpus",
+    "history [clear]",
+    "history size <commands>",
+    "history replay on|off",
+    "movement [<acceleration> <friction> <max speed>]",
+    "joint name <object id> <joint index> <name>|none",
+    "retarget <object id> <source object id>|none",
+    "clear",
+    "camera move <x> <y> <z>",
+    "camera lookat <x> <y> <z>",
+    "camera roll <degrees>",
+    "camera up <x> <y> <z>",
+    "camera clip <near> <far>",
+    "camera ortho | perspective | top",
+    "observer move <x> <y> <z>",
+    "observer lookat <x> <y> <z>",
+    "view single|split|pip",
+    "debug aabb|capsule|frustum|nav|trigger|grid|axes on|off",
+    "path key <path id>",
+    "path play <path id>",
+    "path stop",
+    "path clear <path id>",
+    "spawn <object id> [<x> <y> <z>]",
+    "despawn <id>",
+    "palette [<slot> <object id> [<scale>]|<slot> clear]",
+    "damage <id> <amount>",
+    "heal <id> <amount>",
+    "npc <id> idle|wander <radius>|follow <target id> [<distance>]",
+    "avator move [<id>|selected|all|group <tag>] <x> <y> <z>",
+    "tag|untag <id> <tag>",
+    "avator speed <object id> <multiplier>",
+    "avator layer <object id> <layer> <weight>",
+    "save <name>",
+    "preset save|load <name>",
+    "preset list",
+    "lang <language>",
+    "migrate indices <object id>",
+    "reload [<object id>]",
+    "reload watch on|off",
+    "marker <x> <y> <z>",
+    "marker clear",
+    "markers on|off",
+    "time [<hour>|freeze|resume]",
+    "time cycle <seconds>",
+    "select <id>|none",
+    "pick bounds|mesh",
+    "mesh <id> <mesh id> show|hide",
+    "layers [main|observer world|ui|debug on|off]",
+    "lighting linear|legacy",
+    "fog off|linear <start> <end>|exp <start> <density>",
+    "fog color sky|<r> <g> <b>",
+    "flash <r> <g> <b> [<seconds>]",
+    "fade out|in [<seconds>]",
+    "scene <id> [cut|fade|dissolve]",
+    "sprite ui <texture> <columns> <rows> <fps> <x> <y> <width> <height>",
+    "sprite world <texture> <columns> <rows> <fps> <x> <y> <z>",
+    "sprite clear",
+    "say [none|typewriter,wave,shake <text>]",
+    "dumpframe [<path>]",
+    "log",
+    "log level [<module>] off|error|warn|info|debug|trace",
+];
+
+fn parse_id(id: &str) -> Result<i32, String> {
+    id.parse::<i32>().map_err(|_| format!("not an id: {}", id))
+}
+
+// MeshIds count from 1.
+fn parse_mesh_id(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(mesh_id) if mesh_id >= 1 => Ok(mesh_id),
+        _ => Err(format!("not a mesh id: {}", s)),
+    }
+}
+
+fn parse_number(s: &str) -> Result<f32, String> {
+    s.parse::<f32>().map_err(|_| format!("not a number: {}", s))
+}
+
+fn parse_slot(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(slot) if slot >= 1 && slot <= PALETTE_SLOTS => Ok(slot),
+        _ => Err(format!("not a palette slot, 1 to {}: {}", PALETTE_SLOTS, s)),
+    }
+}
+
+fn parse_amount(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(amount) if amount >= 0.0 => Ok(amount),
+        _ => Err(format!("not an amount: {}", s)),
+    }
+}
+
+fn parse_sheet(texture: &str, columns: &str, rows: &str, fps: &str) -> Result<SpriteSheet, String> {
+    let texture_id = parse_id(texture)?;
+    let parse_count = |s: &str| match s.parse::<u16>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("not a positive count: {}", s)),
+    };
+    Ok(SpriteSheet::new(texture_id, parse_count(columns)?, parse_count(rows)?, parse_number(fps)?))
+}
+
+fn parse_say(line: &str) -> Result<ConsoleCommand, String> {
+    let rest = line.trim_left()["say".len()..].trim_left();
+    if rest.is_empty() {
+        return Ok(ConsoleCommand::Say(Vec::new(), String::new()));
+    }
+    let (names, text) = match rest.find(char::is_whitespace) {
+        Some(end) => (&rest[..end], rest[end..].trim_left()),
+        None => return Err(format!("nothing to say after {}", rest)),
+    };
+    let effects = if names == "none" {
+        Vec::new()
+    } else {
+        names.split(',')
+            .map(|name| TextEffect::from_name(name).ok_or_else(|| format!("unknown text effect: {}", name)))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    Ok(ConsoleCommand::Say(effects, text.to_string()))
+}
+
+fn parse_vector(x: &str, y: &str, z: &str) -> Result<Vector3<f32>, String> {
+    Ok(Vector3::new(parse_number(x)?, parse_number(y)?, parse_number(z)?))
+}
@@ -0,0 +1,191 @@
+use cgmath::{Matrix4, Point3, Vector4};
+
+use debug_draw::Aabb;
+use Vertex;
+
+// bind pose bounds miss limbs an animation swings out, so boxes are tested this much larger
+pub const CULL_MARGIN: f32 = 0.5;
+// cells across and down the screen in an OcclusionBuffer
+const OCCLUSION_WIDTH: usize = 64;
+const OCCLUSION_HEIGHT: usize = 48;
+// meshes with more triangles than this cost more to rasterize than they save
+pub const MAX_OCCLUDER_TRIANGLES: usize = 2048;
+// below this clip w a point counts as at the eye
+const MIN_W: f32 = 1e-5;
+
+// The six planes bounding what a view-projection sees, facing inward.
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // Read off the rows of view_proj (Gribb and Hartmann); the near plane is taken at NDC
+    // z = -1, which only keeps more when a backend clips at 0.
+    pub fn from_matrix(view_proj: &Matrix4<f32>) -> Self {
+        let m = view_proj;
+        let row = |i: usize| Vector4::new(m.x[i], m.y[i], m.z[i], m.w[i]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Frustum {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+    // False only when the box is wholly outside one plane; a box cutting a corner
+    // outside the frustum may still be kept, never the other way around.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane's normal
+            let corner = Vector4::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+                1.0,
+            );
+            plane.x * corner.x + plane.y * corner.y + plane.z * corner.z + plane.w * corner.w >= 0.0
+        })
+    }
+}
+
+// One drawn mesh of an object whose vertices never move, in model space.
+pub struct OccluderMesh {
+    pub mesh_id: usize,
+    positions: Vec<Point3<f32>>,
+    indices: Vec<u32>,
+}
+
+impl OccluderMesh {
+    pub fn new(mesh_id: usize, vertices: &[Vertex], indices: &[u32]) -> Self {
+        OccluderMesh {
+            mesh_id,
+            positions: vertices.iter().map(|v| Point3::new(v.position[0], v.position[1], v.position[2])).collect(),
+            indices: indices.to_vec(),
+        }
+    }
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+// A coarse depth buffer drawn on the CPU, for what occlusion queries would do if the gfx
+// device here had them: objects wholly behind others are not drawn.
+// Every step errs toward drawing. An occluder marks only cells one of its triangles covers
+// whole, at the furthest depth the triangle has over the cell, and skips triangles that
+// are clipped, so a hidden box really is hidden. A box reaching behind the eye is never.
+pub struct OcclusionBuffer {
+    view_proj: Matrix4<f32>,
+    reversed_z: bool,
+    // per cell, the furthest depth an occluder there reaches; infinite where there is none
+    depth: Vec<f32>,
+}
+
+impl OcclusionBuffer {
+    pub fn new(view_proj: Matrix4<f32>, reversed_z: bool) -> Self {
+        OcclusionBuffer {
+            view_proj,
+            reversed_z,
+            depth: vec!(::std::f32::INFINITY; OCCLUSION_WIDTH * OCCLUSION_HEIGHT),
+        }
+    }
+    // The mesh as drawn at position and scale.
+    pub fn add(&mut self, mesh: &OccluderMesh, position: Point3<f32>, scale: f32) {
+        let projected: Vec<Option<[f32; 3]>> = mesh.positions.iter()
+            .map(|p| self.project_unclipped(Point3::new(position.x + p.x * scale, position.y + p.y * scale, position.z + p.z * scale)))
+            .collect();
+        for triangle in mesh.indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+            let corner = |i: u32| projected.get(i as usize).and_then(|&p| p);
+            if let (Some(a), Some(b), Some(c)) = (corner(triangle[0]), corner(triangle[1]), corner(triangle[2])) {
+                self.fill_triangle(a, b, c);
+            }
+        }
+    }
+    // Whether every cell the box's screen rectangle touches has an occluder in front of all of it.
+    pub fn is_hidden(&self, aabb: &Aabb) -> bool {
+        let mut min = [::std::f32::INFINITY; 3];
+        let mut max = [::std::f32::NEG_INFINITY; 2];
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            );
+            let p = match self.project(corner) {
+                Some(p) => p,
+                None => return false,
+            };
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+            }
+            for axis in 0..2 {
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        let (x0, x1) = cell_range(min[0], max[0], OCCLUSION_WIDTH);
+        let (y0, y1) = cell_range(min[1], max[1], OCCLUSION_HEIGHT);
+        // off the screen is the frustum's to judge
+        if x0 >= x1 || y0 >= y1 {
+            return false;
+        }
+        (y0..y1).all(|y| (x0..x1).all(|x| self.depth[y * OCCLUSION_WIDTH + x] < min[2]))
+    }
+    // Cell coordinates across and down, and a depth that grows away from the eye.
+    fn project(&self, p: Point3<f32>) -> Option<[f32; 3]> {
+        let clip = self.view_proj * Vector4::new(p.x, p.y, p.z, 1.0);
+        if clip.w < MIN_W {
+            return None;
+        }
+        let depth = clip.z / clip.w;
+        Some([
+            (clip.x / clip.w + 1.0) / 2.0 * OCCLUSION_WIDTH as f32,
+            (clip.y / clip.w + 1.0) / 2.0 * OCCLUSION_HEIGHT as f32,
+            if self.reversed_z { -depth } else { depth },
+        ])
+    }
+    // As project, but None for a point the near or far plane clips, as GL clips at -w and w.
+    fn project_unclipped(&self, p: Point3<f32>) -> Option<[f32; 3]> {
+        let clip = self.view_proj * Vector4::new(p.x, p.y, p.z, 1.0);
+        if clip.z < -clip.w || clip.z > clip.w {
+            return None;
+        }
+        self.project(p)
+    }
+    fn fill_triangle(&mut self, a: [f32; 3], b: [f32; 3], c: [f32; 3]) {
+        let area = edge(a, b, c);
+        if area.abs() < 1e-6 {
+            return;
+        }
+        let (x0, x1) = cell_range(a[0].min(b[0]).min(c[0]), a[0].max(b[0]).max(c[0]), OCCLUSION_WIDTH);
+        let (y0, y1) = cell_range(a[1].min(b[1]).min(c[1]), a[1].max(b[1]).max(c[1]), OCCLUSION_HEIGHT);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // depth is linear across the triangle on screen, so its furthest over
+                // the cell is at a corner, and a cell with its corners inside is inside
+                let mut furthest = ::std::f32::NEG_INFINITY;
+                let mut inside = true;
+                for &(cx, cy) in &[(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
+                    let p = [cx as f32, cy as f32, 0.0];
+                    let (wa, wb, wc) = (edge(b, c, p) / area, edge(c, a, p) / area, edge(a, b, p) / area);
+                    if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                        inside = false;
+                        break;
+                    }
+                    furthest = furthest.max(wa * a[2] + wb * b[2] + wc * c[2]);
+                }
+                if inside {
+                    let cell = &mut self.depth[y * OCCLUSION_WIDTH + x];
+                    *cell = cell.min(furthest);
+                }
+            }
+        }
+    }
+}
+
+// Twice the signed area of abc on screen.
+fn edge(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+// The cells a span of cell coordinates touches, clamped to the buffer.
+fn cell_range(min: f32, max: f32, cells: usize) -> (usize, usize) {
+    let start = min.floor().max(0.0).min(cells as f32) as usize;
+    let end = max.ceil().max(0.0).min(cells as f32) as usize;
+    (start, end)
+}
@@ -0,0 +1,260 @@
+use std;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+
+use VertexP;
+use axes::WORLD;
+use editor::AXES;
+use math::{screen_to_ndc, Screen};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn from_points<I: Iterator<Item = [f32; 3]>>(points: I) -> Self {
+        let mut min = Point3::new(std::f32::MAX, std::f32::MAX, std::f32::MAX);
+        let mut max = Point3::new(std::f32::MIN, std::f32::MIN, std::f32::MIN);
+        let mut any = false;
+        for p in points {
+            any = true;
+            min = Point3::new(min.x.min(p[0]), min.y.min(p[1]), min.z.min(p[2]));
+            max = Point3::new(max.x.max(p[0]), max.y.max(p[1]), max.z.max(p[2]));
+        }
+        if !any {
+            return Aabb { min: Point3::new(0.0, 0.0, 0.0), max: Point3::new(0.0, 0.0, 0.0) };
+        }
+        Aabb { min, max }
+    }
+    pub fn translated(&self, v: Vector3<f32>) -> Self {
+        Aabb { min: self.min + v, max: self.max + v }
+    }
+    pub fn expanded(&self, margin: f32) -> Self {
+        let v = Vector3::new(margin, margin, margin);
+        Aabb { min: self.min - v, max: self.max + v }
+    }
+    // About the origin; scale is positive.
+    pub fn scaled(&self, scale: f32) -> Self {
+        Aabb { min: self.min * scale, max: self.max * scale }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebugCategory {
+    Aabb,
+    Capsule,
+    Frustum,
+    // the navigation grid's blocked cells and the path being walked
+    Nav,
+    Trigger,
+    // lines on the ground around the camera's target
+    Grid,
+    // the world's axes in a corner of each view
+    Axes,
+}
+
+impl DebugCategory {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "aabb" => Some(DebugCategory::Aabb),
+            "capsule" => Some(DebugCategory::Capsule),
+            "frustum" => Some(DebugCategory::Frustum),
+            "nav" => Some(DebugCategory::Nav),
+            "trigger" => Some(DebugCategory::Trigger),
+            "grid" => Some(DebugCategory::Grid),
+            "axes" => Some(DebugCategory::Axes),
+            _ => None,
+        }
+    }
+}
+
+pub struct DebugFlags {
+    pub aabb: bool,
+    pub capsule: bool,
+    pub frustum: bool,
+    pub nav: bool,
+    pub trigger: bool,
+    pub grid: bool,
+    pub axes: bool,
+}
+
+impl DebugFlags {
+    pub fn new() -> Self {
+        DebugFlags {
+            aabb: false,
+            capsule: false,
+            frustum: false,
+            nav: false,
+            trigger: false,
+            grid: false,
+            axes: false,
+        }
+    }
+    pub fn set(&mut self, category: DebugCategory, enabled: bool) {
+        match category {
+            DebugCategory::Aabb => self.aabb = enabled,
+            DebugCategory::Capsule => self.capsule = enabled,
+            DebugCategory::Frustum => self.frustum = enabled,
+            DebugCategory::Nav => self.nav = enabled,
+            DebugCategory::Trigger => self.trigger = enabled,
+            DebugCategory::Grid => self.grid = enabled,
+            DebugCategory::Axes => self.axes = enabled,
+        }
+    }
+    pub fn any(&self) -> bool {
+        self.aabb || self.capsule || self.frustum || self.nav || self.trigger || self.grid
+    }
+}
+
+const CIRCLE_SEGMENTS: usize = 16;
+
+// The ground grid: a line every GRID_SPACING units, GRID_CELLS of them each way from
+// the middle, and a brighter one every GRID_MAJOR lines.
+const GRID_SPACING: f32 = 1.0;
+const GRID_CELLS: i32 = 40;
+const GRID_MAJOR: i32 = 10;
+const GRID_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+const GRID_MAJOR_COLOR: [f32; 4] = [0.55, 0.55, 0.55, 1.0];
+
+// Line list vertices, two per segment.
+pub struct DebugLines {
+    pub vertices: Vec<VertexP>,
+}
+
+impl DebugLines {
+    // Draws into a buffer kept from an earlier frame; see FrameArena.
+    pub fn with_buffer(mut vertices: Vec<VertexP>) -> Self {
+        vertices.clear();
+        DebugLines { vertices }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+    pub fn line(&mut self, a: Point3<f32>, b: Point3<f32>, color: [f32; 4]) {
+        self.vertices.push(VertexP { position: a.into(), color });
+        self.vertices.push(VertexP { position: b.into(), color });
+    }
+    // Edges between the eight corners, indexed by their x, y, z bits.
+    fn cube(&mut self, corners: &[Point3<f32>; 8], color: [f32; 4]) {
+        for i in 0..8 {
+            for bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 4]) {
+        let (a, b) = (aabb.min, aabb.max);
+        let mut corners = [a; 8];
+        for i in 0..8 {
+            corners[i] = Point3::new(
+                if i & 1 == 0 { a.x } else { b.x },
+                if i & 2 == 0 { a.y } else { b.y },
+                if i & 4 == 0 { a.z } else { b.z },
+            );
+        }
+        self.cube(&corners, color);
+    }
+    fn circle(&mut self, center: Point3<f32>, radius: f32, color: [f32; 4]) {
+        let step = 2.0 * std::f32::consts::PI / CIRCLE_SEGMENTS as f32;
+        let (right, back) = (WORLD.right(), WORLD.back());
+        let at = |i: usize| {
+            let t = step * i as f32;
+            center + (right * t.cos() + back * t.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(at(i), at(i + 1), color);
+        }
+    }
+    // Capsule standing along the world's up axis between two sphere centres.
+    pub fn capsule(&mut self, bottom: Point3<f32>, top: Point3<f32>, radius: f32, color: [f32; 4]) {
+        self.circle(bottom, radius, color);
+        self.circle(top, radius, color);
+        let half = CIRCLE_SEGMENTS / 2;
+        let step = std::f32::consts::PI / half as f32;
+        let (right, up, back) = (WORLD.right(), WORLD.up(), WORLD.back());
+        for &side in &[right, back, -right, -back] {
+            let side = side * radius;
+            self.line(bottom + side, top + side, color);
+        }
+        // arcs over the caps in the two upright planes
+        for &side in &[right, back] {
+            for &(center, sign) in &[(top, 1.0f32), (bottom, -1.0f32)] {
+                let at = |i: usize| {
+                    let t = step * i as f32;
+                    center + (side * t.cos() + up * (sign * t.sin())) * radius
+                };
+                for i in 0..half {
+                    self.line(at(i), at(i + 1), color);
+                }
+            }
+        }
+    }
+    // The volume seen through view_proj, recovered by unprojecting the NDC cube.
+    pub fn frustum(&mut self, view_proj: Matrix4<f32>, color: [f32; 4]) {
+        let inverse = match view_proj.invert() {
+            Some(m) => m,
+            None => return,
+        };
+        let mut corners = [Point3::new(0.0, 0.0, 0.0); 8];
+        for i in 0..8 {
+            let ndc = Vector4::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+                1.0,
+            );
+            let p = inverse * ndc;
+            corners[i] = Point3::new(p.x / p.w, p.y / p.w, p.z / p.w);
+        }
+        self.cube(&corners, color);
+    }
+    // The grid on the ground at height around center, snapped to the lines so it
+    // does not slide as center moves.
+    pub fn ground_grid(&mut self, center: Point3<f32>, height: f32) {
+        let (right, up, back) = (WORLD.right(), WORLD.up(), WORLD.back());
+        let line_near = |along: Vector3<f32>| (center.to_vec().dot(along) / GRID_SPACING).round() as i32;
+        let (a0, b0) = (line_near(right), line_near(back));
+        let extent = GRID_CELLS as f32 * GRID_SPACING;
+        let at = |a: f32, b: f32| Point3::from_vec(right * a + back * b + up * height);
+        for i in -GRID_CELLS..GRID_CELLS + 1 {
+            let (a, b) = (a0 + i, b0 + i);
+            let (middle_a, middle_b) = (a0 as f32 * GRID_SPACING, b0 as f32 * GRID_SPACING);
+            let (a_at, b_at) = (a as f32 * GRID_SPACING, b as f32 * GRID_SPACING);
+            self.line(at(a_at, middle_b - extent), at(a_at, middle_b + extent), grid_color(a, back));
+            self.line(at(middle_a - extent, b_at), at(middle_a + extent, b_at), grid_color(b, right));
+        }
+    }
+    // The world's axes turned as the camera sees them, from origin on the screen and
+    // length pixels long when seen side on. The ones pointing away are dimmer.
+    // The lines are in NDC at depth, to be drawn without a view. Returns the tips in AXES order.
+    pub fn axis_gizmo(&mut self, view: &Matrix4<f32>, screen: Screen, origin: [f32; 2], length: f32, depth: f32) -> [[f32; 2]; 3] {
+        let to_ndc = |p: [f32; 2]| {
+            let ndc = screen_to_ndc(screen, p);
+            Point3::new(ndc[0], ndc[1], depth)
+        };
+        let mut tips = [origin; 3];
+        for (tip, axis) in tips.iter_mut().zip(AXES.iter()) {
+            let seen = (*view * axis.unit().extend(0.0)).truncate();
+            *tip = [origin[0] + seen.x * length, origin[1] + seen.y * length];
+            let color = axis.color();
+            // the view looks down its -z
+            let color = if seen.z < 0.0 { [color[0] * 0.5, color[1] * 0.5, color[2] * 0.5, color[3]] } else { color };
+            self.line(to_ndc(origin), to_ndc(*tip), color);
+        }
+        tips
+    }
+}
+
+// The lines through the origin take the color of the axis they run along.
+fn grid_color(index: i32, along: Vector3<f32>) -> [f32; 4] {
+    if index == 0 {
+        AXES.iter().find(|axis| axis.unit().dot(along).abs() > 0.5).map_or(GRID_MAJOR_COLOR, |axis| axis.color())
+    } else if index % GRID_MAJOR == 0 {
+        GRID_MAJOR_COLOR
+    } else {
+        GRID_COLOR
+    }
+}
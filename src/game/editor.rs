@@ -0,0 +1,137 @@
+use cgmath::{Matrix4, Point3, Vector3};
+
+use math::{world_to_screen, Screen};
+
+// within this many pixels of an axis handle, a press grabs it
+const GRAB_PIXELS: f32 = 8.0;
+// how far the handles reach past an object's bounds
+const HANDLE_MARGIN: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+pub const AXES: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+impl Axis {
+    pub fn unit(self) -> Vector3<f32> {
+        match self {
+            Axis::X => Vector3::new(1.0, 0.0, 0.0),
+            Axis::Y => Vector3::new(0.0, 1.0, 0.0),
+            Axis::Z => Vector3::new(0.0, 0.0, 1.0),
+        }
+    }
+    pub fn color(self) -> [f32; 4] {
+        match self {
+            Axis::X => [1.0, 0.2, 0.2, 1.0],
+            Axis::Y => [0.2, 1.0, 0.2, 1.0],
+            Axis::Z => [0.3, 0.5, 1.0, 1.0],
+        }
+    }
+}
+
+// Three handles from the selected object's position along the world axes,
+// long enough to stick out of the object.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Gizmo {
+    pub origin: Point3<f32>,
+    pub length: f32,
+}
+
+impl Gizmo {
+    // half_extent is the largest of the object's bounds' half sizes.
+    pub fn new(origin: Point3<f32>, half_extent: f32) -> Self {
+        Gizmo {
+            origin,
+            length: half_extent + HANDLE_MARGIN,
+        }
+    }
+    pub fn tip(&self, axis: Axis) -> Point3<f32> {
+        self.origin + axis.unit() * self.length
+    }
+    // The handle nearest the point on the screen, if it is near enough.
+    pub fn grab(&self, view_proj: &Matrix4<f32>, screen: Screen, point: [f32; 2]) -> Option<Axis> {
+        let origin = world_to_screen(view_proj, screen, self.origin)?;
+        let mut nearest: Option<(f32, Axis)> = None;
+        for &axis in AXES.iter() {
+            let tip = match world_to_screen(view_proj, screen, self.tip(axis)) {
+                Some(tip) => tip,
+                None => continue,
+            };
+            let distance = segment_distance(origin, tip, point);
+            if distance <= GRAB_PIXELS && nearest.map_or(true, |(d, _)| distance < d) {
+                nearest = Some((distance, axis));
+            }
+        }
+        nearest.map(|(_, axis)| axis)
+    }
+}
+
+// A handle being dragged. The object follows the cursor's movement along the
+// handle as it looks on the screen, so a handle seen end on barely moves it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Drag {
+    pub key: i32,
+    pub axis: Axis,
+    pub gizmo: Gizmo,
+    start: [f32; 2],
+    // how far the object has been sent so far
+    pub moved: Vector3<f32>,
+}
+
+impl Drag {
+    pub fn new(key: i32, axis: Axis, gizmo: Gizmo, start: [f32; 2]) -> Self {
+        Drag {
+            key,
+            axis,
+            gizmo,
+            start,
+            moved: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+    // Where the object has to be, from where the drag started, for the cursor at point.
+    pub fn offset(&self, view_proj: &Matrix4<f32>, screen: Screen, point: [f32; 2]) -> Option<Vector3<f32>> {
+        let origin = world_to_screen(view_proj, screen, self.gizmo.origin)?;
+        let tip = world_to_screen(view_proj, screen, self.gizmo.tip(self.axis))?;
+        let handle = [tip[0] - origin[0], tip[1] - origin[1]];
+        let length2 = handle[0] * handle[0] + handle[1] * handle[1];
+        if length2 < 1.0 {
+            return None;
+        }
+        let cursor = [point[0] - self.start[0], point[1] - self.start[1]];
+        let along = (cursor[0] * handle[0] + cursor[1] * handle[1]) / length2;
+        Some(self.axis.unit() * (along * self.gizmo.length))
+    }
+    pub fn position(&self) -> Point3<f32> {
+        self.gizmo.origin + self.moved
+    }
+}
+
+// Placement editing: the selected object shows the gizmo, and left presses
+// grab its handles or select instead of walking.
+pub struct Editor {
+    pub enabled: bool,
+    pub drag: Option<Drag>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Editor {
+            enabled: false,
+            drag: None,
+        }
+    }
+}
+
+// from p to the nearest point of the segment a-b, in pixels
+fn segment_distance(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let ap = [p[0] - a[0], p[1] - a[1]];
+    let length2 = ab[0] * ab[0] + ab[1] * ab[1];
+    let t = if length2 > 0.0 { ((ap[0] * ab[0] + ap[1] * ab[1]) / length2).max(0.0).min(1.0) } else { 0.0 };
+    let d = [ap[0] - ab[0] * t, ap[1] - ab[1] * t];
+    (d[0] * d[0] + d[1] * d[1]).sqrt()
+}
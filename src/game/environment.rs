@@ -0,0 +1,69 @@
+use cgmath::{InnerSpace, Vector3};
+
+use axes;
+use models::Image;
+
+// texels along a face's edge at the base level
+pub const FACE_SIZE: u16 = 32;
+
+// Brightness over the sky color, by how far a direction points up. There is no
+// skybox yet; the shaders tint this with the time of day's sky, so the cube
+// itself never has to be made again.
+const ZENITH: f32 = 0.7;
+const HORIZON: f32 = 1.0;
+const GROUND: f32 = 0.2;
+
+// The six faces in GL's order, +x -x +y -y +z -z. Each maps a texel's face
+// coordinates, -1 to 1 from the top left, to the direction it is seen in.
+const FACES: [fn(f32, f32) -> Vector3<f32>; 6] = [
+    positive_x,
+    negative_x,
+    positive_y,
+    negative_y,
+    positive_z,
+    negative_z,
+];
+
+fn positive_x(s: f32, t: f32) -> Vector3<f32> { Vector3::new(1.0, -t, -s) }
+fn negative_x(s: f32, t: f32) -> Vector3<f32> { Vector3::new(-1.0, -t, s) }
+fn positive_y(s: f32, t: f32) -> Vector3<f32> { Vector3::new(s, 1.0, t) }
+fn negative_y(s: f32, t: f32) -> Vector3<f32> { Vector3::new(s, -1.0, -t) }
+fn positive_z(s: f32, t: f32) -> Vector3<f32> { Vector3::new(s, -t, 1.0) }
+fn negative_z(s: f32, t: f32) -> Vector3<f32> { Vector3::new(-s, -t, -1.0) }
+
+// A grey sky over a darker ground, brightest at the horizon.
+pub fn sky_faces<T>() -> Vec<Image<T>> {
+    let up = axes::WORLD.up();
+    let size = FACE_SIZE as usize;
+    FACES.iter().map(|face| {
+        let mut data = Vec::with_capacity(size * size * 4);
+        for y in 0..size {
+            for x in 0..size {
+                let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let v = (linear_to_srgb(brightness(face(s, t).normalize().dot(up))) * 255.0 + 0.5) as u8;
+                data.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        Image {
+            data,
+            width: FACE_SIZE,
+            height: FACE_SIZE,
+            format: ::std::marker::PhantomData::<T>,
+        }
+    }).collect()
+}
+
+// elevation is the sine of the angle above the horizon
+fn brightness(elevation: f32) -> f32 {
+    if elevation >= 0.0 {
+        HORIZON + (ZENITH - HORIZON) * elevation.sqrt()
+    } else {
+        // the ground darkens quickly below the horizon
+        HORIZON + (GROUND - HORIZON) * (-elevation * 4.0).min(1.0)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
@@ -0,0 +1,106 @@
+use cgmath::Point3;
+
+use FiredAnimationEvent;
+
+// Things that happened in the World, for systems that care but are not the target.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    AvatorMoved { key: i32, position: Point3<f32> },
+    AnimationEventFired(FiredAnimationEvent),
+    ObjectSpawned { key: i32, object_id: i32 },
+    ObjectDespawned { key: i32 },
+    // hp ran out; despawned right after unless it has a death clip
+    ObjectDied { key: i32 },
+    // the selection changed; None when it was cleared
+    ObjectSelected { key: Option<i32> },
+    // the local avator crossed into or out of a trigger volume
+    TriggerEntered { trigger: i32 },
+    TriggerExited { trigger: i32 },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    AvatorMoved,
+    AnimationEventFired,
+    ObjectSpawned,
+    ObjectDespawned,
+    ObjectDied,
+    ObjectSelected,
+    TriggerEntered,
+    TriggerExited,
+}
+
+impl GameEvent {
+    pub fn kind(&self) -> EventKind {
+        match *self {
+            GameEvent::AvatorMoved { .. } => EventKind::AvatorMoved,
+            GameEvent::AnimationEventFired(_) => EventKind::AnimationEventFired,
+            GameEvent::ObjectSpawned { .. } => EventKind::ObjectSpawned,
+            GameEvent::ObjectDespawned { .. } => EventKind::ObjectDespawned,
+            GameEvent::ObjectDied { .. } => EventKind::ObjectDied,
+            GameEvent::ObjectSelected { .. } => EventKind::ObjectSelected,
+            GameEvent::TriggerEntered { .. } => EventKind::TriggerEntered,
+            GameEvent::TriggerExited { .. } => EventKind::TriggerExited,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SubscriberId(u32);
+
+struct Subscriber {
+    id: SubscriberId,
+    kinds: Vec<EventKind>,
+    inbox: Vec<GameEvent>,
+}
+
+// Events published during a frame are queued and handed out by dispatch,
+// so publishers never run subscriber code. Each subscriber drains its own inbox.
+pub struct EventBus {
+    next_id: u32,
+    queue: Vec<GameEvent>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus {
+            next_id: 0,
+            queue: Vec::new(),
+            subscribers: Vec::new(),
+        }
+    }
+    // An empty kinds list receives everything.
+    pub fn subscribe(&mut self, kinds: &[EventKind]) -> SubscriberId {
+        let id = SubscriberId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push(Subscriber {
+            id,
+            kinds: kinds.to_vec(),
+            inbox: Vec::new(),
+        });
+        id
+    }
+    pub fn unsubscribe(&mut self, id: SubscriberId) {
+        self.subscribers.retain(|s| s.id != id);
+    }
+    pub fn publish(&mut self, event: GameEvent) {
+        self.queue.push(event);
+    }
+    pub fn dispatch(&mut self) {
+        for event in self.queue.drain(..) {
+            let kind = event.kind();
+            for subscriber in &mut self.subscribers {
+                if subscriber.kinds.is_empty() || subscriber.kinds.contains(&kind) {
+                    subscriber.inbox.push(event.clone());
+                }
+            }
+        }
+    }
+    pub fn drain(&mut self, id: SubscriberId) -> Vec<GameEvent> {
+        match self.subscribers.iter_mut().find(|s| s.id == id) {
+            Some(subscriber) => ::std::mem::replace(&mut subscriber.inbox, Vec::new()),
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use color::Color;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FogMode {
+    Off,
+    // ramps from nothing at start to full at end
+    Linear { start: f32, end: f32 },
+    // 1 - e^(-density * depth), past start
+    Exp { start: f32, density: f32 },
+}
+
+impl FogMode {
+    pub fn from_params(params: [f32; 4]) -> Option<FogMode> {
+        match params[0] as i32 {
+            0 => Some(FogMode::Off),
+            1 => Some(FogMode::Linear { start: params[1], end: params[2] }),
+            2 => Some(FogMode::Exp { start: params[1], density: params[3] }),
+            _ => None,
+        }
+    }
+}
+
+// Fog blended over the world pipelines by view-space depth.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fog {
+    pub mode: FogMode,
+    // None follows the sky so the far plane fades into the clear color
+    pub color: Option<Color>,
+}
+
+impl Fog {
+    pub fn new() -> Self {
+        Fog {
+            mode: FogMode::Linear { start: 60.0, end: 180.0 },
+            color: None,
+        }
+    }
+    pub fn color_over(&self, sky: Color) -> Color {
+        self.color.unwrap_or(sky)
+    }
+    // Packed as the u_fog uniform: mode, start, end, density.
+    // Also how presets store the fog; see FogMode::from_params.
+    pub fn params(&self) -> [f32; 4] {
+        match self.mode {
+            FogMode::Off => [0.0, 0.0, 0.0, 0.0],
+            FogMode::Linear { start, end } => [1.0, start, end.max(start + 0.001), 0.0],
+            FogMode::Exp { start, density } => [2.0, start, 0.0, density.max(0.0)],
+        }
+    }
+}
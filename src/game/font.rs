@@ -6,16 +6,50 @@ use freetype::Error as FreetypeError;
 use freetype::Face;
 use gfx;
 
+use markup::TextRun;
 use models::Image;
 
 pub struct Font {
     pub chars: HashMap<char, BitmapChar>,
 
     pub texture: Image<(gfx::format::R8, gfx::format::Unorm)>,
+    // the texture holds signed distances, 0.5 on the glyph edge
+    pub sdf: bool,
+}
+
+// distance in pixels covered by the 0..1 range of a distance field
+const SDF_SPREAD: i32 = 6;
+
+// One font of a fallback chain. Glyphs come from the first font in the chain that has them.
+#[derive(Debug, Clone)]
+pub struct FontSource {
+    pub path: String,
+    // relative glyph size, to even out fonts drawn at different sizes for the same pixel size
+    pub scale: f32,
+}
+
+impl FontSource {
+    pub fn new(path: &str, scale: f32) -> Self {
+        FontSource {
+            path: path.to_string(),
+            scale,
+        }
+    }
 }
 
 pub type FontResult = Result<Font, FontError>;
 
+// One line of measured text, in the units the text is drawn in.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineMetrics {
+    // from the top of the text down to where this line's glyphs hang from
+    pub top: f32,
+    // the pen's advance along the line
+    pub width: f32,
+    // down to the lowest glyph of the line; 0 for a line with no glyphs
+    pub height: f32,
+}
+
 #[derive(Debug)]
 pub enum FontError {
     FreetypeError(FreetypeError),
@@ -41,52 +75,95 @@ pub struct BitmapChar {
 
 impl Font {
     pub fn from_path(path: &str, font_size: u8, chars: Option<&[char]>) -> FontResult {
+        Self::from_chain(&[FontSource::new(path, 1.0)], font_size, chars, false)
+    }
+    pub fn from_path_sdf(path: &str, font_size: u8, chars: Option<&[char]>) -> FontResult {
+        Self::from_chain(&[FontSource::new(path, 1.0)], font_size, chars, true)
+    }
+    // The first source is required; a fallback that fails to load is skipped with a warning.
+    // Without chars, every character of every font in the chain is rendered.
+    pub fn from_chain(sources: &[FontSource], font_size: u8, chars: Option<&[char]>, sdf: bool) -> FontResult {
         let library = ft::Library::init()?;
-        let face = library.new_face(path, 0)?;
-        Self::new(face, font_size, chars)
+        let mut faces = Vec::with_capacity(sources.len());
+        for (i, source) in sources.iter().enumerate() {
+            match library.new_face(&source.path, 0) {
+                Ok(face) => faces.push((face, source.scale)),
+                Err(e) if i > 0 => warn!("skipped fallback font {}: {}", source.path, e),
+                Err(e) => return Err(FontError::from(e)),
+            }
+        }
+        if faces.is_empty() {
+            return Err(FontError::EmptyFont);
+        }
+        Self::new(faces, font_size, chars, if sdf { SDF_SPREAD } else { 0 })
     }
-    fn new<'a>(mut face: ft::Face<'a>, font_size: u8, chars: Option<&[char]>) -> FontResult {
+    fn new<'a>(mut faces: Vec<(ft::Face<'a>, f32)>, font_size: u8, chars: Option<&[char]>, spread: i32) -> FontResult {
         use std::iter::FromIterator;
         use std::iter::repeat;
         use std::cmp::max;
 
         let needed_chars = chars
             .map(|sl| HashSet::from_iter(sl.iter().cloned()))
-            .unwrap_or_else(|| Self::get_all_face_chars(&mut face));
+            .unwrap_or_else(|| {
+                let mut all = HashSet::default();
+                for &mut (ref mut face, _) in faces.iter_mut() {
+                    all.extend(Self::get_all_face_chars(face));
+                }
+                all
+            });
         if needed_chars.is_empty() {
             return Err(FontError::EmptyFont);
         }
 
-        face.set_pixel_sizes(font_size as u32, font_size as u32)?;
+        for &(ref face, scale) in faces.iter() {
+            let size = (font_size as f32 * scale).round().max(1.0) as u32;
+            face.set_pixel_sizes(size, size)?;
+        }
 
         let mut chars = HashMap::default();
         let mut sum_image_width = 0;
         let mut max_ch_width = 0;
         let mut max_ch_height = 0;
+        let mut missing = 0;
         for ch in needed_chars {
+            // a character no font has gets the first font's missing glyph box
+            let face = match faces.iter().find(|&&(ref face, _)| face.get_char_index(ch as usize) != 0) {
+                Some(&(ref face, _)) => face,
+                None => {
+                    missing += 1;
+                    &faces[0].0
+                },
+            };
             try!(face.load_char(ch as usize, ft::face::RENDER));
 
             let glyph = face.glyph();
             let bitmap = glyph.bitmap();
 
-            let ch_width = bitmap.width();
-            let ch_height = bitmap.rows();
+            let (ch_width, ch_height, data) = if spread > 0 {
+                let data = distance_field(bitmap.buffer(), bitmap.width(), bitmap.rows(), spread);
+                (bitmap.width() + 2 * spread, bitmap.rows() + 2 * spread, data)
+            } else {
+                (bitmap.width(), bitmap.rows(), Vec::from(bitmap.buffer()))
+            };
 
             chars.insert(ch, BitmapChar {
-                x_offset: glyph.bitmap_left(),
-                y_offset: font_size as i32 - glyph.bitmap_top(),
+                x_offset: glyph.bitmap_left() - spread,
+                y_offset: font_size as i32 - glyph.bitmap_top() - spread,
                 x_advance: (glyph.advance().x >> 6) as i32,
                 width: ch_width,
                 height: ch_height,
                 tex: [0.0, 0.0],
                 tex_width: 0.0,
                 tex_height: 0.0,
-                data: Some(Vec::from(bitmap.buffer()))
+                data: Some(data)
             });
             sum_image_width += ch_width;
             max_ch_width = max(max_ch_width, ch_width);
             max_ch_height = max(max_ch_height, ch_height);
         }
+        if missing > 0 {
+            warn!("{} characters are in none of the fonts", missing);
+        }
 
         let ideal_image_size = sum_image_width * max_ch_height;
         let image_width = {
@@ -146,9 +223,51 @@ impl Font {
 
         Ok(Font{
             chars,
-            texture
+            texture,
+            sdf: spread > 0,
         })
     }
+    pub fn advance(&self, text: &str) -> i32 {
+        text.chars()
+            .filter_map(|ch| self.chars.get(&ch))
+            .map(|info| info.x_advance)
+            .sum()
+    }
+    // Width and height of text as font_entry lays it out at scale, with its lines.
+    pub fn measure(&self, text: &str, scale: f32) -> (f32, f32, Vec<LineMetrics>) {
+        self.measure_runs(&[TextRun::plain(text)], scale)
+    }
+    // Follows font_entry_runs step for step: a new line starts under the lowest glyph
+    // so far, so a line with no glyphs takes no room.
+    pub fn measure_runs(&self, runs: &[TextRun], scale: f32) -> (f32, f32, Vec<LineMetrics>) {
+        let (mut x, mut y) = (0.0f32, 0.0f32);
+        let mut min_y_end = y;
+        let mut lines = vec!(LineMetrics { top: 0.0, width: 0.0, height: 0.0 });
+        for run in runs {
+            let s = scale * run.scale;
+            for (i, l) in run.text.split('\n').enumerate() {
+                if i > 0 {
+                    x = 0.0;
+                    y = min_y_end;
+                    lines.push(LineMetrics { top: -y, width: 0.0, height: 0.0 });
+                }
+                let line = lines.last_mut().unwrap();
+                for ch in l.chars() {
+                    let ch_info = match self.chars.get(&ch) {
+                        Some(info) => info,
+                        None => continue,
+                    };
+                    let y_end = y - (ch_info.y_offset + ch_info.height) as f32 * s;
+                    min_y_end = min_y_end.min(y_end);
+                    line.height = line.height.max(y - y_end);
+                    x += ch_info.x_advance as f32 * s;
+                }
+                line.width = x;
+            }
+        }
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+        (width, -min_y_end, lines)
+    }
     pub fn get_all_face_chars<'a>(face: &mut Face<'a>) -> HashSet<char> {
         use std::char::from_u32;
         let mut result = HashSet::default();
@@ -165,3 +284,36 @@ impl Font {
     }
 }
 
+
+// Pads the coverage bitmap by spread on every side and stores, per pixel, the distance
+// to the nearest pixel on the other side of the edge, mapped so 0.5 lies on the edge.
+// Brute force over a (2 * spread + 1)^2 window, which is fine for glyph sized images.
+fn distance_field(coverage: &[u8], width: i32, height: i32, spread: i32) -> Vec<u8> {
+    let inside = |x: i32, y: i32| {
+        x >= 0 && y >= 0 && x < width && y < height && coverage[(y * width + x) as usize] >= 128
+    };
+    let out_width = width + 2 * spread;
+    let out_height = height + 2 * spread;
+    let mut data = Vec::with_capacity((out_width * out_height) as usize);
+    for oy in 0..out_height {
+        for ox in 0..out_width {
+            let (x, y) = (ox - spread, oy - spread);
+            let here = inside(x, y);
+            let mut nearest = (spread * spread) as f32;
+            for dy in -spread..spread + 1 {
+                for dx in -spread..spread + 1 {
+                    let d = (dx * dx + dy * dy) as f32;
+                    if d < nearest && inside(x + dx, y + dy) != here {
+                        nearest = d;
+                    }
+                }
+            }
+            // the edge lies halfway between the two pixels
+            let distance = nearest.sqrt() - 0.5;
+            let signed = if here { distance } else { -distance };
+            let value = 0.5 + signed / (2.0 * spread as f32);
+            data.push((value.max(0.0).min(1.0) * 255.0) as u8);
+        }
+    }
+    data
+}
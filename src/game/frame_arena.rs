@@ -0,0 +1,78 @@
+use std;
+use std::cell::{Cell, RefCell};
+
+use {Skinning, Vertex, VertexP};
+
+// spare buffers a pool keeps; a frame never has more out at once
+const MAX_POOLED: usize = 8;
+
+// Vecs lent out while a draw is built and handed back once it is uploaded, so a
+// steady frame reuses the capacity earlier frames grew instead of allocating.
+pub struct BufferPool<T> {
+    free: RefCell<Vec<Vec<T>>>,
+    // taken this frame with no spare to reuse
+    fresh: Cell<usize>,
+}
+
+impl<T> BufferPool<T> {
+    pub fn new() -> Self {
+        BufferPool {
+            free: RefCell::new(Vec::new()),
+            fresh: Cell::new(0),
+        }
+    }
+    // Empty, with room from an earlier frame when there is a spare.
+    pub fn take(&self) -> Vec<T> {
+        match self.free.borrow_mut().pop() {
+            Some(buffer) => buffer,
+            None => {
+                self.fresh.set(self.fresh.get() + 1);
+                Vec::new()
+            },
+        }
+    }
+    pub fn give_back(&self, mut buffer: Vec<T>) {
+        buffer.clear();
+        let mut free = self.free.borrow_mut();
+        if free.len() < MAX_POOLED {
+            free.push(buffer);
+        }
+    }
+    fn reset(&self) -> usize {
+        let fresh = self.fresh.get();
+        self.fresh.set(0);
+        fresh
+    }
+    fn pooled_bytes(&self) -> usize {
+        self.free.borrow().iter().map(|buffer| buffer.capacity() * std::mem::size_of::<T>()).sum()
+    }
+}
+
+// The CPU side buffers of one frame's transient draws: text quads, debug and
+// minimap lines, and the palettes of split skeletons.
+pub struct FrameArena {
+    pub vertices: BufferPool<Vertex>,
+    pub indices: BufferPool<u32>,
+    pub lines: BufferPool<VertexP>,
+    pub palettes: BufferPool<Skinning>,
+}
+
+impl FrameArena {
+    pub fn new() -> Self {
+        FrameArena {
+            vertices: BufferPool::new(),
+            indices: BufferPool::new(),
+            lines: BufferPool::new(),
+            palettes: BufferPool::new(),
+        }
+    }
+    // Ends the frame. Returns how many buffers it had to allocate, which stays zero
+    // once the pools have warmed up unless a frame draws more than the last.
+    pub fn reset(&self) -> usize {
+        self.vertices.reset() + self.indices.reset() + self.lines.reset() + self.palettes.reset()
+    }
+    // Capacity held for the next frame, in bytes.
+    pub fn pooled_bytes(&self) -> usize {
+        self.vertices.pooled_bytes() + self.indices.pooled_bytes() + self.lines.pooled_bytes() + self.palettes.pooled_bytes()
+    }
+}
@@ -0,0 +1,81 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// One draw as the dump lists it.
+pub struct Draw {
+    pub pipeline: &'static str,
+    // indices, or vertices for a slice without them
+    pub count: u32,
+    // what is bound, by what it is rather than by handle
+    pub textures: Vec<String>,
+    pub uniforms: String,
+}
+
+struct Recording {
+    path: PathBuf,
+    // the frame graph pass the draws are made in
+    pass: String,
+    lines: Vec<String>,
+    draws: usize,
+}
+
+// Writes down every draw of one frame, and why objects were left out of it, for
+// finding out where something went without a graphics debugger. Nothing is
+// formatted unless a dump was asked for, so the draw sites can always call it.
+pub struct FrameDump {
+    recording: RefCell<Option<Recording>>,
+}
+
+impl FrameDump {
+    pub fn new() -> Self {
+        FrameDump {
+            recording: RefCell::new(None),
+        }
+    }
+    // The next frame drawn is written to path.
+    pub fn request(&self, path: &Path) {
+        *self.recording.borrow_mut() = Some(Recording {
+            path: path.to_path_buf(),
+            pass: String::new(),
+            lines: Vec::new(),
+            draws: 0,
+        });
+    }
+    pub fn begin_pass<F: FnOnce() -> String>(&self, pass: F) {
+        if let Some(ref mut recording) = *self.recording.borrow_mut() {
+            recording.pass = pass();
+        }
+    }
+    pub fn record<F: FnOnce() -> Draw>(&self, draw: F) {
+        if let Some(ref mut recording) = *self.recording.borrow_mut() {
+            let draw = draw();
+            let textures = if draw.textures.is_empty() { "-".to_string() } else { draw.textures.join(", ") };
+            let line = format!("{}\t{}\t{}\t{}\t{}", recording.pass, draw.pipeline, draw.count, textures, draw.uniforms);
+            recording.lines.push(line);
+            recording.draws += 1;
+        }
+    }
+    // Something that was not drawn, and why.
+    pub fn note<F: FnOnce() -> String>(&self, note: F) {
+        if let Some(ref mut recording) = *self.recording.borrow_mut() {
+            let line = format!("{}\t# {}", recording.pass, note());
+            recording.lines.push(line);
+        }
+    }
+    // Ends the frame, writing the dump when one was recorded. Gives where it went and its draw count.
+    pub fn finish(&self) -> Option<io::Result<(PathBuf, usize)>> {
+        let recording = self.recording.borrow_mut().take()?;
+        Some(write_dump(&recording).map(|_| (recording.path, recording.draws)))
+    }
+}
+
+fn write_dump(recording: &Recording) -> io::Result<()> {
+    let mut file = File::create(&recording.path)?;
+    writeln!(file, "pass\tpipeline\tcount\ttextures\tuniforms")?;
+    for line in &recording.lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+// What a frame does, as passes that declare the targets they read and write.
+// The graph orders them once at startup; App::render runs them in that order, the
+// swapchain's passes itself and the drawing ones through World::render_pass.
+// A new pass (shadows, post-processing, another UI layer) is a PassId, a declaration
+// and a match arm, plus a Target for anything it renders into offscreen.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Target {
+    BackbufferColor,
+    BackbufferDepth,
+    // last frame of the outgoing scene during a cross-dissolve
+    SceneCapture,
+    // the frame's commands, once handed to the queue
+    Submission,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PassId {
+    // takes the next swapchain image, which picks the backbuffer drawn into
+    Acquire,
+    // renders the outgoing scene offscreen when a cross-dissolve starts
+    Capture,
+    Clear,
+    // GameObjects, billboards and debug lines, once per viewport
+    Scene,
+    // timer and scrubber readouts
+    Hud,
+    // top-down map in a corner
+    Minimap,
+    // flashes and fades laid over everything but the menus
+    ScreenEffects,
+    // fullscreen fade or dissolve while a scene loads
+    Transition,
+    // pause screen, console and prompts
+    Overlay,
+    // submits what the passes recorded
+    Flush,
+    Present,
+}
+
+#[derive(Debug)]
+pub enum FrameGraphError {
+    DuplicatePass(PassId),
+    UnknownDependency { pass: PassId, after: PassId },
+    // the passes left over once nothing more could be ordered
+    Cycle(Vec<PassId>),
+}
+
+struct PassDesc {
+    id: PassId,
+    reads: Vec<Target>,
+    writes: Vec<Target>,
+    after: Vec<PassId>,
+}
+
+pub struct FrameGraph {
+    passes: Vec<PassDesc>,
+    order: Vec<PassId>,
+}
+
+impl FrameGraph {
+    pub fn new() -> Self {
+        FrameGraph {
+            passes: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+    // A pass runs after those listed in `after` and after every pass added before it that
+    // writes a target it reads or writes, or reads one it writes. Otherwise passes keep the
+    // order they were added in, which is also the order a target's writes happen in.
+    pub fn add_pass(&mut self, id: PassId, reads: &[Target], writes: &[Target], after: &[PassId]) -> &mut Self {
+        self.passes.push(PassDesc {
+            id,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            after: after.to_vec(),
+        });
+        self
+    }
+    pub fn compile(&mut self) -> Result<(), FrameGraphError> {
+        let mut dependencies: Vec<Vec<usize>> = Vec::with_capacity(self.passes.len());
+        for (i, pass) in self.passes.iter().enumerate() {
+            if position(&self.passes, pass.id) != Some(i) {
+                return Err(FrameGraphError::DuplicatePass(pass.id));
+            }
+            let mut deps = Vec::new();
+            for &after in &pass.after {
+                match position(&self.passes, after) {
+                    Some(j) => deps.push(j),
+                    None => return Err(FrameGraphError::UnknownDependency { pass: pass.id, after }),
+                }
+            }
+            for (j, earlier) in self.passes[..i].iter().enumerate() {
+                let hazard = earlier.writes.iter().any(|t| pass.reads.contains(t) || pass.writes.contains(t))
+                    || earlier.reads.iter().any(|t| pass.writes.contains(t));
+                if hazard {
+                    deps.push(j);
+                }
+            }
+            dependencies.push(deps);
+        }
+
+        let mut done = vec!(false; self.passes.len());
+        let mut order = Vec::with_capacity(self.passes.len());
+        loop {
+            // earliest added pass whose dependencies have all run
+            let next = (0..self.passes.len()).find(|&i| !done[i] && dependencies[i].iter().all(|&j| done[j]));
+            match next {
+                Some(i) => {
+                    done[i] = true;
+                    order.push(self.passes[i].id);
+                },
+                None => break,
+            }
+        }
+        if order.len() < self.passes.len() {
+            let left = self.passes.iter().zip(done.iter()).filter(|&(_, &d)| !d).map(|(p, _)| p.id).collect();
+            return Err(FrameGraphError::Cycle(left));
+        }
+        self.order = order;
+        Ok(())
+    }
+    pub fn order(&self) -> &[PassId] {
+        &self.order
+    }
+}
+
+fn position(passes: &[PassDesc], id: PassId) -> Option<usize> {
+    passes.iter().position(|p| p.id == id)
+}
@@ -0,0 +1,356 @@
+use gfx;
+
+use input::{ButtonState, InputEvent, Key, MouseButton};
+use models::{query_asset_summaries, AssetSummary};
+use graphics_settings::QualityPreset;
+use text_renderer::{TextHandle, TextStyle};
+use color::{Color, PANEL, TEXT, TITLE};
+use {open_connection, CameraCommand, Vertex, World};
+
+// Screens stacked on the World. Only the top one sees input; all of them draw,
+// bottom first, so a menu can sit over the gameplay it paused.
+pub(crate) trait GameState<W> {
+    fn on_enter(&mut self, _world: &mut W) { }
+    fn on_exit(&mut self, _world: &mut W) { }
+    fn update(&mut self, _world: &mut W) -> Transition<W> { Transition::None }
+    fn handle_input(&mut self, world: &mut W, ev: &InputEvent) -> Transition<W>;
+    fn render(&self, world: &W, canvas: &mut Canvas);
+    // stops the animation clock while anywhere on the stack
+    fn pauses(&self) -> bool { false }
+}
+
+pub(crate) enum Transition<W> {
+    None,
+    Push(Box<GameState<W>>),
+    Pop,
+    Replace(Box<GameState<W>>),
+}
+
+// 2D draws requested by states, executed by the World's overlay pass.
+pub enum CanvasItem {
+    // [left, bottom, right, top] in pixels
+    Rect([f32; 4], Color),
+    // markup, top-left position in pixels from the bottom-left, color, scale
+    Markup(String, [f32; 2], Color, f32),
+    // a text kept in the World's TextRenderer, at a top-left position as for Markup
+    Text(TextHandle, [f32; 2]),
+    // the object rendered on its own, into a rect as for Rect
+    Thumbnail(i32, [f32; 4]),
+}
+
+pub struct Canvas {
+    pub width: f32,
+    pub height: f32,
+    pub items: Vec<CanvasItem>,
+}
+
+impl Canvas {
+    pub fn new(width: f32, height: f32) -> Self {
+        Canvas {
+            width,
+            height,
+            items: Vec::new(),
+        }
+    }
+    pub fn rect(&mut self, rect: [f32; 4], color: Color) {
+        self.items.push(CanvasItem::Rect(rect, color));
+    }
+    pub fn markup(&mut self, markup: &str, pos: [f32; 2], color: Color, scale: f32) {
+        self.items.push(CanvasItem::Markup(markup.to_string(), pos, color, scale));
+    }
+    pub fn text(&mut self, handle: TextHandle, pos: [f32; 2]) {
+        self.items.push(CanvasItem::Text(handle, pos));
+    }
+    pub fn thumbnail(&mut self, object_id: i32, rect: [f32; 4]) {
+        self.items.push(CanvasItem::Thumbnail(object_id, rect));
+    }
+}
+
+const TITLE_STYLE: TextStyle = TextStyle { color: TITLE, scale: 1.5 };
+const PAUSE_STYLE: TextStyle = TextStyle { color: TEXT, scale: 1.0 };
+
+pub struct TitleScreen {
+    label: Option<TextHandle>,
+}
+
+impl TitleScreen {
+    pub fn new() -> Self {
+        TitleScreen {
+            label: None,
+        }
+    }
+}
+
+impl<B: gfx::Backend> GameState<World<B, Vertex>> for TitleScreen {
+    fn on_enter(&mut self, world: &mut World<B, Vertex>) {
+        let text = format!("{}\n{}", world.tr("title.name"), world.tr("title.start"));
+        self.label = Some(world.static_text.create_static(&text, TITLE_STYLE));
+    }
+    fn on_exit(&mut self, world: &mut World<B, Vertex>) {
+        if let Some(label) = self.label.take() {
+            world.static_text.remove(label);
+        }
+    }
+    // the language may change under the title
+    fn update(&mut self, world: &mut World<B, Vertex>) -> Transition<World<B, Vertex>> {
+        if let Some(label) = self.label {
+            let text = format!("{}\n{}", world.tr("title.name"), world.tr("title.start"));
+            world.static_text.set(label, &text, TITLE_STYLE);
+        }
+        Transition::None
+    }
+    fn handle_input(&mut self, _world: &mut World<B, Vertex>, ev: &InputEvent) -> Transition<World<B, Vertex>> {
+        if ev.is_pressed(Key::Return) || ev.is_pressed(Key::Space) {
+            Transition::Replace(Box::new(Gameplay))
+        } else {
+            Transition::None
+        }
+    }
+    fn render(&self, _world: &World<B, Vertex>, canvas: &mut Canvas) {
+        let (width, height) = (canvas.width, canvas.height);
+        canvas.rect([0.0, 0.0, width, height], PANEL.with_alpha(0.85));
+        if let Some(label) = self.label {
+            canvas.text(label, [width / 3.0, height * 0.6]);
+        }
+    }
+    fn pauses(&self) -> bool { true }
+}
+
+pub struct Gameplay;
+
+impl<B: gfx::Backend> GameState<World<B, Vertex>> for Gameplay {
+    fn handle_input(&mut self, world: &mut World<B, Vertex>, ev: &InputEvent) -> Transition<World<B, Vertex>> {
+        world.input.handle(ev);
+        match *ev {
+            InputEvent::Key {
+                key: Key::M,
+                state: ButtonState::Pressed,
+                ..
+            } => return Transition::Push(Box::new(PauseMenu::new())),
+            InputEvent::Key {
+                key: Key::B,
+                state: ButtonState::Pressed,
+                ..
+            } => return Transition::Push(Box::new(AssetBrowser::new())),
+            InputEvent::Key {
+                key: Key::Return,
+                state: ButtonState::Pressed,
+                ..
+            } => world.advance_dialogue(),
+            InputEvent::Key {
+                key: Key::D,
+                state: ButtonState::Pressed,
+                modifiers,
+            } if modifiers.ctrl && world.editor.enabled => world.duplicate_selected(),
+            InputEvent::MouseButton {
+                button: MouseButton::Left,
+                state: ButtonState::Pressed,
+            } => if world.editor.enabled {
+                world.editor_press();
+            } else {
+                let queue = world.input.is_held(Key::LShift) || world.input.is_held(Key::RShift);
+                world.click_move(queue);
+            },
+            InputEvent::MouseButton {
+                button: MouseButton::Left,
+                state: ButtonState::Released,
+            } => world.editor_release(),
+            InputEvent::MouseButton {
+                button: MouseButton::Right,
+                state: ButtonState::Pressed,
+            } => world.pick_select(),
+            InputEvent::Key {
+                key,
+                state: ButtonState::Pressed,
+                ..
+            } => world.handle_key(key),
+            InputEvent::Wheel {
+                delta,
+            } => world.wheel(delta),
+            InputEvent::CursorMoved {
+                x,
+                y,
+            } => {
+                world.cursor = Some((x, y));
+                world.editor_drag();
+                let dragging = world.input.is_button_held(MouseButton::Middle);
+                if let Some((yaw, pitch)) = world.mouse_look.motion((x, y), dragging) {
+                    world.camera.append_command(CameraCommand::Rotate(yaw, pitch));
+                }
+            },
+            InputEvent::Axis {
+                axis,
+                value,
+            } => {
+                debug!("axis motion {}: {}", axis, value);
+            },
+            _   => { }
+        }
+        Transition::None
+    }
+    // the filled spawn palette slots along the bottom, each over its number key
+    fn render(&self, world: &World<B, Vertex>, canvas: &mut Canvas) {
+        for (slot, template) in world.spawn_palette.entries() {
+            let left = 20.0 + (slot - 1) as f32 * (PALETTE_THUMBNAIL + 8.0);
+            let rect = [left, 20.0, left + PALETTE_THUMBNAIL, 20.0 + PALETTE_THUMBNAIL];
+            canvas.rect([rect[0] - 2.0, rect[1] - 2.0, rect[2] + 2.0, rect[3] + 2.0], PANEL.with_alpha(0.6));
+            canvas.thumbnail(template.object_id, rect);
+            canvas.markup(&slot.to_string(), [rect[0] + 4.0, rect[3] - 4.0], TEXT, BROWSER_TEXT_SCALE);
+        }
+    }
+}
+
+pub struct PauseMenu {
+    label: Option<TextHandle>,
+    // mouse look as it was before the menu turned it off, restored on the way out
+    mouse_look: bool,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        PauseMenu {
+            label: None,
+            mouse_look: false,
+        }
+    }
+    fn text<B: gfx::Backend>(world: &World<B, Vertex>) -> String {
+        let quality = world.pending_quality.unwrap_or(world.graphics.quality);
+        format!("{}\n{}\n{{size=0.6}}{} {}{{/size}}", world.tr("pause.title"), world.tr("pause.resume"), world.tr("pause.quality"), quality.name())
+    }
+}
+
+impl<B: gfx::Backend> GameState<World<B, Vertex>> for PauseMenu {
+    fn on_enter(&mut self, world: &mut World<B, Vertex>) {
+        // a grabbed cursor would leave no way to click out of the menu
+        self.mouse_look = world.mouse_look.enabled;
+        world.mouse_look.set_enabled(false);
+        let text = PauseMenu::text(world);
+        self.label = Some(world.static_text.create_static(&text, PAUSE_STYLE));
+    }
+    fn on_exit(&mut self, world: &mut World<B, Vertex>) {
+        if let Some(label) = self.label.take() {
+            world.static_text.remove(label);
+        }
+        world.mouse_look.set_enabled(self.mouse_look);
+    }
+    // built again only when the chosen quality or the language changed
+    fn update(&mut self, world: &mut World<B, Vertex>) -> Transition<World<B, Vertex>> {
+        if let Some(label) = self.label {
+            let text = PauseMenu::text(world);
+            world.static_text.set(label, &text, PAUSE_STYLE);
+        }
+        Transition::None
+    }
+    fn handle_input(&mut self, world: &mut World<B, Vertex>, ev: &InputEvent) -> Transition<World<B, Vertex>> {
+        if ev.is_pressed(Key::M) || ev.is_pressed(Key::Return) {
+            return Transition::Pop;
+        }
+        let tiers = [(Key::Key1, QualityPreset::Low), (Key::Key2, QualityPreset::Medium), (Key::Key3, QualityPreset::High)];
+        if let Some(&(_, quality)) = tiers.iter().find(|&&(key, _)| ev.is_pressed(key)) {
+            world.pending_quality = Some(quality);
+        }
+        Transition::None
+    }
+    fn render(&self, _world: &World<B, Vertex>, canvas: &mut Canvas) {
+        let (width, height) = (canvas.width, canvas.height);
+        canvas.rect([width * 0.025, height * 0.025, width * 0.975, height / 2.0], Color::rgba(0.03, 0.03, 0.03, 0.9));
+        if let Some(label) = self.label {
+            canvas.text(label, [40.0, height / 2.0]);
+        }
+    }
+    fn pauses(&self) -> bool { true }
+}
+
+// side of a spawn palette slot's thumbnail in pixels
+const PALETTE_THUMBNAIL: f32 = 64.0;
+// side of the selected object's thumbnail in the asset browser
+const BROWSER_THUMBNAIL: f32 = 192.0;
+// rows of the asset list shown at once
+const BROWSER_ROWS: usize = 16;
+const BROWSER_TEXT_SCALE: f32 = 0.5;
+
+// Debug overlay listing the database's objects; Return spawns the selected one
+// at the camera target. The game keeps running underneath.
+pub struct AssetBrowser {
+    entries: Vec<AssetSummary>,
+    selected: usize,
+    error: Option<String>,
+}
+
+impl AssetBrowser {
+    pub fn new() -> Self {
+        AssetBrowser {
+            entries: Vec::new(),
+            selected: 0,
+            error: None,
+        }
+    }
+    fn line(entry: &AssetSummary) -> String {
+        let textures = if entry.textures.is_empty() {
+            "-".to_string()
+        } else {
+            entry.textures.join(" ")
+        };
+        format!("{:>4}  meshes {}  vertices {}  joints {}  textures {}",
+            entry.object_id, entry.meshes, entry.vertices, entry.joints, textures)
+    }
+}
+
+impl<B: gfx::Backend> GameState<World<B, Vertex>> for AssetBrowser {
+    // read on every open, so rows imported while running show up
+    fn on_enter(&mut self, _world: &mut World<B, Vertex>) {
+        match query_asset_summaries(&open_connection()) {
+            Ok(entries) => {
+                self.selected = self.selected.min(entries.len().saturating_sub(1));
+                self.entries = entries;
+                self.error = None;
+            },
+            Err(e) => self.error = Some(format!("{:?}", e)),
+        }
+    }
+    fn handle_input(&mut self, world: &mut World<B, Vertex>, ev: &InputEvent) -> Transition<World<B, Vertex>> {
+        let key = match *ev {
+            InputEvent::Key { key, state: ButtonState::Pressed, .. } => key,
+            _ => return Transition::None,
+        };
+        match key {
+            Key::B => return Transition::Pop,
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1)),
+            Key::Return => if let Some(entry) = self.entries.get(self.selected) {
+                let target = world.camera().target;
+                world.pending_spawns.push((entry.object_id, target, 1.0));
+            },
+            _ => {},
+        }
+        Transition::None
+    }
+    fn render(&self, _world: &World<B, Vertex>, canvas: &mut Canvas) {
+        let (width, height) = (canvas.width, canvas.height);
+        let line_height = 48.0 * BROWSER_TEXT_SCALE;
+        let top = height - 20.0;
+        let panel_height = line_height * (BROWSER_ROWS as f32 + 2.0);
+        canvas.rect([20.0, top - panel_height, width * 0.7, top], PANEL.with_alpha(0.85));
+
+        let mut text = format!("objects {}  {{color=#ffcc33}}up/down{{/color}} select  {{color=#ffcc33}}return{{/color}} spawn  {{color=#ffcc33}}b{{/color}} close\n", self.entries.len());
+        if let Some(ref error) = self.error {
+            text.push_str(error);
+        }
+        // keep the selection inside the visible rows
+        let first = (self.selected + 1).saturating_sub(BROWSER_ROWS);
+        for (i, entry) in self.entries.iter().enumerate().skip(first).take(BROWSER_ROWS) {
+            let line = AssetBrowser::line(entry);
+            if i == self.selected {
+                text.push_str(&format!("{{color=#ffcc33}}{}{{/color}}\n", line));
+            } else {
+                text.push_str(&line);
+                text.push('\n');
+            }
+        }
+        canvas.markup(&text, [32.0, top - 8.0], TEXT, BROWSER_TEXT_SCALE);
+        if let Some(entry) = self.entries.get(self.selected) {
+            let (right, bottom) = (width * 0.7 - 12.0, top - 12.0 - BROWSER_THUMBNAIL);
+            canvas.thumbnail(entry.object_id, [right - BROWSER_THUMBNAIL, bottom, right, bottom + BROWSER_THUMBNAIL]);
+        }
+    }
+}
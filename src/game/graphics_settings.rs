@@ -0,0 +1,188 @@
+use gfx;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VsyncMode {
+    // wait for the display on every swap
+    On,
+    // swap as soon as a frame is ready, paced only by the fps cap
+    Off,
+    // no swap interval, but paced to the refresh rate by sleeping;
+    // a late frame is shown at once instead of waiting a whole extra refresh
+    Adaptive,
+}
+
+impl VsyncMode {
+    pub fn from_name(name: &str) -> Option<VsyncMode> {
+        match name {
+            "on" => Some(VsyncMode::On),
+            "off" => Some(VsyncMode::Off),
+            "adaptive" => Some(VsyncMode::Adaptive),
+            _ => None,
+        }
+    }
+}
+
+// assumed display rate; glutin does not report the monitor's
+const REFRESH_RATE: f32 = 60.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+}
+
+impl QualityPreset {
+    pub fn from_name(name: &str) -> Option<QualityPreset> {
+        match name {
+            "low" => Some(QualityPreset::Low),
+            "medium" => Some(QualityPreset::Medium),
+            "high" => Some(QualityPreset::High),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match *self {
+            QualityPreset::Low => "low",
+            QualityPreset::Medium => "medium",
+            QualityPreset::High => "high",
+        }
+    }
+}
+
+// What the lit shaders do their color math on. The textures and the target are both
+// Srgba8, so texels arrive decoded and what is written is encoded on the way out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LightingSpace {
+    // light and ambient scale the texel, specular goes on top, and vertex
+    // colors are decoded from sRGB like the textures
+    Linear,
+    // the shaders as they were, kept to compare against: ambient added over the
+    // lit texel, a floor under diffuse, and vertex colors taken as linear
+    Legacy,
+}
+
+impl LightingSpace {
+    pub fn from_name(name: &str) -> Option<LightingSpace> {
+        match name {
+            "linear" => Some(LightingSpace::Linear),
+            "legacy" => Some(LightingSpace::Legacy),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LightingSpace::Linear => "linear",
+            LightingSpace::Legacy => "legacy",
+        }
+    }
+    pub fn other(&self) -> LightingSpace {
+        match *self {
+            LightingSpace::Linear => LightingSpace::Legacy,
+            LightingSpace::Legacy => LightingSpace::Linear,
+        }
+    }
+    // The u_linear uniform: 1 for linear, 0 for legacy.
+    pub fn uniform(&self) -> f32 {
+        match *self {
+            LightingSpace::Linear => 1.0,
+            LightingSpace::Legacy => 0.0,
+        }
+    }
+}
+
+// Rendering options in one place. The World keeps the current copy and every
+// subsystem reads from it; see World::apply_quality for what changes at runtime.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GraphicsSettings {
+    // tier the fields below were last set from
+    pub quality: QualityPreset,
+    // generate a full mip chain for object textures at load
+    pub mipmaps: bool,
+    // maximum anisotropy; 1 keeps plain trilinear filtering
+    pub anisotropy: u8,
+    // added to the mip level textures are sampled at; positive is blurrier
+    pub lod_bias: f32,
+    // samples per pixel of the window, fixed once it is created
+    pub msaa: u16,
+    // shadow map edge in texels, for the shadow pass once there is one
+    pub shadow_resolution: u16,
+    // fades and dissolves between scenes; off cuts straight over
+    pub post_process: bool,
+    // draws depth alone before shading, for scenes where characters overlap a lot
+    pub depth_prepass: bool,
+    pub vsync: VsyncMode,
+    // frames per second the pacer sleeps down to; None leaves it to the swap
+    pub fps_cap: Option<f32>,
+    pub lighting: LightingSpace,
+    // clip planes the main and observer cameras start with; see the camera clip command
+    pub near: f32,
+    pub far: f32,
+    // depth runs from 1 at the near plane to 0 at the far one, as the pipelines
+    // are made with it, so it is fixed for the session
+    pub reversed_z: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            quality: QualityPreset::Medium,
+            mipmaps: true,
+            anisotropy: 4,
+            lod_bias: 0.0,
+            msaa: 0,
+            shadow_resolution: 1024,
+            post_process: true,
+            depth_prepass: false,
+            vsync: VsyncMode::On,
+            fps_cap: None,
+            lighting: LightingSpace::Linear,
+            near: 0.5,
+            far: 1000.0,
+            reversed_z: false,
+        }
+    }
+}
+
+impl GraphicsSettings {
+    pub fn sampler_info(&self) -> gfx::texture::SamplerInfo {
+        let filter = if self.anisotropy > 1 {
+            gfx::texture::FilterMethod::Anisotropic(self.anisotropy)
+        } else {
+            gfx::texture::FilterMethod::Trilinear
+        };
+        let mut info = gfx::texture::SamplerInfo::new(filter, gfx::texture::WrapMode::Clamp);
+        info.lod_bias = self.lod_bias.into();
+        info
+    }
+    // The tier's values; presentation options are left as they are.
+    pub fn with_quality(self, quality: QualityPreset) -> Self {
+        let (mipmaps, anisotropy, lod_bias, msaa, shadow_resolution, post_process, depth_prepass) = match quality {
+            QualityPreset::Low => (true, 1, 1.0, 0, 512, false, false),
+            QualityPreset::Medium => (true, 4, 0.0, 0, 1024, true, false),
+            QualityPreset::High => (true, 16, 0.0, 4, 2048, true, true),
+        };
+        GraphicsSettings {
+            quality,
+            mipmaps,
+            anisotropy,
+            lod_bias,
+            msaa,
+            shadow_resolution,
+            post_process,
+            depth_prepass,
+            ..self
+        }
+    }
+    // Whether the context is created with a swap interval.
+    pub fn context_vsync(&self) -> bool {
+        self.vsync == VsyncMode::On
+    }
+    // Rate the frame pacer holds to when vsync does not.
+    pub fn pacing_fps(&self) -> Option<f32> {
+        match self.vsync {
+            VsyncMode::On | VsyncMode::Off => self.fps_cap,
+            VsyncMode::Adaptive => Some(self.fps_cap.map(|fps| fps.min(REFRESH_RATE)).unwrap_or(REFRESH_RATE)),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// Input as the game sees it, independent of the windowing crate.
+// The platform layer translates native events into these.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M,
+    N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Up, Down, Left, Right,
+    Home, End, PageUp, PageDown,
+    Back, Delete, Insert,
+    Return, Escape, Tab, Space, Grave,
+    Comma, Period, LBracket, RBracket,
+    LShift, RShift, LControl, RControl, LAlt, RAlt,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ButtonState {
+    Pressed,
+    Released,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    Key {
+        key: Key,
+        state: ButtonState,
+        modifiers: Modifiers,
+    },
+    Character(char),
+    // An input method's text while it is being composed, with the cursor as a char
+    // index into it. Empty when the composition is dropped.
+    Preedit {
+        text: String,
+        cursor: usize,
+    },
+    // what the input method hands over once composed, in place of the preedit
+    Commit(String),
+    MouseButton {
+        button: MouseButton,
+        state: ButtonState,
+    },
+    // window coordinates in pixels from the top-left
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    Axis {
+        axis: u32,
+        value: f64,
+    },
+    // in lines, positive when the wheel turns away from the user
+    Wheel {
+        delta: f32,
+    },
+}
+
+impl InputEvent {
+    pub fn pressed(key: Key) -> Self {
+        InputEvent::Key {
+            key,
+            state: ButtonState::Pressed,
+            modifiers: Modifiers::default(),
+        }
+    }
+    pub fn is_pressed(&self, key: Key) -> bool {
+        match *self {
+            InputEvent::Key { key: k, state: ButtonState::Pressed, .. } => k == key,
+            _ => false,
+        }
+    }
+}
+
+// The keys and buttons down right now, for the tick loop to ask about rather than
+// waiting on events. Keys pressed and released since the last end_tick are kept too,
+// so a tap shorter than a tick is still seen. The OS's key repeat sends
+// Pressed again for a held key; that is not a new press.
+#[derive(Default)]
+pub struct InputState {
+    keys: Vec<Key>,
+    buttons: Vec<MouseButton>,
+    pressed: Vec<Key>,
+    released: Vec<Key>,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        InputState::default()
+    }
+    pub fn handle(&mut self, ev: &InputEvent) {
+        match *ev {
+            InputEvent::Key { key, state, .. } => {
+                let was_held = self.is_held(key);
+                match state {
+                    ButtonState::Pressed if !was_held => {
+                        self.keys.push(key);
+                        self.pressed.push(key);
+                    },
+                    ButtonState::Released if was_held => {
+                        self.keys.retain(|&k| k != key);
+                        self.released.push(key);
+                    },
+                    _ => { }
+                }
+            },
+            InputEvent::MouseButton { button, state: ButtonState::Pressed } => if !self.is_button_held(button) {
+                self.buttons.push(button);
+            },
+            InputEvent::MouseButton { button, state: ButtonState::Released } => self.buttons.retain(|&b| b != button),
+            _ => { }
+        }
+    }
+    pub fn is_held(&self, key: Key) -> bool {
+        self.keys.contains(&key)
+    }
+    pub fn is_button_held(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+    pub fn just_pressed(&self, key: Key) -> bool {
+        self.pressed.contains(&key)
+    }
+    pub fn just_released(&self, key: Key) -> bool {
+        self.released.contains(&key)
+    }
+    pub fn end_tick(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+    // The releases go unseen once the window loses focus or another state takes
+    // the input, so what was down counts as let go.
+    pub fn release_all(&mut self) {
+        self.released.extend(self.keys.drain(..));
+        self.buttons.clear();
+    }
+}
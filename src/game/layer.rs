@@ -0,0 +1,39 @@
+// Which cameras draw what. A mesh is on one or more layers and a camera draws
+// the meshes on any layer it has; the debug layer stands for the lines, handles
+// and outlines drawn over the scene rather than for meshes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LayerMask(u32);
+
+pub const WORLD: LayerMask = LayerMask(1);
+// meshes only thumbnails and other UI renders show
+pub const UI: LayerMask = LayerMask(2);
+pub const DEBUG: LayerMask = LayerMask(4);
+
+const NAMES: [(&'static str, LayerMask); 3] = [
+    ("world", WORLD),
+    ("ui", UI),
+    ("debug", DEBUG),
+];
+
+impl LayerMask {
+    pub fn from_name(name: &str) -> Option<LayerMask> {
+        NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, layer)| layer)
+    }
+    pub fn union(self, other: LayerMask) -> LayerMask {
+        LayerMask(self.0 | other.0)
+    }
+    pub fn with(self, other: LayerMask, on: bool) -> LayerMask {
+        if on { self.union(other) } else { LayerMask(self.0 & !other.0) }
+    }
+    pub fn intersects(self, other: LayerMask) -> bool {
+        self.0 & other.0 != 0
+    }
+    // as the console lists them: "world debug", or "none"
+    pub fn names(self) -> String {
+        let names: Vec<&str> = NAMES.iter()
+            .filter(|&&(_, layer)| self.intersects(layer))
+            .map(|&(name, _)| name)
+            .collect();
+        if names.is_empty() { "none".to_string() } else { names.join(" ") }
+    }
+}
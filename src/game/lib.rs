@@ -7,18 +7,168 @@ extern crate rusqlite;
 extern crate fnv;
 extern crate coarsetime;
 extern crate gfx_device_gl;
+#[cfg(feature = "vulkan")]
+extern crate gfx_device_vulkan;
+#[cfg(feature = "vulkan")]
+extern crate gfx_window_vulkan;
 extern crate freetype;
+extern crate serde_json;
+extern crate image;
+extern crate rayon;
+#[macro_use]
+extern crate log;
 
 mod models;
 mod font;
+mod text_input;
+mod console;
+mod locale;
+mod billboard;
+mod obj;
+mod platform;
+mod timing;
+mod input;
+mod net;
+mod camera_path;
+mod markup;
+mod texture_cache;
+mod animation;
+mod viewport;
+mod debug_draw;
+mod validation;
+mod mipmap;
+mod graphics_settings;
+mod texture_format;
+mod simulation;
+mod frame_graph;
+mod game_state;
+mod tween;
+mod event_bus;
+mod minimap;
+mod locomotion;
+mod time_of_day;
+mod fog;
+mod preset;
+mod mesh_split;
+mod transform_cache;
+mod scene_transition;
+mod sprite;
+mod text_effect;
+mod notifications;
+mod click_move;
+mod nav;
+mod logging;
+mod stats;
+mod behavior;
+mod trigger;
+mod spawn_palette;
+mod frame_arena;
+mod axes;
+mod culling;
+mod adapter;
+mod text_renderer;
+mod command_history;
+mod picking;
+mod thumbnail;
+mod asset_watch;
+mod schema;
+mod connection_pool;
+mod math;
+mod color;
+mod movement;
+mod screen_effect;
+mod editor;
+mod layer;
+mod environment;
+mod sim;
+mod bench;
+mod frame_dump;
+mod session;
 
 use rusqlite::Connection;
 use rusqlite::Error as RusqliteError;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use fnv::FnvHashMap as HashMap;
+use fnv::FnvHashSet as HashSet;
 
 use models::*;
 use font::*;
+use text_input::*;
+use console::*;
+use locale::*;
+use billboard::*;
+use obj::*;
+use viewport::*;
+use debug_draw::*;
+use culling::{Frustum, OcclusionBuffer, OccluderMesh, CULL_MARGIN, MAX_OCCLUDER_TRIANGLES};
+pub use platform::{BackendError, BackendKind, Platform, GlPlatform};
+pub use axes::{Axes, Handedness, Up};
+pub use adapter::{AdapterChoice, AdapterSummary};
+use adapter::adapter_order;
+use text_renderer::{TextHandle, TextRenderer};
+use command_history::{CommandHistory, HistoryTarget, Recordable};
+#[cfg(feature = "vulkan")]
+pub use platform::VulkanPlatform;
+pub use timing::PowerMode;
+use timing::*;
+pub use input::*;
+use platform::translate_event;
+pub use net::{NetError, NetRole};
+use net::Peer;
+use camera_path::*;
+use markup::*;
+pub use logging::{init_logging, install_panic_hook, LogFilters, LogHandle, LogInitError, PanicActions};
+use texture_cache::TextureCache;
+use animation::*;
+pub use validation::{AssetProblem, AssetValidationError};
+use validation::*;
+use mipmap::mip_chain;
+pub use graphics_settings::{GraphicsSettings, LightingSpace, QualityPreset, VsyncMode};
+pub use texture_format::{TextureDataFormat, TextureFormatError};
+use texture_format::decode_texture;
+use simulation::{SimMessage, UpdateThread};
+use frame_graph::*;
+use game_state::*;
+pub use tween::{Easing, TweenHandle, TweenStep, Tweens};
+pub use event_bus::{EventBus, EventKind, GameEvent, SubscriberId};
+use minimap::{MapCamera, Minimap};
+use locomotion::Locomotion;
+pub use time_of_day::{Lighting, TimeOfDay};
+pub use fog::{Fog, FogMode};
+pub use preset::Preset;
+use mesh_split::split_by_joints;
+use transform_cache::TransformCache;
+pub use scene_transition::TransitionKind;
+use scene_transition::{SceneTransition, TransitionStage};
+use screen_effect::{ScreenEffect, ScreenEffectKind, ScreenEffects};
+use editor::{Drag, Editor, Gizmo, AXES};
+use layer::LayerMask;
+use environment::sky_faces;
+pub use bench::run_benchmarks;
+use sim::{AnimationClock, AvatorCommand, Body, BodyCommand, Camera, Command, Dispatcher, Invoker, Level, ProjectionMode, StatsCommand, Translate};
+use sprite::{AnimatedSprite, SpritePlacement, SpriteSheet};
+use text_effect::{glyph_style, EffectText, TextEffect};
+use notifications::Notifications;
+use click_move::{ground_point, MovePlan};
+use movement::{Movement, MovementModel};
+use input::InputState;
+use picking::{PickHit, PickMesh, PickMode, Ray};
+use thumbnail::{Thumbnails, THUMBNAIL_SIZE};
+use asset_watch::AssetWatch;
+use schema::migrate;
+use connection_pool::{enable_wal, ConnectionPool};
+use session::SessionSave;
+use math::{ray_from_screen, screen_to_ndc, world_to_screen, Screen};
+use color::{Color, BLACK, WHITE, TRANSPARENT, TEXT, HIGHLIGHT, PROMPT, SPEECH, ERROR, WARNING, MUTED, SELECTION, PLAYER, REMOTE_PLAYER, OTHERS, SELECTED_OUTLINE};
+use nav::NavGrid;
+use stats::Stats;
+use behavior::{Behavior, BehaviorKind};
+use trigger::{Crossing, Trigger, Triggers, TriggerShape};
+use spawn_palette::{SpawnPalette, SpawnTemplate};
+use frame_arena::FrameArena;
+use frame_dump::{Draw, FrameDump};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
 
 use gfx::{
     Adapter,
@@ -43,13 +193,19 @@ use cgmath::{
     Vector3,
     Matrix4,
     One,
-    Zero,
+    Rad,
 };
 
 #[derive(Debug)]
 pub enum AppError {
     RusqliteError(RusqliteError),
-    FontError(FontError)
+    FontError(FontError),
+    LocaleError(LocaleError),
+    ImportError(ImportError),
+    InvalidAsset(AssetValidationError),
+    TextureFormatError(TextureFormatError),
+    // the database was upgraded by a newer build than this one
+    SchemaVersion { found: i32, supported: i32 },
 }
 
 impl From<RusqliteError> for AppError {
@@ -58,6 +214,18 @@ impl From<RusqliteError> for AppError {
 impl From<FontError> for AppError {
     fn from(e: FontError) -> AppError { AppError::FontError(e) }
 }
+impl From<LocaleError> for AppError {
+    fn from(e: LocaleError) -> AppError { AppError::LocaleError(e) }
+}
+impl From<ImportError> for AppError {
+    fn from(e: ImportError) -> AppError { AppError::ImportError(e) }
+}
+impl From<AssetValidationError> for AppError {
+    fn from(e: AssetValidationError) -> AppError { AppError::InvalidAsset(e) }
+}
+impl From<TextureFormatError> for AppError {
+    fn from(e: TextureFormatError) -> AppError { AppError::TextureFormatError(e) }
+}
 
 
 type View<R> = (
@@ -65,266 +233,1612 @@ type View<R> = (
     gfx::handle::DepthStencilView<R, DepthFormat>
 );
 
-pub struct App<R: gfx::Resources, B: gfx::Backend> {
-    world: World<B, Vertex>,
-    views: Vec<View<R>>,
-    device: gfx_device_gl::Device,
-    graphics_pool: gfx::GraphicsCommandPool<B>,
+// Offscreen copy of a frame, sampled by post passes.
+struct Capture<R: gfx::Resources> {
+    size: (u16, u16),
+    texture: gfx::handle::ShaderResourceView<R, [f32; 4]>,
+    view: View<R>,
+}
+
+// One object alone in a small target, framed by its own camera, for the UI.
+struct Thumbnail<R: gfx::Resources> {
+    object: GameObject<R, Vertex>,
+    camera: Camera<f32>,
+    target: Capture<R>,
+}
+
+// a light from the upper left front and a dark backdrop, whatever the hour in the world
+const THUMBNAIL_LIGHTING: Lighting = Lighting {
+    light: [0.4, 0.6, -0.7],
+    ambient: Color { r: 0.15, g: 0.15, b: 0.15, a: 1.0 },
+    sky: Color { r: 0.08, g: 0.08, b: 0.1, a: 1.0 },
+};
+
+type Res<P> = <<P as Platform>::Backend as gfx::Backend>::Resources;
+type SurfaceOf<P> = <<P as Platform>::Window as gfx::WindowExt<<P as Platform>::Backend>>::Surface;
+type SwapchainOf<P> = <SurfaceOf<P> as gfx::Surface<<P as Platform>::Backend>>::Swapchain;
+
+// Fields drop in declaration order: what the device made goes before the device,
+// and the window, which owns the context, goes last. See App::shutdown.
+pub struct App<P: Platform> {
+    size: (u32, u32),
+    world: World<P::Backend, Vertex>,
+    views: Vec<View<Res<P>>>,
+    // used round robin; frame slot i owns in_flight[i]
+    in_flight: Vec<InFlight<P::Backend>>,
+    next_slot: usize,
+
+    swap_chain: SwapchainOf<P>,
+    // kept to build the swapchain again when the window's size changes
+    surface: SurfaceOf<P>,
+    // set on resize; the swapchain is rebuilt before the next frame is drawn
+    swapchain_stale: bool,
+
+    graphics_queue: gfx::queue::GraphicsQueue<P::Backend>,
+    device: <P::Backend as gfx::Backend>::Device,
+    window: P::Window,
+    shut_down: bool,
+
+    focused: bool,
+    minimized: bool,
+    limiter: FrameLimiter,
+    idle_fps: f32,
+    // set once an embedder polls output events; nothing is recorded before that
+    output_events: Option<SubscriberId>,
+    window_events: Vec<glutin::WindowEvent>,
+}
+
+// What a frame holds until the GPU is done with it: its commands, the semaphores
+// ordering it against the swapchain and, through the frame slot, the World's per-frame buffers.
+struct InFlight<B: gfx::Backend> {
+    pool: gfx::GraphicsCommandPool<B>,
+    // signaled when the backbuffer is ready to be drawn to
+    frame_semaphore: gfx::handle::Semaphore<B::Resources>,
+    // signaled when the drawing is done and the backbuffer can be presented
+    draw_semaphore: gfx::handle::Semaphore<B::Resources>,
+    fence: gfx::handle::Fence<B::Resources>,
+    // whether fence was ever submitted; waiting on it before that would time out
+    submitted: bool,
+}
+
+// What World::begin_frame works out once for the drawing passes of a frame.
+struct FrameContext {
+    lighting: Lighting,
+    canvas: Canvas,
+}
+
+const IDLE_FPS: f32 = 10.0;
+// frames recorded ahead of the GPU; never more than there are backbuffers
+const MAX_FRAMES_IN_FLIGHT: usize = 2;
+// milliseconds shutdown waits for the last frame before dropping resources anyway
+const SHUTDOWN_TIMEOUT: u32 = 2_000;
+
+fn swapchain_config() -> gfx::SwapchainConfig {
+    gfx::SwapchainConfig::new()
+        .with_color::<ColorFormat>()
+        .with_depth_stencil::<DepthFormat>()
+}
+
+// What the game loop reports back to whoever embeds it.
+#[derive(Debug, Clone)]
+pub enum OutputEvent {
+    // as handed to App::handle_input, before translation
+    Window(glutin::WindowEvent),
+    Game(GameEvent),
+}
+
+// The part of App that main drives, independent of the backend.
+pub trait Application {
+    fn handle_input(&mut self, ev: glutin::WindowEvent);
+    fn inject_event(&mut self, ev: InputEvent);
+    fn poll_output_events(&mut self) -> Vec<OutputEvent>;
+    fn render(&mut self);
+    fn is_text_input_active(&self) -> bool;
+    fn poll_animation_events(&mut self) -> Vec<FiredAnimationEvent>;
+    fn is_idle(&self) -> bool;
+    fn set_fps_cap(&mut self, fps_cap: Option<f32>);
+    fn open_network(&mut self, role: &NetRole) -> Result<(), NetError>;
+    fn load_scene(&mut self, scene_id: i32);
+    fn run_command(&mut self, line: &str) -> Result<(), String>;
+    fn attach_log(&mut self, log: LogHandle);
+    fn attach_panic_actions(&mut self, actions: &PanicActions);
+    fn shutdown(&mut self);
+}
 
-    swap_chain: gfx_window_glutin::Swapchain,
+impl<P: Platform> Application for App<P> {
+    fn handle_input(&mut self, ev: glutin::WindowEvent) {
+        App::handle_input(self, ev)
+    }
+    fn inject_event(&mut self, ev: InputEvent) {
+        App::inject_event(self, ev)
+    }
+    fn poll_output_events(&mut self) -> Vec<OutputEvent> {
+        App::poll_output_events(self)
+    }
+    fn render(&mut self) {
+        App::render(self)
+    }
+    fn is_text_input_active(&self) -> bool {
+        App::is_text_input_active(self)
+    }
+    fn poll_animation_events(&mut self) -> Vec<FiredAnimationEvent> {
+        App::poll_animation_events(self)
+    }
+    fn is_idle(&self) -> bool {
+        App::is_idle(self)
+    }
+    fn set_fps_cap(&mut self, fps_cap: Option<f32>) {
+        App::set_fps_cap(self, fps_cap)
+    }
+    fn open_network(&mut self, role: &NetRole) -> Result<(), NetError> {
+        App::open_network(self, role)
+    }
+    fn load_scene(&mut self, scene_id: i32) {
+        App::load_scene(self, scene_id)
+    }
+    fn run_command(&mut self, line: &str) -> Result<(), String> {
+        App::run_command(self, line)
+    }
+    fn attach_log(&mut self, log: LogHandle) {
+        App::attach_log(self, log)
+    }
+    fn attach_panic_actions(&mut self, actions: &PanicActions) {
+        App::attach_panic_actions(self, actions)
+    }
+    fn shutdown(&mut self) {
+        App::shutdown(self)
+    }
+}
 
-    frame_semaphore: gfx::handle::Semaphore<R>,
-    draw_semaphore: gfx::handle::Semaphore<R>,
+pub fn create_app(
+    backend: BackendKind,
+    wb: glutin::WindowBuilder,
+    events_loop: &glutin::EventsLoop,
+    width: u32,
+    height: u32,
+    settings: GraphicsSettings,
+    gpu: Option<AdapterChoice>,
+) -> Result<Box<Application>, BackendError> {
+    let vsync = settings.context_vsync();
+    match backend {
+        BackendKind::Gl => Ok(Box::new(App::<GlPlatform>::new(
+            GlPlatform::create_window(wb, vsync, settings.msaa, events_loop), width, height, settings, gpu
+        ))),
+        // see VulkanPlatform
+        #[cfg(feature = "vulkan")]
+        BackendKind::Vulkan => Err(BackendError::Unsupported(backend)),
+    }
+}
 
-    frame_fence: gfx::handle::Fence<R>,
-    graphics_queue: gfx::queue::GraphicsQueue<B>,
+// What the backend can open, for --list-gpus. Adapters come with a surface,
+// so this makes a hidden window of its own.
+pub fn list_adapters(backend: BackendKind, events_loop: &glutin::EventsLoop) -> Vec<AdapterSummary> {
+    fn list<P: Platform>(mut window: P::Window) -> Vec<AdapterSummary> {
+        let (_, adapters) = window.get_surface_and_adapters();
+        adapters.iter().enumerate().map(|(i, adapter)| AdapterSummary::from_info(i, adapter.get_info())).collect()
+    }
+    let wb = glutin::WindowBuilder::new().with_visibility(false);
+    match backend {
+        BackendKind::Gl => list::<GlPlatform>(GlPlatform::create_window(wb, false, 0, events_loop)),
+        #[cfg(feature = "vulkan")]
+        BackendKind::Vulkan => list::<VulkanPlatform>(VulkanPlatform::create_window(wb, false, 0, events_loop)),
+    }
 }
 
-impl App<gfx_device_gl::Resources, gfx_device_gl::Backend> {
+impl<P: Platform> App<P> {
     pub fn new (
-        window: glutin::GlWindow,
+        mut window: P::Window,
         width: u32,
         height: u32,
-    ) -> App<gfx_device_gl::Resources, gfx_device_gl::Backend> {
+        settings: GraphicsSettings,
+        gpu: Option<AdapterChoice>,
+    ) -> App<P> {
         use gfx::Device;
 
-        let (mut surface, adapters) = gfx_window_glutin::Window::new(window).get_surface_and_adapters();
-        let gfx::Gpu { mut device, mut graphics_queues, .. } = 
-            adapters[0].open_with(|family, ty| {
-                (
-                    (ty.supports_graphics() && surface.supports_queue(&family)) as u32,
-                    gfx::QueueType::Graphics
-                )
-            });
-        let graphics_queue = graphics_queues.pop().expect("Unable to find a graphics queue.");
-
-        let config = gfx::SwapchainConfig::new()
-            .with_color::<ColorFormat>()
-            .with_depth_stencil::<DepthFormat>();
-        let mut swap_chain = surface.build_swapchain(config, &graphics_queue);
-
-        let views: Vec<_> = swap_chain
-            .get_backbuffers()
-            .iter()
-            .map(|&(ref color, ref ds)| {
-                let color_desc = gfx::texture::RenderDesc {
-                    channel: ColorFormat::get_format().1,
-                    level: 0,
-                    layer: None,
-                };
-                let rtv = device.view_texture_as_render_target_raw(color, color_desc).expect("rtv");
-                let ds_desc = gfx::texture::DepthStencilDesc {
-                    level: 0,
-                    layer: None,
-                    flags: gfx::texture::DepthStencilFlags::empty(),
-                };
-                let dsv = device.view_texture_as_depth_stencil_raw(
-                    ds.as_ref().expect("ds"),
-                    ds_desc
-                ).expect("dsv");
-
-                (Typed::new(rtv), Typed::new(dsv))
+        let (mut surface, adapters) = window.get_surface_and_adapters();
+        let summaries: Vec<_> = adapters.iter().enumerate()
+            .map(|(i, adapter)| AdapterSummary::from_info(i, adapter.get_info()))
+            .collect();
+        // an adapter with no queue that can draw to the window is passed over for the next
+        let mut opened = None;
+        for index in adapter_order(&summaries, gpu.as_ref()) {
+            let gfx::Gpu { device, mut graphics_queues, .. } = 
+                adapters[index].open_with(|family, ty| {
+                    (
+                        (ty.supports_graphics() && surface.supports_queue(&family)) as u32,
+                        gfx::QueueType::Graphics
+                    )
+                });
+            match graphics_queues.pop() {
+                Some(graphics_queue) => {
+                    opened = Some((index, device, graphics_queue));
+                    break;
+                },
+                None => warn!("adapter {} cannot draw to the window", summaries[index]),
+            }
+        }
+        let (adapter, mut device, graphics_queue) = opened.expect("Unable to find a graphics queue.");
+        info!("using adapter {}", summaries[adapter]);
+
+        let mut swap_chain = surface.build_swapchain(swapchain_config(), &graphics_queue);
+        let views = Self::backbuffer_views(&mut device, &mut swap_chain);
+
+        let in_flight: Vec<_> = (0..views.len().min(MAX_FRAMES_IN_FLIGHT).max(1))
+            .map(|_| InFlight {
+                pool: graphics_queue.create_graphics_pool(1),
+                frame_semaphore: device.create_semaphore(),
+                draw_semaphore: device.create_semaphore(),
+                fence: device.create_fence(false),
+                submitted: false,
             }).collect();
-
-        let graphics_pool = graphics_queue.create_graphics_pool(1);
             
-        let world = World::new(
+        let mut world = World::new(
             &mut device,
             (width as f32) / (height as f32),
+            settings,
+            in_flight.len(),
         );
-
-        let frame_semaphore = device.create_semaphore();
-        let draw_semaphore = device.create_semaphore();
-        let frame_fence = device.create_fence(false);
+        world.adapters = summaries;
+        world.adapter = adapter;
 
         App {
+            window,
+            size: (width, height),
             device,
             world,
-            frame_semaphore,
-            draw_semaphore,
-            frame_fence,
-            graphics_pool,
+            in_flight,
+            next_slot: 0,
             swap_chain,
+            surface,
+            swapchain_stale: false,
             graphics_queue,
             views,
+            focused: true,
+            minimized: false,
+            limiter: FrameLimiter::new(settings.pacing_fps()),
+            idle_fps: IDLE_FPS,
+            output_events: None,
+            window_events: Vec::new(),
+            shut_down: false,
         }
     }
 
     pub fn handle_input(&mut self, ev :glutin::WindowEvent) {
-        self.world.handle_input(ev)
+        let locked = self.world.mouse_look.enabled;
+        match ev {
+            // minimizing reports a zero sized window
+            glutin::WindowEvent::Resized(width, height) => {
+                self.minimized = width == 0 || height == 0;
+                if !self.minimized {
+                    self.size = (width, height);
+                    P::resize(&self.window, width, height);
+                    self.swapchain_stale = true;
+                }
+                self.update_suspension();
+            },
+            glutin::WindowEvent::Focused(focused) => {
+                self.focused = focused;
+                if !focused {
+                    self.world.input.release_all();
+                }
+                self.update_suspension();
+            },
+            _ => { }
+        }
+        let input = translate_event(&ev);
+        let is_mouse_move = match input {
+            Some(InputEvent::CursorMoved { .. }) => true,
+            _ => false,
+        };
+        if let Some(input) = input {
+            self.world.handle_input(input);
+        }
+        if self.output_events.is_some() {
+            self.window_events.push(ev);
+        }
+
+        if !self.focused && self.world.mouse_look.enabled {
+            self.world.mouse_look.set_enabled(false);
+        }
+        if self.world.mouse_look.enabled != locked {
+            self.apply_cursor_state();
+        } else if locked && is_mouse_move {
+            self.center_cursor();
+        }
+    }
+
+    // Feeds input as if it came from the window, for editors and test harnesses
+    // that drive the loop themselves.
+    pub fn inject_event(&mut self, ev: InputEvent) {
+        self.world.handle_input(ev);
+    }
+
+    // Window events and world events since the last poll. The first call starts
+    // the recording, so it only returns what comes after it.
+    pub fn poll_output_events(&mut self) -> Vec<OutputEvent> {
+        let subscriber = match self.output_events {
+            Some(subscriber) => subscriber,
+            None => {
+                self.output_events = Some(self.world.events.subscribe(&[]));
+                return Vec::new();
+            },
+        };
+        let mut events: Vec<OutputEvent> = self.window_events.drain(..).map(OutputEvent::Window).collect();
+        events.extend(self.world.events.drain(subscriber).into_iter().map(OutputEvent::Game));
+        events
+    }
+
+    pub fn poll_animation_events(&mut self) -> Vec<FiredAnimationEvent> {
+        let subscriber = self.world.app_events;
+        self.world.events.drain(subscriber).into_iter().filter_map(|event| match event {
+            GameEvent::AnimationEventFired(e) => Some(e),
+            _ => None,
+        }).collect()
+    }
+
+    pub fn is_text_input_active(&self) -> bool {
+        self.world.is_text_input_active()
+    }
+
+    // Nothing animates on its own while unfocused or paused.
+    pub fn is_idle(&self) -> bool {
+        !self.focused || self.minimized || self.world.state_pauses()
+    }
+
+    fn update_suspension(&mut self) {
+        if self.focused && !self.minimized {
+            self.world.resume();
+        } else {
+            self.world.suspend();
+        }
+    }
+
+    pub fn set_fps_cap(&mut self, fps_cap: Option<f32>) {
+        self.limiter.fps_cap = fps_cap;
+    }
+
+    // Feeds the console's log page; see init_logging.
+    pub fn attach_log(&mut self, log: LogHandle) {
+        self.world.log = Some(log);
+    }
+    // A panic writes the session as shutdown would, as of the last frame.
+    pub fn attach_panic_actions(&mut self, actions: &PanicActions) {
+        let session = self.world.session.clone();
+        actions.add(move || session.write_from_panic());
+    }
+
+    // Local avator commands are mirrored to the peer, whose commands drive the avator
+    // agreed on in the handshake; listening, it is picked here.
+    pub fn open_network(&mut self, role: &NetRole) -> Result<(), NetError> {
+        let avator = self.world.free_avator();
+        self.world.peer = Some(Peer::open(role, avator)?);
+        Ok(())
+    }
+
+    // Swaps the scene in on the next frame, without a transition.
+    pub fn load_scene(&mut self, scene_id: i32) {
+        self.world.load_scene(scene_id, TransitionKind::Cut);
+    }
+
+    // Runs a line as if typed into the console, for startup scripts and test harnesses.
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let command = console::parse(line)?;
+        self.world.console.print(format!("> {}", line));
+        self.world.run_console_command(command);
+        Ok(())
+    }
+
+    pub fn set_mouse_sensitivity(&mut self, sensitivity: f32) {
+        self.world.mouse_look.sensitivity = sensitivity;
+    }
+
+    pub fn set_mouse_invert_y(&mut self, invert_y: bool) {
+        self.world.mouse_look.invert_y = invert_y;
+    }
+
+    // Units per wheel line when dollying and radians per line with Control held.
+    pub fn set_wheel_speed(&mut self, dolly_speed: f32, fov_speed: f32) {
+        self.world.wheel_zoom.dolly_speed = dolly_speed;
+        self.world.wheel_zoom.fov_speed = fov_speed;
+    }
+
+    fn apply_cursor_state(&mut self) {
+        let state = if self.world.mouse_look.enabled {
+            glutin::CursorState::Grab
+        } else {
+            glutin::CursorState::Normal
+        };
+        if let Err(e) = P::set_cursor_state(&self.window, state) {
+            warn!("failed to change cursor state: {}", e);
+        }
+        if self.world.mouse_look.enabled {
+            self.center_cursor();
+        }
+    }
+
+    fn center_cursor(&mut self) {
+        let center = ((self.size.0 / 2) as i32, (self.size.1 / 2) as i32);
+        if P::set_cursor_position(&self.window, center.0, center.1).is_ok() {
+            self.world.mouse_look.last_position = Some((center.0 as f64, center.1 as f64));
+        }
     }
 
     fn pre_render(&mut self) {
-        self.world.execute_all_commands()
+        self.world.update_states();
+        self.world.apply_pending_spawns(&mut self.device);
+        self.world.apply_reloads(&mut self.device);
+        self.world.apply_scene_swap(&mut self.device);
+        self.world.apply_pending_sprites(&mut self.device);
+        self.world.apply_quality(&mut self.device);
+        self.world.execute_all_commands();
+        self.world.receive_notifications();
+        self.world.update_animation_events();
+        self.world.update_triggers();
+        self.world.events.dispatch();
+        self.world.run_interactions();
+        self.world.refresh_session();
     }
 
     pub fn render(&mut self) {
+        if self.shut_down {
+            return;
+        }
+        if self.minimized {
+            self.limiter.wait_for(Some(self.idle_fps));
+            return;
+        }
         self.pre_render();
 
-        let frame = self.swap_chain.acquire_frame(FrameSync::Semaphore(&self.frame_semaphore));
-        let view = self.views[frame.id()].clone();
+        // This gfx reports neither out-of-date nor suboptimal from acquire or present,
+        // so a backbuffer that no longer matches the window is taken as the sign.
+        if self.swapchain_stale || self.backbuffer_size() != Some(self.size) {
+            self.recreate_swapchain();
+            if self.views.is_empty() {
+                self.limiter.wait_for(Some(self.idle_fps));
+                return;
+            }
+        }
+
+        let slot = self.next_slot;
+        self.next_slot = (slot + 1) % self.in_flight.len();
         {
-            let mut encoder = self.graphics_pool.acquire_graphics_encoder();
+            let in_flight = &mut self.in_flight[slot];
+            // only the frame that last used this slot is waited for; the others keep running
+            if in_flight.submitted {
+                self.device.wait_for_fences(&[&in_flight.fence], gfx::WaitFor::All, 1_000_000);
+            }
+            self.graphics_queue.cleanup();
+            in_flight.pool.reset();
+
+            let mut encoder = in_flight.pool.acquire_graphics_encoder();
+            // the backbuffer and what World::begin_frame worked out, once acquired
+            let mut frame = None;
+            for pass in self.world.frame_graph.order().to_vec() {
+                match pass {
+                    PassId::Acquire => {
+                        let acquired = self.swap_chain.acquire_frame(FrameSync::Semaphore(&in_flight.frame_semaphore));
+                        let view = self.views[acquired.id()].clone();
+                        let context = self.world.begin_frame(&view, slot, &mut encoder, &mut self.device);
+                        frame = Some((view, context));
+                    },
+                    PassId::Flush => {
+                        self.world.end_frame();
+                        encoder.synced_flush(&mut self.graphics_queue, &[&in_flight.frame_semaphore], &[&in_flight.draw_semaphore], Some(&in_flight.fence))
+                            .expect("Colud not flush encoder");
+                        in_flight.submitted = true;
+                    },
+                    PassId::Present => self.swap_chain.present(&mut self.graphics_queue, &[&in_flight.draw_semaphore]),
+                    pass => {
+                        // the graph puts every drawing pass after Acquire
+                        let &(ref view, ref context) = frame.as_ref().expect("a pass drew before the backbuffer was acquired");
+                        self.world.render_pass(pass, view, context, &mut encoder, &mut self.device);
+                    },
+                }
+            }
+        }
+
+        if self.is_idle() {
+            let idle_fps = self.limiter.fps_cap.map(|fps| fps.min(self.idle_fps)).unwrap_or(self.idle_fps);
+            self.limiter.wait_for(Some(idle_fps));
+        } else {
+            self.limiter.wait();
+        }
+    }
+
+    fn wait_in_flight(&mut self, timeout: u32) -> bool {
+        let fences: Vec<_> = self.in_flight.iter()
+            .filter(|in_flight| in_flight.submitted)
+            .map(|in_flight| &in_flight.fence)
+            .collect();
+        fences.is_empty() || self.device.wait_for_fences(&fences, gfx::WaitFor::All, timeout)
+    }
+
+    fn backbuffer_size(&self) -> Option<(u32, u32)> {
+        self.views.first().map(|&(ref color, _)| {
+            let (width, height, _, _) = color.get_dimensions();
+            (width as u32, height as u32)
+        })
+    }
+
+    // Builds the swapchain and its views again for the window's current size.
+    // Frames still drawing to the old backbuffers are waited for first.
+    fn recreate_swapchain(&mut self) {
+        if !self.wait_in_flight(SHUTDOWN_TIMEOUT) {
+            warn!("the frames in flight did not finish within {}ms; recreating the swapchain anyway", SHUTDOWN_TIMEOUT);
+        }
+        self.graphics_queue.cleanup();
+        self.views.clear();
+        self.swap_chain = self.surface.build_swapchain(swapchain_config(), &self.graphics_queue);
+        self.views = Self::backbuffer_views(&mut self.device, &mut self.swap_chain);
+        self.swapchain_stale = false;
+        match self.backbuffer_size() {
+            Some(size) if size != self.size => {
+                warn!("the swapchain came back {:?} for a {:?} window", size, self.size);
+                // keeps the new size, so this is not rebuilt again every frame
+                self.size = size;
+            },
+            Some(_) => info!("swapchain recreated at {}x{}", self.size.0, self.size.1),
+            None => warn!("the swapchain came back without backbuffers; skipping frames"),
+        }
+    }
 
-            encoder.clear(&view.0.clone(), CLEAR_COLOR);
-            encoder.clear_depth(&view.1.clone(), 1.0);
+    fn backbuffer_views(
+        device: &mut <P::Backend as gfx::Backend>::Device,
+        swap_chain: &mut SwapchainOf<P>,
+    ) -> Vec<View<Res<P>>> {
+        swap_chain
+            .get_backbuffers()
+            .iter()
+            .map(|&(ref color, ref ds)| {
+                let color_desc = gfx::texture::RenderDesc {
+                    channel: ColorFormat::get_format().1,
+                    level: 0,
+                    layer: None,
+                };
+                let rtv = device.view_texture_as_render_target_raw(color, color_desc).expect("rtv");
+                let ds_desc = gfx::texture::DepthStencilDesc {
+                    level: 0,
+                    layer: None,
+                    flags: gfx::texture::DepthStencilFlags::empty(),
+                };
+                let dsv = device.view_texture_as_depth_stencil_raw(
+                    ds.as_ref().expect("ds"),
+                    ds_desc
+                ).expect("dsv");
 
-            self.world.render(&view, &mut encoder, &mut self.device);
+                (Typed::new(rtv), Typed::new(dsv))
+            }).collect()
+    }
 
-            encoder.synced_flush(&mut self.graphics_queue, &[&self.frame_semaphore], &[&self.draw_semaphore], Some(&self.frame_fence))
-                .expect("Colud not flush encoder");
+    // Waits for the GPU, then writes what would otherwise be lost. Safe to call more than once;
+    // dropping the App calls it too, which covers unwinding from a panic.
+    pub fn shutdown(&mut self) {
+        if self.shut_down {
+            return;
+        }
+        self.shut_down = true;
+        if !self.wait_in_flight(SHUTDOWN_TIMEOUT) {
+            warn!("the frames in flight did not finish within {}ms", SHUTDOWN_TIMEOUT);
         }
-        self.swap_chain.present(&mut self.graphics_queue, &[&self.draw_semaphore]);
-        self.device.wait_for_fences(&[&self.frame_fence], gfx::WaitFor::All, 1_000_000);
         self.graphics_queue.cleanup();
-        self.graphics_pool.reset();
+        for in_flight in &mut self.in_flight {
+            in_flight.pool.reset();
+        }
+        self.world.shutdown();
+        info!("shut down");
+    }
+}
+
+impl<P: Platform> Drop for App<P> {
+    fn drop(&mut self) {
+        self.shutdown();
     }
 }
 
 
-enum AvatorCommand {
-    Move (Vector3<f32>),
+// Which avators a command goes to. Selected and Group are resolved to keys where
+// the selection and tags are kept, before the command reaches the update thread.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AvatorTarget {
+    One (i32),
+    Selected,
+    All,
+    Group (String),
 }
 enum CameraCommand {
     Move (Vector3<f32>),
     LookAt (Point3<f32>),
+    Rotate (Rad<f32>, Rad<f32>),
+    // turns up around the line of sight
+    Roll (Rad<f32>),
+    SetUp (Vector3<f32>),
+    SetProjection (ProjectionMode),
+    SetOrthoHeight (f32),
+    // looks straight down at the target from where it is
+    TopDown,
+    PlayPath (i32),
+    StopPath,
+    SetFovy (Rad<f32>),
+    // near and far planes
+    SetClip (f32, f32),
+    // position, target and vertical field of view all at once, as presets load
+    SetView (Point3<f32>, Point3<f32>, Rad<f32>),
+}
+// What World tweens drive; each step applies its share of the vector.
+#[derive(Clone)]
+enum TweenAction {
+    MoveCamera(Vector3<f32>),
+    // read by value while running; its end moves the scene transition on
+    SceneFade,
+    // a toast's whole life; see Toast::alpha
+    Toast,
+    // read by value while running, as SceneFade
+    ScreenEffect,
 }
+// update rate of tweens and timers
+const FIXED_STEP: f64 = 1.0 / 60.0;
+const CAMERA_MOVE_DURATION: f32 = 0.1;
+// a fade to black spends half of this going out and half coming back
+const SCENE_TRANSITION_DURATION: f32 = 1.0;
+// what the console's flash and fade take without a duration given
+const FLASH_SECONDS: f32 = 0.3;
+const FADE_SECONDS: f32 = 1.0;
+// where a duplicate goes, from the object it was made from
+const DUPLICATE_OFFSET: [f32; 3] = [1.0, 1.0, 0.0];
+// the local avator taking damage
+const DAMAGE_FLASH: Color = Color { r: 0.8, g: 0.0, b: 0.0, a: 0.4 };
+// how long a toast stays up by default
+const TOAST_SECONDS: f32 = 2.5;
+// units per second the avator walks to a clicked point, and the height of the ground it is clicked on
+const CLICK_MOVE_SPEED: f32 = 3.0;
+// units per second NPCs walk at, before their Stats speed
+const NPC_WALK_SPEED: f32 = 1.5;
+const GROUND_HEIGHT: f32 = 0.0;
+// the navigation grid: its corner, cell edge and cells per side
+const NAV_ORIGIN: [f32; 2] = [-50.0, -50.0];
+const NAV_CELL_SIZE: f32 = 0.5;
+const NAV_CELLS: usize = 200;
+// the whole texture, for billboards that are not sprites
+const FULL_UV: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+// the marker's opaque middle, for plain bars
+const SOLID_UV: [f32; 4] = [0.5, 0.5, 0.5, 0.5];
+
 enum SystemCommand {
     Exit
 }
 
-trait Command<T> {
-    fn get_level(&self) -> Level;
-    fn execute(&self, &mut T);
+struct System {
+    timer: coarsetime::Instant,
+    paused_at: Option<coarsetime::Instant>,
+    // total time spent paused, in seconds
+    paused_total: f64,
 }
 
-struct Invoker<Cmd, T> {
-    commands: Vec<Cmd>,
-    target: T,
-    current_index: usize,
+impl System {
+    fn new() -> Self {
+        System {
+            timer: coarsetime::Instant::now(),
+            paused_at: None,
+            paused_total: 0.0,
+        }
+    }
+    // Animation time, which does not advance while paused.
+    fn elapsed(&self) -> f64 {
+        let now = match self.paused_at {
+            Some(at) => at.duration_since(self.timer).as_f64(),
+            None => self.timer.elapsed().as_f64(),
+        };
+        now - self.paused_total
+    }
+    fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(coarsetime::Instant::now());
+        }
+    }
+    fn resume(&mut self) {
+        if let Some(at) = self.paused_at.take() {
+            self.paused_total += at.elapsed().as_f64();
+        }
+    }
 }
 
-struct System {
-    timer: coarsetime::Instant,
+struct MouseLook {
+    enabled: bool,
+    // radians per pixel of cursor movement
+    sensitivity: f32,
+    invert_y: bool,
+    last_position: Option<(f64, f64)>,
+}
+
+impl MouseLook {
+    fn new() -> Self {
+        MouseLook {
+            enabled: false,
+            sensitivity: 0.003,
+            invert_y: false,
+            last_position: None,
+        }
+    }
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.last_position = None;
+    }
+    // Looks while enabled, or while dragging, as with the middle button held.
+    fn motion(&mut self, position: (f64, f64), dragging: bool) -> Option<(Rad<f32>, Rad<f32>)> {
+        let last = std::mem::replace(&mut self.last_position, Some(position));
+        if !self.enabled && !dragging {
+            return None;
+        }
+        last.map(|(x, y)| {
+            let dx = (position.0 - x) as f32;
+            let dy = (position.1 - y) as f32;
+            let pitch = if self.invert_y { dy } else { -dy };
+            (Rad(-dx * self.sensitivity), Rad(pitch * self.sensitivity))
+        })
+    }
+}
+
+// Wheel dollies the main camera toward its target, or changes the field of view
+// while Control is held. An orthographic camera zooms its extents instead.
+struct WheelZoom {
+    // units per wheel line
+    dolly_speed: f32,
+    // radians per wheel line
+    fov_speed: f32,
+    // distance to the target the camera stays within
+    min_distance: f32,
+    max_distance: f32,
+    min_fovy: Rad<f32>,
+    max_fovy: Rad<f32>,
+    // how much one wheel line scales the orthographic extents
+    ortho_zoom: f32,
+    min_ortho_height: f32,
+    max_ortho_height: f32,
+}
+
+impl WheelZoom {
+    fn new() -> Self {
+        WheelZoom {
+            dolly_speed: 4.0,
+            fov_speed: 1.0f32.to_radians(),
+            min_distance: 10.0,
+            max_distance: 500.0,
+            min_fovy: Rad(5.0f32.to_radians()),
+            max_fovy: Rad(90.0f32.to_radians()),
+            ortho_zoom: 1.1,
+            min_ortho_height: 1.0,
+            max_ortho_height: 1000.0,
+        }
+    }
+    // How far to move along the view direction, kept inside the distance limits.
+    fn dolly(&self, distance: f32, delta: f32) -> f32 {
+        let wanted = distance - delta * self.dolly_speed;
+        distance - wanted.max(self.min_distance).min(self.max_distance)
+    }
+    // Turning the wheel away narrows the view, like dollying in.
+    fn fovy(&self, fovy: Rad<f32>, delta: f32) -> Rad<f32> {
+        Rad((fovy.0 - delta * self.fov_speed).max(self.min_fovy.0).min(self.max_fovy.0))
+    }
+    fn ortho_height(&self, height: f32, delta: f32) -> f32 {
+        (height * self.ortho_zoom.powf(-delta)).max(self.min_ortho_height).min(self.max_ortho_height)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiredAnimationEvent {
+    pub object_id: i32,
+    pub name: String,
+    pub time: f32,
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum RenderMode {
+    Solid,
+    Wireframe,
+    Normals,
+}
+
+impl RenderMode {
+    fn all() -> [RenderMode; 3] {
+        [RenderMode::Solid, RenderMode::Wireframe, RenderMode::Normals]
+    }
+    fn next(&self) -> Self {
+        match *self {
+            RenderMode::Solid => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::Normals,
+            RenderMode::Normals => RenderMode::Solid,
+        }
+    }
+}
+
+// Per mesh, from the Unlit column of Mesh.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Shading {
+    Lit,
+    // texture times vertex color, for flat debug geometry and stylized assets
+    Unlit,
+}
+
+impl Shading {
+    fn all() -> [Shading; 2] {
+        [Shading::Lit, Shading::Unlit]
+    }
+}
+
+// How the shaded pass tests depth.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum DepthTest {
+    // nearest so far wins and is written
+    Write,
+    // after a depth pre-pass: only the fragment that won it is shaded
+    Equal,
+}
+
+impl DepthTest {
+    fn all() -> [DepthTest; 2] {
+        [DepthTest::Write, DepthTest::Equal]
+    }
+    fn state(&self, reversed_z: bool) -> gfx::state::Depth {
+        match *self {
+            DepthTest::Write => nearer_or_equal(reversed_z, true),
+            DepthTest::Equal => gfx::state::Depth {
+                fun: gfx::state::Comparison::Equal,
+                write: false,
+            },
+        }
+    }
+}
+
+// LESS_EQUAL_WRITE or LESS_EQUAL_TEST as the pipelines are declared, or with
+// reversed-Z their mirror, where nearer is greater.
+fn nearer_or_equal(reversed_z: bool, write: bool) -> gfx::state::Depth {
+    gfx::state::Depth {
+        fun: if reversed_z { gfx::state::Comparison::GreaterEqual } else { gfx::state::Comparison::LessEqual },
+        write,
+    }
 }
 
+// What a draw of the slice covers, as the frame dump counts it.
+fn slice_count<R: gfx::Resources>(slice: &gfx::Slice<R>) -> u32 {
+    slice.end - slice.start
+}
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum WorldState {
-    Render,
-    Pose,
+// What the depth buffer clears to: as far as it goes.
+fn depth_clear(reversed_z: bool) -> f32 {
+    if reversed_z { 0.0 } else { 1.0 }
 }
 
 struct World<B: gfx::Backend, V> {
     camera: Invoker<CameraCommand, Camera<f32>>,
+    // second camera for split-screen debugging
+    observer: Invoker<CameraCommand, Camera<f32>>,
+    layout: ViewportLayout,
+    view_cameras: Vec<ViewportCamera>,
+    next_snapshot: u64,
+    // as of the last frame, for mapping the cursor onto view_cameras
+    screen_size: (u16, u16),
     avators: Invoker<AvatorCommand, HashMap<i32, GameObject<B::Resources, V>>>,
     system: Invoker<SystemCommand, System>,
+    mouse_look: MouseLook,
+    // window coordinates of the cursor, once it has moved
+    cursor: Option<(f64, f64)>,
+    // what Gameplay has seen held; other states take the input without it
+    input: InputState,
+    // click to move, walked in fixed steps
+    move_plan: MovePlan,
+    // the direction keys' velocity; integrated on the fixed step
+    movement: Movement,
+    // as authored; objects are added as obstacles when a path is planned
+    nav: NavGrid,
+    // damage and healing by object key, applied with the World level
+    stat_commands: Vec<(i32, StatsCommand)>,
+    // commands for avators other than the player's, sent to the update thread in their level's turn
+    body_commands: Vec<BodyCommand>,
+    // decides which command levels run each frame
+    dispatcher: Dispatcher,
+    wheel_zoom: WheelZoom,
+    time_of_day: TimeOfDay,
+    fog: Fog,
+    graphics: GraphicsSettings,
     sampler: gfx::handle::Sampler<B::Resources>,
-
-    pso: gfx::PipelineState<B::Resources, pipe_w::Meta>,
+    environment: Environment<B::Resources>,
+    // a CameraBlock per frame slot, written before each view draws
+    camera_buffers: Vec<gfx::handle::Buffer<B::Resources, CameraBlock>>,
+
+    // skinning pipelines keyed by palette size, render mode, shading and depth test
+    psos: HashMap<(usize, RenderMode, Shading, DepthTest), gfx::PipelineState<B::Resources, pipe_w::Meta>>,
+    // depth-only pipelines for the pre-pass, keyed by palette size
+    depth_psos: HashMap<usize, gfx::PipelineState<B::Resources, pipe_d::Meta>>,
+    render_mode: RenderMode,
+    // inverted hull pipelines for the selection outline, keyed by palette size
+    outline_psos: HashMap<usize, gfx::PipelineState<B::Resources, pipe_o::Meta>>,
+    // outlined avator, picked with the right button or chosen with select
+    selected: Option<i32>,
+    pick_mode: PickMode,
     pso_w2: gfx::PipelineState<B::Resources, pipe_w2::Meta>,
     pso_p: gfx::PipelineState<B::Resources, pipe_p::Meta>,
     pso_pt: gfx::PipelineState<B::Resources, pipe_pt::Meta>,
+    pso_b: gfx::PipelineState<B::Resources, pipe_b::Meta>,
+    pso_l: gfx::PipelineState<B::Resources, pipe_l::Meta>,
+    pso_f: gfx::PipelineState<B::Resources, pipe_f::Meta>,
+    debug_flags: DebugFlags,
+    minimap: Minimap,
+
+    billboard_quad: (gfx::handle::Buffer<B::Resources, VertexB>, gfx::Slice<B::Resources>),
+    // covers the screen in NDC, for post passes
+    fullscreen_quad: (gfx::handle::Buffer<B::Resources, VertexB>, gfx::Slice<B::Resources>),
+    capture: Option<Capture<B::Resources>>,
+    scene_transition: Option<SceneTransition>,
+    // the scene last loaded, which placement writes to
+    scene_id: Option<i32>,
+    editor: Editor,
+    screen_effects: ScreenEffects,
+    marker_texture: gfx::handle::ShaderResourceView<B::Resources, [f32; 4]>,
+    billboards: Vec<Billboard>,
+    // sprite sheet textures are held in the TextureCache like object textures
+    sprites: Vec<(AnimatedSprite, gfx::handle::ShaderResourceView<B::Resources, [f32; 4]>)>,
+    pending_sprites: Vec<AnimatedSprite>,
+    // a line of dialogue along the bottom of the screen, set from the console with say
+    dialogue: Option<EffectText>,
+    // applied before the next frame, where the device is at hand
+    pending_quality: Option<QualityPreset>,
+    show_avator_markers: bool,
+
+    events: EventBus,
+    // inbox behind App::poll_animation_events
+    app_events: SubscriberId,
+    // trigger crossings, for the interactions the World runs itself
+    interaction_events: SubscriberId,
+    triggers: Triggers,
+    // the prompt of the trigger the avator stands in, by trigger id
+    interaction: Option<(i32, EffectText)>,
+    // None until animation events are first checked
+    last_event_time: Option<f64>,
 
     font: Font,
+    // labels the game states keep across frames, built with font
+    static_text: TextRenderer<Entry<B::Resources, Vertex, f32>>,
+    // objects the asset browser and the spawn palette show, rendered on their own
+    thumbnails: Thumbnails<Thumbnail<B::Resources>>,
+    locale: Locale,
+    console: Console,
+    // None until App::attach_log; the console's log page is empty without it
+    log: Option<LogHandle>,
+    save_prompt: Option<TextInput>,
+
+    // screens from the bottom up; the last one gets input
+    states: Vec<Box<GameState<World<B, V>>>>,
+    // stack depth to return to when the window comes back
+    suspended: Option<usize>,
+    // sample index while the timeline is paused for scrubbing
+    scrub: Option<usize>,
+    peer: Option<Peer>,
+    // simulates avators and samples their poses
+    update: UpdateThread,
+    frame_graph: FrameGraph,
+    tick: FixedStep,
+    tweens: Tweens<TweenAction>,
+    // camera moves in flight, cut short when a camera path starts
+    camera_moves: Vec<TweenHandle>,
+    textures: TextureCache<B::Resources>,
+    clips: AnimationStore,
+    // ObjectIds to load again for their live instances, from reload or the watch
+    pending_reloads: Vec<i32>,
+    // Some while reload watch is on
+    asset_watch: Option<AssetWatch>,
+    // read-only connections for loading objects, textures and thumbnails
+    asset_db: ConnectionPool,
+    // spawns requested from the console, applied when a device is at hand
+    // object id, position and scale
+    pending_spawns: Vec<PendingSpawn>,
+    // templates spawned with the number keys
+    spawn_palette: SpawnPalette,
+    // reused CPU buffers of the draws made each frame
+    arena: FrameArena,
+    // the draws of a frame, written out when the console asks
+    frame_dump: FrameDump,
+    // what the backend offered and the index of the one in use
+    adapters: Vec<AdapterSummary>,
+    adapter: usize,
+    // frames the GPU may have in flight, each with its own copy of per-frame buffers
+    frames: usize,
+    // which copy the frame being drawn writes
+    frame_slot: usize,
+    notifications: Notifications,
+    // what shutdown writes, as of the last frame, for the panic hook to write too,
+    // and the camera and avator commands as they ran
+    session: SessionSave,
+}
 
-    state: WorldState,
+const FONT_SIZE: u8 = 48;
+const CONSOLE_LINES: usize = 12;
+const CONSOLE_TEXT_SCALE: f32 = 0.4;
+const DIALOGUE_TEXT_SCALE: f32 = 0.6;
+
+fn log_color(level: log::LogLevel) -> Color {
+    match level {
+        log::LogLevel::Error => ERROR,
+        log::LogLevel::Warn => WARNING,
+        log::LogLevel::Info => TEXT,
+        log::LogLevel::Debug | log::LogLevel::Trace => MUTED,
+    }
 }
 
-fn open_connection() -> Connection {
-    Connection::open(&Path::new("file.db")).expect("failed to open sqlite file")
+const MARKER_HEIGHT: f32 = 8.0;
+// the corner axes: pixels from the viewport's corner to where they start, and their length
+const AXES_GIZMO_MARGIN: f32 = 16.0;
+const AXES_GIZMO_LENGTH: f32 = 40.0;
+
+// 1 for Key1 through 9 for Key9, the spawn palette's slots.
+fn palette_slot(key: Key) -> usize {
+    match key {
+        Key::Key1 => 1, Key::Key2 => 2, Key::Key3 => 3,
+        Key::Key4 => 4, Key::Key5 => 5, Key::Key6 => 6,
+        Key::Key7 => 7, Key::Key8 => 8, Key::Key9 => 9,
+        _ => 0,
+    }
 }
 
-impl<B: gfx::Backend> World<B, Vertex> {
-    fn new<D: gfx::Device<B::Resources>> (
-        device: &mut D,
-        aspect: f32,
-    ) -> Self {
-        use gfx::traits::DeviceExt;
+// green when full, through yellow to red when nearly gone
+fn health_color(fraction: f32) -> Color {
+    let fraction = fraction.max(0.0).min(1.0);
+    Color::rgba((2.0 * (1.0 - fraction)).min(1.0), (2.0 * fraction).min(1.0), 0.2, 0.9)
+}
+// health bars: world size, and gap above the top of the object's bounds
+const HEALTH_BAR_SIZE: [f32; 2] = [1.0, 0.12];
+const HEALTH_BAR_CLEARANCE: f32 = 0.3;
+
+// seconds for a full day/night cycle, and the hour the world starts at
+const DAY_LENGTH: f64 = 600.0;
+const START_HOUR: f32 = 9.0;
+
+// written on every shutdown, alongside the saves made with F5
+const AUTOSAVE_NAME: &'static str = "autosave";
+const QUALITY_SETTING: &'static str = "quality";
+// the sqlite file everything is read from and written to; main's --db sets the variable
+pub const DATABASE_VAR: &'static str = "PARTI_DB";
+const DEFAULT_DATABASE: &'static str = "file.db";
+// how many commands the history keeps, and whether the camera's are run again on startup
+const HISTORY_SIZE_SETTING: &'static str = "history_size";
+const HISTORY_REPLAY_SETTING: &'static str = "history_replay";
+const DEFAULT_HISTORY_SIZE: usize = 10_000;
+// see MovementModel::from_setting
+const MOVEMENT_SETTING: &'static str = "movement";
+
+// where dumpframe writes without a path, and how much of a text it quotes
+const DEFAULT_FRAME_DUMP: &'static str = "frame_dump.txt";
+const DUMP_TEXT_CHARS: usize = 40;
+
+const LOCALE_DIR: &'static str = "assets/locale";
+const DEFAULT_LANGUAGE: &'static str = "en";
+
+fn load_locale(language: &str) -> Locale {
+    Locale::load(LOCALE_DIR, language).unwrap_or_else(|e| {
+        warn!("failed to load locale {}: {:?}", language, e);
+        Locale::empty(language)
+    })
+}
 
-        let conn = open_connection();
+// The atlas holds printable ASCII plus whatever the string table needs.
+// searched in order per character: Latin and Japanese, then symbols
+const FONT_CHAIN: &'static [(&'static str, f32)] = &[
+    ("assets/VL-PGothic-Regular.ttf", 1.0),
+    // its glyphs run larger than VL-PGothic's at the same pixel size
+    ("assets/DejaVuSans.ttf", 0.9),
+];
+
+fn load_font(locale: &Locale) -> FontResult {
+    let mut font_chars = locale.chars();
+    font_chars.extend((32u8..127).map(|c| c as char));
+    let font_chars: Vec<char> = font_chars.into_iter().collect();
+    let chain: Vec<FontSource> = FONT_CHAIN.iter().map(|&(path, scale)| FontSource::new(path, scale)).collect();
+    Font::from_chain(
+        &chain,
+        FONT_SIZE,
+        Some(font_chars.as_slice()),
+        true
+    )
+}
 
-        let avators = Invoker::<AvatorCommand, HashMap<i32, GameObject<B::Resources, _>>>::new(
-            query_entry::<B::Resources, D, TextureFormat>(&conn, device, &[1,2]).unwrap()
+// outline color and width in distance units, only drawn with distance field fonts
+const TEXT_OUTLINE: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 0.1 };
+
+const MAX_PALETTE_SIZE: usize = 256;
+const PALETTE_GRANULARITY: usize = 64;
+
+// Rounds the joint count up so objects with similar skeletons share a shader variant.
+fn palette_size_for(joints: usize) -> usize {
+    let size = (joints + PALETTE_GRANULARITY - 1) / PALETTE_GRANULARITY * PALETTE_GRANULARITY;
+    std::cmp::max(size, PALETTE_GRANULARITY)
+}
+
+// the depth pre-pass runs this too; invariant makes both passes land on the same depth
+const SKINNING_VERTEX_SHADER: &'static str = "
+invariant gl_Position;
+
+// see ObjectBlock
+layout(std140) uniform b_object {
+    mat4 u_model_view_proj;
+    mat4 u_model_view;
+};
+uniform b_skinning {
+    mat4 u_skinning[PALETTE_SIZE];
+};
+
+in vec3 position, normal;
+in vec2 uv;
+in ivec4 joint_indices;
+in vec4 joint_weights;
+in vec4 color;
+
+out vec2 v_TexCoord;
+out vec3 _normal;
+out vec4 v_Color;
+out float v_viewDepth;
+out vec3 v_viewPosition;
+out vec3 v_viewNormal;
+
+void main() {
+    vec4 bindVertex = vec4(position, 1.0);
+    vec4 bindNormal = vec4(normal, 0.0);
+    vec4 v =  joint_weights.x * u_skinning[joint_indices.x] * bindVertex;
+         v += joint_weights.y * u_skinning[joint_indices.y] * bindVertex;
+         v += joint_weights.z * u_skinning[joint_indices.z] * bindVertex;
+         v += joint_weights.a * u_skinning[joint_indices.a] * bindVertex;
+    vec4 n =  joint_weights.x * u_skinning[joint_indices.x] * bindNormal;
+         n += joint_weights.y * u_skinning[joint_indices.y] * bindNormal;
+         n += joint_weights.z * u_skinning[joint_indices.z] * bindNormal;
+         n += joint_weights.a * u_skinning[joint_indices.a] * bindNormal;
+    vec4 viewPosition = u_model_view * v;
+
+    gl_Position = u_model_view_proj * v;
+    v_TexCoord = uv;
+    _normal = normalize(bindNormal).xyz;
+    v_Color = color;
+    v_viewDepth = -viewPosition.z;
+    v_viewPosition = viewPosition.xyz;
+    v_viewNormal = mat3(u_model_view) * n.xyz;
+}";
+
+const SKINNING_FRAGMENT_SHADER: &'static [u8] = b"#version 150 core
+
+// see CameraBlock
+layout(std140) uniform b_camera {
+    mat4 u_view;
+    vec4 u_ambientColor;
+    vec4 u_skyColor;
+    // mode (0 off, 1 linear, 2 exp), start, end, density
+    vec4 u_fog;
+    vec4 u_fogColor;
+    vec3 u_light;
+    // 1 for LightingSpace::Linear, 0 for Legacy
+    float u_linear;
+};
+uniform sampler2D u_texture;
+uniform samplerCube u_environment;
+// the environment's smallest mip level, which a roughness of 1 reads
+uniform float u_environmentLod;
+uniform float u_roughness;
+
+in vec2 v_TexCoord;
+in vec3 _normal;
+in float v_viewDepth;
+in vec3 v_viewPosition;
+in vec3 v_viewNormal;
+out vec4 Target0;
+
+float fogFactor() {
+    float depth = max(v_viewDepth - u_fog.y, 0.0);
+    if (u_fog.x == 1.0) {
+        return clamp(depth / (u_fog.z - u_fog.y), 0.0, 1.0);
+    }
+    if (u_fog.x == 2.0) {
+        return 1.0 - exp(-u_fog.w * depth);
+    }
+    return 0.0;
+}
+
+// The sky reflected toward the eye, blurrier the rougher the surface, and the
+// sun or moon as a lobe that narrows and brightens as the surface gets smoother.
+vec3 specular() {
+    vec3 toEye = -normalize(v_viewPosition);
+    vec3 normal = normalize(v_viewNormal);
+    // back into world space, which the cube and the light are in
+    vec3 reflected = transpose(mat3(u_view)) * reflect(-toEye, normal);
+    vec3 sky = textureLod(u_environment, reflected, u_roughness * u_environmentLod).rgb * u_skyColor.rgb;
+    float sharpness = exp2(11.0 * (1.0 - u_roughness));
+    float lobe = pow(max(dot(reflected, -u_light), 0.0), sharpness) * (sharpness + 2.0) / 32.0;
+    // Schlick's approximation, for a dielectric
+    float fresnel = 0.04 + 0.96 * pow(1.0 - max(dot(normal, toEye), 0.0), 5.0);
+    return (sky + vec3(lobe)) * fresnel;
+}
+
+void main() {
+    // decoded from sRGB by the sampler, as the texture is Srgba8
+    vec4 texColor = texture(u_texture, v_TexCoord);
+
+    vec3 color;
+    if (u_linear > 0.5) {
+        float diffuse = max(dot(_normal, -u_light), 0.0);
+        color = texColor.rgb * (vec3(diffuse) + u_ambientColor.rgb) + specular();
+    } else {
+        float diffuse = clamp(dot(_normal, -u_light), 0.05f, 1.0f);
+        color = texColor.rgb * diffuse + specular() + u_ambientColor.rgb;
+    }
+    // linear still; the Srgba8 target encodes it
+    Target0 = vec4(mix(color, u_fogColor.rgb, fogFactor()), texColor.a);
+}";
+
+const UNLIT_FRAGMENT_SHADER: &'static [u8] = b"#version 150 core
+
+// see CameraBlock
+layout(std140) uniform b_camera {
+    mat4 u_view;
+    vec4 u_ambientColor;
+    vec4 u_skyColor;
+    // mode (0 off, 1 linear, 2 exp), start, end, density
+    vec4 u_fog;
+    vec4 u_fogColor;
+    vec3 u_light;
+    // 1 for LightingSpace::Linear, 0 for Legacy
+    float u_linear;
+};
+uniform sampler2D u_texture;
+
+in vec2 v_TexCoord;
+in vec4 v_Color;
+in float v_viewDepth;
+out vec4 Target0;
+
+float fogFactor() {
+    float depth = max(v_viewDepth - u_fog.y, 0.0);
+    if (u_fog.x == 1.0) {
+        return clamp(depth / (u_fog.z - u_fog.y), 0.0, 1.0);
+    }
+    if (u_fog.x == 2.0) {
+        return 1.0 - exp(-u_fog.w * depth);
+    }
+    return 0.0;
+}
+
+// Vertex colors are authored as sRGB, as the textures are, but reach the shader as is.
+vec3 srgbToLinear(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+void main() {
+    vec4 vertexColor = u_linear > 0.5 ? vec4(srgbToLinear(v_Color.rgb), v_Color.a) : v_Color;
+    vec4 color = texture(u_texture, v_TexCoord) * vertexColor;
+    Target0 = vec4(mix(color.rgb, u_fogColor.rgb, fogFactor()), color.a);
+}";
+
+// Shows the interpolated normal as a color, to spot flipped or missing normals.
+const NORMALS_FRAGMENT_SHADER: &'static [u8] = b"#version 150 core
+
+in vec2 v_TexCoord;
+in vec3 _normal;
+out vec4 Target0;
+
+void main() {
+    Target0 = vec4(normalize(_normal) * 0.5 + 0.5, 1.0);
+}";
+
+// Pushes the skinned surface out along its normal; drawn with front faces culled.
+const OUTLINE_VERTEX_SHADER: &'static str = "
+// see ObjectBlock
+layout(std140) uniform b_object {
+    mat4 u_model_view_proj;
+    mat4 u_model_view;
+};
+uniform float u_outlineWidth;
+uniform b_skinning {
+    mat4 u_skinning[PALETTE_SIZE];
+};
+
+in vec3 position, normal;
+in ivec4 joint_indices;
+in vec4 joint_weights;
+
+void main() {
+    mat4 skin = joint_weights.x * u_skinning[joint_indices.x]
+              + joint_weights.y * u_skinning[joint_indices.y]
+              + joint_weights.z * u_skinning[joint_indices.z]
+              + joint_weights.a * u_skinning[joint_indices.a];
+    vec4 v = skin * vec4(position, 1.0);
+    vec3 n = normalize((skin * vec4(normal, 0.0)).xyz);
+    gl_Position = u_model_view_proj * vec4(v.xyz + n * u_outlineWidth, 1.0);
+}";
+
+const OUTLINE_FRAGMENT_SHADER: &'static [u8] = b"#version 150 core
+
+uniform vec4 u_outlineColor;
+out vec4 Target0;
+
+void main() {
+    Target0 = u_outlineColor;
+}";
+
+// in model units
+const OUTLINE_WIDTH: f32 = 0.3;
+
+fn create_pso_o<R, D>(device: &mut D, palette_size: usize, reversed_z: bool) -> gfx::PipelineState<R, pipe_o::Meta>
+    where
+        R: gfx::Resources,
+        D: gfx::Device<R>,
+{
+    use gfx::traits::DeviceExt;
+    let vertex_shader = format!("#version 150 core\n#define PALETTE_SIZE {}\n{}", palette_size, OUTLINE_VERTEX_SHADER);
+    let shaders = device.create_shader_set(
+        vertex_shader.as_bytes(),
+        OUTLINE_FRAGMENT_SHADER,
+    ).expect("failed to build shader");
+    let rasterizer = gfx::state::Rasterizer {
+        cull_face: gfx::state::CullFace::Front,
+        .. gfx::state::Rasterizer::new_fill()
+    };
+    device.create_pipeline_state(
+        &shaders,
+        gfx::Primitive::TriangleList,
+        rasterizer,
+        pipe_o::Init {
+            out_depth: nearer_or_equal(reversed_z, true),
+            .. pipe_o::new()
+        }
+    ).expect("failed to create pipeline o")
+}
+
+fn create_pso_w<R, D>(device: &mut D, palette_size: usize, mode: RenderMode, shading: Shading, depth: DepthTest, reversed_z: bool) -> gfx::PipelineState<R, pipe_w::Meta>
+    where
+        R: gfx::Resources,
+        D: gfx::Device<R>,
+{
+    use gfx::traits::DeviceExt;
+    let vertex_shader = format!("#version 150 core\n#define PALETTE_SIZE {}\n{}", palette_size, SKINNING_VERTEX_SHADER);
+    let fragment_shader = match (mode, shading) {
+        (RenderMode::Normals, _) => NORMALS_FRAGMENT_SHADER,
+        (_, Shading::Unlit) => UNLIT_FRAGMENT_SHADER,
+        (_, Shading::Lit) => SKINNING_FRAGMENT_SHADER,
+    };
+    let shaders = device.create_shader_set(
+        vertex_shader.as_bytes(),
+        fragment_shader,
+    ).expect("failed to build shader");
+    let rasterizer = match mode {
+        RenderMode::Wireframe => gfx::state::Rasterizer {
+            method: gfx::state::RasterMethod::Line(1),
+            .. gfx::state::Rasterizer::new_fill()
+        },
+        RenderMode::Solid | RenderMode::Normals => gfx::state::Rasterizer::new_fill(),
+    };
+    device.create_pipeline_state(
+        &shaders,
+        gfx::Primitive::TriangleList,
+        rasterizer,
+        pipe_w::Init {
+            out_depth: depth.state(reversed_z),
+            .. pipe_w::new()
+        }
+    ).expect("failed to create pipeline w")
+}
+
+const DEPTH_FRAGMENT_SHADER: &'static [u8] = b"#version 150 core
+
+out vec4 Target0;
+
+void main() {
+    Target0 = vec4(0.0);
+}";
+
+fn create_pso_d<R, D>(device: &mut D, palette_size: usize, reversed_z: bool) -> gfx::PipelineState<R, pipe_d::Meta>
+    where
+        R: gfx::Resources,
+        D: gfx::Device<R>,
+{
+    use gfx::traits::DeviceExt;
+    let vertex_shader = format!("#version 150 core\n#define PALETTE_SIZE {}\n{}", palette_size, SKINNING_VERTEX_SHADER);
+    let shaders = device.create_shader_set(
+        vertex_shader.as_bytes(),
+        DEPTH_FRAGMENT_SHADER,
+    ).expect("failed to build shader");
+    device.create_pipeline_state(
+        &shaders,
+        gfx::Primitive::TriangleList,
+        gfx::state::Rasterizer::new_fill(),
+        pipe_d::Init {
+            out_depth: nearer_or_equal(reversed_z, true),
+            .. pipe_d::new()
+        }
+    ).expect("failed to create pipeline d")
+}
+
+fn database_path() -> String {
+    std::env::var(DATABASE_VAR).unwrap_or_else(|_| DEFAULT_DATABASE.to_string())
+}
+
+// set once this process has brought the database up to date
+static MIGRATED: AtomicBool = ATOMIC_BOOL_INIT;
+
+fn try_open_connection() -> Result<Connection, AppError> {
+    let mut conn = Connection::open(&Path::new(&database_path()))?;
+    if !MIGRATED.load(Ordering::Acquire) {
+        migrate(&mut conn)?;
+        enable_wal(&conn)?;
+        MIGRATED.store(true, Ordering::Release);
+    }
+    Ok(conn)
+}
+
+fn open_connection() -> Connection {
+    try_open_connection().unwrap_or_else(|e| panic!("failed to open {}: {:?}", database_path(), e))
+}
+
+// Opens the database once, upgrading it, so a file this build cannot use is told
+// before anything reads from it.
+pub fn check_database() -> Result<(), AppError> {
+    try_open_connection().map(|_| ())
+}
+
+// The preset in use when the game last shut down.
+pub fn saved_quality() -> Option<QualityPreset> {
+    match query_setting(&open_connection(), QUALITY_SETTING) {
+        Ok(name) => name.and_then(|name| QualityPreset::from_name(&name)),
+        Err(e) => {
+            warn!("failed to read the quality setting: {}", e);
+            None
+        },
+    }
+}
+
+fn history_size(conn: &Connection) -> usize {
+    match query_setting(conn, HISTORY_SIZE_SETTING) {
+        Ok(Some(size)) => size.parse().unwrap_or_else(|_| {
+            warn!("not a history size: {}", size);
+            DEFAULT_HISTORY_SIZE
+        }),
+        Ok(None) => DEFAULT_HISTORY_SIZE,
+        Err(e) => {
+            warn!("failed to read the history size: {}", e);
+            DEFAULT_HISTORY_SIZE
+        },
+    }
+}
+
+fn movement_model(conn: &Connection) -> MovementModel {
+    match query_setting(conn, MOVEMENT_SETTING) {
+        Ok(Some(value)) => MovementModel::from_setting(&value).unwrap_or_else(|| {
+            warn!("not a movement model: {}", value);
+            MovementModel::default()
+        }),
+        Ok(None) => MovementModel::default(),
+        Err(e) => {
+            warn!("failed to read the movement model: {}", e);
+            MovementModel::default()
+        },
+    }
+}
+
+// Off unless turned on with `history replay on`.
+fn history_replay(conn: &Connection) -> bool {
+    match query_setting(conn, HISTORY_REPLAY_SETTING) {
+        Ok(value) => value.map_or(false, |value| value == "on"),
+        Err(e) => {
+            warn!("failed to read the history replay setting: {}", e);
+            false
+        },
+    }
+}
+
+// axes is the convention the file was authored in; see Axes::from_name.
+pub fn import_obj(path: &str, object_id: i32, axes: Axes) -> Result<(), AppError> {
+    let model = load_obj(Path::new(path), axes)?;
+    let mut conn = open_connection();
+    insert_obj(&mut conn, &model, object_id)?;
+    Ok(())
+}
+
+impl<B: gfx::Backend> World<B, Vertex> {
+    fn new<D: gfx::Device<B::Resources>> (
+        device: &mut D,
+        aspect: f32,
+        graphics: GraphicsSettings,
+        frames: usize,
+    ) -> Self {
+        use gfx::traits::DeviceExt;
+
+        let conn = open_connection();
+
+        let mut textures = TextureCache::new();
+        let mut clips = AnimationStore::new();
+        let avators = Invoker::<AvatorCommand, HashMap<i32, GameObject<B::Resources, _>>>::new(
+            query_entry(&conn, device, &mut textures, &mut clips, graphics.mipmaps, frames, &[1,2]).unwrap()
         );
+        let mut nav = NavGrid::new(NAV_ORIGIN, NAV_CELL_SIZE, NAV_CELLS, NAV_CELLS);
+        match query_nav_blocked(&conn) {
+            Ok(cells) => for (x, y) in cells {
+                if x >= 0 && y >= 0 {
+                    nav.set_blocked((x as usize, y as usize), true);
+                }
+            },
+            Err(e) => warn!("failed to load the navigation grid: {}", e),
+        }
+        let mut frame_graph = FrameGraph::new();
+        {
+            let backbuffer = [Target::BackbufferColor, Target::BackbufferDepth];
+            let color = [Target::BackbufferColor];
+            // the passes after Clear draw over what is there, so they read what they write
+            frame_graph
+                .add_pass(PassId::Acquire, &[], &backbuffer, &[])
+                // sized to the backbuffer it takes over from
+                .add_pass(PassId::Capture, &[], &[Target::SceneCapture], &[PassId::Acquire])
+                .add_pass(PassId::Clear, &[], &backbuffer, &[])
+                // the scene is swapped once the capture has the outgoing one
+                .add_pass(PassId::Scene, &backbuffer, &backbuffer, &[PassId::Capture])
+                .add_pass(PassId::Hud, &color, &backbuffer, &[])
+                .add_pass(PassId::Minimap, &color, &color, &[])
+                .add_pass(PassId::ScreenEffects, &color, &color, &[])
+                .add_pass(PassId::Transition, &[Target::SceneCapture, Target::BackbufferColor], &color, &[])
+                .add_pass(PassId::Overlay, &color, &color, &[])
+                .add_pass(PassId::Flush, &[Target::BackbufferColor, Target::BackbufferDepth, Target::SceneCapture], &[Target::Submission], &[])
+                .add_pass(PassId::Present, &[Target::Submission, Target::BackbufferColor], &[], &[]);
+        }
+        frame_graph.compile().expect("invalid frame graph");
+
+        let update = UpdateThread::spawn();
+        for (key, obj) in &avators.target {
+            update.send(SimMessage::Insert(*key, obj.body.clone()));
+        }
+        let reversed_z = graphics.reversed_z;
         let camera = Invoker::<CameraCommand, Camera<f32>>::new(
             Camera::new(
                 Point3::new(30.0, -40.0, 30.0),
                 Point3::new(0.0, 0.0, 0.0),
+                axes::WORLD.up(),
                 cgmath::PerspectiveFov {
                     fovy: cgmath::Rad(16.0f32.to_radians()),
                     aspect,
-                    near: 5.0,
-                    far: 1000.0,
-            })
+                    near: graphics.near,
+                    far: graphics.far,
+            }).with_reversed_z(reversed_z)
         );
-        let sampler = {
-            let sampler_info = gfx::texture::SamplerInfo::new(
-                gfx::texture::FilterMethod::Trilinear,
-                gfx::texture::WrapMode::Clamp
-            );
-            device.create_sampler(sampler_info)
-        };
-        let pso = {
-            let shaders = device.create_shader_set(
-          b"#version 150 core
-            
-            uniform mat4 u_model_view_proj;
-            uniform mat4 u_model_view;
-            uniform b_skinning {
-                mat4 u_skinning[64];
-            };
-            
-            in vec3 position, normal;
-            in vec2 uv;
-            in ivec4 joint_indices;
-            in vec4 joint_weights;
-            
-            out vec2 v_TexCoord;
-            out vec3 _normal;
-            
-            void main() {
-                vec4 bindVertex = vec4(position, 1.0);
-                vec4 bindNormal = vec4(normal, 0.0);
-                vec4 v =  joint_weights.x * u_skinning[joint_indices.x] * bindVertex;
-                     v += joint_weights.y * u_skinning[joint_indices.y] * bindVertex;
-                     v += joint_weights.z * u_skinning[joint_indices.z] * bindVertex;
-                     v += joint_weights.a * u_skinning[joint_indices.a] * bindVertex;
-                vec4 n = bindNormal * u_skinning[joint_indices.x] * joint_weights.x;
-                n += bindNormal * u_skinning[joint_indices.y] * joint_weights.y;
-                n += bindNormal * u_skinning[joint_indices.z] * joint_weights.z;
-                n += bindNormal * u_skinning[joint_indices.a] * joint_weights.a;
-            
-                gl_Position = u_model_view_proj * v;
-                v_TexCoord = uv;
-                _normal = normalize(bindNormal).xyz;
-            }",
-          b"#version 150 core
-            
-            uniform vec3 u_light;
-            uniform vec4 u_ambientColor;
-            uniform vec3 u_eyeDirection;
-            uniform sampler2D u_texture;
-            
-            in vec2 v_TexCoord;
-            in vec3 _normal;
-            out vec4 Target0;
-            
-            void main() {
-                vec4 texColor = texture(u_texture, v_TexCoord);
-            
-                float diffuse = clamp(dot(_normal, -u_light), 0.05f, 1.0f);
-                vec3 halfLE = normalize(u_eyeDirection);
-                float specular = pow(clamp(dot(_normal, halfLE), 0.0, 1.0), 50.0);
-                Target0 = texColor * vec4(vec3(diffuse), 1.0) + vec4(vec3(specular), 1.0) + u_ambientColor;
-            }").expect("failed to build shader");
-            device.create_pipeline_state(
-                &shaders,
-                gfx::Primitive::TriangleList,
-                gfx::state::Rasterizer::new_fill(),
-                pipe_w::new()
-                ).expect("failed to create pipeline w")
-        };
+        let observer = Invoker::<CameraCommand, Camera<f32>>::new(
+            Camera::new(
+                Point3::new(-60.0, 40.0, 80.0),
+                Point3::new(0.0, 0.0, 0.0),
+                axes::WORLD.up(),
+                cgmath::PerspectiveFov {
+                    fovy: cgmath::Rad(30.0f32.to_radians()),
+                    aspect,
+                    near: graphics.near,
+                    far: graphics.far,
+            }).with_reversed_z(reversed_z)
+        );
+        let sampler = device.create_sampler(graphics.sampler_info());
+        let environment = create_environment(device);
+        let camera_buffers = (0..frames).map(|_| device.create_constant_buffer(1)).collect();
+        let psos = avators.target.values()
+            .map(|obj| obj.palette_size)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .flat_map(|size| RenderMode::all().iter()
+                .flat_map(|&mode| Shading::all().iter()
+                    .flat_map(|&shading| DepthTest::all().iter().map(|&depth| (size, mode, shading, depth)).collect::<Vec<_>>())
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>())
+            .map(|key| (key, create_pso_w(device, key.0, key.1, key.2, key.3, reversed_z)))
+            .collect();
+        let depth_psos = avators.target.values()
+            .map(|obj| obj.palette_size)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|size| (size, create_pso_d(device, size, reversed_z)))
+            .collect();
+        let outline_psos = avators.target.values()
+            .map(|obj| obj.palette_size)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|size| (size, create_pso_o(device, size, reversed_z)))
+            .collect();
 
         let pso_w2 = {
             let shaders = device.create_shader_set(b"
@@ -356,6 +1870,7 @@ impl<B: gfx::Backend> World<B, Vertex> {
             uniform vec4 u_ambientColor;
             uniform vec3 u_eyeDirection;
             uniform sampler2D u_texture;
+            uniform float u_sdf;
             
             in vec2 v_TexCoord;
             in vec3 _normal;
@@ -365,6 +1880,10 @@ impl<B: gfx::Backend> World<B, Vertex> {
             
             void main() {
                 vec4 texColor = texture(u_texture, v_TexCoord);
+                if (u_sdf > 0.5) {
+                    float w = fwidth(texColor.r);
+                    texColor.r = smoothstep(0.5 - w, 0.5 + w, texColor.r);
+                }
             
                 float diffuse = clamp(dot(_normal, -u_light), 0.05f, 1.0f);
                 vec3 halfLE = normalize(u_eyeDirection);
@@ -375,7 +1894,10 @@ impl<B: gfx::Backend> World<B, Vertex> {
                 &shaders,
                 gfx::Primitive::TriangleList,
                 gfx::state::Rasterizer::new_fill().with_cull_back(),
-                pipe_w2::new()
+                pipe_w2::Init {
+                    out_depth: nearer_or_equal(reversed_z, true),
+                    .. pipe_w2::new()
+                }
             ).expect("failed to create pipeline w2")
         };
         let pso_p = {
@@ -403,9 +1925,44 @@ impl<B: gfx::Backend> World<B, Vertex> {
                 &shaders,
                 gfx::Primitive::TriangleStrip,
                 gfx::state::Rasterizer::new_fill().with_cull_back(),
-                pipe_p::new()
+                pipe_p::Init {
+                    out_depth: nearer_or_equal(reversed_z, true),
+                    .. pipe_p::new()
+                }
                 ).expect("failed to create pipeline p")
         };
+        let pso_l = {
+            let shaders = device.create_shader_set(b"
+            #version 150 core
+
+            uniform mat4 u_view_proj;
+            in vec3 position;
+            in vec4 color;
+            out vec4 v_color;
+
+            void main() {
+                gl_Position = u_view_proj * vec4(position, 1.0);
+                v_color = color;
+            }
+            ",
+            b"
+            #version 150 core
+            in vec4 v_color;
+            out vec4 Target0;
+
+            void main() {
+                Target0 = v_color;
+            }").expect("failed to build shader");
+            device.create_pipeline_state(
+                &shaders,
+                gfx::Primitive::LineList,
+                gfx::state::Rasterizer::new_fill(),
+                pipe_l::Init {
+                    out_depth: nearer_or_equal(reversed_z, false),
+                    .. pipe_l::new()
+                }
+                ).expect("failed to create pipeline l")
+        };
         let pso_pt = {
             let shaders = device.create_shader_set(b"
             #version 150 core
@@ -417,11 +1974,12 @@ impl<B: gfx::Backend> World<B, Vertex> {
             out vec4 v_Color;
 
             uniform vec2 u_screen_size;
+            uniform vec2 u_offset;
             
             void main() {
                 vec2 screenOffset = vec2(
-                    2 * position.x / u_screen_size.x - 1,
-                    2 * position.z / u_screen_size.y - 1
+                    2 * (position.x + u_offset.x) / u_screen_size.x - 1,
+                    2 * (position.y + u_offset.y) / u_screen_size.y - 1
                 );
                 v_TexCoord = vec2(uv.x, uv.y);
                 gl_Position = vec4(screenOffset, 0.0, 1.0);
@@ -432,6 +1990,8 @@ impl<B: gfx::Backend> World<B, Vertex> {
             #version 150 core
 
             uniform sampler2D u_texture;
+            uniform float u_sdf;
+            uniform vec4 u_outline;
             
             in vec2 v_TexCoord;
             in vec4 v_Color;
@@ -440,286 +2000,2875 @@ impl<B: gfx::Backend> World<B, Vertex> {
             
             void main() {
                 vec4 texColor = texture(u_texture, v_TexCoord);
-                Target0 = vec4(v_Color.rgb, texColor.r * v_Color.a);
+                if (u_sdf > 0.5) {
+                    // u_outline.a is the outline width in distance units
+                    float d = texColor.r;
+                    float w = fwidth(d);
+                    float fill = smoothstep(0.5 - w, 0.5 + w, d);
+                    float outline = smoothstep(0.5 - u_outline.a - w, 0.5 - u_outline.a + w, d);
+                    vec3 rgb = mix(u_outline.rgb, v_Color.rgb, fill);
+                    Target0 = vec4(rgb, max(fill, outline) * v_Color.a);
+                } else {
+                    Target0 = vec4(v_Color.rgb, texColor.r * v_Color.a);
+                }
             }").expect("failed to build shader");
             device.create_pipeline_state(
                 &shaders,
                 gfx::Primitive::TriangleList,
                 gfx::state::Rasterizer::new_fill().with_cull_back(),
-                pipe_pt::new()
+                pipe_pt::Init {
+                    out_depth: nearer_or_equal(reversed_z, true),
+                    .. pipe_pt::new()
+                }
             ).expect("failed to create pipeline p")
         };
 
-        let state = WorldState::Render;
-        let font = {
-            let font_chars: Vec<char> = "abcdefghijklmnopqrstuvwxyz0123456789.+-_".chars().map(|c| c).collect();
-            Font::from_path(
-                "assets/VL-PGothic-Regular.ttf",
-                48,
-                Some(font_chars.as_slice())
-            )
-        }.expect("failed to create font");
+        let pso_b = {
+            let shaders = device.create_shader_set(b"
+            #version 150 core
+
+            uniform mat4 u_view_proj;
+            uniform vec2 u_projection_scale;
+            uniform vec3 u_center;
+            uniform vec2 u_size;
+            uniform vec2 u_pixel_range;
+            uniform vec2 u_screen_size;
+            // part of the texture shown, as left, top, right, bottom
+            uniform vec4 u_uv_rect;
+
+            in vec2 offset;
+            in vec2 uv;
+            out vec2 v_TexCoord;
+
+            void main() {
+                vec4 clip = u_view_proj * vec4(u_center, 1.0);
+                vec2 pixels = u_size * u_projection_scale * 0.5 * u_screen_size / clip.w;
+                float height = clamp(pixels.y, u_pixel_range.x, u_pixel_range.y);
+                pixels *= height / max(pixels.y, 0.0001);
+                vec2 ndc = offset * pixels * 2.0 / u_screen_size;
+                gl_Position = clip + vec4(ndc * clip.w, 0.0, 0.0);
+                v_TexCoord = mix(u_uv_rect.xy, u_uv_rect.zw, uv);
+            }
+            ",
+            b"
+            #version 150 core
+
+            uniform sampler2D u_texture;
+            uniform vec4 u_color;
+
+            in vec2 v_TexCoord;
+            out vec4 Target0;
+
+            void main() {
+                Target0 = texture(u_texture, v_TexCoord) * u_color;
+            }").expect("failed to build shader");
+            device.create_pipeline_state(
+                &shaders,
+                gfx::Primitive::TriangleList,
+                gfx::state::Rasterizer::new_fill(),
+                pipe_b::Init {
+                    out_depth: nearer_or_equal(reversed_z, false),
+                    .. pipe_b::new()
+                }
+            ).expect("failed to create pipeline b")
+        };
+        let billboard_quad = {
+            let vertex_data = [
+                VertexB { offset: [-0.5,  0.5], uv: [0.0, 0.0] },
+                VertexB { offset: [-0.5, -0.5], uv: [0.0, 1.0] },
+                VertexB { offset: [ 0.5, -0.5], uv: [1.0, 1.0] },
+                VertexB { offset: [ 0.5,  0.5], uv: [1.0, 0.0] },
+            ];
+            device.create_vertex_buffer_with_slice(&vertex_data, &[0u32, 1, 3, 3, 1, 2][..])
+        };
+        let fullscreen_quad = {
+            // render targets have their origin at the bottom-left
+            let vertex_data = [
+                VertexB { offset: [-1.0,  1.0], uv: [0.0, 1.0] },
+                VertexB { offset: [-1.0, -1.0], uv: [0.0, 0.0] },
+                VertexB { offset: [ 1.0, -1.0], uv: [1.0, 0.0] },
+                VertexB { offset: [ 1.0,  1.0], uv: [1.0, 1.0] },
+            ];
+            device.create_vertex_buffer_with_slice(&vertex_data, &[0u32, 1, 3, 3, 1, 2][..])
+        };
+        let pso_f = {
+            let shaders = device.create_shader_set(b"
+            #version 150 core
+
+            in vec2 offset;
+            in vec2 uv;
+            out vec2 v_TexCoord;
+
+            void main() {
+                v_TexCoord = uv;
+                gl_Position = vec4(offset, 0.0, 1.0);
+            }
+            ",
+            b"
+            #version 150 core
+
+            uniform sampler2D u_texture;
+            uniform vec4 u_color;
+            uniform float u_textured;
+            uniform float u_cover;
+
+            in vec2 v_TexCoord;
+            out vec4 Target0;
+
+            void main() {
+                vec4 color = mix(u_color, texture(u_texture, v_TexCoord), u_textured);
+                Target0 = vec4(color.rgb, color.a * u_cover);
+            }").expect("failed to build shader");
+            device.create_pipeline_state(
+                &shaders,
+                gfx::Primitive::TriangleList,
+                gfx::state::Rasterizer::new_fill(),
+                pipe_f::new()
+            ).expect("failed to create pipeline f")
+        };
+        let marker_texture = {
+            let img = marker_image::<TextureFormat>(32);
+            let tex_kind = gfx::texture::Kind::D2(img.width, img.height, gfx::texture::AaMode::Single);
+            let (_, view) = device.create_texture_immutable_u8::<TextureFormat>(tex_kind, &[&img.data]).expect("failed to create texture");
+            view
+        };
+
+        let locale = load_locale(DEFAULT_LANGUAGE);
+        let font = load_font(&locale).expect("failed to create font");
  
-        World {
+        let mut events = EventBus::new();
+        let app_events = events.subscribe(&[EventKind::AnimationEventFired]);
+        let interaction_events = events.subscribe(&[EventKind::TriggerEntered, EventKind::TriggerExited]);
+
+        let mut world = World {
             avators,
             camera, 
-            system: Invoker::<SystemCommand, System>::new(System {
-                timer: coarsetime::Instant::now()
-            }),
+            observer,
+            layout: ViewportLayout::Single,
+            view_cameras: Vec::new(),
+            screen_size: (0, 0),
+            next_snapshot: 0,
+            system: Invoker::<SystemCommand, System>::new(System::new()),
+            mouse_look: MouseLook::new(),
+            cursor: None,
+            input: InputState::new(),
+            move_plan: MovePlan::new(CLICK_MOVE_SPEED),
+            movement: Movement::new(movement_model(&conn)),
+            nav,
+            stat_commands: Vec::new(),
+            body_commands: Vec::new(),
+            dispatcher: Dispatcher::new(),
+            wheel_zoom: WheelZoom::new(),
+            time_of_day: TimeOfDay::new(DAY_LENGTH, START_HOUR),
+            fog: Fog::new(),
+            graphics,
             sampler,
-            pso,
+            environment,
+            camera_buffers,
+            psos,
+            depth_psos,
+            render_mode: RenderMode::Solid,
+            outline_psos,
+            selected: Some(1),
+            pick_mode: PickMode::Bounds,
             pso_w2,
             pso_p,
             pso_pt,
+            pso_b,
+            pso_l,
+            debug_flags: DebugFlags::new(),
+            minimap: Minimap::new(),
+            billboard_quad,
+            fullscreen_quad,
+            capture: None,
+            scene_transition: None,
+            scene_id: None,
+            editor: Editor::new(),
+            screen_effects: ScreenEffects::new(),
+            pso_f,
+            marker_texture,
+            billboards: Vec::new(),
+            sprites: Vec::new(),
+            pending_sprites: Vec::new(),
+            dialogue: None,
+            pending_quality: None,
+            show_avator_markers: true,
+            events,
+            app_events,
+            interaction_events,
+            triggers: Triggers::new(load_triggers(&conn, 0)),
+            interaction: None,
+            last_event_time: None,
             font,
-
-            state,
+            static_text: TextRenderer::new(),
+            thumbnails: Thumbnails::new(),
+            locale,
+            console: Console::new(),
+            log: None,
+            save_prompt: None,
+
+            states: Vec::new(),
+            suspended: None,
+            scrub: None,
+            peer: None,
+            update,
+            frame_graph,
+            tick: FixedStep::new(FIXED_STEP),
+            tweens: Tweens::new(),
+            camera_moves: Vec::new(),
+            textures,
+            clips,
+            pending_reloads: Vec::new(),
+            asset_watch: None,
+            asset_db: ConnectionPool::new(PathBuf::from(database_path())),
+            pending_spawns: Vec::new(),
+            arena: FrameArena::new(),
+            frame_dump: FrameDump::new(),
+            adapters: Vec::new(),
+            adapter: 0,
+            frames,
+            frame_slot: 0,
+            spawn_palette: SpawnPalette::from_rows(query_spawn_palette(&conn).unwrap_or_else(|e| {
+                warn!("failed to load the spawn palette: {}", e);
+                Vec::new()
+            })),
+            notifications: Notifications::new(),
+            session: SessionSave::new(graphics.quality, CommandHistory::new(history_size(&conn))),
+        };
+        world.camera.target.notifier = Some(world.notifications.notifier());
+        world.observer.target.notifier = Some(world.notifications.notifier());
+        if history_replay(&conn) {
+            world.replay_history(&conn);
         }
+        world.apply_transition(Transition::Push(Box::new(TitleScreen::new())));
+        world
     }
     fn camera(&self) -> &Camera<f32> {
         &self.camera.target
     }
-    fn render<D: gfx::Device<B::Resources>>(&mut self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
-        use gfx::traits::DeviceExt;
-        let elapsed = self.system.target.timer.elapsed().as_f64();
-        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
-
-        let camera = self.camera(); 
-        for obj in self.avators.target.values() {
-            obj.render(view, camera, elapsed, &self.pso, encoder,  &self.sampler, device);
+    // Finishes revealing the dialogue line, or dismisses it once fully shown.
+    fn advance_dialogue(&mut self) {
+        let revealed = match self.dialogue {
+            Some(ref mut dialogue) if !dialogue.is_revealed() => {
+                dialogue.reveal();
+                false
+            },
+            Some(_) => true,
+            None => false,
+        };
+        if revealed {
+            self.dialogue = None;
         }
-        {
-            let font_entry = font_entry(device, &self.font, &format!("{:?}", elapsed), [0.0, 0.0], [0.0;4], 0.1);
-
-            let data = pipe_w2::Data {
-                vbuf: font_entry.vertex_buffer,
-                u_model_view_proj: camera.projection.into(),
-                u_model_view: camera.view.into(),
-                u_light: [1.0, 0.5, -0.5f32],
-                u_ambient_color: [0.00, 0.00, 0.01, 0.4],
-                u_eye_direction: camera.direction().into(),
-                u_texture: (font_entry.texture, self.sampler.clone()),
-                out_color: view.0.clone(),
-                out_depth: view.1.clone()
-            };
-            encoder.draw(&font_entry.slice, &self.pso_w2, &data);
+    }
+    // Shows a toast in the top right corner for seconds, fades included.
+    fn notify<S: Into<String>>(&mut self, text: S, seconds: f32) {
+        let dropped = {
+            let tweens = &mut self.tweens;
+            self.notifications.push(&text.into(), seconds, |seconds| tweens.start(TweenAction::Toast, seconds, Easing::Linear))
+        };
+        for handle in dropped {
+            self.tweens.cancel(handle);
         }
-        if self.state == WorldState::Pose {
-            let vertex_data = vec!(
-                VertexP {
-                    position: [-0.95, 0.0, 0.0],
-                    color: [0.03, 0.03, 0.03, 0.9],
-                }, 
-                VertexP {
-                    position: [0.95, 0.0, 0.0],
-                    color: [0.03, 0.03, 0.03, 0.9],
-                },
-                VertexP {
-                    position: [-0.95, -0.95, 0.0],
-                    color: [0.03, 0.03, 0.03, 1.0],
-                },
-                VertexP {
-                    position: [0.95,  -0.95, 0.0],
-                    color: [0.03, 0.03, 0.03, 1.0],
-                },
-            );
-            {
-                let (vbuf, slice) = device.create_vertex_buffer_with_slice(&vertex_data, &[1u32, 0u32, 2u32, 3u32, 1u32][..]);
-                let data = pipe_p::Data {
-                    vbuf: vbuf,
+    }
+    fn receive_notifications(&mut self) {
+        for (text, seconds) in self.notifications.receive() {
+            self.notify(text, seconds);
+        }
+    }
+    fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        self.locale.tr(key)
+    }
+    fn set_language(&mut self, language: &str) -> Result<(), AppError> {
+        let locale = Locale::load(LOCALE_DIR, language)?;
+        self.font = load_font(&locale)?;
+        self.static_text.invalidate_all();
+        self.locale = locale;
+        Ok(())
+    }
+    // Once the backbuffer is acquired, before the drawing passes. slot picks the per-frame
+    // buffers; the caller waits until the GPU is done with its last use.
+    fn begin_frame<D: gfx::Device<B::Resources>>(&mut self, view: &View<B::Resources>, slot: usize, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) -> FrameContext {
+        self.frame_slot = slot % self.frames;
+        let lighting = self.time_of_day.lighting(self.system.target.elapsed());
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        self.refresh_view_cameras(screen_width, screen_height);
+        self.prepare_static_text(device);
+        let canvas = self.overlay_canvas(screen_width, screen_height);
+        self.frame_dump.begin_pass(|| "Thumbnails".to_string());
+        self.prepare_thumbnails(&canvas, encoder, device);
+        FrameContext {
+            lighting,
+            canvas,
+        }
+    }
+    // The passes that draw; App::render runs the swapchain's.
+    fn render_pass<D: gfx::Device<B::Resources>>(&mut self, pass: PassId, view: &View<B::Resources>, frame: &FrameContext, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        self.frame_dump.begin_pass(|| format!("{:?}", pass));
+        let lighting = &frame.lighting;
+        match pass {
+            PassId::Capture => self.capture_outgoing_scene(view, encoder, device, lighting),
+            PassId::Clear => {
+                encoder.clear(&view.0.clone(), lighting.sky.into());
+                encoder.clear_depth(&view.1.clone(), depth_clear(self.graphics.reversed_z));
+            },
+            PassId::Scene => self.render_scene(view, encoder, device, lighting),
+            PassId::Hud => {
+                self.render_hud(view, encoder, device);
+                self.render_screen_sprites(view, encoder, device);
+                self.render_notifications(view, encoder, device);
+            },
+            PassId::Minimap => self.render_minimap(view, encoder, device),
+            PassId::ScreenEffects => self.render_screen_effects(view, encoder, device),
+            PassId::Transition => self.render_transition(view, encoder, device),
+            PassId::Overlay => self.render_overlay(view, encoder, device, &frame.canvas),
+            PassId::Acquire | PassId::Flush | PassId::Present => unreachable!("{:?} is run by the App", pass),
+        }
+    }
+    // After the drawing passes, before the frame is flushed.
+    fn end_frame(&mut self) {
+        let fresh = self.arena.reset();
+        if fresh > 0 {
+            debug!("frame arena grew {} buffers, {} KiB pooled", fresh, self.arena.pooled_bytes() / 1024);
+        }
+        match self.frame_dump.finish() {
+            Some(Ok((path, draws))) => {
+                let text = format!("wrote {} draws to {}", draws, path.display());
+                self.console.print(text.clone());
+                self.notify(text, TOAST_SECONDS);
+            },
+            Some(Err(e)) => self.console.print(format!("failed to write the frame dump: {}", e)),
+            None => (),
+        }
+    }
+
+    fn render_scene<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, lighting: &Lighting) {
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+
+        // lines leave most of the depth buffer empty, so wireframe gains nothing from it
+        let prepass = self.graphics.depth_prepass && self.render_mode != RenderMode::Wireframe;
+        let depth = if prepass { DepthTest::Equal } else { DepthTest::Write };
+        for viewport_camera in &self.view_cameras {
+            let camera = &viewport_camera.camera;
+            let scissor = viewport_camera.viewport.rect(screen_width, screen_height);
+            let camera_buffer = &self.camera_buffers[self.frame_slot];
+            encoder.update_constant_buffer(camera_buffer, &camera_block(camera, lighting, &self.fog, self.graphics.lighting));
+            let frustum = Frustum::from_matrix(&camera.projection);
+            let inside: Vec<_> = self.avators.target.iter()
+                .filter(|&(key, obj)| {
+                    let inside = frustum.intersects(&obj.cull_bounds());
+                    if !inside {
+                        self.frame_dump.note(|| format!("object {} ({}) is outside the {:?} camera's frustum", key, obj.object_id, viewport_camera.which));
+                    }
+                    inside
+                })
+                .collect();
+            let occlusion = self.occlusion_buffer(camera, inside.iter().map(|&(_, obj)| obj));
+            let visible: Vec<_> = inside.into_iter()
+                .filter(|&(key, obj)| {
+                    let hidden = occlusion.as_ref().map_or(false, |occlusion| occlusion.is_hidden(&obj.cull_bounds()));
+                    if hidden {
+                        self.frame_dump.note(|| format!("object {} ({}) is hidden behind others from the {:?} camera", key, obj.object_id, viewport_camera.which));
+                    }
+                    !hidden
+                })
+                .map(|(_, obj)| obj)
+                .collect();
+            if prepass {
+                self.render_depth_prepass(view, encoder, camera, scissor, &visible);
+            }
+            for obj in &visible {
+                obj.render(view, camera, camera_buffer, scissor, self.render_mode, depth, &self.psos, encoder, &self.sampler, &self.environment, &self.arena, &self.frame_dump, self.frame_slot, device);
+            }
+            self.render_outline(view, encoder, camera, scissor);
+            self.render_billboards(view, encoder, camera, scissor);
+            if camera.layers.intersects(layer::DEBUG) {
+                if self.debug_flags.any() {
+                    self.render_debug(view, encoder, device, camera, scissor);
+                }
+                self.render_gizmo(view, encoder, device, camera, scissor);
+                if self.debug_flags.axes {
+                    let near = camera.ndc_depth(viewport_camera.viewport.depth)[0];
+                    self.render_axes(view, encoder, device, camera, scissor, near);
+                }
+            }
+        }
+    }
+
+    // The camera's view of the objects' occluders. None in wireframe, where nothing is solid,
+    // and meshes hidden or left off the camera's layers hide nothing either.
+    fn occlusion_buffer<'a, I>(&self, camera: &Camera<f32>, objects: I) -> Option<OcclusionBuffer>
+        where I: Iterator<Item = &'a GameObject<B::Resources, Vertex>>, B::Resources: 'a
+    {
+        if self.render_mode == RenderMode::Wireframe {
+            return None;
+        }
+        let mut occlusion = OcclusionBuffer::new(camera.projection, camera.reversed_z);
+        for obj in objects {
+            for mesh in &obj.occluders {
+                let mut entries = obj.entries.iter().filter(|entry| entry.mesh_id == mesh.mesh_id).peekable();
+                if entries.peek().is_some() && entries.all(|entry| entry.shown_to(camera.layers)) {
+                    occlusion.add(mesh, obj.body.position, obj.scale);
+                }
+            }
+        }
+        Some(occlusion)
+    }
+
+    // Rebuilds a viewport's camera only when its layout slot, the screen or the camera it follows changed.
+    fn refresh_view_cameras(&mut self, screen_width: u16, screen_height: u16) {
+        self.screen_size = (screen_width, screen_height);
+        let viewports = self.layout.viewports();
+        self.view_cameras.truncate(viewports.len());
+        for (i, (which, viewport)) in viewports.into_iter().enumerate() {
+            let base = match which {
+                ViewCamera::Main => &self.camera.target,
+                ViewCamera::Observer => &self.observer.target,
+            };
+            let aspect = viewport.aspect(screen_width, screen_height);
+            let fresh = match self.view_cameras.get(i) {
+                Some(current) => current.which == which && current.viewport == viewport
+                    && current.aspect == aspect && current.generation == base.generation,
+                None => false,
+            };
+            if fresh {
+                continue;
+            }
+            let mut camera = base.with_viewport(aspect, viewport.matrix());
+            self.next_snapshot += 1;
+            camera.snapshot = self.next_snapshot;
+            let viewport_camera = ViewportCamera { which, viewport, aspect, generation: base.generation, camera };
+            if i < self.view_cameras.len() {
+                self.view_cameras[i] = viewport_camera;
+            } else {
+                self.view_cameras.push(viewport_camera);
+            }
+        }
+    }
+
+    // (Re)creates the offscreen target when missing or the screen size changed.
+    fn ensure_capture<D: gfx::Device<B::Resources>>(&mut self, device: &mut D, width: u16, height: u16) {
+        use gfx::traits::DeviceExt;
+        if self.capture.as_ref().map(|capture| capture.size) == Some((width, height)) {
+            return;
+        }
+        let (_, texture, color) = device.create_render_target::<ColorFormat>(width, height).expect("failed to create capture target");
+        let depth = device.create_depth_stencil_view_only::<DepthFormat>(width, height).expect("failed to create capture depth");
+        self.capture = Some(Capture {
+            size: (width, height),
+            texture,
+            view: (color, depth),
+        });
+    }
+
+    // Draws the outgoing scene into the capture, then swaps the new one in for the Scene pass.
+    fn capture_outgoing_scene<D: gfx::Device<B::Resources>>(&mut self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, lighting: &Lighting) {
+        let scene_id = match self.scene_transition {
+            Some(transition) if transition.stage == TransitionStage::Capture => transition.scene_id,
+            _ => return,
+        };
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        self.ensure_capture(device, screen_width, screen_height);
+        let target = self.capture.as_ref().expect("capture target").view.clone();
+        encoder.clear(&target.0, lighting.sky.into());
+        encoder.clear_depth(&target.1, depth_clear(self.graphics.reversed_z));
+        self.render_scene(&target, encoder, device, lighting);
+
+        self.swap_scene(device, scene_id);
+        let handle = self.tweens.start(TweenAction::SceneFade, SCENE_TRANSITION_DURATION, Easing::EaseInOut);
+        if let Some(ref mut transition) = self.scene_transition {
+            transition.stage = TransitionStage::In(handle);
+        }
+    }
+
+    // Post pass over everything but the overlay: black for a fade, the capture for a dissolve.
+    fn render_screen_effects<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let rect = [0.0, 0.0, screen_width as f32, screen_height as f32];
+        for effect in self.screen_effects.iter() {
+            let alpha = effect.alpha(self.tweens.value(effect.handle));
+            if alpha > 0.0 {
+                self.draw_rect(view, encoder, device, rect, effect.color.with_alpha(alpha));
+            }
+        }
+    }
+    fn render_transition<D: gfx::Device<B::Resources>>(&mut self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        let (cover, dissolve) = match self.scene_transition {
+            Some(transition) => {
+                let value = transition.stage.handle().and_then(|handle| self.tweens.value(handle));
+                (transition.cover(value), transition.kind == TransitionKind::CrossDissolve)
+            },
+            None => return,
+        };
+        if cover <= 0.0 {
+            return;
+        }
+        // the fade only needs something bound; a dissolve keeps its capture across resizes
+        if self.capture.is_none() {
+            let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+            self.ensure_capture(device, screen_width, screen_height);
+        }
+        let texture = self.capture.as_ref().expect("capture target").texture.clone();
+        let data = pipe_f::Data {
+            vbuf: self.fullscreen_quad.0.clone(),
+            u_texture: (texture, self.sampler.clone()),
+            u_color: BLACK.into(),
+            u_textured: if dissolve { 1.0 } else { 0.0 },
+            u_cover: cover,
+            out_color: view.0.clone(),
+        };
+        encoder.draw(&self.fullscreen_quad.1, &self.pso_f, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_f",
+            count: slice_count(&self.fullscreen_quad.1),
+            textures: vec!["scene capture".to_string()],
+            uniforms: format!("{} cover {:.2}", if dissolve { "dissolve" } else { "fade" }, cover),
+        });
+    }
+
+    fn render_hud<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        let elapsed = self.system.target.elapsed();
+        let camera = self.camera(); 
+        {
+            let font_entry = font_entry(device, &self.font, &format!("{:?}", elapsed), [0.0, 0.0], TRANSPARENT.into(), 0.1);
+            // stood upright on the ground
+            let plane = axes::WORLD.layout_plane();
+
+            let data = pipe_w2::Data {
+                vbuf: font_entry.vertex_buffer,
+                u_model_view_proj: (camera.projection * plane).into(),
+                u_model_view: (camera.view * plane).into(),
+                u_light: [1.0, 0.5, -0.5f32],
+                u_ambient_color: Color::rgba(0.0, 0.0, 0.01, 0.4).into(),
+                u_eye_direction: camera.direction().into(),
+                u_texture: (font_entry.texture, self.sampler.clone()),
+                u_sdf: if self.font.sdf { 1.0 } else { 0.0 },
+                out_color: view.0.clone(),
+                out_depth: view.1.clone()
+            };
+            encoder.draw(&font_entry.slice, &self.pso_w2, &data);
+            self.frame_dump.record(|| Draw {
+                pipeline: "pipe_w2",
+                count: slice_count(&font_entry.slice),
+                textures: vec!["font".to_string()],
+                uniforms: format!("clock {:?}", elapsed),
+            });
+        }
+        if let Some(index) = self.scrub {
+            let text = match self.avators.target.get(&1) {
+                Some(obj) => match obj.body.sample_time(index) {
+                    Some(time) => format!("frame {{color=#ffffff}}{}{{/color}} / {}  t = {:.3}s", index, obj.body.sample_count(), time),
+                    None => "no animation samples".to_string(),
+                },
+                None => "no avator".to_string(),
+            };
+            let y = 8.0 + FONT_SIZE as f32 * CONSOLE_TEXT_SCALE;
+            self.draw_markup(view, encoder, device, &text, [8.0, y], HIGHLIGHT, CONSOLE_TEXT_SCALE);
+        }
+        if let Some(ref dialogue) = self.dialogue {
+            let (screen_width, _, _, _) = view.0.get_dimensions();
+            let line_height = FONT_SIZE as f32 * DIALOGUE_TEXT_SCALE;
+            let panel = [16.0, 16.0, screen_width as f32 - 16.0, 32.0 + line_height * 2.0];
+            self.draw_rect(view, encoder, device, panel, BLACK.with_alpha(0.6));
+            self.draw_effect_text(view, encoder, device, dialogue, [32.0, panel[3] - 12.0], WHITE, DIALOGUE_TEXT_SCALE);
+        }
+        // above where the dialogue panel goes, so both can show at once
+        if let Some((_, ref prompt)) = self.interaction {
+            let (screen_width, _, _, _) = view.0.get_dimensions();
+            let line_height = FONT_SIZE as f32 * DIALOGUE_TEXT_SCALE;
+            let bottom = 48.0 + line_height * 2.0;
+            let panel = [screen_width as f32 / 3.0, bottom, screen_width as f32 * 2.0 / 3.0, bottom + 24.0 + line_height];
+            self.draw_rect(view, encoder, device, panel, BLACK.with_alpha(0.6));
+            let (width, _, _) = self.font.measure_runs(&parse_markup(&prompt.markup), DIALOGUE_TEXT_SCALE);
+            let x = ((panel[0] + panel[2] - width) / 2.0).max(panel[0] + 16.0);
+            self.draw_effect_text(view, encoder, device, prompt, [x, panel[3] - 12.0], SPEECH, DIALOGUE_TEXT_SCALE);
+        }
+    }
+
+    // Newest at the bottom of the stack; each toast fades its panel with its text.
+    fn render_notifications<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let (screen_width, screen_height) = (screen_width as f32, screen_height as f32);
+        let line_height = FONT_SIZE as f32 * CONSOLE_TEXT_SCALE;
+        let mut top = screen_height - 16.0;
+        for toast in &self.notifications.toasts {
+            let alpha = toast.alpha(self.tweens.value(toast.handle));
+            let (width, _, lines) = self.font.measure_runs(&parse_markup(&toast.text.markup), CONSOLE_TEXT_SCALE);
+            // at least a line high, so a toast of spaces keeps its panel
+            let height = lines.last().map_or(line_height, |line| line.top + line.height.max(line_height));
+            let panel = [screen_width - width - 32.0, top - height - 16.0, screen_width - 16.0, top];
+            self.draw_rect(view, encoder, device, panel, BLACK.with_alpha(0.7 * alpha));
+            self.draw_effect_text(view, encoder, device, &toast.text, [panel[0] + 8.0, top - 8.0], WHITE.with_alpha(alpha), CONSOLE_TEXT_SCALE);
+            top = panel[1] - 8.0;
+        }
+    }
+
+    fn render_minimap<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        use gfx::traits::DeviceExt;
+        if !self.minimap.visible {
+            return;
+        }
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let screen = Screen::new(screen_width, screen_height);
+        let panel = self.minimap.panel(screen.width, screen.height);
+        self.draw_rect(view, encoder, device, panel, BLACK.with_alpha(0.6));
+
+        let main = self.camera();
+        let half_fov = ((main.fov.fovy.0 / 2.0).tan() * main.fov.aspect).atan();
+        let camera = MapCamera {
+            position: main.position,
+            direction: main.direction(),
+            half_fov,
+        };
+        let remote = self.remote_avator();
+        let objects: Vec<(Point3<f32>, Color)> = self.avators.target.iter().map(|(&key, obj)| {
+            let color = match key {
+                1 => PLAYER,
+                _ if Some(key) == remote => REMOTE_PLAYER,
+                _ => OTHERS,
+            };
+            (obj.body.position, color)
+        }).collect();
+        let lines = self.minimap.lines(panel, screen, &camera, &objects, self.arena.lines.take());
+
+        let identity: Matrix4<f32> = One::one();
+        let (vbuf, slice) = device.create_vertex_buffer_with_slice(&lines.vertices, ());
+        let data = pipe_l::Data {
+            vbuf,
+            u_view_proj: identity.into(),
+            scissor: gfx::Rect {
+                x: panel[0] as u16,
+                y: panel[1] as u16,
+                w: (panel[2] - panel[0]) as u16 + 1,
+                h: (panel[3] - panel[1]) as u16 + 1,
+            },
+            out_color: view.0.clone(),
+            out_depth: view.1.clone(),
+        };
+        encoder.draw(&slice, &self.pso_l, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_l",
+            count: slice_count(&slice),
+            textures: Vec::new(),
+            uniforms: format!("minimap {:?}", panel),
+        });
+        self.arena.lines.give_back(lines.vertices);
+    }
+
+    // What the states draw over the frame, asked for before the passes run
+    // so the thumbnails in it can be rendered first.
+    fn overlay_canvas(&self, screen_width: u16, screen_height: u16) -> Canvas {
+        let mut canvas = Canvas::new(screen_width as f32, screen_height as f32);
+        for state in &self.states {
+            state.render(self, &mut canvas);
+        }
+        canvas
+    }
+
+    fn render_overlay<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, canvas: &Canvas) {
+        let (_, screen_height, _, _) = view.0.get_dimensions();
+        for item in &canvas.items {
+            match *item {
+                CanvasItem::Rect(rect, color) => self.draw_rect(view, encoder, device, rect, color),
+                CanvasItem::Markup(ref text, pos, color, scale) => self.draw_markup(view, encoder, device, text, pos, color, scale),
+                CanvasItem::Text(handle, pos) => self.draw_static_text(view, encoder, handle, pos),
+                CanvasItem::Thumbnail(object_id, rect) => self.draw_thumbnail(view, encoder, device, object_id, rect),
+            }
+        }
+        if self.console.visible {
+            self.render_console(view, encoder, device);
+        }
+        if let Some(ref prompt) = self.save_prompt {
+            let (x, y) = (40.0, screen_height as f32 / 3.0);
+            self.draw_text(view, encoder, device, self.tr("prompt.save_as"), [x, y + 48.0 * CONSOLE_TEXT_SCALE], TEXT, CONSOLE_TEXT_SCALE);
+            self.draw_text_input(view, encoder, device, prompt, [x, y], CONSOLE_TEXT_SCALE);
+        }
+    }
+
+    // Lays down the nearest depth of every object with a cheap fragment shader, so the shaded
+    // pass after it runs the expensive one once per pixel however many characters overlap.
+    fn render_depth_prepass(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, camera: &Camera<f32>, scissor: gfx::Rect, objects: &[&GameObject<B::Resources, Vertex>]) {
+        let mut palette = self.arena.palettes.take();
+        for obj in objects {
+            let pso = match self.depth_psos.get(&obj.palette_size) {
+                Some(pso) => pso,
+                None => continue,
+            };
+            let object_buffer = obj.update_object_buffer(encoder, camera, self.frame_slot);
+            let skinning_buffer = obj.skinning_buffer(self.frame_slot);
+            if obj.entries.iter().all(|entry| entry.joint_map.is_none()) {
+                encoder.update_buffer(skinning_buffer, &obj.palette, 0).expect("ub");
+            }
+            for entry in obj.entries.iter().filter(|entry| entry.shown_to(camera.layers)) {
+                if obj.entry_palette(entry, &mut palette) {
+                    encoder.update_buffer(skinning_buffer, &palette, 0).expect("ub");
+                }
+                let data = pipe_d::Data {
+                    vbuf: entry.vertex_buffer.clone(),
+                    b_object: object_buffer.clone(),
                     out_color: view.0.clone(),
                     out_depth: view.1.clone(),
+                    b_skinning: skinning_buffer.raw().clone(),
+                    scissor,
+                };
+                encoder.draw(&entry.slice, pso, &data);
+                self.frame_dump.record(|| Draw {
+                    pipeline: "pipe_d",
+                    count: slice_count(&entry.slice),
+                    textures: Vec::new(),
+                    uniforms: format!("object {} mesh {} palette {}", obj.object_id, entry.mesh_id, obj.palette_size),
+                });
+            }
+        }
+        self.arena.palettes.give_back(palette);
+    }
+
+    // Drawn after the object: the hull's back faces only show past its silhouette.
+    fn render_outline(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, camera: &Camera<f32>, scissor: gfx::Rect) {
+        if !camera.layers.intersects(layer::DEBUG) {
+            return;
+        }
+        let obj = match self.selected.and_then(|key| self.avators.target.get(&key)) {
+            Some(obj) => obj,
+            None => return,
+        };
+        let pso = match self.outline_psos.get(&obj.palette_size) {
+            Some(pso) => pso,
+            None => return,
+        };
+        let object_buffer = obj.update_object_buffer(encoder, camera, self.frame_slot);
+        let skinning_buffer = obj.skinning_buffer(self.frame_slot);
+        let mut palette = self.arena.palettes.take();
+        for entry in obj.entries.iter().filter(|entry| entry.shown_to(camera.layers)) {
+            if obj.entry_palette(entry, &mut palette) {
+                encoder.update_buffer(skinning_buffer, &palette, 0).expect("ub");
+            }
+            let data = pipe_o::Data {
+                vbuf: entry.vertex_buffer.clone(),
+                b_object: object_buffer.clone(),
+                u_outline_color: SELECTED_OUTLINE.into(),
+                u_outline_width: OUTLINE_WIDTH,
+                out_color: view.0.clone(),
+                out_depth: view.1.clone(),
+                b_skinning: skinning_buffer.raw().clone(),
+                scissor,
+            };
+            encoder.draw(&entry.slice, pso, &data);
+            self.frame_dump.record(|| Draw {
+                pipeline: "pipe_o",
+                count: slice_count(&entry.slice),
+                textures: Vec::new(),
+                uniforms: format!("object {} mesh {} width {}", obj.object_id, entry.mesh_id, OUTLINE_WIDTH),
+            });
+        }
+        self.arena.palettes.give_back(palette);
+    }
+
+    fn render_billboards(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, camera: &Camera<f32>, scissor: gfx::Rect) {
+        let avator_markers: Vec<Billboard> = if self.show_avator_markers {
+            self.avators.target.values().map(|obj| {
+                Billboard::new(
+                    obj.body.position + Vector3::new(0.0, 0.0, MARKER_HEIGHT),
+                    [0.6, 0.6],
+                    PLAYER.with_alpha(0.9)
+                )
+            }).collect()
+        } else {
+            Vec::new()
+        };
+        for billboard in self.billboards.iter().chain(avator_markers.iter()) {
+            self.draw_billboard(view, encoder, camera, scissor, billboard, &self.marker_texture, FULL_UV);
+        }
+        for billboard in &self.health_bars(camera) {
+            self.draw_billboard(view, encoder, camera, scissor, billboard, &self.marker_texture, SOLID_UV);
+        }
+        for &(ref sprite, ref texture) in &self.sprites {
+            if let SpritePlacement::World(ref billboard) = sprite.placement {
+                self.draw_billboard(view, encoder, camera, scissor, billboard, texture, sprite.uv_rect());
+            }
+        }
+    }
+
+    // A dark back and a fill that shrinks toward its left end, above each living object.
+    // The pixel range is left open, since clamping would scale the two apart.
+    fn health_bars(&self, camera: &Camera<f32>) -> Vec<Billboard> {
+        // the camera's right in world space, along which the fill is shifted
+        let right = Vector3::new(camera.view.x.x, camera.view.y.x, camera.view.z.x);
+        let (width, height) = (HEALTH_BAR_SIZE[0], HEALTH_BAR_SIZE[1]);
+        self.avators.target.values()
+            .filter(|obj| !obj.stats.is_dead())
+            .flat_map(|obj| {
+                let above = obj.body.position + Vector3::new(0.0, 0.0, obj.bounds.max.z + HEALTH_BAR_CLEARANCE);
+                let fraction = obj.stats.fraction();
+                let fill = above - right * (width * (1.0 - fraction) / 2.0);
+                vec!(
+                    Billboard::new(above, HEALTH_BAR_SIZE, Color::rgba(0.1, 0.1, 0.1, 0.8)).with_pixel_range(0.0, std::f32::MAX),
+                    Billboard::new(fill, [width * fraction, height], health_color(fraction)).with_pixel_range(0.0, std::f32::MAX),
+                )
+            })
+            .collect()
+    }
+    fn draw_billboard(
+        &self,
+        view: &View<B::Resources>,
+        encoder: &mut gfx::GraphicsEncoder<B>,
+        camera: &Camera<f32>,
+        scissor: gfx::Rect,
+        billboard: &Billboard,
+        texture: &gfx::handle::ShaderResourceView<B::Resources, [f32; 4]>,
+        uv_rect: [f32; 4],
+    ) {
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        // clipped whole behind the camera; skipping saves the draw
+        if world_to_screen(&camera.projection, Screen::new(screen_width, screen_height), billboard.position).is_none() {
+            self.frame_dump.note(|| format!("billboard at {:?} is behind the camera", billboard.position));
+            return;
+        }
+        let data = pipe_b::Data {
+            vbuf: self.billboard_quad.0.clone(),
+            u_view_proj: camera.projection.into(),
+            u_projection_scale: [camera.perspective.x.x, camera.perspective.y.y],
+            u_center: billboard.position.into(),
+            u_size: billboard.size,
+            u_pixel_range: [billboard.min_pixels, billboard.max_pixels],
+            u_color: billboard.color.into(),
+            u_uv_rect: uv_rect,
+            screen_size: [screen_width as f32, screen_height as f32],
+            u_texture: (texture.clone(), self.sampler.clone()),
+            scissor,
+            out_color: view.0.clone(),
+            out_depth: view.1.clone(),
+        };
+        encoder.draw(&self.billboard_quad.1, &self.pso_b, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_b",
+            count: slice_count(&self.billboard_quad.1),
+            textures: vec!["billboard".to_string()],
+            uniforms: format!("center {:?} size {:?} uv {:?} {:?}", billboard.position, billboard.size, uv_rect, billboard.color),
+        });
+    }
+
+    // Loads the objects the canvas shows thumbnails of that are not loaded yet, and renders
+    // those whose animation frame moved on, before any pass samples them.
+    fn prepare_thumbnails<D: gfx::Device<B::Resources>>(&mut self, canvas: &Canvas, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        let time = self.system.target.elapsed();
+        for item in &canvas.items {
+            let object_id = match *item {
+                CanvasItem::Thumbnail(object_id, _) => object_id,
+                _ => continue,
+            };
+            if !self.thumbnails.contains(object_id) {
+                let thumbnail = match self.create_thumbnail(device, object_id) {
+                    Ok(thumbnail) => thumbnail,
+                    Err(e) => {
+                        warn!("no thumbnail for object {}: {:?}", object_id, e);
+                        None
+                    },
+                };
+                self.thumbnails.insert(object_id, thumbnail);
+            }
+            let frame = match self.thumbnails.peek(object_id) {
+                Some(thumbnail) => thumbnail.object.body.sample_index_at(time),
+                None => continue,
+            };
+            if let Some(thumbnail) = self.thumbnails.stale_mut(object_id, frame) {
+                thumbnail.object.palette = thumbnail.object.body.skinning(AnimationClock::Sample(frame));
+                let target = thumbnail.target.view.clone();
+                encoder.clear(&target.0, THUMBNAIL_LIGHTING.sky.into());
+                encoder.clear_depth(&target.1, depth_clear(self.graphics.reversed_z));
+                let scissor = gfx::Rect { x: 0, y: 0, w: THUMBNAIL_SIZE, h: THUMBNAIL_SIZE };
+                let fog = Fog { mode: FogMode::Off, color: None };
+                let camera_buffer = &self.camera_buffers[self.frame_slot];
+                encoder.update_constant_buffer(camera_buffer, &camera_block(&thumbnail.camera, &THUMBNAIL_LIGHTING, &fog, self.graphics.lighting));
+                thumbnail.object.render(&target, &thumbnail.camera, camera_buffer, scissor, RenderMode::Solid, DepthTest::Write, &self.psos, encoder, &self.sampler, &self.environment, &self.arena, &self.frame_dump, 0, device);
+            }
+        }
+        let unused = self.thumbnails.collect_unused();
+        if !unused.is_empty() {
+            for thumbnail in unused {
+                self.release_thumbnail(thumbnail);
+            }
+            self.clips.collect_unused();
+        }
+    }
+    // None when there is no pipeline for the object's palette; they are made at startup
+    // for the palette sizes loaded then.
+    fn create_thumbnail<D: gfx::Device<B::Resources>>(&mut self, device: &mut D, object_id: i32) -> Result<Option<Thumbnail<B::Resources>>, AppError> {
+        use gfx::traits::DeviceExt;
+        let object = query_object(&self.asset_db.get()?, device, &mut self.textures, &mut self.clips, self.graphics.mipmaps, 1, &object_id)?;
+        if !self.psos.keys().any(|key| key.0 == object.palette_size) {
+            warn!("no thumbnail for object {}: no pipeline for a palette of {}", object_id, object.palette_size);
+            for texture_id in &object.texture_ids {
+                self.textures.release(*texture_id);
+            }
+            return Ok(None);
+        }
+        let size = THUMBNAIL_SIZE;
+        let (_, texture, color) = device.create_render_target::<ColorFormat>(size, size).expect("failed to create thumbnail target");
+        let depth = device.create_depth_stencil_view_only::<DepthFormat>(size, size).expect("failed to create thumbnail depth");
+        Ok(Some(Thumbnail {
+            camera: thumbnail_camera(&object.bounds, self.graphics.reversed_z),
+            object,
+            target: Capture { size: (size, size), texture, view: (color, depth) },
+        }))
+    }
+    // Gives back the textures the thumbnail's object took from the TextureCache.
+    fn release_thumbnail(&mut self, thumbnail: Thumbnail<B::Resources>) {
+        for texture_id in &thumbnail.object.texture_ids {
+            self.textures.release(*texture_id);
+        }
+    }
+    // Loaded again the next time it is shown.
+    fn forget_thumbnail(&mut self, object_id: i32) {
+        if let Some(thumbnail) = self.thumbnails.remove(object_id) {
+            self.release_thumbnail(thumbnail);
+            self.clips.collect_unused();
+        }
+    }
+    // Nothing is drawn until the thumbnail has been rendered once.
+    fn draw_thumbnail<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, object_id: i32, rect: [f32; 4]) {
+        use gfx::traits::DeviceExt;
+        let thumbnail = match self.thumbnails.get(object_id) {
+            Some(thumbnail) => thumbnail,
+            None => return,
+        };
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let screen = Screen::new(screen_width, screen_height);
+        let to_ndc = |x: f32, y: f32| screen_to_ndc(screen, [x, y]);
+        // render targets have their origin at the bottom-left
+        let vertex_data = [
+            VertexB { offset: to_ndc(rect[0], rect[3]), uv: [0.0, 1.0] },
+            VertexB { offset: to_ndc(rect[0], rect[1]), uv: [0.0, 0.0] },
+            VertexB { offset: to_ndc(rect[2], rect[1]), uv: [1.0, 0.0] },
+            VertexB { offset: to_ndc(rect[2], rect[3]), uv: [1.0, 1.0] },
+        ];
+        let (vbuf, slice) = device.create_vertex_buffer_with_slice(&vertex_data, &[0u32, 1, 3, 3, 1, 2][..]);
+        let data = pipe_f::Data {
+            vbuf,
+            u_texture: (thumbnail.target.texture.clone(), self.sampler.clone()),
+            u_color: [0.0; 4],
+            u_textured: 1.0,
+            u_cover: 1.0,
+            out_color: view.0.clone(),
+        };
+        encoder.draw(&slice, &self.pso_f, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_f",
+            count: slice_count(&slice),
+            textures: vec!["thumbnail".to_string()],
+            uniforms: format!("rect {:?}", rect),
+        });
+    }
+
+    // Screen sprites go through the post pass pipeline with a quad of their own, like draw_rect.
+    fn render_screen_sprites<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        use gfx::traits::DeviceExt;
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let screen = Screen::new(screen_width, screen_height);
+        let to_ndc = |x: f32, y: f32| screen_to_ndc(screen, [x, y]);
+        for &(ref sprite, ref texture) in &self.sprites {
+            let rect = match sprite.placement {
+                SpritePlacement::Screen(rect) => rect,
+                SpritePlacement::World(_) => continue,
+            };
+            let uv = sprite.uv_rect();
+            let vertex_data = [
+                VertexB { offset: to_ndc(rect[0], rect[3]), uv: [uv[0], uv[1]] },
+                VertexB { offset: to_ndc(rect[0], rect[1]), uv: [uv[0], uv[3]] },
+                VertexB { offset: to_ndc(rect[2], rect[1]), uv: [uv[2], uv[3]] },
+                VertexB { offset: to_ndc(rect[2], rect[3]), uv: [uv[2], uv[1]] },
+            ];
+            let (vbuf, slice) = device.create_vertex_buffer_with_slice(&vertex_data, &[0u32, 1, 3, 3, 1, 2][..]);
+            let data = pipe_f::Data {
+                vbuf,
+                u_texture: (texture.clone(), self.sampler.clone()),
+                u_color: [0.0; 4],
+                u_textured: 1.0,
+                u_cover: 1.0,
+                out_color: view.0.clone(),
+            };
+            encoder.draw(&slice, &self.pso_f, &data);
+            self.frame_dump.record(|| Draw {
+                pipeline: "pipe_f",
+                count: slice_count(&slice),
+                textures: vec!["sprite".to_string()],
+                uniforms: format!("rect {:?} uv {:?}", rect, uv),
+            });
+        }
+    }
+
+    fn render_debug<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, camera: &Camera<f32>, scissor: gfx::Rect) {
+        use gfx::traits::DeviceExt;
+        let mut lines = DebugLines::with_buffer(self.arena.lines.take());
+        for obj in self.avators.target.values() {
+            let bounds = obj.bounds.translated(obj.body.position.to_vec());
+            if self.debug_flags.aabb {
+                lines.aabb(&bounds, [0.2, 1.0, 0.2, 1.0]);
+            }
+            if self.debug_flags.capsule {
+                // there is no collider data yet, so fit an upright capsule to the bounds
+                let center = Point3::new((bounds.min.x + bounds.max.x) / 2.0, (bounds.min.y + bounds.max.y) / 2.0, 0.0);
+                let radius = ((bounds.max.x - bounds.min.x).max(bounds.max.y - bounds.min.y)) / 2.0;
+                let bottom = (bounds.min.z + radius).min((bounds.min.z + bounds.max.z) / 2.0);
+                let top = (bounds.max.z - radius).max(bottom);
+                lines.capsule(Point3::new(center.x, center.y, bottom), Point3::new(center.x, center.y, top), radius, [0.2, 0.6, 1.0, 1.0]);
+            }
+        }
+        if self.debug_flags.frustum {
+            lines.frustum(self.camera().projection, [1.0, 1.0, 0.2, 1.0]);
+            lines.frustum(self.observer.target.projection, [1.0, 0.4, 0.8, 1.0]);
+        }
+        if self.debug_flags.nav {
+            let grid = self.nav_grid();
+            let lift = Vector3::new(0.0, 0.0, 0.05);
+            for cell in grid.blocked_cells() {
+                let center = grid.center(cell, GROUND_HEIGHT) + lift;
+                let half = grid.cell_size / 2.0;
+                let corners = [
+                    center + Vector3::new(-half, -half, 0.0),
+                    center + Vector3::new(half, -half, 0.0),
+                    center + Vector3::new(half, half, 0.0),
+                    center + Vector3::new(-half, half, 0.0),
+                ];
+                for i in 0..4 {
+                    lines.line(corners[i], corners[(i + 1) % 4], [1.0, 0.3, 0.2, 1.0]);
+                }
+            }
+            let path = self.move_plan.path();
+            for pair in path.windows(2) {
+                lines.line(pair[0] + lift, pair[1] + lift, [0.3, 1.0, 1.0, 1.0]);
+            }
+        }
+        if self.debug_flags.grid {
+            // just above the ground, so a floor there does not flicker through it
+            lines.ground_grid(camera.target, GROUND_HEIGHT + 0.01);
+        }
+        if self.debug_flags.trigger {
+            for trigger in &self.triggers.triggers {
+                let color = if self.interaction.as_ref().map_or(false, |&(id, _)| id == trigger.id) { HIGHLIGHT.into() } else { [0.8, 0.4, 1.0, 1.0] };
+                match trigger.shape {
+                    TriggerShape::Sphere { center, radius } => lines.capsule(center, center, radius, color),
+                    TriggerShape::Box(ref aabb) => lines.aabb(aabb, color),
+                }
+            }
+        }
+        if !lines.is_empty() {
+            let (vbuf, slice) = device.create_vertex_buffer_with_slice(&lines.vertices, ());
+            let data = pipe_l::Data {
+                vbuf,
+                u_view_proj: camera.projection.into(),
+                scissor,
+                out_color: view.0.clone(),
+                out_depth: view.1.clone(),
+            };
+            encoder.draw(&slice, &self.pso_l, &data);
+            self.frame_dump.record(|| Draw {
+                pipeline: "pipe_l",
+                count: slice_count(&slice),
+                textures: Vec::new(),
+                uniforms: "debug lines".to_string(),
+            });
+        }
+        self.arena.lines.give_back(lines.vertices);
+    }
+
+    // The world's axes in the viewport's bottom-left corner, turned with its camera and
+    // drawn at its near plane, depth, over the scene.
+    fn render_axes<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, camera: &Camera<f32>, scissor: gfx::Rect, depth: f32) {
+        use gfx::traits::DeviceExt;
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let inset = AXES_GIZMO_MARGIN + AXES_GIZMO_LENGTH;
+        let origin = [scissor.x as f32 + inset, scissor.y as f32 + inset];
+        let mut lines = DebugLines::with_buffer(self.arena.lines.take());
+        let tips = lines.axis_gizmo(&camera.view, Screen::new(screen_width, screen_height), origin, AXES_GIZMO_LENGTH, depth);
+        let identity: Matrix4<f32> = One::one();
+        let (vbuf, slice) = device.create_vertex_buffer_with_slice(&lines.vertices, ());
+        let data = pipe_l::Data {
+            vbuf,
+            u_view_proj: identity.into(),
+            scissor,
+            out_color: view.0.clone(),
+            out_depth: view.1.clone(),
+        };
+        encoder.draw(&slice, &self.pso_l, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_l",
+            count: slice_count(&slice),
+            textures: Vec::new(),
+            uniforms: format!("axes at {:?}", origin),
+        });
+        self.arena.lines.give_back(lines.vertices);
+        let line_height = FONT_SIZE as f32 * CONSOLE_TEXT_SCALE;
+        for (axis, tip) in AXES.iter().zip(tips.iter()) {
+            let c = axis.color();
+            self.draw_text(view, encoder, device, &format!("{:?}", axis), [tip[0] + 2.0, tip[1] + line_height / 2.0], Color::rgba(c[0], c[1], c[2], c[3]), CONSOLE_TEXT_SCALE);
+        }
+    }
+
+    // The handles are drawn where the drag has sent the object, ahead of its body.
+    fn render_gizmo<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, camera: &Camera<f32>, scissor: gfx::Rect) {
+        use gfx::traits::DeviceExt;
+        let (gizmo, dragged) = match (self.editor.drag, self.gizmo()) {
+            (Some(drag), _) => (Gizmo { origin: drag.position(), ..drag.gizmo }, Some(drag.axis)),
+            (None, Some((_, gizmo))) => (gizmo, None),
+            (None, None) => return,
+        };
+        let mut lines = DebugLines::with_buffer(self.arena.lines.take());
+        for &axis in AXES.iter() {
+            let color = if dragged == Some(axis) { HIGHLIGHT.into() } else { axis.color() };
+            lines.line(gizmo.origin, gizmo.tip(axis), color);
+        }
+        let (vbuf, slice) = device.create_vertex_buffer_with_slice(&lines.vertices, ());
+        let data = pipe_l::Data {
+            vbuf,
+            u_view_proj: camera.projection.into(),
+            scissor,
+            out_color: view.0.clone(),
+            out_depth: view.1.clone(),
+        };
+        encoder.draw(&slice, &self.pso_l, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_l",
+            count: slice_count(&slice),
+            textures: Vec::new(),
+            uniforms: format!("gizmo at {:?}", gizmo.origin),
+        });
+        self.arena.lines.give_back(lines.vertices);
+    }
+
+    fn render_console<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D) {
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let (screen_width, screen_height) = (screen_width as f32, screen_height as f32);
+        let line_height = FONT_SIZE as f32 * CONSOLE_TEXT_SCALE;
+        let console_height = line_height * (CONSOLE_LINES + 1) as f32 + 8.0;
+
+        self.draw_rect(view, encoder, device, [0.0, screen_height - console_height, screen_width, screen_height], BLACK.with_alpha(0.8));
+
+        let lines: Vec<(String, Color)> = match self.console.page {
+            ConsolePage::Output => self.console.lines.iter().map(|line| (line.clone(), TEXT)).collect(),
+            ConsolePage::Log => self.log.as_ref().map(|log| log.lines()).unwrap_or_default().into_iter()
+                .map(|line| (format!("{:5} {}: {}", line.level, line.target, line.message), log_color(line.level)))
+                .collect(),
+        };
+        let skip = if lines.len() > CONSOLE_LINES { lines.len() - CONSOLE_LINES } else { 0 };
+        let mut y = screen_height - 4.0;
+        for &(ref line, color) in lines.iter().skip(skip) {
+            self.draw_text(view, encoder, device, line, [4.0, y], color, CONSOLE_TEXT_SCALE);
+            y -= line_height;
+        }
+        let y = screen_height - 4.0 - line_height * CONSOLE_LINES as f32;
+        self.draw_text(view, encoder, device, ">", [4.0, y], PROMPT, CONSOLE_TEXT_SCALE);
+        let x = 4.0 + self.font.advance("> ") as f32 * CONSOLE_TEXT_SCALE;
+        self.draw_text_input(view, encoder, device, &self.console.input, [x, y], CONSOLE_TEXT_SCALE);
+    }
+
+    fn draw_text_input<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, input: &TextInput, pos: [f32;2], scale: f32) {
+        let line_height = FONT_SIZE as f32 * scale;
+        if let Some((start, end)) = input.selection() {
+            let x0 = pos[0] + input.offset_of(&self.font, start) * scale;
+            let x1 = pos[0] + input.offset_of(&self.font, end) * scale;
+            self.draw_rect(view, encoder, device, [x0, pos[1] - line_height, x1, pos[1]], SELECTION);
+        }
+        self.draw_runs(view, encoder, device, &input.runs(), pos, WHITE, scale, &[], 0.0);
+
+        let caret_x = pos[0] + input.offset_of(&self.font, input.caret()) * scale;
+        self.draw_text(view, encoder, device, "|", [caret_x, pos[1]], WHITE, scale);
+    }
+
+    // pos is in pixels from the bottom-left corner, marking the top of the first line.
+    fn draw_text<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, text: &str, pos: [f32;2], color: Color, scale: f32) {
+        self.draw_runs(view, encoder, device, &[TextRun::plain(text)], pos, color, scale, &[], 0.0);
+    }
+
+    // Like draw_text, with inline color and size markup; see markup.rs.
+    fn draw_markup<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, markup: &str, pos: [f32;2], color: Color, scale: f32) {
+        self.draw_runs(view, encoder, device, &parse_markup(markup), pos, color, scale, &[], 0.0);
+    }
+
+    // Like draw_markup, animated by the text's effects.
+    fn draw_effect_text<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, text: &EffectText, pos: [f32;2], color: Color, scale: f32) {
+        self.draw_runs(view, encoder, device, &parse_markup(&text.markup), pos, color, scale, &text.effects, text.time);
+    }
+
+    fn draw_runs<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, runs: &[TextRun], pos: [f32;2], color: Color, scale: f32, effects: &[TextEffect], time: f32) {
+        if runs.iter().all(|run| run.text.is_empty()) {
+            return;
+        }
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let (mut vertices, mut indices) = (self.arena.vertices.take(), self.arena.indices.take());
+        let font_entry = font_entry_runs(device, &self.font, runs, [pos[0] / scale, pos[1] / scale], color.into(), scale, effects, time, &mut vertices, &mut indices);
+        self.arena.vertices.give_back(vertices);
+        self.arena.indices.give_back(indices);
+        let data = pipe_pt::Data {
+            vbuf: font_entry.vertex_buffer,
+            u_texture: (font_entry.texture, self.sampler.clone()),
+            u_sdf: if self.font.sdf { 1.0 } else { 0.0 },
+            u_outline: TEXT_OUTLINE.into(),
+            u_offset: [0.0, 0.0],
+            out_color: view.0.clone(),
+            out_depth: view.1.clone(),
+            screen_size: [screen_width as f32, screen_height as f32],
+        };
+        encoder.draw(&font_entry.slice, &self.pso_pt, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_pt",
+            count: slice_count(&font_entry.slice),
+            textures: vec!["font".to_string()],
+            uniforms: format!("text {:?} at {:?} scale {}", runs.iter().flat_map(|run| run.text.chars()).take(DUMP_TEXT_CHARS).collect::<String>(), pos, scale),
+        });
+    }
+
+    // Lays out the static texts created or changed since the last frame, at the origin;
+    // draw_static_text moves them into place.
+    fn prepare_static_text<D: gfx::Device<B::Resources>>(&mut self, device: &mut D) {
+        let font = &self.font;
+        let (mut vertices, mut indices) = (self.arena.vertices.take(), self.arena.indices.take());
+        self.static_text.prepare(|markup, style| {
+            font_entry_runs(device, font, &parse_markup(markup), [0.0, 0.0], style.color.into(), style.scale, &[], 0.0, &mut vertices, &mut indices)
+        });
+        self.arena.vertices.give_back(vertices);
+        self.arena.indices.give_back(indices);
+    }
+
+    // pos is the top-left in pixels, as for draw_text. Nothing is drawn before the text is prepared.
+    fn draw_static_text(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, handle: TextHandle, pos: [f32;2]) {
+        let font_entry = match self.static_text.get(handle) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let data = pipe_pt::Data {
+            vbuf: font_entry.vertex_buffer.clone(),
+            u_texture: (font_entry.texture.clone(), self.sampler.clone()),
+            u_sdf: if self.font.sdf { 1.0 } else { 0.0 },
+            u_outline: TEXT_OUTLINE.into(),
+            u_offset: pos,
+            out_color: view.0.clone(),
+            out_depth: view.1.clone(),
+            screen_size: [screen_width as f32, screen_height as f32],
+        };
+        encoder.draw(&font_entry.slice, &self.pso_pt, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_pt",
+            count: slice_count(&font_entry.slice),
+            textures: vec!["font".to_string()],
+            uniforms: format!("static text {:?} at {:?}", handle, pos),
+        });
+    }
+
+    // rect is [left, bottom, right, top] in pixels.
+    fn draw_rect<D: gfx::Device<B::Resources>>(&self, view: &View<B::Resources>, encoder: &mut gfx::GraphicsEncoder<B>, device: &mut D, rect: [f32;4], color: Color) {
+        use gfx::traits::DeviceExt;
+        let (screen_width, screen_height, _, _) = view.0.get_dimensions();
+        let screen = Screen::new(screen_width, screen_height);
+        let to_ndc = |x: f32, y: f32| {
+            let ndc = screen_to_ndc(screen, [x, y]);
+            [ndc[0], ndc[1], 0.0]
+        };
+        let vertex_data = [
+            VertexP { position: to_ndc(rect[0], rect[3]), color: color.into() },
+            VertexP { position: to_ndc(rect[2], rect[3]), color: color.into() },
+            VertexP { position: to_ndc(rect[0], rect[1]), color: color.into() },
+            VertexP { position: to_ndc(rect[2], rect[1]), color: color.into() },
+        ];
+        let (vbuf, slice) = device.create_vertex_buffer_with_slice(&vertex_data, &[1u32, 0u32, 2u32, 3u32][..]);
+        let data = pipe_p::Data {
+            vbuf: vbuf,
+            out_color: view.0.clone(),
+            out_depth: view.1.clone(),
+        };
+        encoder.draw(&slice, &self.pso_p, &data);
+        self.frame_dump.record(|| Draw {
+            pipeline: "pipe_p",
+            count: slice_count(&slice),
+            textures: Vec::new(),
+            uniforms: format!("rect {:?} {:?}", rect, color),
+        });
+    }
+
+    fn is_text_input_active(&self) -> bool {
+        self.console.visible || self.save_prompt.is_some()
+    }
+
+    fn suspend(&mut self) {
+        if self.suspended.is_none() {
+            self.suspended = Some(self.states.len());
+            self.apply_transition(Transition::Push(Box::new(PauseMenu::new())));
+        }
+    }
+    fn resume(&mut self) {
+        if let Some(depth) = self.suspended.take() {
+            while self.states.len() > depth {
+                self.apply_transition(Transition::Pop);
+            }
+        }
+    }
+    fn state_pauses(&self) -> bool {
+        self.states.iter().any(|state| state.pauses())
+    }
+    // The clock runs unless a state or the scrubber holds it.
+    fn update_clock(&mut self) {
+        if self.scrub.is_some() || self.state_pauses() {
+            self.system.target.pause();
+        } else {
+            self.system.target.resume();
+        }
+        let paused = self.state_pauses();
+        self.dispatcher.set_blocked(Level::Avator, paused);
+    }
+    fn apply_transition(&mut self, transition: Transition<World<B, Vertex>>) {
+        match transition {
+            Transition::None => return,
+            Transition::Push(state) => self.push_state(state),
+            Transition::Pop => self.pop_state(),
+            Transition::Replace(state) => {
+                self.pop_state();
+                self.push_state(state);
+            },
+        }
+        self.update_clock();
+    }
+    fn push_state(&mut self, mut state: Box<GameState<World<B, Vertex>>>) {
+        // the state under it stops seeing the keys come up
+        self.input.release_all();
+        state.on_enter(self);
+        self.states.push(state);
+    }
+    fn pop_state(&mut self) {
+        if let Some(mut state) = self.states.pop() {
+            state.on_exit(self);
+        }
+    }
+    fn update_states(&mut self) {
+        // the top state is off the stack while it runs
+        let transition = match self.states.pop() {
+            Some(mut top) => {
+                let transition = top.update(self);
+                self.states.push(top);
+                transition
+            },
+            None => Transition::None,
+        };
+        self.apply_transition(transition);
+    }
+    fn toggle_scrub(&mut self) {
+        if self.scrub.is_some() {
+            self.scrub = None;
+        } else {
+            // start from the sample currently on screen
+            let elapsed = self.system.target.elapsed();
+            let index = self.avators.target.get(&1)
+                .map(|obj| obj.body.sample_index_at(elapsed))
+                .unwrap_or(0);
+            self.scrub = Some(index);
+        }
+        self.update_clock();
+    }
+    fn step_scrub(&mut self, step: isize) {
+        let count = self.avators.target.values().map(|obj| obj.body.sample_count()).max().unwrap_or(0) as isize;
+        if let Some(index) = self.scrub {
+            let next = if count > 0 { ((index as isize + step) % count + count) % count } else { 0 };
+            self.scrub = Some(next as usize);
+        }
+    }
+
+    fn run_console_command(&mut self, command: ConsoleCommand) {
+        match command {
+            ConsoleCommand::Help => {
+                for line in HELP {
+                    self.console.print(*line);
+                }
+            },
+            ConsoleCommand::JointName(object_id, joint_index, name) => {
+                match update_joint_name(&open_connection(), object_id, joint_index, name.as_ref().map(|name| name.as_str())) {
+                    Ok(true) => {},
+                    Ok(false) => self.console.print(format!("object {} has no joint {}", object_id, joint_index)),
+                    Err(e) => self.console.print(format!("{:?}", e)),
+                }
+            },
+            ConsoleCommand::Retarget(object_id, source_object_id) => {
+                match insert_animation_source(&open_connection(), object_id, source_object_id) {
+                    // clips are cached per object, so loaded instances keep theirs
+                    Ok(_) => {
+                        self.forget_thumbnail(object_id);
+                        self.console.print(format!("object {} takes its clips from the next spawn on", object_id));
+                    },
+                    Err(e) => self.console.print(format!("{:?}", e)),
+                }
+            },
+            ConsoleCommand::History => {
+                let replay = if history_replay(&open_connection()) { "on" } else { "off" };
+                let (len, capacity) = {
+                    let history = self.session.history();
+                    (history.len(), history.capacity())
+                };
+                self.console.print(format!("{} of {} commands, replay {}", len, capacity, replay));
+            },
+            ConsoleCommand::HistorySize(size) => {
+                self.session.history().set_capacity(size);
+                if let Err(e) = insert_setting(&open_connection(), HISTORY_SIZE_SETTING, &size.to_string()) {
+                    self.console.print(format!("failed to store the history size: {}", e));
+                }
+            },
+            ConsoleCommand::HistoryReplay(replay) => {
+                let value = if replay { "on" } else { "off" };
+                if let Err(e) = insert_setting(&open_connection(), HISTORY_REPLAY_SETTING, value) {
+                    self.console.print(format!("failed to store the history replay setting: {}", e));
+                }
+            },
+            ConsoleCommand::HistoryClear => self.session.history().clear(),
+            ConsoleCommand::Movement => {
+                let model = self.movement.model;
+                self.console.print(format!("acceleration {}, friction {}, max speed {}", model.acceleration, model.friction, model.max_speed));
+            },
+            ConsoleCommand::SetMovement(model) => {
+                self.movement.model = model;
+                if let Err(e) = insert_setting(&open_connection(), MOVEMENT_SETTING, &model.to_setting()) {
+                    self.console.print(format!("failed to store the movement model: {}", e));
+                }
+            },
+            ConsoleCommand::Gpus => {
+                for summary in &self.adapters {
+                    let mark = if summary.index == self.adapter { " *" } else { "" };
+                    self.console.print(format!("{}{}", summary, mark));
+                }
+            },
+            ConsoleCommand::Clear => match self.console.page {
+                ConsolePage::Output => self.console.clear(),
+                ConsolePage::Log => if let Some(ref log) = self.log {
+                    log.clear();
+                },
+            },
+            ConsoleCommand::LogPage => self.console.page = match self.console.page {
+                ConsolePage::Output => ConsolePage::Log,
+                ConsolePage::Log => ConsolePage::Output,
+            },
+            ConsoleCommand::LogLevel(module, level) => match self.log {
+                Some(ref log) => {
+                    log.set_level(module.as_ref().map(|m| m.as_str()), level);
+                    self.console.print(format!("log level {}: {}", module.as_ref().map(|m| m.as_str()).unwrap_or("*"), level));
+                },
+                None => self.console.print("logging is not set up"),
+            },
+            ConsoleCommand::CameraMove(v) => self.camera.append_command(CameraCommand::Move(v)),
+            ConsoleCommand::CameraLookAt(p) => self.camera.append_command(CameraCommand::LookAt(p)),
+            ConsoleCommand::CameraRoll(degrees) => self.camera.append_command(CameraCommand::Roll(Rad(degrees.to_radians()))),
+            ConsoleCommand::CameraUp(up) => self.camera.append_command(CameraCommand::SetUp(up)),
+            ConsoleCommand::CameraClip(near, far) => self.camera.append_command(CameraCommand::SetClip(near, far)),
+            ConsoleCommand::CameraOrtho(ortho) => {
+                let mode = if ortho { ProjectionMode::Orthographic } else { ProjectionMode::Perspective };
+                self.camera.append_command(CameraCommand::SetProjection(mode));
+            },
+            ConsoleCommand::CameraTop => {
+                self.camera.append_command(CameraCommand::TopDown);
+                self.camera.append_command(CameraCommand::SetProjection(ProjectionMode::Orthographic));
+            },
+            ConsoleCommand::ObserverMove(v) => self.observer.append_command(CameraCommand::Move(v)),
+            ConsoleCommand::ObserverLookAt(p) => self.observer.append_command(CameraCommand::LookAt(p)),
+            ConsoleCommand::Layout(layout) => self.layout = layout,
+            ConsoleCommand::Spawn(object_id, position) => self.pending_spawns.push(PendingSpawn { object_id, position, scale: 1.0, placed: false }),
+            ConsoleCommand::PaletteList => {
+                let entries = self.spawn_palette.entries();
+                if entries.is_empty() {
+                    self.console.print("palette is empty");
+                }
+                for (slot, template) in entries {
+                    self.console.print(format!("{}: object {} x{}", slot, template.object_id, template.scale));
+                }
+            },
+            ConsoleCommand::PaletteSet(slot, template) => self.set_palette_slot(slot, template),
+            ConsoleCommand::Despawn(key) => {
+                if self.despawn(key) {
+                    self.console.print(format!("despawned {}, {} textures loaded", key, self.textures.len()));
+                } else {
+                    self.console.print(format!("no object {}", key));
+                }
+            },
+            ConsoleCommand::Npc(key, kind) => self.set_behavior(key, kind),
+            ConsoleCommand::Damage(key, amount) => self.queue_stats(key, StatsCommand::Damage(amount)),
+            ConsoleCommand::Heal(key, amount) => self.queue_stats(key, StatsCommand::Heal(amount)),
+            ConsoleCommand::PathKey(path_id) => {
+                let (position, target) = (self.camera().position, self.camera().target);
+                match insert_camera_key(&open_connection(), path_id, position, target) {
+                    Ok(time) => self.console.print(format!("path {}: key at {:.1}s", path_id, time)),
+                    Err(e) => self.console.print(format!("{:?}", e)),
+                }
+            },
+            ConsoleCommand::PathPlay(path_id) => {
+                self.cancel_camera_moves();
+                self.camera.append_command(CameraCommand::PlayPath(path_id));
+            },
+            ConsoleCommand::PathStop => self.camera.append_command(CameraCommand::StopPath),
+            ConsoleCommand::PathClear(path_id) => {
+                if let Err(e) = delete_camera_path(&open_connection(), path_id) {
+                    self.console.print(format!("{:?}", e));
+                }
+            },
+            ConsoleCommand::DebugDraw(category, enabled) => self.debug_flags.set(category, enabled),
+            ConsoleCommand::AvatorMove(target, v) => self.command_avators(&target, AvatorCommand::Move(v)),
+            ConsoleCommand::Tag(key, tag, add) => match self.avators.target.get_mut(&key) {
+                Some(obj) => {
+                    obj.tags.retain(|t| *t != tag);
+                    if add {
+                        obj.tags.push(tag);
+                    }
+                },
+                None => self.console.print(format!("no object {}", key)),
+            },
+            ConsoleCommand::AvatorSpeed(id, speed) => match self.avators.target.get_mut(&id) {
+                Some(obj) => {
+                    let now = self.system.target.elapsed();
+                    obj.body.set_speed(speed, now);
+                    self.update.send(SimMessage::SetSpeed(id, speed, now));
+                },
+                None => self.console.print(format!("no avator {}", id)),
+            },
+            ConsoleCommand::LayerWeight(id, layer, weight) => match self.avators.target.get_mut(&id) {
+                Some(obj) => {
+                    let count = obj.body.layers.len();
+                    match obj.body.layers.get_mut(layer) {
+                        Some(layer_state) => {
+                            layer_state.weight = weight;
+                            self.update.send(SimMessage::SetLayerWeight(id, layer, weight));
+                        },
+                        None => self.console.print(format!("avator {} has {} layers", id, count)),
+                    }
+                },
+                None => self.console.print(format!("no avator {}", id)),
+            },
+            ConsoleCommand::Save(name) => self.save(&name),
+            ConsoleCommand::PresetSave(name) => self.save_preset(&name),
+            ConsoleCommand::PresetLoad(name) => self.load_preset(&name),
+            ConsoleCommand::Scene(scene_id, kind) => self.load_scene(scene_id, kind),
+            ConsoleCommand::PresetList => match query_preset_names(&open_connection()) {
+                Ok(ref names) if names.is_empty() => self.console.print("no presets"),
+                Ok(names) => self.console.print(names.join(" ")),
+                Err(e) => self.console.print(format!("{:?}", e)),
+            },
+            ConsoleCommand::Reload(Some(object_id)) => self.pending_reloads.push(object_id),
+            ConsoleCommand::Reload(None) => {
+                let object_ids = self.live_object_ids();
+                self.pending_reloads.extend(object_ids);
+            },
+            ConsoleCommand::ReloadWatch(true) => if self.asset_watch.is_none() {
+                self.asset_watch = Some(AssetWatch::new(PathBuf::from(database_path())));
+                let conn = open_connection();
+                for object_id in self.live_object_ids() {
+                    self.remember_fingerprint(&conn, object_id);
+                }
+            },
+            ConsoleCommand::ReloadWatch(false) => self.asset_watch = None,
+            ConsoleCommand::MigrateIndices(object_id) => {
+                match migrate_mesh_indices(object_id) {
+                    Ok(_) => self.console.print(format!("migrated {}", object_id)),
+                    Err(e) => self.console.print(format!("{:?}", e)),
+                }
+            },
+            ConsoleCommand::AddMarker(p) => self.billboards.push(
+                Billboard::new(p, [1.0, 1.0], Color::rgba(0.2, 0.8, 1.0, 0.9)).with_pixel_range(12.0, 48.0)
+            ),
+            ConsoleCommand::ClearMarkers => self.billboards.clear(),
+            ConsoleCommand::SpriteScreen(sheet, rect) => self.pending_sprites.push(
+                AnimatedSprite::new(sheet, SpritePlacement::Screen(rect))
+            ),
+            ConsoleCommand::SpriteWorld(sheet, p) => self.pending_sprites.push(
+                AnimatedSprite::new(sheet, SpritePlacement::World(Billboard::new(p, [1.0, 1.0], WHITE).with_pixel_range(16.0, 128.0)))
+            ),
+            ConsoleCommand::Say(effects, text) => self.dialogue = if text.is_empty() {
+                None
+            } else {
+                Some(EffectText::new(&text, effects))
+            },
+            ConsoleCommand::SpriteClear => {
+                for (sprite, _) in std::mem::replace(&mut self.sprites, Vec::new()) {
+                    self.textures.release(sprite.sheet.texture_id);
+                }
+            },
+            ConsoleCommand::AvatorMarkers(visible) => self.show_avator_markers = visible,
+            ConsoleCommand::Select(key) => self.select(key),
+            ConsoleCommand::Pick(mode) => self.pick_mode = mode,
+            ConsoleCommand::Lighting(space) => self.set_lighting(space),
+            ConsoleCommand::DumpFrame(path) => {
+                let path = PathBuf::from(path.unwrap_or_else(|| DEFAULT_FRAME_DUMP.to_string()));
+                self.frame_dump.request(&path);
+            },
+            ConsoleCommand::MeshVisible(key, mesh_id, visible) => match self.avators.target.get_mut(&key) {
+                Some(obj) => {
+                    let mut found = false;
+                    for entry in obj.entries.iter_mut().filter(|entry| entry.mesh_id == mesh_id) {
+                        entry.visible = visible;
+                        found = true;
+                    }
+                    if !found {
+                        self.console.print(format!("object {} draws no mesh {}", key, mesh_id));
+                    }
+                },
+                None => self.console.print(format!("no object {}", key)),
+            },
+            ConsoleCommand::Layers => {
+                let main = self.camera.target.layers.names();
+                let observer = self.observer.target.layers.names();
+                self.console.print(format!("main: {}, observer: {}", main, observer));
+            },
+            ConsoleCommand::CameraLayer(which, layer, on) => {
+                let camera = match which {
+                    ViewCamera::Main => &mut self.camera.target,
+                    ViewCamera::Observer => &mut self.observer.target,
+                };
+                camera.layers = camera.layers.with(layer, on);
+                // the viewport cameras are copies, made again when the generation moves
+                camera.dirty = true;
+            },
+            ConsoleCommand::Fog(mode) => self.fog.mode = mode,
+            ConsoleCommand::FogColor(color) => self.fog.color = color,
+            ConsoleCommand::Flash(color, seconds) => self.flash(color, seconds),
+            ConsoleCommand::Fade(true, seconds) => self.fade_out(seconds),
+            ConsoleCommand::Fade(false, seconds) => self.fade_in(seconds),
+            ConsoleCommand::TimeShow => {
+                let elapsed = self.system.target.elapsed();
+                let line = format!("{:05.2}h of a {}s day{}",
+                                   self.time_of_day.hour(elapsed),
+                                   self.time_of_day.cycle_length(),
+                                   if self.time_of_day.is_frozen() { ", frozen" } else { "" });
+                self.console.print(line);
+            },
+            ConsoleCommand::TimeSet(hour) => self.time_of_day.set_hour(hour, self.system.target.elapsed()),
+            ConsoleCommand::TimeFreeze(true) => self.time_of_day.freeze(self.system.target.elapsed()),
+            ConsoleCommand::TimeFreeze(false) => self.time_of_day.resume(self.system.target.elapsed()),
+            ConsoleCommand::TimeCycle(seconds) => self.time_of_day.set_cycle_length(seconds, self.system.target.elapsed()),
+            ConsoleCommand::Language(language) => {
+                match self.set_language(&language) {
+                    Ok(_) => {
+                        let line = format!("{}: {}", self.tr("console.language"), language);
+                        self.console.print(line);
+                    },
+                    Err(e) => self.console.print(format!("{:?}", e)),
+                }
+            },
+        }
+    }
+
+    fn save_preset(&mut self, name: &str) {
+        let elapsed = self.system.target.elapsed();
+        let camera = &self.camera.target;
+        let preset = Preset {
+            position: camera.position,
+            target: camera.target,
+            fovy: camera.fov.fovy.0,
+            hour: self.time_of_day.hour(elapsed),
+            time_frozen: self.time_of_day.is_frozen(),
+            fog: self.fog,
+        };
+        let line = match insert_preset(&open_connection(), name, &preset) {
+            Ok(_) => format!("preset {} saved", name),
+            Err(e) => format!("failed to save preset {}: {:?}", name, e),
+        };
+        self.console.print(line);
+    }
+    fn load_preset(&mut self, name: &str) {
+        let preset = match query_preset(&open_connection(), name) {
+            Ok(Some(preset)) => preset,
+            Ok(None) => return self.console.print(format!("no preset {}", name)),
+            Err(e) => return self.console.print(format!("failed to load preset {}: {:?}", name, e)),
+        };
+        self.cancel_camera_moves();
+        self.camera.append_command(CameraCommand::SetView(preset.position, preset.target, Rad(preset.fovy)));
+        let elapsed = self.system.target.elapsed();
+        self.time_of_day.set_hour(preset.hour, elapsed);
+        if preset.time_frozen {
+            self.time_of_day.freeze(elapsed);
+        } else {
+            self.time_of_day.resume(elapsed);
+        }
+        self.fog = preset.fog;
+        self.console.print(format!("preset {} loaded", name));
+    }
+    fn avator_positions(&self) -> Vec<(i32, [f32; 3])> {
+        self.avators.target.iter()
+            .map(|(id, obj)| (*id, obj.body.position.into()))
+            .collect()
+    }
+    fn save(&mut self, name: &str) {
+        match insert_save(&open_connection(), name, &self.avator_positions()) {
+            Ok(_) => {
+                let line = format!("{} {}", self.tr("console.saved"), name);
+                self.console.print(line.clone());
+                self.notify(line, TOAST_SECONDS);
+            },
+            Err(e) => {
+                let line = format!("{} {}: {}", self.tr("console.save_failed"), name, e);
+                self.console.print(line);
+            },
+        }
+    }
+
+    fn refresh_session(&mut self) {
+        let quality = self.pending_quality.unwrap_or(self.graphics.quality);
+        self.session.refresh(self.avator_positions(), quality);
+    }
+    // Stops the threads and sockets first, so nothing moves while the state is written.
+    // Runs from Drop while unwinding too, so failures are logged rather than panicking.
+    fn shutdown(&mut self) {
+        self.update.stop();
+        self.peer = None;
+        self.refresh_session();
+        self.session.write();
+    }
+
+    // Queues the camera and observer commands of the last session, so the views end up
+    // where they were left; a history cut short by its size starts from a later view.
+    // The avators come back from the autosave instead, so their moves are only kept.
+    fn replay_history(&mut self, conn: &Connection) {
+        let entries = match query_command_history(conn, self.session.history().capacity()) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("failed to read the command history: {}", e);
+                return;
+            },
+        };
+        let mut replayed = 0;
+        for entry in entries {
+            let command = match entry.target {
+                HistoryTarget::Camera | HistoryTarget::Observer => CameraCommand::replay(&entry.kind, &entry.args),
+                HistoryTarget::Avator => None,
+            };
+            match (entry.target, command) {
+                (HistoryTarget::Camera, Some(command)) => self.camera.append_command(command),
+                (HistoryTarget::Observer, Some(command)) => self.observer.append_command(command),
+                _ => {},
+            }
+            if entry.target != HistoryTarget::Avator {
+                replayed += 1;
+            }
+            self.session.history().push(entry);
+        }
+        info!("replaying {} camera commands from the last session", replayed);
+    }
+
+    fn handle_input(&mut self, ev: InputEvent) {
+        if ev.is_pressed(Key::Grave) && self.save_prompt.is_none() {
+            self.console.toggle();
+            return;
+        }
+        if self.console.visible {
+            if let Some(command) = self.console.handle_input(&ev) {
+                self.run_console_command(command);
+            }
+            return;
+        }
+        if self.save_prompt.is_some() {
+            match self.save_prompt.as_mut().and_then(|p| p.handle_input(&ev)) {
+                Some(TextInputEvent::Submit(name)) => {
+                    self.save_prompt = None;
+                    if !name.is_empty() {
+                        self.save(&name);
+                    }
+                },
+                Some(TextInputEvent::Cancel) => self.save_prompt = None,
+                None => { }
+            }
+            return;
+        }
+        // the top state is off the stack while it runs
+        let transition = match self.states.pop() {
+            Some(mut top) => {
+                let transition = top.handle_input(self, &ev);
+                self.states.push(top);
+                transition
+            },
+            None => Transition::None,
+        };
+        self.apply_transition(transition);
+    }
+    fn handle_key(&mut self, key: Key) {
+        match key {
+            Key::W => self.move_camera(Vector3::new(0.0, 0.1, 0.0)),
+            Key::S => self.move_camera(Vector3::new(0.0, -0.1, 0.0)),
+            Key::A => self.move_camera(Vector3::new(-0.1, 0.0, 0.0)),
+            Key::D => self.move_camera(Vector3::new(0.1, 0.0, 0.0)),
+            Key::F2 => self.layout = self.layout.next(),
+            Key::F3 => self.render_mode = self.render_mode.next(),
+            Key::F4 => self.toggle_scrub(),
+            Key::F6 => self.toggle_projection(),
+            Key::F7 => self.toggle_editor(),
+            Key::F8 => {
+                let space = self.graphics.lighting.other();
+                self.set_lighting(space);
+            },
+            // the scrubber has keys of its own, leaving the arrows to the observer
+            Key::Comma => self.step_scrub(-1),
+            Key::Period => self.step_scrub(1),
+            Key::LBracket => self.step_scrub(-10),
+            Key::RBracket => self.step_scrub(10),
+            Key::Up | Key::Down | Key::Left | Key::Right if self.layout != ViewportLayout::Single => {
+                let v = match key {
+                    Key::Up => Vector3::new(0.0, 0.5, 0.0),
+                    Key::Down => Vector3::new(0.0, -0.5, 0.0),
+                    Key::Left => Vector3::new(-0.5, 0.0, 0.0),
+                    _ => Vector3::new(0.5, 0.0, 0.0),
                 };
-                encoder.draw(&slice, &self.pso_p, &data);
+                self.observer.append_command(CameraCommand::Move(v));
+            },
+            Key::E => self.interact(),
+            Key::Key1 | Key::Key2 | Key::Key3 | Key::Key4 | Key::Key5 |
+            Key::Key6 | Key::Key7 | Key::Key8 | Key::Key9 => self.spawn_from_palette(palette_slot(key)),
+            Key::F5 => self.save_prompt = Some(TextInput::new()),
+            Key::N => self.minimap.visible = !self.minimap.visible,
+            Key::PageUp => self.minimap.zoom_in(),
+            Key::PageDown => self.minimap.zoom_out(),
+            Key::Tab => {
+                let enabled = !self.mouse_look.enabled;
+                self.mouse_look.set_enabled(enabled);
+            },
+            _   => { }
+        }
+    }
+    // Loads object_id from the database as a new GameObject and returns its key.
+    // The object id itself is used as the key unless it is taken.
+    fn spawn<D: gfx::Device<B::Resources>>(&mut self, device: &mut D, object_id: i32, position: Point3<f32>, scale: f32) -> Result<i32, AppError> {
+        let conn = self.asset_db.get()?;
+        let mut obj = query_object(&conn, device, &mut self.textures, &mut self.clips, self.graphics.mipmaps, self.frames, &object_id)?;
+        self.remember_fingerprint(&conn, object_id);
+        obj.body.position = position;
+        obj.scale = scale;
+        obj.bounds = obj.bounds.scaled(scale);
+        let key = if self.avators.target.contains_key(&object_id) {
+            self.avators.target.keys().max().map(|k| k + 1).unwrap_or(object_id)
+        } else {
+            object_id
+        };
+        self.update.send(SimMessage::Insert(key, obj.body.clone()));
+        self.avators.target.insert(key, obj);
+        self.events.publish(GameEvent::ObjectSpawned { key, object_id });
+        Ok(key)
+    }
+    // Buffers are freed with the object; textures once no other object uses them.
+    fn despawn(&mut self, key: i32) -> bool {
+        match self.avators.target.remove(&key) {
+            Some(obj) => {
+                self.update.send(SimMessage::Remove(key));
+                self.events.publish(GameEvent::ObjectDespawned { key });
+                for texture_id in &obj.texture_ids {
+                    self.textures.release(*texture_id);
+                }
+                drop(obj);
+                self.clips.collect_unused();
+                true
+            },
+            None => false,
+        }
+    }
+    // Replaces every object with the scene's behind the given transition.
+    fn load_scene(&mut self, scene_id: i32, kind: TransitionKind) {
+        let kind = if self.graphics.post_process { kind } else { TransitionKind::Cut };
+        if let Some(handle) = self.scene_transition.and_then(|t| t.stage.handle()) {
+            self.tweens.cancel(handle);
+        }
+        let stage = match kind {
+            TransitionKind::Cut => TransitionStage::Swap,
+            TransitionKind::FadeToBlack => TransitionStage::Out(
+                self.tweens.start(TweenAction::SceneFade, SCENE_TRANSITION_DURATION / 2.0, Easing::EaseInOut)
+            ),
+            TransitionKind::CrossDissolve => TransitionStage::Capture,
+        };
+        self.scene_transition = Some(SceneTransition { kind, scene_id, stage });
+    }
+    fn swap_scene<D: gfx::Device<B::Resources>>(&mut self, device: &mut D, scene_id: i32) {
+        let objects = match query_scene(&open_connection(), scene_id) {
+            Ok(ref objects) if objects.is_empty() => {
+                self.console.print(format!("scene {} has no objects", scene_id));
+                return;
+            },
+            Ok(objects) => objects,
+            Err(e) => {
+                self.console.print(format!("failed to load scene {}: {:?}", scene_id, e));
+                return;
+            },
+        };
+        let keys: Vec<i32> = self.avators.target.keys().cloned().collect();
+        for key in keys {
+            self.despawn(key);
+        }
+        self.editor.drag = None;
+        self.scene_id = Some(scene_id);
+        for (row, object_id, position) in objects {
+            match self.spawn(device, object_id, position, 1.0) {
+                Ok(key) => if let Some(obj) = self.avators.target.get_mut(&key) {
+                    obj.scene_row = Some(row);
+                },
+                Err(e) => self.console.print(format!("failed to spawn {}: {:?}", object_id, e)),
             }
-            {
-                let font_entry = font_entry(device, &self.font, &format!("abc\n0efg"), [40.0, screen_height as f32 / 2.0], [0.8, 0.8, 0.8, 1.0], 1.0);
-
-                let data = pipe_pt::Data {
-                    vbuf: font_entry.vertex_buffer,
-                    u_texture: (font_entry.texture, self.sampler.clone()),
-                    out_color: view.0.clone(),
-                    out_depth: view.1.clone(),
-                    screen_size: {
-                        [screen_width as f32, screen_height as f32]
-                    },
-                };
-                encoder.draw(&font_entry.slice, &self.pso_pt, &data);
+        }
+        for crossing in self.triggers.replace(load_triggers(&open_connection(), scene_id)) {
+            self.publish_crossing(crossing);
+        }
+    }
+    // Where the local avator went since the last frame, as trigger events.
+    fn update_triggers(&mut self) {
+        let position = self.avators.target.get(&1).map(|obj| obj.body.position);
+        for crossing in self.triggers.update(position) {
+            self.publish_crossing(crossing);
+        }
+    }
+    fn publish_crossing(&mut self, crossing: Crossing) {
+        self.events.publish(match crossing {
+            Crossing::Enter(trigger) => GameEvent::TriggerEntered { trigger },
+            Crossing::Exit(trigger) => GameEvent::TriggerExited { trigger },
+        });
+    }
+    // The sample interaction: a trigger's prompt shows while the avator is inside,
+    // and one leading to a scene offers to go there by default.
+    fn run_interactions(&mut self) {
+        let subscriber = self.interaction_events;
+        for event in self.events.drain(subscriber) {
+            match event {
+                GameEvent::TriggerEntered { trigger } => {
+                    let prompt = match self.triggers.get(trigger) {
+                        Some(&Trigger { prompt: Some(ref prompt), .. }) => prompt.clone(),
+                        Some(&Trigger { scene: Some(_), .. }) => self.tr("trigger.enter_scene").to_string(),
+                        _ => continue,
+                    };
+                    let effects = vec!(TextEffect::Typewriter { chars_per_second: 60.0 });
+                    self.interaction = Some((trigger, EffectText::new(&prompt, effects)));
+                },
+                GameEvent::TriggerExited { trigger } => if self.interaction.as_ref().map_or(false, |&(id, _)| id == trigger) {
+                    self.interaction = None;
+                },
+                _ => {},
             }
         }
     }
-
-    fn handle_input(&mut self, ev: glutin::WindowEvent) {
-        match ev {
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::L), ..
-                }, ..
-            } => self.avators.append_command(AvatorCommand::Move(Vector3::new(0.5,0.0,0.0))),
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::H), ..
-                }, ..
-            } => self.avators.append_command(AvatorCommand::Move(Vector3::new(-0.5,0.0,0.0))),
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::J), ..
-                }, ..
-            } => self.avators.append_command(AvatorCommand::Move(Vector3::new(0.0,-0.5,0.0))),
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::K), ..
-                }, ..
-            } => self.avators.append_command(AvatorCommand::Move(Vector3::new(0.0,0.5,0.0))),
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::W), ..
-                }, ..
-            } => self.camera.append_command(CameraCommand::Move(Vector3::new(0.0, 0.1, 0.0))),
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::S), ..
-                }, ..
-            } => self.camera.append_command(CameraCommand::Move(Vector3::new(0.0, -0.1, 0.0))),
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::A), ..
-                }, ..
-            } => self.camera.append_command(CameraCommand::Move(Vector3::new(-0.1, 0.0, 0.0))),
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::D), ..
-                }, ..
-            } => self.camera.append_command(CameraCommand::Move(Vector3::new(0.1, 0.0, 0.0))),
-            glutin::WindowEvent::KeyboardInput {
-                input: glutin::KeyboardInput {
-                    state: glutin::ElementState::Pressed,
-                    virtual_keycode: Some(glutin::VirtualKeyCode::M), ..
-                }, ..
-            } => self.state = if self.state == WorldState::Render { WorldState::Pose } else { WorldState::Render } , 
-            glutin::WindowEvent::AxisMotion {
-                axis,
-                value,
-                ..
-            } => {
-                println!("axis motion {}: {}", axis, value);
+    // The interact key: takes a scene trigger the avator stands in.
+    fn interact(&mut self) {
+        let scene = self.interaction.as_ref()
+            .and_then(|&(id, _)| self.triggers.get(id))
+            .and_then(|trigger| trigger.scene);
+        if let Some(scene_id) = scene {
+            self.load_scene(scene_id, TransitionKind::FadeToBlack);
+        }
+    }
+    // Swaps the scene once a cut starts or a fade has covered the screen.
+    fn apply_scene_swap<D: gfx::Device<B::Resources>>(&mut self, device: &mut D) {
+        let transition = match self.scene_transition {
+            Some(transition) if transition.stage == TransitionStage::Swap => transition,
+            _ => return,
+        };
+        self.swap_scene(device, transition.scene_id);
+        self.scene_transition = match transition.kind {
+            TransitionKind::FadeToBlack => {
+                let handle = self.tweens.start(TweenAction::SceneFade, SCENE_TRANSITION_DURATION / 2.0, Easing::EaseInOut);
+                Some(SceneTransition { stage: TransitionStage::In(handle), ..transition })
             },
-            _   => { }
+            _ => None,
+        };
+    }
+    // The screen in color, clearing over duration seconds; color's alpha is where it starts.
+    fn flash(&mut self, color: Color, duration: f32) {
+        let handle = self.tweens.start(TweenAction::ScreenEffect, duration, Easing::EaseOut);
+        self.screen_effects.add(ScreenEffect { kind: ScreenEffectKind::Flash, color, handle });
+    }
+    // To black, staying covered until fade_in.
+    fn fade_out(&mut self, duration: f32) {
+        let handle = self.tweens.start(TweenAction::ScreenEffect, duration, Easing::EaseInOut);
+        self.screen_effects.add(ScreenEffect { kind: ScreenEffectKind::FadeOut, color: BLACK, handle });
+    }
+    // Back from what fade_out covered; nothing to do when it covers nothing.
+    fn fade_in(&mut self, duration: f32) {
+        if let Some(cover) = self.screen_effects.lift() {
+            self.tweens.cancel(cover.handle);
+            let handle = self.tweens.start(TweenAction::ScreenEffect, duration, Easing::EaseInOut);
+            self.screen_effects.add(ScreenEffect { kind: ScreenEffectKind::FadeIn, handle, ..cover });
         }
     }
-    fn execute_all_commands(&mut self) {
-        self.avators.execute_all_commands();
-        self.camera.execute_all_commands();
+    fn finish_scene_fade(&mut self, handle: TweenHandle) {
+        let stage = match self.scene_transition {
+            Some(transition) => transition.stage,
+            None => return,
+        };
+        match stage {
+            TransitionStage::Out(h) if h == handle => {
+                if let Some(ref mut transition) = self.scene_transition {
+                    transition.stage = TransitionStage::Swap;
+                }
+            },
+            TransitionStage::In(h) if h == handle => self.scene_transition = None,
+            _ => {},
+        }
     }
-}
-
-impl<Cmd, T> Invoker<Cmd, T> {
-    fn new(t: T) -> Self {
-        Invoker {
-            commands: Vec::new(),
-            target: t,
-            current_index: 0,
+    // Switches tier between frames. The sampler is rebuilt at once, mipmaps apply to
+    // textures loaded from then on, and multisampling waits for the next start.
+    fn apply_quality<D: gfx::Device<B::Resources>>(&mut self, device: &mut D) {
+        let quality = match self.pending_quality.take() {
+            Some(quality) => quality,
+            None => return,
+        };
+        let msaa = self.graphics.msaa;
+        self.graphics = self.graphics.with_quality(quality);
+        self.graphics.msaa = msaa;
+        self.sampler = device.create_sampler(self.graphics.sampler_info());
+        self.console.print(format!("quality {}", quality.name()));
+        self.notify(format!("quality {}", quality.name()), TOAST_SECONDS);
+    }
+    fn apply_pending_sprites<D: gfx::Device<B::Resources>>(&mut self, device: &mut D) {
+        for sprite in std::mem::replace(&mut self.pending_sprites, Vec::new()) {
+            match self.load_sprite_sheet(device, &sprite.sheet) {
+                Ok(view) => self.sprites.push((sprite, view)),
+                Err(e) => self.console.print(format!("failed to load sprite sheet {}: {:?}", sprite.sheet.texture_id, e)),
+            }
         }
     }
-}
-
-impl<Cmd, T> Invoker<Cmd, T>
-    where Cmd: Command<T> {
-    fn execute_command(&mut self) {
-        if self.commands.len() <= self.current_index {
+    fn load_sprite_sheet<D: gfx::Device<B::Resources>>(&mut self, device: &mut D, sheet: &SpriteSheet) -> Result<gfx::handle::ShaderResourceView<B::Resources, [f32; 4]>, AppError> {
+        let mipmaps = self.graphics.mipmaps;
+        let asset_db = &self.asset_db;
+        self.textures.acquire(sheet.texture_id, || -> Result<_, AppError> {
+            let img = query_texture::<TextureFormat>(&asset_db.get()?, sheet.texture_id)?;
+            // sprites belong to no object
+            validate_texture(0, sheet.texture_id, &img)?;
+            Ok(create_texture(device, &img, mipmaps))
+        })
+    }
+    // Queues the live objects whose rows changed since they were loaded, when watching,
+    // and loads again what is queued.
+    fn apply_reloads<D: gfx::Device<B::Resources>>(&mut self, device: &mut D) {
+        let written = match self.asset_watch {
+            Some(ref mut watch) => watch.poll(),
+            None => false,
+        };
+        if written {
+            match self.asset_db.get() {
+                Ok(conn) => for object_id in self.live_object_ids() {
+                    let fingerprint = match query_asset_fingerprint(&conn, &object_id) {
+                        Ok(fingerprint) => fingerprint,
+                        Err(e) => {
+                            warn!("failed to read the rows of object {}: {}", object_id, e);
+                            continue;
+                        },
+                    };
+                    if self.asset_watch.as_mut().map_or(false, |watch| watch.changed(object_id, fingerprint)) {
+                        self.pending_reloads.push(object_id);
+                    }
+                },
+                Err(e) => warn!("failed to open the database for reading: {}", e),
+            }
+        }
+        let mut object_ids = std::mem::replace(&mut self.pending_reloads, Vec::new());
+        object_ids.sort();
+        object_ids.dedup();
+        for object_id in object_ids {
+            match self.reload_object(device, object_id) {
+                Ok(count) => self.console.print(format!("reloaded object {} for {} live", object_id, count)),
+                Err(e) => self.console.print(format!("failed to reload {}: {:?}", object_id, e)),
+            }
+        }
+    }
+    fn live_object_ids(&self) -> Vec<i32> {
+        let mut object_ids: Vec<i32> = self.avators.target.values().map(|obj| obj.object_id).collect();
+        object_ids.sort();
+        object_ids.dedup();
+        object_ids
+    }
+    fn remember_fingerprint(&mut self, conn: &Connection, object_id: i32) {
+        if let Some(ref mut watch) = self.asset_watch {
+            match query_asset_fingerprint(conn, &object_id) {
+                Ok(fingerprint) => watch.remember(object_id, fingerprint),
+                Err(e) => warn!("failed to read the rows of object {}: {}", object_id, e),
+            }
+        }
+    }
+    // Loads object_id again in place of each live instance, which keeps its position,
+    // scale, stats and behavior; the animation clock is the world's, so the pose carries on.
+    // The old GPU buffers go with the old objects. An instance that fails to load keeps its old self.
+    fn reload_object<D: gfx::Device<B::Resources>>(&mut self, device: &mut D, object_id: i32) -> Result<usize, AppError> {
+        let keys: Vec<i32> = self.avators.target.iter()
+            .filter(|&(_, obj)| obj.object_id == object_id)
+            .map(|(&key, _)| key)
+            .collect();
+        self.forget_thumbnail(object_id);
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        self.clips.forget(object_id);
+        for key in &keys {
+            for texture_id in &self.avators.target[key].texture_ids {
+                self.textures.invalidate(*texture_id);
+            }
+        }
+        let conn = self.asset_db.get()?;
+        let mut reloaded = 0;
+        for &key in &keys {
+            let mut obj = query_object(&conn, device, &mut self.textures, &mut self.clips, self.graphics.mipmaps, self.frames, &object_id)?;
+            // pipelines are made at startup for the palette sizes loaded then
+            if !self.psos.keys().any(|pso_key| pso_key.0 == obj.palette_size) {
+                for texture_id in &obj.texture_ids {
+                    self.textures.release(*texture_id);
+                }
+                self.console.print(format!("object {} now needs a palette of {}; restart to see it", object_id, obj.palette_size));
+                continue;
+            }
+            let old = self.avators.target.remove(&key).expect("live object");
+            obj.body.position = old.body.position;
+            obj.body.speed = old.body.speed;
+            obj.body.phase = old.body.phase;
+            obj.body.phase_time = old.body.phase_time;
+            if let (Some(start), Some(clip)) = (old.body.once_from, obj.death_clip.clone()) {
+                obj.body.clip = clip;
+                obj.body.locomotion = None;
+                obj.body.once_from = Some(start);
+            }
+            obj.scale = old.scale;
+            obj.bounds = obj.bounds.scaled(old.scale);
+            obj.stats = old.stats;
+            obj.behavior = old.behavior;
+            obj.tags = old.tags;
+            obj.scene_row = old.scene_row;
+            for entry in &mut obj.entries {
+                entry.visible = old.entries.iter().find(|e| e.mesh_id == entry.mesh_id).map_or(true, |e| e.visible);
+            }
+            for texture_id in &old.texture_ids {
+                self.textures.release(*texture_id);
+            }
+            self.update.send(SimMessage::Insert(key, obj.body.clone()));
+            self.avators.target.insert(key, obj);
+            reloaded += 1;
+        }
+        self.clips.collect_unused();
+        self.remember_fingerprint(&conn, object_id);
+        Ok(reloaded)
+    }
+    fn apply_pending_spawns<D: gfx::Device<B::Resources>>(&mut self, device: &mut D) {
+        for pending in std::mem::replace(&mut self.pending_spawns, Vec::new()) {
+            let object_id = pending.object_id;
+            match self.spawn(device, object_id, pending.position, pending.scale) {
+                Ok(key) => {
+                    self.console.print(format!("spawned object {} as {}", object_id, key));
+                    if pending.placed {
+                        self.select(Some(key));
+                        self.store_placement(key, pending.position);
+                    }
+                },
+                Err(e) => self.console.print(format!("failed to spawn {}: {:?}", object_id, e)),
+            }
+        }
+    }
+    // The local avator's speed multiplier from its Stats.
+    fn avator_speed(&self) -> f32 {
+        self.avators.target.get(&1).map_or(1.0, |obj| obj.stats.speed)
+    }
+    fn move_camera(&mut self, v: Vector3<f32>) {
+        {
+            let tweens = &self.tweens;
+            self.camera_moves.retain(|&handle| tweens.is_active(handle));
+        }
+        let handle = self.tweens.start(TweenAction::MoveCamera(v), CAMERA_MOVE_DURATION, Easing::EaseOut);
+        self.camera_moves.push(handle);
+    }
+    // Sends the local avator to the ground under the cursor, after its other waypoints when queued.
+    fn click_move(&mut self, queue: bool) {
+        if self.mouse_look.enabled {
             return;
         }
-        let c = &self.commands[self.current_index];
-        let t = &mut self.target;
-
-        c.execute(t);
-
-        self.current_index += 1;
+        let (target, position) = match (self.cursor_ground_point(), self.avators.target.get(&1)) {
+            (Some(target), Some(obj)) => (target, obj.body.position),
+            _ => return,
+        };
+        // a queued leg starts where the plan ends
+        let from = if queue { self.move_plan.end().unwrap_or(position) } else { position };
+        let path = match self.nav_grid().find_path(from, target) {
+            Some(path) => path,
+            None => {
+                let text = self.tr("nav.no_path").to_string();
+                self.notify(text, TOAST_SECONDS);
+                return;
+            },
+        };
+        self.move_plan.speed = CLICK_MOVE_SPEED * self.avator_speed();
+        self.movement.stop();
+        if queue {
+            self.move_plan.queue(position, path);
+        } else {
+            self.move_plan.go_to(position, path);
+        }
+    }
+    // The line through the cursor, seen through the viewport it is over.
+    // The inset is drawn last, so it wins where viewports overlap.
+    fn cursor_ray(&self) -> Option<Ray> {
+        let (viewport_camera, screen, point) = self.cursor_view()?;
+        let depth = viewport_camera.camera.ndc_depth(viewport_camera.viewport.depth);
+        ray_from_screen(&viewport_camera.camera.projection, depth, screen, point)
+    }
+    // The viewport the cursor is over, with the screen and the cursor on it.
+    fn cursor_view(&self) -> Option<(&ViewportCamera, Screen, [f32; 2])> {
+        let screen = Screen::new(self.screen_size.0, self.screen_size.1);
+        if screen.is_empty() {
+            return None;
+        }
+        let point = screen.from_cursor(self.cursor?);
+        let fraction = screen.fraction(point);
+        self.view_cameras.iter().rev()
+            .find(|viewport_camera| viewport_camera.viewport.contains(fraction[0], fraction[1]))
+            .map(|viewport_camera| (viewport_camera, screen, point))
+    }
+    fn cursor_ground_point(&self) -> Option<Point3<f32>> {
+        self.cursor_ray().and_then(|ray| ground_point(&ray, GROUND_HEIGHT))
+    }
+    // The nearest object under the cursor, tested as pick_mode says.
+    fn pick(&self) -> Option<PickHit> {
+        let ray = self.cursor_ray()?;
+        let mut nearest: Option<(f32, PickHit)> = None;
+        for (&key, obj) in self.avators.target.iter() {
+            let hit = match self.pick_mode {
+                PickMode::Bounds => ray.hit_aabb(&obj.bounds.translated(obj.body.position.to_vec()))
+                    .map(|t| (t, PickHit { key, joint_index: None, point: ray.at(t) })),
+                // the margin keeps limbs swung out of the bind pose bounds
+                PickMode::Mesh => ray.hit_aabb(&obj.cull_bounds())
+                    .and_then(|_| obj.pick_mesh.cast(&ray.to_model(obj.body.position, obj.scale), &obj.palette))
+                    .map(|hit| {
+                        let joint_index = obj.body.joints.get(hit.slot).map(|joint| joint.joint_index);
+                        (hit.t, PickHit { key, joint_index, point: ray.at(hit.t) })
+                    }),
+            };
+            if let Some((t, hit)) = hit {
+                if nearest.map_or(true, |(nearest_t, _)| t < nearest_t) {
+                    nearest = Some((t, hit));
+                }
+            }
+        }
+        nearest.map(|(_, hit)| hit)
+    }
+    // Selects what is under the cursor, or nothing when there is nothing.
+    fn pick_select(&mut self) {
+        if self.mouse_look.enabled {
+            return;
+        }
+        let hit = self.pick();
+        if let Some(hit) = hit {
+            let p = hit.point;
+            match hit.joint_index {
+                Some(joint_index) => info!("picked {} at joint {} ({:.2}, {:.2}, {:.2})", hit.key, joint_index, p.x, p.y, p.z),
+                None => info!("picked {} at ({:.2}, {:.2}, {:.2})", hit.key, p.x, p.y, p.z),
+            }
+        }
+        self.select(hit.map(|hit| hit.key));
+    }
+    // Subscribers hear of a change only.
+    fn select(&mut self, key: Option<i32>) {
+        if self.selected != key {
+            self.selected = key;
+            self.events.publish(GameEvent::ObjectSelected { key });
+        }
+    }
+    fn set_lighting(&mut self, space: LightingSpace) {
+        self.graphics.lighting = space;
+        self.console.print(format!("lighting {}", space.name()));
+        self.notify(format!("lighting {}", space.name()), TOAST_SECONDS);
+    }
+    fn toggle_editor(&mut self) {
+        self.editor.enabled = !self.editor.enabled;
+        self.editor.drag = None;
+        let text = self.tr(if self.editor.enabled { "editor.on" } else { "editor.off" }).to_string();
+        self.notify(text, TOAST_SECONDS);
+    }
+    // The selected object's handles, while editing placement.
+    fn gizmo(&self) -> Option<(i32, Gizmo)> {
+        if !self.editor.enabled {
+            return None;
+        }
+        let key = self.selected?;
+        let obj = self.avators.target.get(&key)?;
+        let half = (obj.bounds.max - obj.bounds.min) / 2.0;
+        Some((key, Gizmo::new(obj.body.position, half.x.max(half.y).max(half.z))))
+    }
+    // A left press while editing: grabs a handle under the cursor, or selects.
+    fn editor_press(&mut self) {
+        let grabbed = match (self.gizmo(), self.cursor_view()) {
+            (Some((key, gizmo)), Some((viewport_camera, screen, point))) =>
+                gizmo.grab(&viewport_camera.camera.projection, screen, point).map(|axis| Drag::new(key, axis, gizmo, point)),
+            _ => None,
+        };
+        match grabbed {
+            Some(drag) => self.editor.drag = Some(drag),
+            None => self.pick_select(),
+        }
+    }
+    // Moves the dragged object by what is left to go, on the update thread as other moves.
+    fn editor_drag(&mut self) {
+        let mut drag = match self.editor.drag {
+            Some(drag) => drag,
+            None => return,
+        };
+        let offset = match self.cursor_view() {
+            Some((viewport_camera, screen, point)) => drag.offset(&viewport_camera.camera.projection, screen, point),
+            None => None,
+        };
+        if let Some(offset) = offset {
+            let delta = offset - drag.moved;
+            if delta != Vector3::new(0.0, 0.0, 0.0) {
+                self.body_commands.push(BodyCommand { key: drag.key, command: AvatorCommand::Move(delta) });
+                drag.moved = offset;
+                self.editor.drag = Some(drag);
+            }
+        }
+    }
+    fn editor_release(&mut self) {
+        if let Some(drag) = self.editor.drag.take() {
+            if drag.moved != Vector3::new(0.0, 0.0, 0.0) {
+                self.store_placement(drag.key, drag.position());
+            }
+        }
+    }
+    // Ctrl+D while editing: a copy of the selected object beside it, in the scene.
+    fn duplicate_selected(&mut self) {
+        let pending = match self.selected.and_then(|key| self.avators.target.get(&key)) {
+            Some(obj) => PendingSpawn {
+                object_id: obj.object_id,
+                position: obj.body.position + Vector3::from(DUPLICATE_OFFSET),
+                scale: obj.scale,
+                placed: true,
+            },
+            None => {
+                self.console.print("nothing selected to duplicate");
+                return;
+            },
+        };
+        self.pending_spawns.push(pending);
+    }
+    // Writes where the object stands to its Scene row, adding one to the scene
+    // last loaded for an object that has none.
+    fn store_placement(&mut self, key: i32, position: Point3<f32>) {
+        let (object_id, row) = match self.avators.target.get(&key) {
+            Some(obj) => (obj.object_id, obj.scene_row),
+            None => return,
+        };
+        let conn = open_connection();
+        let result = match (row, self.scene_id) {
+            (Some(row), _) => update_scene_position(&conn, row, position).map(|_| row),
+            (None, Some(scene_id)) => insert_scene_object(&conn, scene_id, object_id, position),
+            (None, None) => {
+                self.console.print(format!("no scene loaded to place {} in", key));
+                return;
+            },
+        };
+        match result {
+            Ok(row) => if let Some(obj) = self.avators.target.get_mut(&key) {
+                obj.scene_row = Some(row);
+            },
+            Err(e) => self.console.print(format!("failed to store the placement of {}: {}", key, e)),
+        }
+    }
+    // Spawns the slot's template on the ground under the cursor, with the next frame's spawns.
+    fn spawn_from_palette(&mut self, slot: usize) {
+        let template = match self.spawn_palette.get(slot) {
+            Some(template) => template,
+            None => {
+                self.console.print(format!("palette slot {} is empty", slot));
+                return;
+            },
+        };
+        match self.cursor_ground_point() {
+            Some(position) => self.pending_spawns.push(PendingSpawn { object_id: template.object_id, position, scale: template.scale, placed: false }),
+            None => self.console.print("no ground under the cursor"),
+        }
+    }
+    // Saved at once, so the palette is there next time.
+    fn set_palette_slot(&mut self, slot: usize, template: Option<SpawnTemplate>) {
+        let conn = open_connection();
+        let saved = match template {
+            Some(template) => insert_palette_slot(&conn, slot as i32, template.object_id, template.scale),
+            None => delete_palette_slot(&conn, slot as i32),
+        };
+        match saved {
+            Ok(()) => {
+                self.spawn_palette.set(slot, template);
+                self.console.print(match template {
+                    Some(template) => format!("palette {}: object {} x{}", slot, template.object_id, template.scale),
+                    None => format!("palette {} cleared", slot),
+                });
+            },
+            Err(e) => self.console.print(format!("{:?}", e)),
+        }
+    }
+    // The authored grid with every object but the local avator blocked where it stands now.
+    fn nav_grid(&self) -> NavGrid {
+        let mut grid = self.nav.clone();
+        for (&key, obj) in &self.avators.target {
+            if key != 1 {
+                grid.block_aabb(&obj.bounds.translated(obj.body.position.to_vec()));
+            }
+        }
+        grid
+    }
+    fn wheel(&mut self, delta: f32) {
+        use cgmath::InnerSpace;
+        let (direction, fovy, mode, ortho_height) = {
+            let camera = self.camera();
+            (camera.direction(), camera.fov.fovy, camera.mode, camera.ortho_height)
+        };
+        // moving closer shows nothing bigger without perspective
+        if mode == ProjectionMode::Orthographic {
+            let height = self.wheel_zoom.ortho_height(ortho_height, delta);
+            self.camera.append_command(CameraCommand::SetOrthoHeight(height));
+            return;
+        }
+        if self.input.is_held(Key::LControl) || self.input.is_held(Key::RControl) {
+            let fovy = self.wheel_zoom.fovy(fovy, delta);
+            self.camera.append_command(CameraCommand::SetFovy(fovy));
+            return;
+        }
+        let distance = direction.magnitude();
+        if distance > 0.0 {
+            let step = self.wheel_zoom.dolly(distance, delta);
+            self.camera.append_command(CameraCommand::Move(direction * (step / distance)));
+        }
+    }
+    fn toggle_projection(&mut self) {
+        let mode = match self.camera().mode {
+            ProjectionMode::Perspective => ProjectionMode::Orthographic,
+            ProjectionMode::Orthographic => ProjectionMode::Perspective,
+        };
+        self.camera.append_command(CameraCommand::SetProjection(mode));
+    }
+    fn cancel_camera_moves(&mut self) {
+        for handle in self.camera_moves.drain(..) {
+            self.tweens.cancel(handle);
+        }
+    }
+    fn step_tweens(&mut self) {
+        for _ in 0..self.tick.advance() {
+            for step in self.tweens.step(FIXED_STEP as f32) {
+                match step.payload {
+                    TweenAction::MoveCamera(v) => self.camera.append_command(CameraCommand::Move(v * step.delta)),
+                    TweenAction::SceneFade => if step.finished {
+                        self.finish_scene_fade(step.handle);
+                    },
+                    TweenAction::Toast => if step.finished {
+                        self.notifications.expire(step.handle);
+                    },
+                    TweenAction::ScreenEffect => if step.finished {
+                        self.screen_effects.finish(step.handle);
+                    },
+                }
+            }
+            for &mut (ref mut sprite, _) in &mut self.sprites {
+                sprite.tick(FIXED_STEP as f32);
+            }
+            if let Some(ref mut dialogue) = self.dialogue {
+                dialogue.tick(FIXED_STEP as f32);
+            }
+            if let Some((_, ref mut prompt)) = self.interaction {
+                prompt.tick(FIXED_STEP as f32);
+            }
+            self.notifications.tick(FIXED_STEP as f32);
+            // a paused avator would drop the moves, leaving the plan ahead of the body
+            if self.dispatcher.allows(Level::Avator) {
+                // the keys take over from a click
+                if Movement::started(&self.input) {
+                    self.move_plan.clear();
+                }
+                let speed = self.avator_speed();
+                if let Some(v) = self.movement.step(&self.input, speed, FIXED_STEP as f32) {
+                    self.command_avator(AvatorCommand::Move(v));
+                }
+                if let Some(v) = self.move_plan.step(FIXED_STEP as f32) {
+                    self.command_avator(AvatorCommand::Move(v));
+                }
+                self.step_behaviors(FIXED_STEP as f32);
+            }
+            self.input.end_tick();
+        }
+    }
+    // NPC moves are queued for their own bodies, as remote commands are, and stay off the network.
+    // Positions are the latest snapshot's, so an NPC may walk a step or two past where it meant to.
+    fn step_behaviors(&mut self, dt: f32) {
+        let positions: HashMap<i32, Point3<f32>> = self.avators.target.iter()
+            .map(|(&key, obj)| (key, obj.body.position))
+            .collect();
+        for (&key, obj) in &mut self.avators.target {
+            if obj.stats.is_dead() {
+                continue;
+            }
+            let (position, speed) = (obj.body.position, NPC_WALK_SPEED * obj.stats.speed);
+            if let Some(ref mut behavior) = obj.behavior {
+                if let Some(v) = behavior.step(position, &positions, speed, dt) {
+                    self.body_commands.push(BodyCommand { key, command: AvatorCommand::Move(v) });
+                }
+            }
+        }
+    }
+    // The avators the players drive keep theirs.
+    fn set_behavior(&mut self, key: i32, kind: BehaviorKind) {
+        if key == 1 || Some(key) == self.remote_avator() {
+            self.console.print(format!("{} is not an NPC", key));
+            return;
+        }
+        match self.avators.target.get_mut(&key) {
+            Some(obj) => obj.behavior = Some(kind.build(obj.body.position, key as u32)),
+            None => self.console.print(format!("no object {}", key)),
+        }
+    }
+    // Blocked commands are dropped here, before the peer sees them, as are moves of a dead avator.
+    fn command_avator(&mut self, command: AvatorCommand) {
+        if !self.dispatcher.allows(Command::<Body>::get_level(&command)) {
+            return;
+        }
+        if self.avators.target.get(&1).map_or(false, |obj| obj.stats.is_dead()) {
+            return;
+        }
+        if let Some(ref peer) = self.peer {
+            if let Err(e) = peer.send(&command) {
+                warn!("failed to send command: {:?}", e);
+            }
+        }
+        self.avators.append_command(command);
+    }
+    // The player's own avator goes through command_avator, so the peer and the history
+    // see it; the others are queued for their bodies, each by the same amount, so a group
+    // keeps its formation. The peer's avator is left to the peer.
+    fn command_avators(&mut self, target: &AvatorTarget, command: AvatorCommand) {
+        if !self.dispatcher.allows(Command::<Body>::get_level(&command)) {
+            return;
+        }
+        let keys = self.target_keys(target);
+        if keys.is_empty() {
+            self.console.print(format!("no avator for {:?}", target));
+            return;
+        }
+        for key in keys {
+            if key == 1 {
+                self.command_avator(command.clone());
+            } else {
+                self.body_commands.push(BodyCommand { key, command: command.clone() });
+            }
+        }
+    }
+    // Living avators only, in key order.
+    fn target_keys(&self, target: &AvatorTarget) -> Vec<i32> {
+        let (selected, remote) = (self.selected, self.remote_avator());
+        let mut keys: Vec<i32> = self.avators.target.iter()
+            .filter(|&(&key, obj)| Some(key) != remote && !obj.stats.is_dead() && match *target {
+                AvatorTarget::One(id) => key == id,
+                AvatorTarget::Selected => Some(key) == selected,
+                AvatorTarget::All => true,
+                AvatorTarget::Group(ref tag) => obj.tags.contains(tag),
+            })
+            .map(|(&key, _)| key)
+            .collect();
+        keys.sort();
+        keys
+    }
+    // The avator the network peer drives, once the handshake has settled it.
+    fn remote_avator(&self) -> Option<i32> {
+        self.peer.as_ref().and_then(|peer| peer.avator())
+    }
+    // What a listener offers the peer: the first living avator after the player's that
+    // no behavior walks.
+    fn free_avator(&self) -> Option<i32> {
+        let mut keys: Vec<i32> = self.avators.target.iter()
+            .filter(|&(&key, obj)| key != 1 && !obj.stats.is_dead() && obj.behavior.is_none())
+            .map(|(&key, _)| key)
+            .collect();
+        keys.sort();
+        keys.first().cloned()
+    }
+    // Queued with the other avators' commands, so a paused game drops them too.
+    fn receive_remote_commands(&mut self) {
+        let commands = match self.peer.as_mut().map(|peer| peer.poll()) {
+            Some(Ok(commands)) => commands,
+            Some(Err(e)) => {
+                warn!("failed to receive commands: {:?}", e);
+                return;
+            },
+            None => return,
+        };
+        let key = match self.remote_avator() {
+            Some(key) => key,
+            None => return,
+        };
+        self.body_commands.extend(commands.into_iter().map(|command| BodyCommand { key, command }));
+    }
+    fn apply_snapshot(&mut self) {
+        let snapshot = match self.update.latest() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        for (key, state) in snapshot.bodies {
+            // objects despawned since the step was sent are skipped
+            if let Some(obj) = self.avators.target.get_mut(&key) {
+                if obj.body.position != state.position {
+                    self.events.publish(GameEvent::AvatorMoved { key, position: state.position });
+                }
+                obj.body.position = state.position;
+                let used = std::mem::replace(&mut obj.palette, state.palette);
+                self.update.send(SimMessage::ReturnPalette(key, used));
+            }
+        }
+    }
+    // Which pose GameObjects are skinned with this frame.
+    fn animation_clock(&self) -> AnimationClock {
+        match self.scrub {
+            Some(index) => AnimationClock::Sample(index),
+            None => AnimationClock::Time(self.system.target.elapsed()),
+        }
     }
     fn execute_all_commands(&mut self) {
-        for _ in self.current_index..self.commands.len() {
-            self.execute_command();
+        self.step_tweens();
+        self.receive_remote_commands();
+        // avator commands only run on the update thread, so they are sent there in their turn
+        let mut avator_commands = self.avators.take_commands();
+        let mut body_commands = std::mem::replace(&mut self.body_commands, Vec::new());
+        let now = self.system.target.elapsed();
+        for &level in Level::by_priority().iter() {
+            let allowed = self.dispatcher.allows(level);
+            if allowed {
+                for command in self.camera.execute_level(level) {
+                    self.session.history().record(now, HistoryTarget::Camera, &command);
+                }
+                for command in self.observer.execute_level(level) {
+                    self.session.history().record(now, HistoryTarget::Observer, &command);
+                }
+            } else {
+                self.camera.discard_level(level);
+                self.observer.discard_level(level);
+            }
+            let (queued, rest): (Vec<_>, Vec<_>) = avator_commands.into_iter()
+                .partition(|c| Command::<Body>::get_level(c) == level);
+            avator_commands = rest;
+            let (queued_bodies, rest): (Vec<_>, Vec<_>) = body_commands.into_iter()
+                .partition(|c| Command::<HashMap<i32, Body>>::get_level(c) == level);
+            body_commands = rest;
+            if allowed {
+                for command in queued {
+                    self.session.history().record(now, HistoryTarget::Avator, &command);
+                    self.update.send(SimMessage::Command(BodyCommand { key: 1, command }));
+                }
+                for command in queued_bodies {
+                    self.update.send(SimMessage::Command(command));
+                }
+            }
+        }
+        self.apply_stat_commands();
+        self.update.send(SimMessage::Step(self.animation_clock()));
+        self.apply_snapshot();
+        let now = self.system.target.elapsed();
+        self.camera.target.advance_path(now);
+        self.observer.target.advance_path(now);
+        self.camera.target.update();
+        self.observer.target.update();
+    }
+    fn queue_stats(&mut self, key: i32, command: StatsCommand) {
+        if self.avators.target.contains_key(&key) {
+            self.stat_commands.push((key, command));
+        } else {
+            self.console.print(format!("no object {}", key));
+        }
+    }
+    // Commands for objects that are gone are dropped; the dead take no damage or healing.
+    fn apply_stat_commands(&mut self) {
+        let commands = std::mem::replace(&mut self.stat_commands, Vec::new());
+        for (key, command) in commands {
+            if !self.dispatcher.allows(Command::<Stats>::get_level(&command)) {
+                continue;
+            }
+            let (died, hurt) = match self.avators.target.get_mut(&key) {
+                Some(obj) => {
+                    let (alive, hp) = (!obj.stats.is_dead(), obj.stats.hp);
+                    command.execute(&mut obj.stats);
+                    (alive && obj.stats.is_dead(), obj.stats.hp < hp)
+                },
+                None => (false, false),
+            };
+            if hurt && key == 1 {
+                self.flash(DAMAGE_FLASH, FLASH_SECONDS);
+            }
+            if died {
+                self.die(key);
+            }
+        }
+    }
+    // Plays the object's death clip and leaves it lying there, or despawns it without one.
+    fn die(&mut self, key: i32) {
+        self.events.publish(GameEvent::ObjectDied { key });
+        if key == 1 {
+            self.move_plan.clear();
+        }
+        let start = self.system.target.elapsed();
+        let clip = match self.avators.target.get_mut(&key) {
+            Some(obj) => match obj.death_clip.clone() {
+                Some(clip) => {
+                    obj.body.clip = clip.clone();
+                    obj.body.locomotion = None;
+                    obj.body.once_from = Some(start);
+                    Some(clip)
+                },
+                None => None,
+            },
+            None => return,
+        };
+        match clip {
+            Some(clip) => self.update.send(SimMessage::PlayOnce(key, clip, start)),
+            None => { self.despawn(key); },
         }
-        self.commands.clear();
-        self.current_index = 0;
     }
-    fn append_command(&mut self, c: Cmd) {
-        self.commands.push(c);
+    fn update_animation_events(&mut self) {
+        let now = self.system.target.elapsed();
+        // the first check starts at 0 and includes it, so events on a clip's first frame fire
+        let (from, include_from) = match self.last_event_time {
+            Some(last) => (last, false),
+            None => (0.0, true),
+        };
+        self.last_event_time = Some(now);
+        for (id, obj) in &self.avators.target {
+            for e in obj.crossed_events(from, now, include_from) {
+                self.events.publish(GameEvent::AnimationEventFired(FiredAnimationEvent {
+                    object_id: *id,
+                    name: e.name.clone(),
+                    time: e.time,
+                }));
+            }
+        }
     }
 }
 
 
 impl Command<Camera<f32>> for CameraCommand {
     fn get_level(&self) -> Level {
-        Level::System
+        Level::World
     }
     fn execute(&self, c: &mut Camera<f32>) {
         match *self {
-            CameraCommand::Move(v) => {
-                c.translate(v); 
-                c.update();
+            CameraCommand::Move(v) => c.translate(v),
+            CameraCommand::LookAt(v) => c.look_at(v),
+            CameraCommand::Rotate(yaw, pitch) => c.rotate(yaw, pitch),
+            CameraCommand::Roll(angle) => c.roll(angle),
+            CameraCommand::SetUp(up) => c.set_up(up),
+            CameraCommand::SetProjection(mode) => c.set_mode(mode),
+            CameraCommand::SetOrthoHeight(height) => c.set_ortho_height(height),
+            CameraCommand::TopDown => {
+                use cgmath::InnerSpace;
+                c.playback = None;
+                let distance = c.direction().magnitude();
+                c.position = c.target + c.up * distance;
+                c.dirty = true;
+            },
+            CameraCommand::PlayPath(path_id) => {
+                match query_camera_path(&open_connection(), path_id) {
+                    Ok(ref keys) if keys.is_empty() => {
+                        warn!("camera path {} has no keys", path_id);
+                        if let Some(ref notifier) = c.notifier {
+                            notifier.notify(format!("camera path {} has no keys", path_id), TOAST_SECONDS);
+                        }
+                    },
+                    Ok(keys) => c.playback = Some(PathPlayback::new(keys)),
+                    Err(e) => error!("failed to load camera path {}: {:?}", path_id, e),
+                }
+            },
+            CameraCommand::StopPath => c.playback = None,
+            CameraCommand::SetFovy(fovy) => c.set_fovy(fovy),
+            CameraCommand::SetClip(near, far) => c.set_clip(near, far),
+            CameraCommand::SetView(position, target, fovy) => {
+                c.playback = None;
+                c.position = position;
+                c.look_at(target);
+                c.set_fovy(fovy);
+                // presets are taken upright
+                c.set_up(axes::WORLD.up());
             },
-            CameraCommand::LookAt(v) => {
-                c.look_at(v);
-                c.update();
-            }
         }
     }
 }
 
-impl<R: gfx::Resources, V> Command<GameObject<R, V>> for AvatorCommand {
-    fn get_level(&self) -> Level {
-        Level::Avator
+// Paths are left out: they play against the clock, which starts over every session.
+impl Recordable for CameraCommand {
+    fn record(&self) -> Option<(&'static str, Vec<f32>)> {
+        Some(match *self {
+            CameraCommand::Move(v) => ("move", vec!(v.x, v.y, v.z)),
+            CameraCommand::LookAt(p) => ("lookat", vec!(p.x, p.y, p.z)),
+            CameraCommand::Rotate(yaw, pitch) => ("rotate", vec!(yaw.0, pitch.0)),
+            CameraCommand::Roll(angle) => ("roll", vec!(angle.0)),
+            CameraCommand::SetUp(up) => ("up", vec!(up.x, up.y, up.z)),
+            CameraCommand::SetProjection(mode) => ("projection", vec!(match mode {
+                ProjectionMode::Perspective => 0.0,
+                ProjectionMode::Orthographic => 1.0,
+            })),
+            CameraCommand::SetOrthoHeight(height) => ("ortho_height", vec!(height)),
+            CameraCommand::TopDown => ("top", Vec::new()),
+            CameraCommand::PlayPath(_) | CameraCommand::StopPath => return None,
+            CameraCommand::SetFovy(fovy) => ("fovy", vec!(fovy.0)),
+            CameraCommand::SetClip(near, far) => ("clip", vec!(near, far)),
+            CameraCommand::SetView(position, target, fovy) => ("view", vec!(
+                position.x, position.y, position.z,
+                target.x, target.y, target.z,
+                fovy.0,
+            )),
+        })
     }
-    fn execute(&self, c: &mut GameObject<R, V>) {
-        match *self {
-            AvatorCommand::Move(v) => {
-                c.translate(v); 
-            },
-        }
+    fn replay(kind: &str, args: &[f32]) -> Option<Self> {
+        Some(match (kind, args) {
+            ("move", &[x, y, z]) => CameraCommand::Move(Vector3::new(x, y, z)),
+            ("lookat", &[x, y, z]) => CameraCommand::LookAt(Point3::new(x, y, z)),
+            ("rotate", &[yaw, pitch]) => CameraCommand::Rotate(Rad(yaw), Rad(pitch)),
+            ("roll", &[angle]) => CameraCommand::Roll(Rad(angle)),
+            ("up", &[x, y, z]) => CameraCommand::SetUp(Vector3::new(x, y, z)),
+            ("projection", &[mode]) => CameraCommand::SetProjection(
+                if mode == 0.0 { ProjectionMode::Perspective } else { ProjectionMode::Orthographic }
+            ),
+            ("ortho_height", &[height]) => CameraCommand::SetOrthoHeight(height),
+            ("top", &[]) => CameraCommand::TopDown,
+            ("fovy", &[fovy]) => CameraCommand::SetFovy(Rad(fovy)),
+            ("clip", &[near, far]) => CameraCommand::SetClip(near, far),
+            ("view", &[px, py, pz, tx, ty, tz, fovy]) => CameraCommand::SetView(
+                Point3::new(px, py, pz), Point3::new(tx, ty, tz), Rad(fovy)
+            ),
+            _ => return None,
+        })
     }
 }
-impl<R: gfx::Resources, V> Command<HashMap<i32, GameObject<R, V>>> for AvatorCommand {
-    fn get_level(&self) -> Level {
-        Level::Avator
-    }
-    fn execute(&self, c: &mut HashMap<i32, GameObject<R, V>>) {
+
+impl Recordable for AvatorCommand {
+    fn record(&self) -> Option<(&'static str, Vec<f32>)> {
         match *self {
-            AvatorCommand::Move(v) => {
-                c.get_mut(&1).unwrap().translate(v); 
-            },
+            AvatorCommand::Move(v) => Some(("move", vec!(v.x, v.y, v.z))),
+        }
+    }
+    fn replay(kind: &str, args: &[f32]) -> Option<Self> {
+        match (kind, args) {
+            ("move", &[x, y, z]) => Some(AvatorCommand::Move(Vector3::new(x, y, z))),
+            _ => None,
         }
     }
 }
 
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum Level {
-    Avator,
-    World,
-    System,
-}
-
 gfx_defines!{
     pipeline pipe_w {
         vbuf: gfx::VertexBuffer<Vertex> = (),
-        u_model_view_proj: gfx::Global<[[f32; 4]; 4]> = "u_model_view_proj",
-        u_model_view: gfx::Global<[[f32; 4]; 4]> = "u_model_view",
-        u_light: gfx::Global<[f32; 3]> = "u_light",
-        u_ambient_color: gfx::Global<[f32; 4]> = "u_ambientColor",
-        u_eye_direction: gfx::Global<[f32; 3]> = "u_eyeDirection",
+        b_camera: gfx::ConstantBuffer<CameraBlock> = "b_camera",
+        b_object: gfx::ConstantBuffer<ObjectBlock> = "b_object",
         u_texture: gfx::TextureSampler<[f32; 4]> = "u_texture",
+        u_environment: gfx::TextureSampler<[f32; 4]> = "u_environment",
+        u_environment_lod: gfx::Global<f32> = "u_environmentLod",
+        u_roughness: gfx::Global<f32> = "u_roughness",
+        out_color: gfx::RenderTarget<ColorFormat> = "Target0",
+        out_depth: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_WRITE,
+        b_skinning: gfx::RawConstantBuffer = "b_skinning",
+        scissor: gfx::Scissor = (),
+    }
+    pipeline pipe_d {
+        vbuf: gfx::VertexBuffer<Vertex> = (),
+        b_object: gfx::ConstantBuffer<ObjectBlock> = "b_object",
+        // color is left alone; the target is bound for backends that want one
+        out_color: gfx::BlendTarget<ColorFormat> = ("Target0", gfx::state::ColorMask::empty(), gfx::preset::blend::REPLACE),
+        out_depth: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_WRITE,
+        b_skinning: gfx::RawConstantBuffer = "b_skinning",
+        scissor: gfx::Scissor = (),
+    }
+    pipeline pipe_o {
+        vbuf: gfx::VertexBuffer<Vertex> = (),
+        b_object: gfx::ConstantBuffer<ObjectBlock> = "b_object",
+        u_outline_color: gfx::Global<[f32; 4]> = "u_outlineColor",
+        u_outline_width: gfx::Global<f32> = "u_outlineWidth",
         out_color: gfx::RenderTarget<ColorFormat> = "Target0",
         out_depth: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_WRITE,
         b_skinning: gfx::RawConstantBuffer = "b_skinning",
+        scissor: gfx::Scissor = (),
     }
     vertex Vertex {
         position: [f32; 3] = "position",
@@ -739,7 +4888,45 @@ gfx_defines!{
         out_color: gfx::BlendTarget<ColorFormat> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
         out_depth: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_WRITE,
         u_texture: gfx::TextureSampler<f32> = "u_texture",
+        u_sdf: gfx::Global<f32> = "u_sdf",
+        u_outline: gfx::Global<[f32; 4]> = "u_outline",
+        // pixels added to every vertex, so cached text can be drawn anywhere
+        u_offset: gfx::Global<[f32; 2]> = "u_offset",
+        screen_size: gfx::Global<[f32; 2]> = "u_screen_size",
+    }
+    pipeline pipe_b {
+        vbuf: gfx::VertexBuffer<VertexB> = (),
+        u_view_proj: gfx::Global<[[f32; 4]; 4]> = "u_view_proj",
+        u_projection_scale: gfx::Global<[f32; 2]> = "u_projection_scale",
+        u_center: gfx::Global<[f32; 3]> = "u_center",
+        u_size: gfx::Global<[f32; 2]> = "u_size",
+        u_pixel_range: gfx::Global<[f32; 2]> = "u_pixel_range",
+        u_color: gfx::Global<[f32; 4]> = "u_color",
+        u_uv_rect: gfx::Global<[f32; 4]> = "u_uv_rect",
         screen_size: gfx::Global<[f32; 2]> = "u_screen_size",
+        u_texture: gfx::TextureSampler<[f32; 4]> = "u_texture",
+        scissor: gfx::Scissor = (),
+        out_color: gfx::BlendTarget<ColorFormat> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
+        out_depth: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_TEST,
+    }
+    pipeline pipe_f {
+        vbuf: gfx::VertexBuffer<VertexB> = (),
+        u_texture: gfx::TextureSampler<[f32; 4]> = "u_texture",
+        u_color: gfx::Global<[f32; 4]> = "u_color",
+        u_textured: gfx::Global<f32> = "u_textured",
+        u_cover: gfx::Global<f32> = "u_cover",
+        out_color: gfx::BlendTarget<ColorFormat> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
+    }
+    pipeline pipe_l {
+        vbuf: gfx::VertexBuffer<VertexP> = (),
+        u_view_proj: gfx::Global<[[f32; 4]; 4]> = "u_view_proj",
+        scissor: gfx::Scissor = (),
+        out_color: gfx::RenderTarget<ColorFormat> = "Target0",
+        out_depth: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_TEST,
+    }
+    vertex VertexB {
+        offset: [f32; 2] = "offset",
+        uv: [f32; 2] = "uv",
     }
     vertex VertexP {
         position: [f32; 3] = "position",
@@ -754,51 +4941,80 @@ gfx_defines!{
         u_ambient_color: gfx::Global<[f32; 4]> = "u_ambientColor",
         u_eye_direction: gfx::Global<[f32; 3]> = "u_eyeDirection",
         u_texture: gfx::TextureSampler<f32> = "u_texture",
+        u_sdf: gfx::Global<f32> = "u_sdf",
         out_color: gfx::BlendTarget<ColorFormat> = ("Target0", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
         out_depth: gfx::DepthTarget<DepthFormat> = gfx::preset::depth::LESS_EQUAL_WRITE,
     }
     constant Skinning {
         transform: [[f32; 4]; 4] = "u_transform",
     }
+    // What every object drawn through one view shares, written once per view.
+    // Laid out as std140 has it: u_linear fills the end of u_light's row.
+    constant CameraBlock {
+        view: [[f32; 4]; 4] = "u_view",
+        ambient_color: [f32; 4] = "u_ambientColor",
+        sky_color: [f32; 4] = "u_skyColor",
+        fog: [f32; 4] = "u_fog",
+        fog_color: [f32; 4] = "u_fogColor",
+        light: [f32; 3] = "u_light",
+        linear: f32 = "u_linear",
+    }
+    // An object's transforms for one view, written once before its entries are drawn.
+    constant ObjectBlock {
+        model_view_proj: [[f32; 4]; 4] = "u_model_view_proj",
+        model_view: [[f32; 4]; 4] = "u_model_view",
+    }
 }
 
-struct Camera<T> {
-    position: Point3<T>,
-    target: Point3<T>,
-    // up: Vector3<T>,
-    view: Matrix4<T>,
-    perspective: Matrix4<T>,
-    projection: Matrix4<T>
+impl From<Matrix4<f32>> for Skinning {
+    fn from(transform: Matrix4<f32>) -> Self {
+        Skinning { transform: transform.into() }
+    }
 }
 
+fn camera_block(camera: &Camera<f32>, lighting: &Lighting, fog: &Fog, space: LightingSpace) -> CameraBlock {
+    CameraBlock {
+        view: camera.view.into(),
+        ambient_color: lighting.ambient.into(),
+        sky_color: lighting.sky.into(),
+        fog: fog.params(),
+        fog_color: fog.color_over(lighting.sky).into(),
+        light: lighting.light,
+        linear: space.uniform(),
+    }
+}
 
-impl<T: cgmath::BaseFloat> Camera<T> {
-    fn new(position: Point3<T>, target: Point3<T>, perspective: cgmath::PerspectiveFov<T>) -> Camera<T> {
-        let view = Matrix4::look_at(position,
-                                    target,
-                                    Vector3::new(Zero::zero(), Zero::zero(), One::one()));
-        let perspective = Matrix4::from(perspective);
 
-        Camera {
-            position,
-            target,
-            view,
-            perspective,
-            projection: perspective * view
-        }
-    }
-    fn look_at(&mut self, target: Point3<T>) {
-        self.target = target;
-    }
-    fn direction(& self) -> Vector3<T> {
-        self.target - self.position
-    }
-    fn update(&mut self) {
-        self.view = Matrix4::look_at(self.position, self.target, Vector3::new(Zero::zero(), Zero::zero(), One::one()));
-        self.projection = self.perspective * self.view;
-    }
+// A camera as one viewport sees it, kept across frames while nothing it derives from changes.
+struct ViewportCamera {
+    which: ViewCamera,
+    viewport: Viewport,
+    aspect: f32,
+    generation: u64,
+    camera: Camera<f32>,
 }
 
+
+// Looks at the object's bounds from the front, which faces -y, and a little above,
+// close enough for them to fill a square view.
+fn thumbnail_camera(bounds: &Aabb, reversed_z: bool) -> Camera<f32> {
+    use cgmath::InnerSpace;
+    let center = bounds.min.midpoint(bounds.max);
+    let radius = ((bounds.max - bounds.min).magnitude() / 2.0).max(0.01);
+    let fovy = 30.0f32.to_radians();
+    let distance = radius / (fovy / 2.0).sin();
+    let position = center + Vector3::new(0.3, -1.0, 0.4).normalize() * distance;
+    let mut camera = Camera::new(position, center, axes::WORLD.up(), cgmath::PerspectiveFov {
+        fovy: Rad(fovy),
+        aspect: 1.0,
+        near: (distance - radius) * 0.5,
+        far: distance + radius * 2.0,
+    }).with_reversed_z(reversed_z);
+    camera.layers = layer::WORLD.union(layer::UI);
+    camera
+}
+
+
 impl Default for Vertex {
     fn default() -> Vertex {
         Vertex {
@@ -812,189 +5028,476 @@ impl Default for Vertex {
     }
 }
 
-const CLEAR_COLOR: [f32; 4] = [0.1, 0.2, 0.3, 1.0];
 
 pub struct Entry<R: gfx::Resources, V, View> {
     slice: gfx::Slice<R>,
     vertex_buffer: gfx::handle::Buffer<R, V>,
-    texture:  gfx::handle::ShaderResourceView<R, View>
+    texture:  gfx::handle::ShaderResourceView<R, View>,
+    shading: Shading,
+    // skeleton joint per palette slot when the mesh was split to fit the palette
+    joint_map: Option<Vec<usize>>,
+    // the MeshId it was made from; the parts of a split mesh share it
+    mesh_id: usize,
+    layers: LayerMask,
+    // hidden at runtime with the mesh command, whatever the camera's layers
+    visible: bool,
+    // 0 a mirror to 1 matte, which picks how blurred a reflection the shader reads
+    roughness: f32,
 }
 
-fn entry<'e, R, F, V, T>(device: &mut F, vertex_data: &[V], img: &'e Image<T>) -> Entry<R, V, T::View> 
+impl<R: gfx::Resources, V, View> Entry<R, V, View> {
+    fn shown_to(&self, layers: LayerMask) -> bool {
+        self.visible && layers.intersects(self.layers)
+    }
+}
+
+fn entry_<'e, R, F, V, T>(device: &mut F, vertex_data: &[V], index_data: &[u32], img: &'e Image<T>) -> Entry<R, V, T::View> 
     where 
         R: gfx::Resources,
         F: gfx::Device<R>,
         V: gfx::traits::Pod + gfx::pso::buffer::Structure<gfx::format::Format>,
         T: gfx::format::TextureFormat,
 {
-    let index_data: Vec<u32> = vertex_data.iter().enumerate().map(|(i, _)| i as u32).collect();
-    entry_(device, &vertex_data, &index_data[..], img)
+    let view = create_texture(device, img, false);
+    entry_with_texture(device, vertex_data, index_data, view)
 }
 
-fn entry_<'e, R, F, V, T>(device: &mut F, vertex_data: &[V], index_data: &[u32], img: &'e Image<T>) -> Entry<R, V, T::View> 
+fn create_texture<R, F, T>(device: &mut F, img: &Image<T>, mipmaps: bool) -> gfx::handle::ShaderResourceView<R, T::View>
     where 
         R: gfx::Resources,
         F: gfx::Device<R>,
-        V: gfx::traits::Pod + gfx::pso::buffer::Structure<gfx::format::Format>,
         T: gfx::format::TextureFormat,
 {
     use gfx::traits::DeviceExt;
-    let (vbuf, slice) = device.create_vertex_buffer_with_slice(&vertex_data, index_data);
-
     let tex_kind = gfx::texture::Kind::D2(img.width, img.height, gfx::texture::AaMode::Single);
-    let (_, view) = device.create_texture_immutable_u8::<T>(tex_kind, &[&img.data]).expect("failed to create texture");
+    let chain = if mipmaps { mip_chain(img) } else { Vec::new() };
+    // one slice per mip level, the base image first
+    let levels: Vec<&[u8]> = std::iter::once(&img.data[..])
+        .chain(chain.iter().map(|level| &level.data[..]))
+        .collect();
+    let (_, view) = device.create_texture_immutable_u8::<T>(tex_kind, &levels).expect("failed to create texture");
+    view
+}
+
+// The sky the lit shaders reflect, with every mip level whatever the graphics settings,
+// as rough surfaces read the small ones.
+struct Environment<R: gfx::Resources> {
+    cube: gfx::handle::ShaderResourceView<R, [f32; 4]>,
+    sampler: gfx::handle::Sampler<R>,
+    max_lod: f32,
+}
+
+fn create_environment<R, F>(device: &mut F) -> Environment<R>
+    where
+        R: gfx::Resources,
+        F: gfx::Device<R>,
+{
+    use gfx::traits::DeviceExt;
+    let faces = sky_faces::<TextureFormat>();
+    let chains: Vec<_> = faces.iter().map(mip_chain).collect();
+    // face by face, each with its levels from the base down
+    let levels: Vec<&[u8]> = faces.iter().zip(&chains)
+        .flat_map(|(face, chain)| std::iter::once(&face.data[..]).chain(chain.iter().map(|level| &level.data[..])))
+        .collect();
+    let tex_kind = gfx::texture::Kind::Cube(environment::FACE_SIZE);
+    let (_, cube) = device.create_texture_immutable_u8::<TextureFormat>(tex_kind, &levels).expect("failed to create environment");
+    let sampler = device.create_sampler(gfx::texture::SamplerInfo::new(gfx::texture::FilterMethod::Trilinear, gfx::texture::WrapMode::Clamp));
+    Environment {
+        cube,
+        sampler,
+        max_lod: chains[0].len() as f32,
+    }
+}
+
+fn entry_with_texture<R, F, V, View>(device: &mut F, vertex_data: &[V], index_data: &[u32], view: gfx::handle::ShaderResourceView<R, View>) -> Entry<R, V, View> 
+    where 
+        R: gfx::Resources,
+        F: gfx::Device<R>,
+        V: gfx::traits::Pod + gfx::pso::buffer::Structure<gfx::format::Format>,
+{
+    use gfx::traits::DeviceExt;
+    let (vbuf, slice) = device.create_vertex_buffer_with_slice(&vertex_data, index_data);
 
     Entry {
         slice,
         vertex_buffer: vbuf,
-        texture: view
+        texture: view,
+        shading: Shading::Lit,
+        joint_map: None,
+        mesh_id: 0,
+        layers: layer::WORLD,
+        visible: true,
+        roughness: DEFAULT_ROUGHNESS,
     }
 }
 
 
 fn font_entry<R: gfx::Resources, D: gfx::Device<R>>(device: &mut D, font: &Font, text: &str, pos: [f32;2], color: [f32;4], scale: f32) -> Entry<R, Vertex, f32> 
 {
-    let mut vertex_data = Vec::new();
-    let mut index_data = Vec::new();
-
-    let (mut x, z, mut y) = (pos[0], 0.0, pos[1]);
-
-    let mut min_y_end = y as i32;
-    for l in text.split('\n') {
-        for ch in l.chars() {
-            let ch_info = match font.chars.get(&ch) {
-                Some(info) => info,
-                None => continue,
-            };
-            let x_offset = (x + ch_info.x_offset as f32) * scale;
-            let y_offset = (y - ch_info.y_offset as f32) * scale;
-            let tex = ch_info.tex;
-            let x_end = x_offset + ch_info.width as f32 * scale;
-            let y_end = y_offset - ch_info.height as f32 * scale;
-            min_y_end = std::cmp::min(min_y_end, y_end as i32);
-
-            let index = vertex_data.len() as u32;
-
-            vertex_data.push(
-                Vertex { 
-                    position: [x_offset, z, y_offset],
-                    normal: [0.0, 1.0, 0.0],
-                    uv: [tex[0], tex[1]] ,
-                    joint_indices: [0;4], joint_weights: [0.0;4], color 
-                }
-            );
-            vertex_data.push(
-                Vertex { 
-                    position: [x_offset, z, y_end],
-                    normal: [0.0, 1.0, 0.0],
-                    uv: [tex[0], tex[1] + ch_info.tex_height], 
-                    joint_indices: [0;4], joint_weights: [0.0;4], color
-                }
-            );
-            vertex_data.push(
-                Vertex { 
-                    position: [x_end, z, y_end],
-                    normal: [0.0, 1.0, 0.0],
-                    uv: [tex[0] + ch_info.tex_width, tex[1] + ch_info.tex_height], 
-                    joint_indices: [0;4], joint_weights: [0.0;4], color
-                }
-            );
-            vertex_data.push(
-                Vertex { 
-                    position: [x_end, z, y_offset],
-                    normal: [0.0, 1.0, 0.0],
-                    uv: [tex[0] + ch_info.tex_width, tex[1]] ,
-                    joint_indices: [0;4], joint_weights: [0.0;4], color
-                }
-            );
-            index_data.push(index + 0);
-            index_data.push(index + 1);
-            index_data.push(index + 3);
-            index_data.push(index + 3);
-            index_data.push(index + 1);
-            index_data.push(index + 2);
+    font_entry_runs(device, font, &[TextRun::plain(text)], pos, color, scale, &[], 0.0, &mut Vec::new(), &mut Vec::new())
+}
 
-            x += ch_info.x_advance as f32;
+// pos is in font units like font_entry; each run may override the color and multiply the scale.
+// effects move and fade single glyphs after layout, so lines keep their places.
+// The quads are built in vertex_data and index_data, which are cleared first and only
+// needed until the buffers are made, so a caller can hand in the same ones every frame.
+// Glyphs lie on x and y facing +z, as on screen; text in the world goes through Axes::layout_plane.
+// A glyph's texels over rect, [left, top, right, bottom]; the underline stretches
+// the underscore's across a run.
+fn push_quad(vertex_data: &mut Vec<Vertex>, index_data: &mut Vec<u32>, rect: [f32; 4], ch_info: &BitmapChar, color: [f32; 4]) {
+    let (x_offset, y_offset, x_end, y_end, z) = (rect[0], rect[1], rect[2], rect[3], 0.0);
+    let tex = ch_info.tex;
+    let index = vertex_data.len() as u32;
+
+    vertex_data.push(
+        Vertex { 
+            position: [x_offset, y_offset, z],
+            normal: [0.0, 0.0, 1.0],
+            uv: [tex[0], tex[1]] ,
+            joint_indices: [0;4], joint_weights: [0.0;4], color 
         }
-        x = pos[0];
-        y = min_y_end as f32;
-        min_y_end = pos[1] as i32;
-    }
+    );
+    vertex_data.push(
+        Vertex { 
+            position: [x_offset, y_end, z],
+            normal: [0.0, 0.0, 1.0],
+            uv: [tex[0], tex[1] + ch_info.tex_height], 
+            joint_indices: [0;4], joint_weights: [0.0;4], color
+        }
+    );
+    vertex_data.push(
+        Vertex { 
+            position: [x_end, y_end, z],
+            normal: [0.0, 0.0, 1.0],
+            uv: [tex[0] + ch_info.tex_width, tex[1] + ch_info.tex_height], 
+            joint_indices: [0;4], joint_weights: [0.0;4], color
+        }
+    );
+    vertex_data.push(
+        Vertex { 
+            position: [x_end, y_offset, z],
+            normal: [0.0, 0.0, 1.0],
+            uv: [tex[0] + ch_info.tex_width, tex[1]] ,
+            joint_indices: [0;4], joint_weights: [0.0;4], color
+        }
+    );
+    index_data.push(index + 0);
+    index_data.push(index + 1);
+    index_data.push(index + 3);
+    index_data.push(index + 3);
+    index_data.push(index + 1);
+    index_data.push(index + 2);
+}
+
+fn font_entry_runs<R: gfx::Resources, D: gfx::Device<R>>(
+    device: &mut D,
+    font: &Font,
+    runs: &[TextRun],
+    pos: [f32;2],
+    color: [f32;4],
+    scale: f32,
+    effects: &[TextEffect],
+    time: f32,
+    vertex_data: &mut Vec<Vertex>,
+    index_data: &mut Vec<u32>,
+) -> Entry<R, Vertex, f32> 
+{
+    text_quads(font, runs, pos, color, scale, effects, time, vertex_data, index_data);
     entry_(
         device,
-        &vertex_data,
-        &index_data,
+        &vertex_data[..],
+        &index_data[..],
         &font.texture,
     )
 }
 
-fn query_entry<R, D, T> (
+// The layout half of font_entry_runs, which needs no device.
+fn text_quads(
+    font: &Font,
+    runs: &[TextRun],
+    pos: [f32;2],
+    color: [f32;4],
+    scale: f32,
+    effects: &[TextEffect],
+    time: f32,
+    vertex_data: &mut Vec<Vertex>,
+    index_data: &mut Vec<u32>,
+) {
+    vertex_data.clear();
+    index_data.clear();
+
+    let origin = [pos[0] * scale, pos[1] * scale];
+    let (mut x, mut y) = (origin[0], origin[1]);
+
+    let mut min_y_end = y;
+    let mut glyph = 0;
+    for run in runs {
+        let run_color = run.color.unwrap_or(color);
+        let s = scale * run.scale;
+        for (i, l) in run.text.split('\n').enumerate() {
+            if i > 0 {
+                x = origin[0];
+                y = min_y_end;
+            }
+            let line_start = x;
+            for ch in l.chars() {
+                let style = glyph_style(effects, glyph, time);
+                glyph += 1;
+                let ch_info = match font.chars.get(&ch) {
+                    Some(info) => info,
+                    None => continue,
+                };
+                let x_offset = x + ch_info.x_offset as f32 * s;
+                let y_offset = y - ch_info.y_offset as f32 * s;
+                let x_end = x_offset + ch_info.width as f32 * s;
+                let y_end = y_offset - ch_info.height as f32 * s;
+                min_y_end = min_y_end.min(y_end);
+
+                if style.alpha <= 0.0 {
+                    x += ch_info.x_advance as f32 * s;
+                    continue;
+                }
+                let color = [run_color[0], run_color[1], run_color[2], run_color[3] * style.alpha];
+                let (dx, dy) = (style.offset[0] * s, style.offset[1] * s);
+                let (x_offset, x_end, y_offset, y_end) = (x_offset + dx, x_end + dx, y_offset + dy, y_end + dy);
+
+                push_quad(vertex_data, index_data, [x_offset, y_offset, x_end, y_end], ch_info, color);
+
+                x += ch_info.x_advance as f32 * s;
+            }
+            if run.underline && x > line_start {
+                if let Some(bar) = font.chars.get(&'_') {
+                    let y_offset = y - bar.y_offset as f32 * s;
+                    let y_end = y_offset - bar.height as f32 * s;
+                    push_quad(vertex_data, index_data, [line_start, y_offset, x, y_end], bar, run_color);
+                }
+            }
+        }
+    }
+}
+
+// A scene without triggers is better than no scene.
+fn load_triggers(conn: &Connection, scene_id: i32) -> Vec<Trigger> {
+    query_triggers(conn, scene_id).unwrap_or_else(|e| {
+        warn!("failed to load the triggers of scene {}: {}", scene_id, e);
+        Vec::new()
+    })
+}
+
+fn query_entry<R, D> (
     conn: &Connection,
     device: &mut D,
+    textures: &mut TextureCache<R>,
+    clips: &mut AnimationStore,
+    mipmaps: bool,
+    frames: usize,
     ids: &[i32],
-) -> RusqliteResult<HashMap<i32, GameObject<R, Vertex>>> 
+) -> Result<HashMap<i32, GameObject<R, Vertex>>, AppError>
     where
         R: gfx::Resources,
         D: gfx::Device<R>,
-        T: gfx::format::TextureFormat,
 {
-    use gfx::traits::DeviceExt;
-
     let mut result = HashMap::default();
-
     for id in ids {
-        let meshes = query_mesh(&conn, id)?;
-        let joints = query_skeleton(&conn, id)?;
-        let animations = query_animation(&conn, id)?;
-        let entries = meshes.iter().map(|&(ref vertex_data, texture_id)| {
-            let img = query_texture::<TextureFormat>(&conn, texture_id).expect("failed to create texture");
-            entry(device, vertex_data.as_slice(), &img)
-        }).collect();
+        result.insert(*id, query_object(conn, device, textures, clips, mipmaps, frames, id)?);
+    }
+    Ok(result)
+}
 
-        let skinning_buffer = device.create_constant_buffer(64);
+fn query_object<R, D> (
+    conn: &Connection,
+    device: &mut D,
+    textures: &mut TextureCache<R>,
+    clips: &mut AnimationStore,
+    mipmaps: bool,
+    frames: usize,
+    id: &i32,
+) -> Result<GameObject<R, Vertex>, AppError>
+    where
+        R: gfx::Resources,
+        D: gfx::Device<R>,
+{
+    use gfx::traits::DeviceExt;
 
-        result.insert(
-            id.clone(), 
-            GameObject {
-                entries,
-                position: Point3::new(0.0, 0.0, 0.0),
-                // front: Vector3::new(0.0, -1.0, 0.0)
-                joints,
-                animations,
-                skinning_buffer,
+    let meshes = query_mesh(&conn, id)?;
+    let unlit = query_unlit_meshes(&conn, id)?;
+    let mesh_layers = query_mesh_layers(&conn, id)?;
+    let roughness = query_mesh_roughness(&conn, id)?;
+    let collision = query_collision_meshes(&conn, id)?;
+    let joints = query_skeleton(&conn, id)?;
+    let retarget = match query_animation_source(&conn, id)? {
+        Some(source_id) if source_id != *id => Some(load_retarget(&conn, source_id, *id, &joints)?),
+        _ => None,
+    };
+    let retarget = retarget.as_ref();
+    let clip = clips.get_or_load(&conn, *id, ANIMATION_DURATION, retarget)?;
+    let locomotion = clips.get_or_load_locomotion(&conn, *id, ANIMATION_DURATION, retarget)?.map(Locomotion::new);
+    let death_clip = clips.get_or_load_death(&conn, *id, ANIMATION_DURATION, retarget)?;
+    let layers = clips.get_or_load_layers(&conn, *id, ANIMATION_DURATION, retarget, &joints)?
+        .into_iter()
+        .map(AdditiveLayer::new)
+        .collect();
+    let animation_events = query_animation_events(&conn, id)?;
+    let stats = query_stats(&conn, id)?
+        .map(|(max_hp, speed)| Stats::new(max_hp, speed))
+        .unwrap_or_default();
+    let tags = query_object_tags(&conn, id)?;
+
+    validate_object(*id, &joints, &meshes, &clip.tracks)?;
+    let joint_order = joint_order(&joints)
+        .map_err(|joint_index| AssetValidationError { object_id: *id, problems: vec!(AssetProblem::JointCycle { joint_index }) })?;
+    // a skeleton too large for one palette is drawn in parts that each fit
+    let split = joints.len() > MAX_PALETTE_SIZE;
+    let palette_size = palette_size_for(joints.len().min(MAX_PALETTE_SIZE));
+    // collision meshes are only picked against; without any, what is drawn is
+    let pick_mesh = PickMesh::new(meshes.iter().enumerate()
+        .filter(|&(i, _)| collision.is_empty() || collision.contains(&(i + 1)))
+        .map(|(_, mesh)| (mesh.0.as_slice(), mesh.1.as_slice())));
+    debug!("object {}: {} triangles to pick", id, pick_mesh.triangle_count());
+    // only what is never posed can hide others without skinning it every frame
+    let occluders: Vec<OccluderMesh> = if joints.is_empty() {
+        meshes.iter().enumerate()
+            .filter(|&(i, _)| !collision.contains(&(i + 1)))
+            .map(|(i, mesh)| OccluderMesh::new(i + 1, &mesh.0, &mesh.1))
+            .filter(|mesh| mesh.triangle_count() <= MAX_OCCLUDER_TRIANGLES)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let parts: Vec<(usize, Vec<Vertex>, Vec<u32>, i32, Option<Vec<usize>>)> = meshes.iter().enumerate()
+        .filter(|&(i, _)| !collision.contains(&(i + 1)))
+        .flat_map(|(i, &(ref vertex_data, ref index_data, texture_id))| -> Vec<_> {
+            if split {
+                split_by_joints(vertex_data, index_data, MAX_PALETTE_SIZE).into_iter()
+                    .map(|sub| (i, sub.vertices, sub.indices, texture_id, Some(sub.joints)))
+                    .collect()
+            } else {
+                vec!((i, vertex_data.clone(), index_data.clone(), texture_id, None))
             }
-        );
+        })
+        .collect();
+    let mut entries = Vec::with_capacity(parts.len());
+    let mut texture_ids = Vec::with_capacity(parts.len());
+    for (i, vertex_data, index_data, texture_id, joint_map) in parts {
+        let view = textures.acquire(texture_id, || -> Result<_, AppError> {
+            let img = query_texture::<TextureFormat>(&conn, texture_id)?;
+            validate_texture(*id, texture_id, &img)?;
+            Ok(create_texture(device, &img, mipmaps))
+        });
+        let view = match view {
+            Ok(view) => view,
+            Err(e) => {
+                // give back what this object already took
+                for texture_id in texture_ids {
+                    textures.release(texture_id);
+                }
+                return Err(e);
+            }
+        };
+        texture_ids.push(texture_id);
+        let mut entry = entry_with_texture(device, vertex_data.as_slice(), index_data.as_slice(), view);
+        if unlit.contains(&(i + 1)) {
+            entry.shading = Shading::Unlit;
+        }
+        entry.joint_map = joint_map;
+        entry.mesh_id = i + 1;
+        if let Some(&mask) = mesh_layers.get(&(i + 1)) {
+            entry.layers = mask;
+        }
+        if let Some(&r) = roughness.get(&(i + 1)) {
+            entry.roughness = r;
+        }
+        entries.push(entry);
     }
 
-    Ok(result)
+    let skinning_buffers = (0..frames).map(|_| device.create_constant_buffer(palette_size)).collect();
+    let object_buffers = (0..frames).map(|_| device.create_constant_buffer(1)).collect();
+    let bounds = Aabb::from_points(meshes.iter().flat_map(|m| m.0.iter().map(|v| v.position)));
+
+    let body = Body {
+        position: Point3::new(0.0, 0.0, 0.0),
+        // front: Vector3::new(0.0, -1.0, 0.0)
+        joints: Arc::new(joints),
+        joint_order: Arc::new(joint_order),
+        clip,
+        speed: 1.0,
+        phase: 0.0,
+        phase_time: 0.0,
+        locomotion,
+        once_from: None,
+        layers,
+    };
+    // bind pose until the update thread publishes one
+    let palette = body.skinning(AnimationClock::Sample(0));
+
+    Ok(GameObject {
+        object_id: *id,
+        entries,
+        body,
+        palette,
+        animation_events,
+        skinning_buffers,
+        object_buffers,
+        palette_size,
+        bounds,
+        texture_ids,
+        transforms: TransformCache::new(),
+        stats,
+        death_clip,
+        behavior: None,
+        scale: 1.0,
+        pick_mesh,
+        occluders,
+        tags,
+        scene_row: None,
+    })
 }
 
 struct GameObject<R: gfx::Resources, V> {
+    // the Object row it was loaded from; several GameObjects can share one
+    object_id: i32,
     entries: Vec<Entry<R, V, [f32;4]>>,
-    position: Point3<f32>,
-    // front: Vector3<f32>,
-    joints: Vec<Joint>,
-    animations: Vec<Vec<(f32, Animation)>>,
-
-    skinning_buffer: gfx::handle::Buffer<R, Skinning>,
+    // copy of what the update thread simulates, refreshed from each snapshot
+    body: Body,
+    // pose from the latest snapshot
+    palette: Vec<Skinning>,
+    animation_events: Vec<AnimationEvent>,
+
+    // one per frame slot, so a frame never overwrites a palette the GPU is still reading
+    skinning_buffers: Vec<gfx::handle::Buffer<R, Skinning>>,
+    // likewise for the ObjectBlock
+    object_buffers: Vec<gfx::handle::Buffer<R, ObjectBlock>>,
+    palette_size: usize,
+    // bind pose bounds relative to position
+    bounds: Aabb,
+    // textures held in the World's TextureCache, one per entry
+    texture_ids: Vec<i32>,
+    transforms: TransformCache,
+    stats: Stats,
+    // played once in place of the other clips when hp runs out
+    death_clip: Option<Arc<AnimationClip>>,
+    // walks the object about when it is an NPC
+    behavior: Option<Box<Behavior>>,
+    // uniform, about position; bounds are already scaled
+    scale: f32,
+    pick_mesh: PickMesh,
+    // the drawn meshes objects behind it are culled against; see culling::OcclusionBuffer
+    occluders: Vec<OccluderMesh>,
+    // groups that avator commands can address, from ObjectTag and the tag command
+    tags: Vec<String>,
+    // the rowid of the Scene row it was loaded from or placed with
+    scene_row: Option<i64>,
 }
 
-trait Translate<T: cgmath::BaseFloat> {
-    fn translate(&mut self, v: Vector3<T>);
+// Spawned before the next frame draws, where the device is at hand.
+struct PendingSpawn {
+    object_id: i32,
+    position: Point3<f32>,
+    scale: f32,
+    // written to the scene and selected, as duplicates are
+    placed: bool,
 }
 
-impl<R: gfx::Resources, V> Translate<f32> for GameObject<R, V>
-{
-    fn translate(&mut self, v: Vector3<f32>) {
-        self.position += v;
-    }
-}
-impl<T: cgmath::BaseFloat> Translate<T> for Camera<T> 
-{
-    fn translate(&mut self, v: Vector3<T>) {
-        self.position += v;
-    }
-}
 
 trait GraphicsComponent<B: gfx::Backend, D: gfx::Device<B::Resources>> 
 {
@@ -1004,10 +5507,17 @@ trait GraphicsComponent<B: gfx::Backend, D: gfx::Device<B::Resources>>
         &self,
         view: &View<B::Resources>,
         camera: &Camera<f32>,
-        elapsed: f64,
-        pso: &Self::PSO,
+        camera_buffer: &gfx::handle::Buffer<B::Resources, CameraBlock>,
+        scissor: gfx::Rect,
+        mode: RenderMode,
+        depth: DepthTest,
+        psos: &Self::PSO,
         encoder: &mut gfx::GraphicsEncoder<B>,
         sampler: &gfx::handle::Sampler<B::Resources>,
+        environment: &Environment<B::Resources>,
+        arena: &FrameArena,
+        dump: &FrameDump,
+        slot: usize,
         dievice: &mut D,
     );
 }
@@ -1017,159 +5527,154 @@ impl<B, D> GraphicsComponent<B, D> for GameObject<B::Resources, Vertex>
         B: gfx::Backend,
         D: gfx::Device<B::Resources>,
 {
-    type PSO = gfx::PipelineState<B::Resources, pipe_w::Meta>;
+    // one pipeline per palette size, render mode, shading and depth test
+    type PSO = HashMap<(usize, RenderMode, Shading, DepthTest), gfx::PipelineState<B::Resources, pipe_w::Meta>>;
     fn render(
         &self,
         view: &View<B::Resources>,
         camera: &Camera<f32>,
-        elapsed: f64,
-        pso: &Self::PSO,
+        camera_buffer: &gfx::handle::Buffer<B::Resources, CameraBlock>,
+        scissor: gfx::Rect,
+        mode: RenderMode,
+        depth: DepthTest,
+        psos: &Self::PSO,
         encoder: &mut gfx::GraphicsEncoder<B>,
         sampler: &gfx::handle::Sampler<B::Resources>,
+        environment: &Environment<B::Resources>,
+        arena: &FrameArena,
+        dump: &FrameDump,
+        slot: usize,
         _:  &mut D,
     ) {
-        let mv = camera.view * Matrix4::from_translation(self.position.to_vec());
-        let mvp = camera.perspective * mv;
-        {
-            let a = self.get_skinning(elapsed);
-            encoder.update_buffer(&self.skinning_buffer, &a, 0).expect("ub");
+        let object_buffer = self.update_object_buffer(encoder, camera, slot);
+        let skinning_buffer = self.skinning_buffer(slot);
+        if self.entries.iter().all(|entry| entry.joint_map.is_none()) {
+            encoder.update_buffer(skinning_buffer, &self.palette, 0).expect("ub");
         }
-        for entry in &self.entries {
+        let mut palette = arena.palettes.take();
+        for (i, entry) in self.entries.iter().enumerate() {
+            if !entry.shown_to(camera.layers) {
+                dump.note(|| if entry.visible {
+                    format!("object {} mesh {} is on layers {}, the camera draws {}", self.object_id, entry.mesh_id, entry.layers.names(), camera.layers.names())
+                } else {
+                    format!("object {} mesh {} is hidden", self.object_id, entry.mesh_id)
+                });
+                continue;
+            }
+            if self.entry_palette(entry, &mut palette) {
+                encoder.update_buffer(skinning_buffer, &palette, 0).expect("ub");
+            }
             let data = pipe_w::Data {
                 vbuf: entry.vertex_buffer.clone(),
-                u_model_view_proj: mvp.into(),
-                u_model_view: mv.into(),
-                u_light: [0.2, 0.2, -0.2f32],
-                u_ambient_color: [0.01, 0.01, 0.01, 1.0],
-                u_eye_direction: camera.direction().into(),
+                b_camera: camera_buffer.clone(),
+                b_object: object_buffer.clone(),
                 u_texture: (entry.texture.clone(), sampler.clone()),
+                u_environment: (environment.cube.clone(), environment.sampler.clone()),
+                u_environment_lod: environment.max_lod,
+                u_roughness: entry.roughness,
                 out_color: view.0.clone(),
                 out_depth: view.1.clone(),
-                b_skinning: self.skinning_buffer.raw().clone(),
+                b_skinning: skinning_buffer.raw().clone(),
+                scissor,
             };
-            encoder.draw(&entry.slice, pso, &data);
+            encoder.draw(&entry.slice, &psos[&(self.palette_size, mode, entry.shading, depth)], &data);
+            dump.record(|| Draw {
+                pipeline: "pipe_w",
+                count: slice_count(&entry.slice),
+                textures: vec![
+                    self.texture_ids.get(i).map_or("texture".to_string(), |id| format!("texture {}", id)),
+                    "environment".to_string(),
+                ],
+                uniforms: format!("object {} mesh {} at {:?} {:?} {:?} {:?} roughness {} palette {}",
+                    self.object_id, entry.mesh_id, self.body.position, mode, entry.shading, depth, entry.roughness, self.palette_size),
+            });
         }
+        arena.palettes.give_back(palette);
     }
 }
 
-impl<R: gfx::Resources, V> GameObject<R, V> {
-    fn get_skinning(&self, time: f64) -> Vec<Skinning> {
-        if self.joints.len() > 0 {
-            let mut local = Vec::<Matrix4<f32>>::with_capacity(255);
-            self.joints.iter().map(|j| {
-
-                let p = if j.parent == 255 {
-                    cgmath::One::one()
-                } else { 
-                    *local.get(j.parent as usize).unwrap()
-                };
-           
-                match self.animations.get(j.joint_index as usize) {
-                    Some(v) => {
-                        let length = v.len();
-
-                        let transform = (
-                            p * if length > 0 {
-                                let duration = 4.0;
-                                let sample_per_second = length as f32 / duration; 
-                                let t = (time as f32 % duration) * sample_per_second;
-
-                                let index_1 = t.floor() as usize;
-                                let ceiled = t.ceil() as usize;
-                                let index_2 = if ceiled == length { 0 } else { ceiled };
-
-                                let blend_factor = t - index_1 as f32;
-
-                                let sample_1 = &v[index_1];
-                                let sample_2 = &v[index_2];
-
-                                let pose_1: Matrix4<f32> = sample_1.1.pose;
-                                let pose_2: Matrix4<f32> = sample_2.1.pose;
-
-                                let pose = pose_1 + (pose_2 - pose_1) * blend_factor;
-
-                                local.insert(j.joint_index as usize, p * pose);
-                                pose * j.inverse
-                            } else {
-                                local.insert(j.joint_index as usize, j.bind);
-                                j.bind 
-                            }
-                        ).into();
-
-                        Skinning{ 
-                            transform,
-                        }
-                    },
-                    _ => {
-                        let output = j.bind;
-                        local.insert(j.joint_index as usize, output);
+const ANIMATION_DURATION: f32 = 4.0;
 
-                        Skinning{ 
-                            transform: (output).into()
-                        }
-                    }
-                }
-            }).collect()
-        } else { 
-            let identity: Matrix4<f32> = cgmath::One::one();
-            vec!({Skinning{ transform: identity.into()}})
+impl<R: gfx::Resources, V> GameObject<R, V> {
+    // Where the object may be drawn, with room for animation; see culling::CULL_MARGIN.
+    fn cull_bounds(&self) -> Aabb {
+        self.bounds.translated(self.body.position.to_vec()).expanded(CULL_MARGIN)
+    }
+    fn skinning_buffer(&self, slot: usize) -> &gfx::handle::Buffer<R, Skinning> {
+        &self.skinning_buffers[slot % self.skinning_buffers.len()]
+    }
+    // Writes the transforms for the camera into the slot's ObjectBlock and hands it back.
+    fn update_object_buffer<B: gfx::Backend<Resources = R>>(&self, encoder: &mut gfx::GraphicsEncoder<B>, camera: &Camera<f32>, slot: usize) -> &gfx::handle::Buffer<R, ObjectBlock> {
+        let (mv, mvp) = self.transforms.get(camera.snapshot, self.body.position, self.scale, &camera.view, &camera.perspective);
+        let buffer = &self.object_buffers[slot % self.object_buffers.len()];
+        encoder.update_constant_buffer(buffer, &ObjectBlock {
+            model_view_proj: mvp.into(),
+            model_view: mv.into(),
+        });
+        buffer
+    }
+    // Fills palette with the part a split entry's joint indices refer to.
+    // False, leaving it empty, when the entry uses the whole palette.
+    fn entry_palette<T>(&self, entry: &Entry<R, V, T>, palette: &mut Vec<Skinning>) -> bool {
+        palette.clear();
+        match entry.joint_map {
+            Some(ref joint_map) => {
+                palette.extend(joint_map.iter().map(|&joint| self.palette[joint]));
+                true
+            },
+            None => false,
         }
     }
-    fn get_skinning_at(&self, index: usize) -> Vec<Skinning> {
-        if self.joints.len() > 0 {
-            let mut local = Vec::<Matrix4<f32>>::with_capacity(255);
-            self.joints.iter().map(|j| {
-
-                let p = if j.parent == 255 {
-                    cgmath::One::one()
-                } else { 
-                    *local.get(j.parent as usize).unwrap()
-                };
-           
-                match self.animations.get(j.joint_index as usize) {
-                    Some(v) => {
-                        let length = v.len();
-
-                        let output = p * if length > 0 {
-                            let inx = index % length;
-
-                            let sample_1 = &v[inx];
-
-                            let pose_1: Matrix4<f32> = sample_1.1.pose;
-
-                            let pose = pose_1; 
-
-                            local.insert(j.joint_index as usize, p * pose);
-                            pose * j.inverse
-                        } else {
-                            local.insert(j.joint_index as usize, j.bind);
-                            j.bind 
-                        };
-
-                        Skinning{ 
-                            transform: output.into()
-                        }
-                    },
-                    _ => {
-                        let output = j.bind;
-                        local.insert(j.joint_index as usize, output);
-
-                        Skinning{ 
-                            transform: (output).into()
-                        }
-                    }
-                }
-            }).collect()
-        } else { 
-            let identity: Matrix4<f32> = cgmath::One::one();
-            vec!({Skinning{ transform: identity.into()}})
+    // Events whose time lies in (from, to] on the looping timeline, or [from, to] with include_from.
+    fn crossed_events(&self, from: f64, to: f64, include_from: bool) -> Vec<&AnimationEvent> {
+        let duration = self.body.clip.duration as f64;
+        let (from, to) = (self.body.clip_position(from), self.body.clip_position(to));
+        // played backwards, the interval is crossed from its other end
+        let (from, to) = if to < from { (to, from) } else { (from, to) };
+        if self.animation_events.is_empty() || to <= from {
+            return Vec::new();
         }
+        if to - from >= duration {
+            return self.animation_events.iter().collect();
+        }
+        let from_t = self.body.wrap(from) as f32;
+        let to_t = self.body.wrap(to) as f32;
+        let after_from = |time: f32| from_t < time || (include_from && from_t == time);
+        self.animation_events.iter().filter(|e| {
+            if from_t <= to_t {
+                after_from(e.time) && e.time <= to_t
+            } else {
+                after_from(e.time) || e.time <= to_t
+            }
+        }).collect()
     }
 }
 
 
-fn query_mesh(conn: &Connection, object_id: &i32) -> RusqliteResult<Vec<(Vec<Vertex>, i32)>> {
-    let mut stmt = conn.prepare("
+// Meshes are (vertices, indices, texture id).
+// Rows without MeshIndex entries are legacy expanded triangle lists and get deduplicated here.
+fn query_mesh(conn: &Connection, object_id: &i32) -> RusqliteResult<Vec<(Vec<Vertex>, Vec<u32>, i32)>> {
+    let mut indices = query_mesh_indices(conn, object_id)?;
+    let meshes = query_mesh_vertices(conn, object_id)?;
+    Ok(meshes.into_iter().enumerate().map(|(i, (vertices, texture_id))| {
+        match indices.remove(&(i + 1)) {
+            Some(index_data) => (vertices, index_data, texture_id),
+            None => {
+                let (vertices, index_data) = deduplicate_vertices(&vertices, vertex_key);
+                (vertices, index_data, texture_id)
+            }
+        }
+    }).collect())
+}
+
+fn query_mesh_vertices(conn: &Connection, object_id: &i32) -> RusqliteResult<Vec<(Vec<Vertex>, i32)>> {
+    // white where the data has no vertex colors
+    let color_columns = if column_exists(conn, "MeshVertex", "ColorR")? {
+        "MV.ColorR AS ColorR, MV.ColorG AS ColorG, MV.ColorB AS ColorB, MV.ColorA AS ColorA"
+    } else {
+        "1.0 AS ColorR, 1.0 AS ColorG, 1.0 AS ColorB, 1.0 AS ColorA"
+    };
+    let mut stmt = conn.prepare(&format!("
 SELECT 
   M.MeshId
 , M.TextureId
@@ -1189,6 +5694,7 @@ SELECT
 , MV.JointWeight2
 , MV.JointWeight3
 , MV.JointWeight4
+, {}
   FROM Object AS O
 LEFT JOIN Mesh AS M
   ON O.ObjectId = M.ObjectId
@@ -1197,7 +5703,7 @@ LEFT JOIN MeshVertex AS MV
   and M.MeshId = MV.MeshId
 WHERE O.ObjectId = ?1
 Order By MV.ObjectId, MV.MeshId, MV.IndexNo
-")?;
+", color_columns))?;
     let result = stmt.query_map(&[object_id], |r| {
         ( r.get::<&str,i32>("MeshId") as usize,
           r.get::<&str,i32>("TextureId"),
@@ -1218,7 +5724,10 @@ Order By MV.ObjectId, MV.MeshId, MV.IndexNo
                                r.get::<&str,f64>("JointWeight2") as f32,
                                r.get::<&str,f64>("JointWeight3") as f32,
                                r.get::<&str,f64>("JointWeight4") as f32],
-              color: [0.0;4]
+              color: [ r.get::<&str,f64>("ColorR") as f32,
+                       r.get::<&str,f64>("ColorG") as f32,
+                       r.get::<&str,f64>("ColorB") as f32,
+                       r.get::<&str,f64>("ColorA") as f32]
           }
         )
     })?;
@@ -1236,27 +5745,269 @@ Order By MV.ObjectId, MV.MeshId, MV.IndexNo
     Ok(meshes)
 }
 
-fn query_texture<T>(conn: &Connection, texture_id: i32) -> RusqliteResult<Image<T>> 
+// MeshIds flagged Unlit; every mesh is lit in data without the column.
+fn query_unlit_meshes(conn: &Connection, object_id: &i32) -> RusqliteResult<HashSet<usize>> {
+    let mut unlit = HashSet::default();
+    if !column_exists(conn, "Mesh", "Unlit")? {
+        return Ok(unlit);
+    }
+    let mut stmt = conn.prepare("
+SELECT
+  M.MeshId
+  FROM Mesh AS M
+WHERE M.ObjectId = ?1
+  AND M.Unlit <> 0
+")?;
+    let result = stmt.query_map(&[object_id], |r| r.get::<&str,i32>("MeshId") as usize)?;
+    for r in result {
+        unlit.insert(r?);
+    }
+    Ok(unlit)
+}
+
+// for meshes that leave Roughness NULL: a broad, dim highlight
+const DEFAULT_ROUGHNESS: f32 = 0.6;
+
+// Roughness of the meshes that set one, clamped to 0 to 1.
+fn query_mesh_roughness(conn: &Connection, object_id: &i32) -> RusqliteResult<HashMap<usize, f32>> {
+    let mut roughness = HashMap::default();
+    if !column_exists(conn, "Mesh", "Roughness")? {
+        return Ok(roughness);
+    }
+    let mut stmt = conn.prepare("
+SELECT
+  M.MeshId
+, M.Roughness
+  FROM Mesh AS M
+WHERE M.ObjectId = ?1
+  AND M.Roughness IS NOT NULL
+")?;
+    let result = stmt.query_map(&[object_id], |r| (r.get::<&str,i32>("MeshId") as usize, r.get::<&str,f64>("Roughness") as f32))?;
+    for r in result {
+        let (mesh_id, value) = r?;
+        roughness.insert(mesh_id, value.max(0.0).min(1.0));
+    }
+    Ok(roughness)
+}
+
+// The layers of the meshes that name theirs; see LayerMask. Unknown names are left out,
+// and a mesh with none known stays on the world layer.
+fn query_mesh_layers(conn: &Connection, object_id: &i32) -> RusqliteResult<HashMap<usize, LayerMask>> {
+    let mut layers = HashMap::default();
+    if !column_exists(conn, "Mesh", "Layer")? {
+        return Ok(layers);
+    }
+    let mut stmt = conn.prepare("
+SELECT
+  M.MeshId
+, M.Layer
+  FROM Mesh AS M
+WHERE M.ObjectId = ?1
+  AND M.Layer IS NOT NULL
+")?;
+    let result = stmt.query_map(&[object_id], |r| (r.get::<&str,i32>("MeshId") as usize, r.get::<&str,String>("Layer")))?;
+    for r in result {
+        let (mesh_id, names) = r?;
+        let mut mask: Option<LayerMask> = None;
+        for name in names.split(|c: char| c == ',' || c.is_whitespace()).filter(|name| !name.is_empty()) {
+            match LayerMask::from_name(name) {
+                Some(layer) => mask = Some(mask.map_or(layer, |mask| mask.union(layer))),
+                None => warn!("object {} mesh {}: unknown layer {}", object_id, mesh_id, name),
+            }
+        }
+        if let Some(mask) = mask {
+            layers.insert(mesh_id, mask);
+        }
+    }
+    Ok(layers)
+}
+
+// MeshIds of the low-res meshes kept for picking rather than drawn.
+fn query_collision_meshes(conn: &Connection, object_id: &i32) -> RusqliteResult<HashSet<usize>> {
+    let mut collision = HashSet::default();
+    if !column_exists(conn, "Mesh", "Collision")? {
+        return Ok(collision);
+    }
+    let mut stmt = conn.prepare("
+SELECT
+  M.MeshId
+  FROM Mesh AS M
+WHERE M.ObjectId = ?1
+  AND M.Collision <> 0
+")?;
+    let result = stmt.query_map(&[object_id], |r| r.get::<&str,i32>("MeshId") as usize)?;
+    for r in result {
+        collision.insert(r?);
+    }
+    Ok(collision)
+}
+
+fn query_mesh_indices(conn: &Connection, object_id: &i32) -> RusqliteResult<HashMap<usize, Vec<u32>>> {
+    let mut indices = HashMap::default();
+    if !table_exists(conn, "MeshIndex")? {
+        return Ok(indices);
+    }
+    let mut stmt = conn.prepare("
+SELECT
+  MI.MeshId
+, MI.VertexIndex
+  FROM MeshIndex AS MI
+WHERE MI.ObjectId = ?1
+Order By MI.MeshId, MI.IndexNo
+")?;
+    let result = stmt.query_map(&[object_id], |r| {
+        ( r.get::<&str,i32>("MeshId") as usize,
+          r.get::<&str,i32>("VertexIndex") as u32 )
+    })?;
+    for r in result {
+        let (mesh_id, index) = r?;
+        indices.entry(mesh_id).or_insert_with(Vec::new).push(index);
+    }
+    Ok(indices)
+}
+
+fn vertex_key(v: &Vertex) -> [u32; 20] {
+    [ v.position[0].to_bits(), v.position[1].to_bits(), v.position[2].to_bits(),
+      v.normal[0].to_bits(), v.normal[1].to_bits(), v.normal[2].to_bits(),
+      v.uv[0].to_bits(), v.uv[1].to_bits(),
+      v.joint_indices[0] as u32, v.joint_indices[1] as u32, v.joint_indices[2] as u32, v.joint_indices[3] as u32,
+      v.joint_weights[0].to_bits(), v.joint_weights[1].to_bits(), v.joint_weights[2].to_bits(), v.joint_weights[3].to_bits(),
+      v.color[0].to_bits(), v.color[1].to_bits(), v.color[2].to_bits(), v.color[3].to_bits() ]
+}
+
+// Vertices with the same key are shared; gives the unique ones and an index per vertex passed in.
+fn deduplicate_vertices<V, K, F>(vertices: &[V], key: F) -> (Vec<V>, Vec<u32>)
+    where V: Copy, K: std::hash::Hash + Eq, F: Fn(&V) -> K
+{
+    let mut seen = HashMap::default();
+    let mut unique = Vec::new();
+    let mut index_data = Vec::with_capacity(vertices.len());
+    for v in vertices {
+        let index = *seen.entry(key(v)).or_insert_with(|| {
+            unique.push(*v);
+            unique.len() as u32 - 1
+        });
+        index_data.push(index);
+    }
+    (unique, index_data)
+}
+
+// Rewrites legacy expanded rows of an object as shared vertices plus MeshIndex rows.
+pub fn migrate_mesh_indices(object_id: i32) -> Result<(), AppError> {
+    let mut conn = open_connection();
+    let indexed = query_mesh_indices(&conn, &object_id)?;
+    let meshes = query_mesh_vertices(&conn, &object_id)?;
+    let has_color = column_exists(&conn, "MeshVertex", "ColorR")?;
+    // rows already indexed are checked too, and nothing is committed while any index is out of range
+    let mut problems: Vec<AssetProblem> = meshes.iter().enumerate()
+        .filter_map(|(i, &(ref vertices, _))| {
+            indexed.get(&(i + 1)).and_then(|indices| vertex_index_problem(i, indices, vertices.len()))
+        })
+        .collect();
+
+    let tx = conn.transaction()?;
+    for (i, &(ref vertices, _)) in meshes.iter().enumerate() {
+        let mesh_id = i as i32 + 1;
+        if indexed.contains_key(&(i + 1)) {
+            continue;
+        }
+        let (unique, index_data) = deduplicate_vertices(vertices, vertex_key);
+        if let Some(problem) = vertex_index_problem(i, &index_data, unique.len()) {
+            problems.push(problem);
+            continue;
+        }
+        tx.execute("DELETE FROM MeshVertex WHERE ObjectId = ?1 AND MeshId = ?2", &[&object_id, &mesh_id])?;
+        {
+            let mut stmt = tx.prepare("
+INSERT INTO MeshVertex (
+    ObjectId, MeshId, IndexNo,
+    PositionX, PositionY, PositionZ,
+    NormalX, NormalY, NormalZ,
+    U, V,
+    Joint1, Joint2, Joint3, Joint4,
+    JointWeight1, JointWeight2, JointWeight3, JointWeight4
+) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)")?;
+            for (index_no, v) in unique.iter().enumerate() {
+                stmt.execute(&[
+                    &object_id, &mesh_id, &(index_no as i32),
+                    &(v.position[0] as f64), &(v.position[1] as f64), &(v.position[2] as f64),
+                    &(v.normal[0] as f64), &(v.normal[1] as f64), &(v.normal[2] as f64),
+                    // query_mesh flips V on load
+                    &(v.uv[0] as f64), &(1.0 - v.uv[1] as f64),
+                    &v.joint_indices[0], &v.joint_indices[1], &v.joint_indices[2], &v.joint_indices[3],
+                    &(v.joint_weights[0] as f64), &(v.joint_weights[1] as f64), &(v.joint_weights[2] as f64), &(v.joint_weights[3] as f64),
+                ])?;
+            }
+        }
+        if has_color {
+            let mut stmt = tx.prepare("
+UPDATE MeshVertex SET ColorR = ?4, ColorG = ?5, ColorB = ?6, ColorA = ?7
+WHERE ObjectId = ?1 AND MeshId = ?2 AND IndexNo = ?3")?;
+            for (index_no, v) in unique.iter().enumerate() {
+                stmt.execute(&[
+                    &object_id, &mesh_id, &(index_no as i32),
+                    &(v.color[0] as f64), &(v.color[1] as f64), &(v.color[2] as f64), &(v.color[3] as f64),
+                ])?;
+            }
+        }
+        insert_mesh_indices(&tx, object_id, mesh_id, &index_data)?;
+    }
+    if !problems.is_empty() {
+        return Err(AssetValidationError { object_id, problems }.into());
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+// Data is expanded to RGBA8 from whatever the Format column names.
+fn query_texture<T>(conn: &Connection, texture_id: i32) -> Result<Image<T>, AppError> 
     where 
         T: gfx::format::TextureFormat
 {
-    conn.query_row("
+    let format_column = if column_exists(conn, "Texture", "Format")? { "T.Format" } else { "NULL" };
+    let (width, height, data, format) = conn.query_row(&format!("
 SELECT 
   T.Width
 , T.Height
 , T.Data
+, {} AS Format
 FROM Texture AS T
 WHERE T.TextureId = ?1
-", &[&texture_id], |r| {
-        Image {
-            data: r.get::<&str, Vec<u8>>("Data"),
-            width: r.get::<&str, i32>("Width") as u16, 
-            height: r.get::<&str, i32>("Height") as u16,
-            format: std::marker::PhantomData::<T>
-        }
+", format_column), &[&texture_id], |r| {
+        (
+            r.get::<&str, i32>("Width") as u16, 
+            r.get::<&str, i32>("Height") as u16,
+            r.get::<&str, Vec<u8>>("Data"),
+            r.get::<&str, Option<String>>("Format"),
+        )
+    })?;
+    let format = TextureDataFormat::from_name(format.as_ref().map(|f| f.as_str()))?;
+    Ok(Image {
+        data: decode_texture(format, width, height, data)?,
+        width,
+        height,
+        format: std::marker::PhantomData::<T>
     })
 }
 
+// Pairs the joints of object_id with those of source_id that have the same names.
+fn load_retarget(conn: &Connection, source_id: i32, object_id: i32, joints: &[Joint]) -> RusqliteResult<Retarget> {
+    let source = query_skeleton(conn, &source_id)?;
+    let retarget = Retarget::new(
+        source_id,
+        &source,
+        &query_joint_names(conn, &source_id)?,
+        joints,
+        &query_joint_names(conn, &object_id)?,
+    );
+    if retarget.matched() == 0 {
+        warn!("object {} shares no joint names with object {}; it will hold its bind pose", object_id, source_id);
+    } else {
+        info!("object {} plays the clips of object {} on {} of {} joints", object_id, source_id, retarget.matched(), joints.len());
+    }
+    Ok(retarget)
+}
+
 fn query_skeleton(conn: &Connection, object_id: &i32) -> RusqliteResult<Vec<Joint>> {
     let mut stmt = conn.prepare("
 SELECT
@@ -1349,7 +6100,8 @@ ORDER BY JointIndex
             inverse,
         };
 
-        joints.insert(joint_index as usize, joint);
+        // ordered by JointIndex; gaps are reported by validate_object
+        joints.push(joint);
     }
     Ok(joints)
 }
@@ -0,0 +1,79 @@
+use std;
+use std::io::Read;
+use std::path::Path;
+use fnv::FnvHashMap as HashMap;
+use fnv::FnvHashSet as HashSet;
+use serde_json;
+use serde_json::Value;
+
+pub struct Locale {
+    pub language: String,
+    strings: HashMap<String, String>,
+}
+
+pub type LocaleResult = Result<Locale, LocaleError>;
+
+#[derive(Debug)]
+pub enum LocaleError {
+    IoError(std::io::Error),
+    JsonError(serde_json::Error),
+    InvalidEntry(String),
+}
+
+impl From<std::io::Error> for LocaleError {
+    fn from(e: std::io::Error) -> LocaleError { LocaleError::IoError(e) }
+}
+impl From<serde_json::Error> for LocaleError {
+    fn from(e: serde_json::Error) -> LocaleError { LocaleError::JsonError(e) }
+}
+
+impl Locale {
+    pub fn empty(language: &str) -> Self {
+        Locale {
+            language: language.to_string(),
+            strings: HashMap::default(),
+        }
+    }
+    // Loads assets/locale/<language>.json.
+    // Nested objects are flattened into dotted keys: {"pause": {"title": ..}} => "pause.title"
+    pub fn load(dir: &str, language: &str) -> LocaleResult {
+        let path = Path::new(dir).join(format!("{}.json", language));
+        let mut source = String::new();
+        std::fs::File::open(path)?.read_to_string(&mut source)?;
+        Self::from_str(language, &source)
+    }
+    pub fn from_str(language: &str, source: &str) -> LocaleResult {
+        let value: Value = serde_json::from_str(source)?;
+        let mut locale = Self::empty(language);
+        flatten("", &value, &mut locale.strings)?;
+        Ok(locale)
+    }
+    // Returns the key itself when no translation is found, so missing entries stay visible.
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).map(|s| s.as_str()).unwrap_or(key)
+    }
+    // Every char used by the table, for building the glyph atlas.
+    pub fn chars(&self) -> HashSet<char> {
+        self.strings.values()
+            .flat_map(|s| s.chars())
+            .filter(|ch| !ch.is_control())
+            .collect()
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, strings: &mut HashMap<String, String>) -> Result<(), LocaleError> {
+    match *value {
+        Value::Object(ref map) => {
+            for (k, v) in map {
+                let key = if prefix.is_empty() { k.clone() } else { format!("{}.{}", prefix, k) };
+                flatten(&key, v, strings)?;
+            }
+            Ok(())
+        },
+        Value::String(ref s) => {
+            strings.insert(prefix.to_string(), s.clone());
+            Ok(())
+        },
+        _ => Err(LocaleError::InvalidEntry(prefix.to_string())),
+    }
+}
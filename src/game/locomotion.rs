@@ -0,0 +1,117 @@
+use std::sync::Arc;
+use cgmath::Matrix4;
+
+use animation::AnimationClip;
+
+// movement speeds, in units per second, at which walk and run play unblended
+const WALK_SPEED: f32 = 2.0;
+const RUN_SPEED: f32 = 6.0;
+// how fast the measured speed follows the moves, per second
+const SPEED_SMOOTHING: f32 = 8.0;
+
+pub struct LocomotionClips {
+    pub idle: AnimationClip,
+    pub walk: AnimationClip,
+    pub run: Option<AnimationClip>,
+}
+
+impl LocomotionClips {
+    // Weights of idle, walk and run for a movement speed; they sum to one.
+    pub fn weights(&self, speed: f32) -> [f32; 3] {
+        if speed <= 0.0 {
+            return [1.0, 0.0, 0.0];
+        }
+        if speed < WALK_SPEED {
+            let w = speed / WALK_SPEED;
+            return [1.0 - w, w, 0.0];
+        }
+        match self.run {
+            Some(_) if speed < RUN_SPEED => {
+                let w = (speed - WALK_SPEED) / (RUN_SPEED - WALK_SPEED);
+                [0.0, 1.0 - w, w]
+            },
+            Some(_) => [0.0, 0.0, 1.0],
+            None => [0.0, 1.0, 0.0],
+        }
+    }
+    fn clips(&self) -> [Option<&AnimationClip>; 3] {
+        [Some(&self.idle), Some(&self.walk), self.run.as_ref()]
+    }
+}
+
+// Blend state of one body. The clips share a phase, so a walk and a run
+// of different lengths still put their feet down together.
+#[derive(Clone)]
+pub struct Locomotion {
+    pub clips: Arc<LocomotionClips>,
+    // distance moved since the last advance
+    moved: f32,
+    speed: f32,
+    // 0..1 through the blended cycle
+    phase: f32,
+    last_time: Option<f64>,
+}
+
+impl Locomotion {
+    pub fn new(clips: Arc<LocomotionClips>) -> Self {
+        Locomotion {
+            clips,
+            moved: 0.0,
+            speed: 0.0,
+            phase: 0.0,
+            last_time: None,
+        }
+    }
+    pub fn add_distance(&mut self, distance: f32) {
+        self.moved += distance;
+    }
+    // Measures the speed from the distance moved since the last call and
+    // moves the phase on; playback scales the cycle like Body::speed.
+    pub fn advance(&mut self, time: f64, playback: f32) {
+        let dt = match self.last_time {
+            Some(last) => (time - last) as f32,
+            None => 0.0,
+        };
+        self.last_time = Some(time);
+        if dt <= 0.0 {
+            return;
+        }
+        let measured = self.moved / dt;
+        self.moved = 0.0;
+        self.speed += (measured - self.speed) * (dt * SPEED_SMOOTHING).min(1.0);
+
+        let weights = self.clips.weights(self.speed);
+        let clips = self.clips.clips();
+        let cycle: f32 = (0..3)
+            .filter_map(|i| clips[i].map(|clip| clip.duration * weights[i]))
+            .sum();
+        if cycle > 0.0 {
+            let phase = (self.phase + dt * playback / cycle) % 1.0;
+            self.phase = if phase < 0.0 { phase + 1.0 } else { phase };
+        }
+    }
+    pub fn weights(&self) -> [f32; 3] {
+        self.clips.weights(self.speed)
+    }
+    // Weighted blend of the clips that animate the joint; None when none do.
+    pub fn pose(&self, joint_index: usize, weights: &[f32; 3]) -> Option<Matrix4<f32>> {
+        let clips = self.clips.clips();
+        let mut total = 0.0;
+        let mut blended: Option<Matrix4<f32>> = None;
+        for i in 0..3 {
+            let clip = match clips[i] {
+                Some(clip) if weights[i] > 0.0 => clip,
+                _ => continue,
+            };
+            if let Some(pose) = clip.pose_at(joint_index, self.phase * clip.duration) {
+                let weighted = pose * weights[i];
+                blended = Some(match blended {
+                    Some(b) => b + weighted,
+                    None => weighted,
+                });
+                total += weights[i];
+            }
+        }
+        blended.map(|b| b * (1.0 / total))
+    }
+}
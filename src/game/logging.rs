@@ -0,0 +1,234 @@
+use std;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::panic;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock, TryLockError};
+use log::{self, Log, LogLevel, LogLevelFilter, LogMetadata, LogRecord, MaxLogLevelFilter, SetLoggerError};
+
+// lines kept for the console's log page
+const BUFFER_LINES: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub level: LogLevel,
+    pub target: String,
+    pub message: String,
+}
+
+// Levels per module path; the longest matching prefix wins over the default.
+#[derive(Debug, Clone)]
+pub struct LogFilters {
+    pub default: LogLevelFilter,
+    modules: Vec<(String, LogLevelFilter)>,
+}
+
+impl LogFilters {
+    pub fn new(default: LogLevelFilter) -> Self {
+        LogFilters {
+            default,
+            modules: Vec::new(),
+        }
+    }
+    // As in "warn,parti_game::net=debug"; a bare level sets the default.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filters = LogFilters::new(LogLevelFilter::Info);
+        for part in spec.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let mut halves = part.splitn(2, '=');
+            match (halves.next(), halves.next()) {
+                (Some(module), Some(level)) => filters.set(Some(module), parse_level(level)?),
+                (Some(level), None) => filters.set(None, parse_level(level)?),
+                _ => unreachable!(),
+            }
+        }
+        Ok(filters)
+    }
+    pub fn set(&mut self, module: Option<&str>, level: LogLevelFilter) {
+        let module = match module {
+            Some(module) => module,
+            None => {
+                self.default = level;
+                return;
+            },
+        };
+        self.modules.retain(|&(ref m, _)| m != module);
+        self.modules.push((module.to_string(), level));
+    }
+    pub fn level_for(&self, target: &str) -> LogLevelFilter {
+        self.modules.iter()
+            .filter(|&&(ref module, _)| target.starts_with(module.as_str()))
+            .max_by_key(|&&(ref module, _)| module.len())
+            .map(|&(_, level)| level)
+            .unwrap_or(self.default)
+    }
+    // the most verbose level anything passes at, so the macros can skip the rest early
+    fn max(&self) -> LogLevelFilter {
+        self.modules.iter().map(|&(_, level)| level).fold(self.default, std::cmp::max)
+    }
+}
+
+pub fn parse_level(s: &str) -> Result<LogLevelFilter, String> {
+    LogLevelFilter::from_str(s).map_err(|_| format!("not a log level: {}", s))
+}
+
+// Shared between the installed logger and whoever shows or tunes it.
+#[derive(Clone)]
+pub struct LogHandle {
+    lines: Arc<Mutex<VecDeque<LogLine>>>,
+    filters: Arc<RwLock<LogFilters>>,
+    max_level: Arc<Mutex<Option<MaxLogLevelFilter>>>,
+    file: Option<Arc<Mutex<File>>>,
+}
+
+impl LogHandle {
+    // The most recent lines, oldest first.
+    pub fn lines(&self) -> Vec<LogLine> {
+        self.lines.lock().expect("log buffer").iter().cloned().collect()
+    }
+    pub fn clear(&self) {
+        self.lines.lock().expect("log buffer").clear();
+    }
+    // None sets the default level.
+    pub fn set_level(&self, module: Option<&str>, level: LogLevelFilter) {
+        let mut filters = self.filters.write().expect("log filters");
+        filters.set(module, level);
+        if let Some(ref max_level) = *self.max_level.lock().expect("log max level") {
+            max_level.set(filters.max());
+        }
+    }
+    // Gets the log file onto the disk, as when about to abort. Skipped while another
+    // thread is writing it, since a panicking one cannot wait.
+    pub fn flush(&self) {
+        if let Some(ref file) = self.file {
+            let file = match file.try_lock() {
+                Ok(file) => file,
+                Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => return,
+            };
+            let _ = file.sync_data();
+        }
+    }
+    fn push(&self, line: LogLine) {
+        let mut lines = self.lines.lock().expect("log buffer");
+        if lines.len() == BUFFER_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+struct Logger {
+    handle: LogHandle,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= self.handle.filters.read().expect("log filters").level_for(metadata.target())
+    }
+    fn log(&self, record: &LogRecord) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = LogLine {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: format!("{}", record.args()),
+        };
+        // where they are seen without the console's log page or a log file
+        if line.level <= LogLevel::Warn {
+            eprintln!("{:5} {}: {}", line.level, line.target, line.message);
+        }
+        if let Some(ref file) = self.handle.file {
+            // a full disk is no reason to stop the game
+            let _ = writeln!(file.lock().expect("log file"), "{:5} {}: {}", line.level, line.target, line.message);
+        }
+        self.handle.push(line);
+    }
+}
+
+#[derive(Debug)]
+pub enum LogInitError {
+    SetLoggerError(SetLoggerError),
+    IoError(std::io::Error),
+}
+
+impl From<SetLoggerError> for LogInitError {
+    fn from(e: SetLoggerError) -> LogInitError { LogInitError::SetLoggerError(e) }
+}
+impl From<std::io::Error> for LogInitError {
+    fn from(e: std::io::Error) -> LogInitError { LogInitError::IoError(e) }
+}
+
+// Installs the process-wide logger; hand the returned handle to App::attach_log.
+pub fn init_logging(filters: LogFilters, file: Option<&Path>) -> Result<LogHandle, LogInitError> {
+    let file = match file {
+        Some(path) => Some(Arc::new(Mutex::new(File::create(path)?))),
+        None => None,
+    };
+    let handle = LogHandle {
+        lines: Arc::new(Mutex::new(VecDeque::with_capacity(BUFFER_LINES))),
+        filters: Arc::new(RwLock::new(filters)),
+        max_level: Arc::new(Mutex::new(None)),
+        file,
+    };
+    let logger_handle = handle.clone();
+    log::set_logger(move |max_level| {
+        max_level.set(logger_handle.filters.read().expect("log filters").max());
+        *logger_handle.max_level.lock().expect("log max level") = Some(max_level);
+        Box::new(Logger {
+            handle: logger_handle,
+        })
+    })?;
+    Ok(handle)
+}
+
+// What the panic hook runs after logging the panic, such as saving the session.
+#[derive(Clone)]
+pub struct PanicActions {
+    actions: Arc<Mutex<Vec<Box<Fn() + Send>>>>,
+}
+
+impl PanicActions {
+    pub fn add<F: Fn() + Send + 'static>(&self, action: F) {
+        self.actions.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(Box::new(action));
+    }
+    fn run(&self) {
+        let actions = match self.actions.try_lock() {
+            Ok(actions) => actions,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        for action in actions.iter() {
+            action();
+        }
+    }
+}
+
+// Puts panics in the log, runs the actions added since and flushes the log, all before
+// the default report. Nothing here waits on unwinding: a panic on the update thread never
+// reaches the App, and with panic=abort nothing is dropped at all. When the App is
+// dropped after all, its shutdown still waits for the GPU before anything is freed.
+pub fn install_panic_hook(log: Option<LogHandle>) -> PanicActions {
+    let actions = PanicActions {
+        actions: Arc::new(Mutex::new(Vec::new())),
+    };
+    let hook_actions = actions.clone();
+    let report = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let message = info.payload().downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        match info.location() {
+            Some(location) => error!("panicked at {}:{}: {}", location.file(), location.line(), message),
+            None => error!("panicked: {}", message),
+        }
+        hook_actions.run();
+        if let Some(ref log) = log {
+            log.flush();
+        }
+        report(info);
+    }));
+    actions
+}
@@ -0,0 +1,124 @@
+use std;
+
+use color::Color;
+
+// Inline text markup: `{color=#rrggbb}` or `{color=#rrggbbaa}` ... `{/color}`
+// and `{size=2}` ... `{/size}`. Tags nest; `{{` is a literal brace.
+// Unknown or malformed tags are kept as plain text.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    // None keeps the color the text is drawn with
+    pub color: Option<[f32; 4]>,
+    // multiplier on the scale the text is drawn with
+    pub scale: f32,
+    // drawn with the font's underscore stretched under it, as an input method's preedit
+    pub underline: bool,
+}
+
+impl TextRun {
+    pub fn plain(text: &str) -> Self {
+        TextRun {
+            text: text.to_string(),
+            color: None,
+            scale: 1.0,
+            underline: false,
+        }
+    }
+    pub fn underlined(text: &str) -> Self {
+        TextRun {
+            underline: true,
+            ..TextRun::plain(text)
+        }
+    }
+}
+
+enum Tag {
+    PushColor([f32; 4]),
+    PopColor,
+    PushSize(f32),
+    PopSize,
+}
+
+pub fn parse_markup(source: &str) -> Vec<TextRun> {
+    let mut runs = Vec::new();
+    let mut colors: Vec<Option<[f32; 4]>> = vec!(None);
+    let mut scales: Vec<f32> = vec!(1.0);
+    let mut text = String::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("{{") {
+            text.push('{');
+            rest = &rest[2..];
+            continue;
+        }
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = match parse_tag(&rest[1..end]) {
+            Some(Tag::PopColor) if colors.len() == 1 => None,
+            Some(Tag::PopSize) if scales.len() == 1 => None,
+            tag => tag,
+        };
+        match tag {
+            Some(tag) => {
+                // the text so far keeps the style it was written in
+                let style = (*colors.last().unwrap(), *scales.last().unwrap());
+                flush(&mut runs, &mut text, style);
+                match tag {
+                    Tag::PushColor(c) => colors.push(Some(c)),
+                    Tag::PopColor => { colors.pop(); },
+                    Tag::PushSize(s) => scales.push(style.1 * s),
+                    Tag::PopSize => { scales.pop(); },
+                }
+            },
+            None => text.push_str(&rest[..end + 1]),
+        }
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+    let style = (*colors.last().unwrap(), *scales.last().unwrap());
+    flush(&mut runs, &mut text, style);
+    runs
+}
+
+fn parse_tag(tag: &str) -> Option<Tag> {
+    match tag {
+        "/color" => Some(Tag::PopColor),
+        "/size" => Some(Tag::PopSize),
+        _ if tag.starts_with("color=") => parse_color(&tag["color=".len()..]).map(Tag::PushColor),
+        _ if tag.starts_with("size=") => match tag["size=".len()..].parse::<f32>() {
+            Ok(s) if s > 0.0 => Some(Tag::PushSize(s)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn flush(runs: &mut Vec<TextRun>, text: &mut String, style: (Option<[f32; 4]>, f32)) {
+    if text.is_empty() {
+        return;
+    }
+    runs.push(TextRun {
+        text: std::mem::replace(text, String::new()),
+        color: style.0,
+        scale: style.1,
+        underline: false,
+    });
+}
+
+// Hex codes are sRGB, as color pickers give them, so the text shows the color written.
+fn parse_color(hex: &str) -> Option<[f32; 4]> {
+    let hex = if hex.starts_with('#') { &hex[1..] } else { hex };
+    if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(3)? } else { 255 };
+    Some(Color::from_srgb8([channel(0)?, channel(1)?, channel(2)?, alpha]).into())
+}
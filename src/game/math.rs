@@ -0,0 +1,56 @@
+use cgmath::{Matrix4, Point3, Vector4};
+
+use picking::Ray;
+
+// A render target's size in pixels. Points on it are pixels from the bottom-left,
+// where render targets have their origin, as the canvas and sprites place things.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Screen {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Self {
+        Screen {
+            width: width as f32,
+            height: height as f32,
+        }
+    }
+    // as while minimized, when nothing maps onto it
+    pub fn is_empty(&self) -> bool {
+        self.width < 1.0 || self.height < 1.0
+    }
+    // The window reports the cursor from its top-left.
+    pub fn from_cursor(&self, cursor: (f64, f64)) -> [f32; 2] {
+        [cursor.0 as f32, self.height - cursor.1 as f32]
+    }
+    // As fractions of the screen, which viewports are laid out in.
+    pub fn fraction(&self, point: [f32; 2]) -> [f32; 2] {
+        [point[0] / self.width, point[1] / self.height]
+    }
+}
+
+pub fn screen_to_ndc(screen: Screen, point: [f32; 2]) -> [f32; 2] {
+    [2.0 * point[0] / screen.width - 1.0, 2.0 * point[1] / screen.height - 1.0]
+}
+
+// None behind the camera; a point in front may still be off the screen.
+// view_proj is a viewport camera's, which maps onto the whole screen.
+pub fn world_to_screen(view_proj: &Matrix4<f32>, screen: Screen, p: Point3<f32>) -> Option<[f32; 2]> {
+    let clip = *view_proj * Vector4::new(p.x, p.y, p.z, 1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    Some([
+        (clip.x / clip.w + 1.0) / 2.0 * screen.width,
+        (clip.y / clip.w + 1.0) / 2.0 * screen.height,
+    ])
+}
+
+// The line through a point on the screen as a viewport camera sees it,
+// from its near plane at t = 0 to its far one at t = 1. depth is the NDC
+// depth of those planes, which a viewport's depth range and reversed-Z move.
+pub fn ray_from_screen(view_proj: &Matrix4<f32>, depth: [f32; 2], screen: Screen, point: [f32; 2]) -> Option<Ray> {
+    Ray::from_screen(view_proj, screen_to_ndc(screen, point), depth)
+}
@@ -0,0 +1,79 @@
+use fnv::FnvHashMap as HashMap;
+use Vertex;
+
+// A part of a mesh whose joints fit one skinning palette.
+pub struct SubMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    // skeleton joint for each palette slot the vertices refer to
+    pub joints: Vec<usize>,
+}
+
+// Joints a vertex is actually skinned to; zero weights may carry any index.
+fn influences(v: &Vertex) -> Vec<usize> {
+    v.joint_indices.iter().zip(v.joint_weights.iter())
+        .filter(|&(_, &weight)| weight > 0.0)
+        .map(|(&joint_index, _)| joint_index as usize)
+        .collect()
+}
+
+// Greedily packs triangles into sub-meshes referencing at most max_joints joints each,
+// rewriting joint_indices into each sub-mesh's own palette.
+// Vertices shared across a split are duplicated.
+pub fn split_by_joints(vertices: &[Vertex], indices: &[u32], max_joints: usize) -> Vec<SubMesh> {
+    let mut result = Vec::new();
+    let mut current = SubMesh { vertices: Vec::new(), indices: Vec::new(), joints: Vec::new() };
+    // skeleton joint to palette slot, and source vertex to sub-mesh vertex
+    let mut slots: HashMap<usize, usize> = HashMap::default();
+    let mut remap: HashMap<u32, u32> = HashMap::default();
+
+    for triangle in indices.chunks(3) {
+        let mut needed: Vec<usize> = triangle.iter()
+            .flat_map(|&i| influences(&vertices[i as usize]))
+            .filter(|joint| !slots.contains_key(joint))
+            .collect();
+        needed.sort();
+        needed.dedup();
+        if current.joints.len() + needed.len() > max_joints && !current.indices.is_empty() {
+            result.push(::std::mem::replace(
+                &mut current,
+                SubMesh { vertices: Vec::new(), indices: Vec::new(), joints: Vec::new() }
+            ));
+            slots.clear();
+            remap.clear();
+        }
+        for &i in triangle {
+            let local = match remap.get(&i) {
+                Some(&local) => local,
+                None => {
+                    let mut v = vertices[i as usize];
+                    for k in 0..4 {
+                        if v.joint_weights[k] <= 0.0 {
+                            v.joint_indices[k] = 0;
+                            continue;
+                        }
+                        let joint = v.joint_indices[k] as usize;
+                        let slot = match slots.get(&joint) {
+                            Some(&slot) => slot,
+                            None => {
+                                current.joints.push(joint);
+                                current.joints.len() - 1
+                            },
+                        };
+                        slots.insert(joint, slot);
+                        v.joint_indices[k] = slot as i32;
+                    }
+                    current.vertices.push(v);
+                    let local = current.vertices.len() as u32 - 1;
+                    remap.insert(i, local);
+                    local
+                },
+            };
+            current.indices.push(local);
+        }
+    }
+    if !current.indices.is_empty() {
+        result.push(current);
+    }
+    result
+}
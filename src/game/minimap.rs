@@ -0,0 +1,105 @@
+use cgmath::{Point3, Vector3};
+
+use debug_draw::DebugLines;
+use math::{screen_to_ndc, Screen};
+use color::{Color, TEXT};
+use VertexP;
+
+// world units from the centre of the map to its edge
+const DEFAULT_RANGE: f32 = 40.0;
+const MIN_RANGE: f32 = 5.0;
+const MAX_RANGE: f32 = 400.0;
+const ZOOM_STEP: f32 = 1.25;
+// half size of an object marker, in pixels
+const MARKER_SIZE: f32 = 4.0;
+
+// Top-down view of the XY plane in a corner panel, centred on the camera.
+pub struct Minimap {
+    pub visible: bool,
+    pub range: f32,
+}
+
+// The camera as seen from above.
+pub struct MapCamera {
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    // half of the horizontal field of view, in radians
+    pub half_fov: f32,
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Minimap {
+            visible: true,
+            range: DEFAULT_RANGE,
+        }
+    }
+    pub fn zoom_in(&mut self) {
+        self.range = (self.range / ZOOM_STEP).max(MIN_RANGE);
+    }
+    pub fn zoom_out(&mut self) {
+        self.range = (self.range * ZOOM_STEP).min(MAX_RANGE);
+    }
+    // [left, bottom, right, top] in pixels, in the bottom-right corner.
+    pub fn panel(&self, screen_width: f32, screen_height: f32) -> [f32; 4] {
+        let size = screen_width.min(screen_height) * 0.28;
+        let margin = 12.0;
+        [screen_width - margin - size, margin, screen_width - margin, margin + size]
+    }
+    // Line vertices already in NDC, to be drawn with an identity view projection.
+    // vertices is reused for the lines, as from FrameArena.
+    pub fn lines(&self, panel: [f32; 4], screen: Screen, camera: &MapCamera, objects: &[(Point3<f32>, Color)], vertices: Vec<VertexP>) -> DebugLines {
+        let center = [(panel[0] + panel[2]) / 2.0, (panel[1] + panel[3]) / 2.0];
+        let half = (panel[2] - panel[0]) / 2.0;
+        let pixels_per_unit = half / self.range;
+        // drawn at the near plane so the scene depth never hides it
+        let to_ndc = |x: f32, y: f32| {
+            let ndc = screen_to_ndc(screen, [x, y]);
+            Point3::new(ndc[0], ndc[1], -1.0)
+        };
+        let to_map = |p: Point3<f32>| [
+            center[0] + (p.x - camera.position.x) * pixels_per_unit,
+            center[1] + (p.y - camera.position.y) * pixels_per_unit,
+        ];
+
+        let mut lines = DebugLines::with_buffer(vertices);
+        let border = TEXT.into();
+        let corners = [
+            to_ndc(panel[0], panel[1]), to_ndc(panel[2], panel[1]),
+            to_ndc(panel[2], panel[3]), to_ndc(panel[0], panel[3]),
+        ];
+        for i in 0..4 {
+            lines.line(corners[i], corners[(i + 1) % 4], border);
+        }
+
+        for &(position, color) in objects {
+            let (p, color) = (to_map(position), color.into());
+            let (x, y) = (p[0], p[1]);
+            let s = MARKER_SIZE;
+            let (l, r, b, t) = (to_ndc(x - s, y), to_ndc(x + s, y), to_ndc(x, y - s), to_ndc(x, y + s));
+            lines.line(l, t, color);
+            lines.line(t, r, color);
+            lines.line(r, b, color);
+            lines.line(b, l, color);
+        }
+
+        // the frustum as a wedge reaching half way to the edge
+        let flat = Vector3::new(camera.direction.x, camera.direction.y, 0.0);
+        let length = (flat.x * flat.x + flat.y * flat.y).sqrt();
+        if length > 0.0 {
+            let (dx, dy) = (flat.x / length, flat.y / length);
+            let reach = half * 0.5;
+            let edge = |angle: f32| {
+                let (sin, cos) = angle.sin_cos();
+                to_ndc(center[0] + (dx * cos - dy * sin) * reach, center[1] + (dx * sin + dy * cos) * reach)
+            };
+            let apex = to_ndc(center[0], center[1]);
+            let (left, right) = (edge(camera.half_fov), edge(-camera.half_fov));
+            let color = [1.0, 1.0, 0.2, 1.0];
+            lines.line(apex, left, color);
+            lines.line(apex, right, color);
+            lines.line(left, right, color);
+        }
+        lines
+    }
+}
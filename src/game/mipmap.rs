@@ -0,0 +1,59 @@
+use models::Image;
+
+// Every level below the base image down to 1x1, each a 2x2 box filter of the one above.
+// The data is sRGB, so texels are averaged in linear space.
+pub fn mip_chain<T>(img: &Image<T>) -> Vec<Image<T>> {
+    let to_linear: Vec<f32> = (0..256).map(|v| srgb_to_linear(v as f32 / 255.0)).collect();
+    let mut levels: Vec<Image<T>> = Vec::new();
+    let (mut width, mut height) = (img.width, img.height);
+    while width > 1 || height > 1 {
+        let next = {
+            let above = levels.last().map(|l| &l.data).unwrap_or(&img.data);
+            downsample(above, width, height, &to_linear)
+        };
+        levels.push(next);
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+    }
+    levels
+}
+
+fn downsample<T>(data: &[u8], width: u16, height: u16, to_linear: &[f32]) -> Image<T> {
+    let (w, h) = (width as usize, height as usize);
+    let (next_w, next_h) = ((w / 2).max(1), (h / 2).max(1));
+    let mut out = Vec::with_capacity(next_w * next_h * 4);
+    for y in 0..next_h {
+        for x in 0..next_w {
+            // odd sizes clamp to the last row or column
+            let xs = [(x * 2).min(w - 1), (x * 2 + 1).min(w - 1)];
+            let ys = [(y * 2).min(h - 1), (y * 2 + 1).min(h - 1)];
+            for c in 0..4 {
+                let mut sum = 0.0;
+                for &sy in &ys {
+                    for &sx in &xs {
+                        let v = data[(sy * w + sx) * 4 + c];
+                        // alpha is stored linearly
+                        sum += if c == 3 { v as f32 / 255.0 } else { to_linear[v as usize] };
+                    }
+                }
+                let avg = sum / 4.0;
+                let v = if c == 3 { avg } else { linear_to_srgb(avg) };
+                out.push((v * 255.0 + 0.5).max(0.0).min(255.0) as u8);
+            }
+        }
+    }
+    Image {
+        data: out,
+        width: next_w as u16,
+        height: next_h as u16,
+        format: ::std::marker::PhantomData,
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
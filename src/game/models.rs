@@ -1,10 +1,20 @@
 use std;
+use fnv::FnvHashMap as HashMap;
 use rusqlite::Connection;
 use rusqlite::Error as RusqliteError;
 use cgmath::{
     Matrix4,
+    Point3,
 };
 
+use camera_path::{CameraKey, PATH_KEY_INTERVAL};
+use fog::{Fog, FogMode};
+use preset::Preset;
+use trigger::{Trigger, TriggerShape};
+use debug_draw::Aabb;
+use command_history::{HistoryEntry, HistoryTarget};
+use color::Color;
+
 #[derive(Debug, Copy, Clone)]
 pub struct Joint {
     pub joint_index: i32,
@@ -21,6 +31,12 @@ pub struct Animation {
     pub pose: Matrix4<f32>,
 }
 
+#[derive(Debug, Clone)]
+pub struct AnimationEvent {
+    pub time: f32,
+    pub name: String,
+}
+
 pub struct Image<T> {
     pub data: Vec<u8>,
     pub width: u16,
@@ -30,7 +46,8 @@ pub struct Image<T> {
 
 pub type RusqliteResult<T> = Result<T, RusqliteError>;
 
-pub fn query_animation(conn: &Connection, object_id: &i32) -> RusqliteResult<Vec<Vec<(f32, Animation)>>> {
+// With a name, only the rows of that clip; without, only the unnamed rows of the base clip.
+pub fn query_animation(conn: &Connection, object_id: &i32, name: Option<&str>) -> RusqliteResult<Vec<Vec<(f32, Animation)>>> {
     let mut stmt = conn.prepare("
 SELECT
     AnimationId ,
@@ -57,9 +74,10 @@ SELECT
   FROM Animation AS A
 WHERE A.ObjectId = ?1
   AND JointIndex <> 0
+  AND COALESCE(A.Name, '') = ?2
 Order By JointIndex, SampleTime
 ")?;
-    let result = stmt.query_map(&[object_id], |r| {
+    let result = stmt.query_map(&[object_id, &name.unwrap_or("")], |r| {
         ( r.get::<&str,i32>("AnimationId"),
           r.get::<&str,i32>("JointIndex"),
           r.get::<&str,f64>("SampleTime") as f32,
@@ -108,3 +126,758 @@ Order By JointIndex, SampleTime
     }
     Ok(animations)
 }
+
+pub fn table_exists(conn: &Connection, name: &str) -> RusqliteResult<bool> {
+    conn.query_row("
+SELECT COUNT(*)
+  FROM sqlite_master
+WHERE type = 'table'
+  AND name = ?1
+", &[&name], |r| r.get::<i32, i32>(0) > 0)
+}
+
+pub fn column_exists(conn: &Connection, table: &str, column: &str) -> RusqliteResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map(&[], |r| r.get::<&str, String>("name"))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Clip length in seconds. None for legacy data, whose SampleTime is only an ordering
+// and whose samples are spread evenly over the default duration.
+pub fn query_animation_duration(conn: &Connection, object_id: &i32, name: Option<&str>) -> RusqliteResult<Option<f32>> {
+    if !column_exists(conn, "Animation", "Duration")? {
+        return Ok(None);
+    }
+    conn.query_row("
+SELECT MAX(Duration)
+  FROM Animation AS A
+WHERE A.ObjectId = ?1
+  AND COALESCE(A.Name, '') = ?2
+", &[object_id, &name.unwrap_or("")], |r| r.get::<i32, Option<f64>>(0).map(|d| d as f32))
+}
+
+pub fn create_animation_event_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS AnimationEvent (
+    ObjectId  INTEGER NOT NULL,
+    EventTime REAL    NOT NULL,
+    Name      TEXT    NOT NULL
+)", &[])?;
+    Ok(())
+}
+
+pub fn query_animation_events(conn: &Connection, object_id: &i32) -> RusqliteResult<Vec<AnimationEvent>> {
+    if !table_exists(conn, "AnimationEvent")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT
+    EventTime,
+    Name
+  FROM AnimationEvent AS E
+WHERE E.ObjectId = ?1
+Order By EventTime
+")?;
+    let result = stmt.query_map(&[object_id], |r| {
+        AnimationEvent {
+            time: r.get::<&str,f64>("EventTime") as f32,
+            name: r.get::<&str,String>("Name"),
+        }
+    })?;
+    let mut events = Vec::new();
+    for r in result {
+        events.push(r?);
+    }
+    Ok(events)
+}
+
+// The tables the game writes itself, for a database the asset tools made.
+pub fn create_game_tables(conn: &Connection) -> RusqliteResult<()> {
+    create_mesh_index_table(conn)?;
+    create_save_table(conn)?;
+    create_setting_table(conn)?;
+    create_animation_source_table(conn)?;
+    create_command_history_table(conn)?;
+    create_spawn_palette_table(conn)?;
+    create_camera_path_table(conn)?;
+    create_preset_table(conn)?;
+    Ok(())
+}
+
+fn create_mesh_index_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS MeshIndex (
+    ObjectId    INTEGER NOT NULL,
+    MeshId      INTEGER NOT NULL,
+    IndexNo     INTEGER NOT NULL,
+    VertexIndex INTEGER NOT NULL,
+    PRIMARY KEY (ObjectId, MeshId, IndexNo)
+)", &[])?;
+    Ok(())
+}
+
+pub fn insert_mesh_indices(conn: &Connection, object_id: i32, mesh_id: i32, indices: &[u32]) -> RusqliteResult<()> {
+    conn.execute("DELETE FROM MeshIndex WHERE ObjectId = ?1 AND MeshId = ?2", &[&object_id, &mesh_id])?;
+    let mut stmt = conn.prepare("
+INSERT INTO MeshIndex (ObjectId, MeshId, IndexNo, VertexIndex)
+VALUES (?1, ?2, ?3, ?4)
+")?;
+    for (index_no, &index) in indices.iter().enumerate() {
+        stmt.execute(&[&object_id, &mesh_id, &(index_no as i32), &(index as i32)])?;
+    }
+    Ok(())
+}
+
+fn create_save_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS Save (
+    Name      TEXT    NOT NULL,
+    ObjectId  INTEGER NOT NULL,
+    PositionX REAL    NOT NULL,
+    PositionY REAL    NOT NULL,
+    PositionZ REAL    NOT NULL,
+    PRIMARY KEY (Name, ObjectId)
+)", &[])?;
+    Ok(())
+}
+
+pub fn insert_save(conn: &Connection, name: &str, positions: &[(i32, [f32; 3])]) -> RusqliteResult<()> {
+    let mut stmt = conn.prepare("
+INSERT OR REPLACE INTO Save (Name, ObjectId, PositionX, PositionY, PositionZ)
+VALUES (?1, ?2, ?3, ?4, ?5)
+")?;
+    for &(object_id, p) in positions {
+        stmt.execute(&[&name, &object_id, &(p[0] as f64), &(p[1] as f64), &(p[2] as f64)])?;
+    }
+    Ok(())
+}
+
+fn create_setting_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS Setting (
+    Key   TEXT NOT NULL PRIMARY KEY,
+    Value TEXT NOT NULL
+)", &[])?;
+    Ok(())
+}
+
+pub fn insert_setting(conn: &Connection, key: &str, value: &str) -> RusqliteResult<()> {
+    conn.execute("
+INSERT OR REPLACE INTO Setting (Key, Value)
+VALUES (?1, ?2)
+", &[&key, &value])?;
+    Ok(())
+}
+
+pub fn query_setting(conn: &Connection, key: &str) -> RusqliteResult<Option<String>> {
+    if !table_exists(conn, "Setting")? {
+        return Ok(None);
+    }
+    match conn.query_row("
+SELECT Value
+  FROM Setting AS S
+WHERE S.Key = ?1
+", &[&key], |r| r.get::<i32, String>(0)) {
+        Ok(value) => Ok(Some(value)),
+        Err(RusqliteError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// None clears the name. False when the object has no such joint.
+pub fn update_joint_name(conn: &Connection, object_id: i32, joint_index: i32, name: Option<&str>) -> RusqliteResult<bool> {
+    if !table_exists(conn, "Joint")? {
+        return Ok(false);
+    }
+    let changed = conn.execute("
+UPDATE Joint
+   SET Name = ?3
+WHERE ObjectId = ?1
+  AND JointIndex = ?2
+", &[&object_id, &joint_index, &name])?;
+    Ok(changed > 0)
+}
+
+// Names by joint index; unnamed joints are left out.
+pub fn query_joint_names(conn: &Connection, object_id: &i32) -> RusqliteResult<HashMap<i32, String>> {
+    let mut names = HashMap::default();
+    if !table_exists(conn, "Joint")? || !column_exists(conn, "Joint", "Name")? {
+        return Ok(names);
+    }
+    let mut stmt = conn.prepare("
+SELECT JointIndex, Name
+  FROM Joint AS J
+WHERE J.ObjectId = ?1
+  AND J.Name IS NOT NULL
+")?;
+    let result = stmt.query_map(&[object_id], |r| (r.get::<&str,i32>("JointIndex"), r.get::<&str,String>("Name")))?;
+    for r in result {
+        let (joint_index, name) = r?;
+        names.insert(joint_index, name);
+    }
+    Ok(names)
+}
+
+fn create_animation_source_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS AnimationSource (
+    ObjectId       INTEGER NOT NULL PRIMARY KEY,
+    -- the object whose clips this one plays, retargeted by joint name
+    SourceObjectId INTEGER NOT NULL
+)", &[])?;
+    Ok(())
+}
+
+// None goes back to the object's own clips.
+pub fn insert_animation_source(conn: &Connection, object_id: i32, source_object_id: Option<i32>) -> RusqliteResult<()> {
+    match source_object_id {
+        Some(source_object_id) => conn.execute("
+INSERT OR REPLACE INTO AnimationSource (ObjectId, SourceObjectId)
+VALUES (?1, ?2)
+", &[&object_id, &source_object_id])?,
+        None => conn.execute("DELETE FROM AnimationSource WHERE ObjectId = ?1", &[&object_id])?,
+    };
+    Ok(())
+}
+
+pub fn query_animation_source(conn: &Connection, object_id: &i32) -> RusqliteResult<Option<i32>> {
+    if !table_exists(conn, "AnimationSource")? {
+        return Ok(None);
+    }
+    match conn.query_row("
+SELECT SourceObjectId
+  FROM AnimationSource AS S
+WHERE S.ObjectId = ?1
+", &[object_id], |r| r.get::<i32, i32>(0)) {
+        Ok(source) => Ok(Some(source)),
+        Err(RusqliteError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// A digest of an object's vertex, joint and texture rows, cheap to read, that changes
+// when a tool edits them; see AssetWatch. Texture data is only measured, so a texture
+// replaced by another of the same size and length goes unnoticed.
+pub fn query_asset_fingerprint(conn: &Connection, object_id: &i32) -> RusqliteResult<String> {
+    conn.query_row("
+SELECT
+  (SELECT COUNT(*) || ':' || TOTAL(PositionX + 3 * PositionY + 5 * PositionZ + 7 * U + 11 * V + 13 * Joint1 + 17 * JointWeight1)
+     FROM MeshVertex AS MV
+    WHERE MV.ObjectId = ?1)
+  || ' ' ||
+  (SELECT COUNT(*) || ':' || TOTAL(ParentIndex + 3 * BindPose11 + 5 * BindPose14 + 7 * BindPose24 + 11 * BindPose34)
+     FROM Joint AS J
+    WHERE J.ObjectId = ?1)
+  || ' ' ||
+  (SELECT COUNT(*) || ':' || TOTAL(T.TextureId + 3 * T.Width + 5 * T.Height + 7 * LENGTH(T.Data))
+     FROM Mesh AS M
+     JOIN Texture AS T
+       ON T.TextureId = M.TextureId
+    WHERE M.ObjectId = ?1)
+", &[object_id], |r| r.get::<i32, String>(0))
+}
+
+// The additive clips played over an object's base clip, lowest LayerNo first, as
+// (clip name, root joint, weight). A root joint limits the layer to it and its children;
+// None lets it move the whole body.
+pub fn query_animation_layers(conn: &Connection, object_id: &i32) -> RusqliteResult<Vec<(String, Option<i32>, f32)>> {
+    if !table_exists(conn, "AnimationLayer")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT ClipName, RootJoint, Weight
+  FROM AnimationLayer AS L
+WHERE L.ObjectId = ?1
+ORDER BY L.LayerNo
+")?;
+    let result = stmt.query_map(&[object_id], |r| {
+        (r.get::<&str,String>("ClipName"), r.get::<&str,Option<i32>>("RootJoint"), r.get::<&str,f64>("Weight") as f32)
+    })?;
+    let mut layers = Vec::new();
+    for r in result {
+        layers.push(r?);
+    }
+    Ok(layers)
+}
+
+fn create_command_history_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS CommandHistory (
+    SeqNo  INTEGER NOT NULL PRIMARY KEY,
+    Time   REAL    NOT NULL,
+    Target TEXT    NOT NULL,
+    Kind   TEXT    NOT NULL,
+    -- the command's numbers, separated by spaces
+    Args   TEXT    NOT NULL
+)", &[])?;
+    Ok(())
+}
+
+// Replaces what was stored, so the table holds one session's history. The rows go
+// in one transaction, and a failure leaves the previous history as it was.
+pub fn insert_command_history<'a, I>(conn: &mut Connection, entries: I) -> RusqliteResult<()>
+    where I: IntoIterator<Item = &'a HistoryEntry>
+{
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM CommandHistory", &[])?;
+    {
+        let mut stmt = tx.prepare("
+INSERT INTO CommandHistory (SeqNo, Time, Target, Kind, Args)
+VALUES (?1, ?2, ?3, ?4, ?5)
+")?;
+        for (seq_no, entry) in entries.into_iter().enumerate() {
+            let args: Vec<String> = entry.args.iter().map(|a| a.to_string()).collect();
+            stmt.execute(&[&(seq_no as i32), &entry.time, &entry.target.name(), &entry.kind, &args.join(" ")])?;
+        }
+    }
+    tx.commit()
+}
+
+// The last limit entries, oldest first. Rows that no longer parse are skipped.
+pub fn query_command_history(conn: &Connection, limit: usize) -> RusqliteResult<Vec<HistoryEntry>> {
+    if !table_exists(conn, "CommandHistory")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT Time, Target, Kind, Args
+  FROM (SELECT * FROM CommandHistory Order By SeqNo DESC LIMIT ?1)
+Order By SeqNo
+")?;
+    let result = stmt.query_map(&[&(limit as i64)], |r| {
+        let target = HistoryTarget::from_name(&r.get::<&str, String>("Target"));
+        let args: Result<Vec<f32>, _> = r.get::<&str, String>("Args").split_whitespace().map(|a| a.parse::<f32>()).collect();
+        match (target, args) {
+            (Some(target), Ok(args)) => Some(HistoryEntry {
+                time: r.get::<&str, f64>("Time"),
+                target,
+                kind: r.get::<&str, String>("Kind"),
+                args,
+            }),
+            _ => None,
+        }
+    })?;
+    let mut entries = Vec::new();
+    for r in result {
+        if let Some(entry) = r? {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn create_spawn_palette_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS SpawnPalette (
+    Slot     INTEGER NOT NULL PRIMARY KEY,
+    ObjectId INTEGER NOT NULL,
+    Scale    REAL    NOT NULL
+)", &[])?;
+    Ok(())
+}
+
+pub fn insert_palette_slot(conn: &Connection, slot: i32, object_id: i32, scale: f32) -> RusqliteResult<()> {
+    conn.execute("
+INSERT OR REPLACE INTO SpawnPalette (Slot, ObjectId, Scale)
+VALUES (?1, ?2, ?3)
+", &[&slot, &object_id, &(scale as f64)])?;
+    Ok(())
+}
+
+pub fn delete_palette_slot(conn: &Connection, slot: i32) -> RusqliteResult<()> {
+    if table_exists(conn, "SpawnPalette")? {
+        conn.execute("DELETE FROM SpawnPalette WHERE Slot = ?1", &[&slot])?;
+    }
+    Ok(())
+}
+
+// Rows are (slot, object id, scale).
+pub fn query_spawn_palette(conn: &Connection) -> RusqliteResult<Vec<(i32, i32, f32)>> {
+    if !table_exists(conn, "SpawnPalette")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT Slot, ObjectId, Scale
+  FROM SpawnPalette
+Order By Slot
+")?;
+    let result = stmt.query_map(&[], |r| (r.get::<&str,i32>("Slot"), r.get::<&str,i32>("ObjectId"), r.get::<&str,f64>("Scale") as f32))?;
+    let mut rows = Vec::new();
+    for r in result {
+        rows.push(r?);
+    }
+    Ok(rows)
+}
+
+fn create_camera_path_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS CameraPath (
+    PathId    INTEGER NOT NULL,
+    KeyNo     INTEGER NOT NULL,
+    KeyTime   REAL    NOT NULL,
+    PositionX REAL    NOT NULL,
+    PositionY REAL    NOT NULL,
+    PositionZ REAL    NOT NULL,
+    TargetX   REAL    NOT NULL,
+    TargetY   REAL    NOT NULL,
+    TargetZ   REAL    NOT NULL,
+    PRIMARY KEY (PathId, KeyNo)
+)", &[])?;
+    Ok(())
+}
+
+pub fn query_camera_path(conn: &Connection, path_id: i32) -> RusqliteResult<Vec<CameraKey>> {
+    if !table_exists(conn, "CameraPath")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT
+    KeyTime,
+    PositionX, PositionY, PositionZ,
+    TargetX, TargetY, TargetZ
+  FROM CameraPath AS P
+WHERE P.PathId = ?1
+Order By KeyTime, KeyNo
+")?;
+    let result = stmt.query_map(&[&path_id], |r| {
+        let f = |name: &str| r.get::<&str,f64>(name) as f32;
+        CameraKey {
+            time: f("KeyTime"),
+            position: Point3::new(f("PositionX"), f("PositionY"), f("PositionZ")),
+            target: Point3::new(f("TargetX"), f("TargetY"), f("TargetZ")),
+        }
+    })?;
+    let mut keys = Vec::new();
+    for r in result {
+        keys.push(r?);
+    }
+    Ok(keys)
+}
+
+// Appends a key PATH_KEY_INTERVAL after the last one and returns its time.
+pub fn insert_camera_key(conn: &Connection, path_id: i32, position: Point3<f32>, target: Point3<f32>) -> RusqliteResult<f32> {
+    let (key_no, time) = conn.query_row("
+SELECT IFNULL(MAX(KeyNo), -1) + 1, MAX(KeyTime)
+  FROM CameraPath
+WHERE PathId = ?1
+", &[&path_id], |r| (r.get::<i32, i32>(0), r.get::<i32, Option<f64>>(1)))?;
+    let time = time.map(|t| t as f32 + PATH_KEY_INTERVAL).unwrap_or(0.0);
+    conn.execute("
+INSERT INTO CameraPath (PathId, KeyNo, KeyTime, PositionX, PositionY, PositionZ, TargetX, TargetY, TargetZ)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+", &[&path_id, &key_no, &(time as f64),
+     &(position.x as f64), &(position.y as f64), &(position.z as f64),
+     &(target.x as f64), &(target.y as f64), &(target.z as f64)])?;
+    Ok(time)
+}
+
+fn create_preset_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS Preset (
+    Name       TEXT    NOT NULL PRIMARY KEY,
+    PositionX  REAL    NOT NULL,
+    PositionY  REAL    NOT NULL,
+    PositionZ  REAL    NOT NULL,
+    TargetX    REAL    NOT NULL,
+    TargetY    REAL    NOT NULL,
+    TargetZ    REAL    NOT NULL,
+    Fovy       REAL    NOT NULL,
+    Hour       REAL    NOT NULL,
+    TimeFrozen INTEGER NOT NULL,
+    FogMode    INTEGER NOT NULL,
+    FogStart   REAL    NOT NULL,
+    FogEnd     REAL    NOT NULL,
+    FogDensity REAL    NOT NULL,
+    -- NULL follows the sky
+    FogColorR  REAL,
+    FogColorG  REAL,
+    FogColorB  REAL
+)", &[])?;
+    Ok(())
+}
+
+pub fn insert_preset(conn: &Connection, name: &str, preset: &Preset) -> RusqliteResult<()> {
+    let fog = preset.fog.params();
+    let color = preset.fog.color.map(|c| (c.r as f64, c.g as f64, c.b as f64));
+    conn.execute("
+INSERT OR REPLACE INTO Preset (
+    Name,
+    PositionX, PositionY, PositionZ,
+    TargetX, TargetY, TargetZ,
+    Fovy, Hour, TimeFrozen,
+    FogMode, FogStart, FogEnd, FogDensity,
+    FogColorR, FogColorG, FogColorB
+) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+", &[&name,
+     &(preset.position.x as f64), &(preset.position.y as f64), &(preset.position.z as f64),
+     &(preset.target.x as f64), &(preset.target.y as f64), &(preset.target.z as f64),
+     &(preset.fovy as f64), &(preset.hour as f64), &(preset.time_frozen as i32),
+     &(fog[0] as i32), &(fog[1] as f64), &(fog[2] as f64), &(fog[3] as f64),
+     &color.map(|c| c.0), &color.map(|c| c.1), &color.map(|c| c.2)])?;
+    Ok(())
+}
+
+pub fn query_preset(conn: &Connection, name: &str) -> RusqliteResult<Option<Preset>> {
+    if !table_exists(conn, "Preset")? {
+        return Ok(None);
+    }
+    let preset = conn.query_row("
+SELECT *
+  FROM Preset AS P
+WHERE P.Name = ?1
+", &[&name], |r| {
+        let f = |name: &str| r.get::<&str,f64>(name) as f32;
+        let params = [r.get::<&str,i32>("FogMode") as f32, f("FogStart"), f("FogEnd"), f("FogDensity")];
+        let color = match (r.get::<&str,Option<f64>>("FogColorR"), r.get::<&str,Option<f64>>("FogColorG"), r.get::<&str,Option<f64>>("FogColorB")) {
+            (Some(red), Some(green), Some(blue)) => Some(Color::rgb(red as f32, green as f32, blue as f32)),
+            _ => None,
+        };
+        Preset {
+            position: Point3::new(f("PositionX"), f("PositionY"), f("PositionZ")),
+            target: Point3::new(f("TargetX"), f("TargetY"), f("TargetZ")),
+            fovy: f("Fovy"),
+            hour: f("Hour"),
+            time_frozen: r.get::<&str,i32>("TimeFrozen") != 0,
+            fog: Fog {
+                mode: FogMode::from_params(params).unwrap_or(FogMode::Off),
+                color,
+            },
+        }
+    });
+    match preset {
+        Ok(preset) => Ok(Some(preset)),
+        Err(RusqliteError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn query_preset_names(conn: &Connection) -> RusqliteResult<Vec<String>> {
+    if !table_exists(conn, "Preset")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT Name FROM Preset Order By Name")?;
+    let result = stmt.query_map(&[], |r| r.get::<i32, String>(0))?;
+    let mut names = Vec::new();
+    for r in result {
+        names.push(r?);
+    }
+    Ok(names)
+}
+
+// One Object row with what it is made of, for browsing the database in-game.
+#[derive(Debug, Clone)]
+pub struct AssetSummary {
+    pub object_id: i32,
+    pub meshes: i32,
+    pub vertices: i32,
+    pub joints: i32,
+    // "id:WxH" per texture the meshes use
+    pub textures: Vec<String>,
+}
+
+pub fn query_asset_summaries(conn: &Connection) -> RusqliteResult<Vec<AssetSummary>> {
+    if !table_exists(conn, "Object")? {
+        return Ok(Vec::new());
+    }
+    let count_joints = if table_exists(conn, "Joint")? {
+        "(SELECT COUNT(*) FROM Joint AS J WHERE J.ObjectId = O.ObjectId)"
+    } else {
+        "0"
+    };
+    let mut stmt = conn.prepare(&format!("
+SELECT
+    O.ObjectId,
+    (SELECT COUNT(*) FROM Mesh AS M WHERE M.ObjectId = O.ObjectId) AS Meshes,
+    (SELECT COUNT(*) FROM MeshVertex AS MV WHERE MV.ObjectId = O.ObjectId) AS Vertices,
+    {} AS Joints,
+    (SELECT GROUP_CONCAT(T.TextureId || ':' || T.Width || 'x' || T.Height, ' ')
+       FROM Texture AS T
+      WHERE T.TextureId IN (SELECT M.TextureId FROM Mesh AS M WHERE M.ObjectId = O.ObjectId)) AS Textures
+  FROM Object AS O
+Order By O.ObjectId
+", count_joints))?;
+    let result = stmt.query_map(&[], |r| {
+        AssetSummary {
+            object_id: r.get::<&str,i32>("ObjectId"),
+            meshes: r.get::<&str,i32>("Meshes"),
+            vertices: r.get::<&str,i32>("Vertices"),
+            joints: r.get::<&str,i32>("Joints"),
+            textures: r.get::<&str,Option<String>>("Textures")
+                .map(|t| t.split_whitespace().map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+        }
+    })?;
+    let mut summaries = Vec::new();
+    for r in result {
+        summaries.push(r?);
+    }
+    Ok(summaries)
+}
+
+// Objects a scene is made of, with where they start. The Scene table is authored by hand,
+// or by placing objects in the game: SceneId, ObjectId and PositionX/Y/Z, one row per object.
+// Rows come with their rowid, which placement writes back to.
+pub fn query_scene(conn: &Connection, scene_id: i32) -> RusqliteResult<Vec<(i64, i32, Point3<f32>)>> {
+    if !table_exists(conn, "Scene")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT
+    S.rowid AS RowId,
+    ObjectId,
+    PositionX, PositionY, PositionZ
+  FROM Scene AS S
+WHERE S.SceneId = ?1
+Order By ObjectId
+")?;
+    let result = stmt.query_map(&[&scene_id], |r| {
+        let f = |name: &str| r.get::<&str,f64>(name) as f32;
+        (r.get::<&str,i64>("RowId"), r.get::<&str,i32>("ObjectId"), Point3::new(f("PositionX"), f("PositionY"), f("PositionZ")))
+    })?;
+    let mut objects = Vec::new();
+    for r in result {
+        objects.push(r?);
+    }
+    Ok(objects)
+}
+
+pub fn create_scene_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS Scene (
+    SceneId   INTEGER NOT NULL,
+    ObjectId  INTEGER NOT NULL,
+    PositionX REAL    NOT NULL,
+    PositionY REAL    NOT NULL,
+    PositionZ REAL    NOT NULL
+)", &[])?;
+    Ok(())
+}
+
+// Adds an object to a scene, returning its row.
+pub fn insert_scene_object(conn: &Connection, scene_id: i32, object_id: i32, position: Point3<f32>) -> RusqliteResult<i64> {
+    conn.execute("
+INSERT INTO Scene (SceneId, ObjectId, PositionX, PositionY, PositionZ)
+VALUES (?1, ?2, ?3, ?4, ?5)
+", &[&scene_id, &object_id, &(position.x as f64), &(position.y as f64), &(position.z as f64)])?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_scene_position(conn: &Connection, row: i64, position: Point3<f32>) -> RusqliteResult<()> {
+    conn.execute("
+UPDATE Scene
+   SET PositionX = ?2, PositionY = ?3, PositionZ = ?4
+WHERE rowid = ?1
+", &[&row, &(position.x as f64), &(position.y as f64), &(position.z as f64)])?;
+    Ok(())
+}
+
+// Unwalkable cells of the navigation grid, authored by hand: one row per cell, X and Y.
+pub fn query_nav_blocked(conn: &Connection) -> RusqliteResult<Vec<(i32, i32)>> {
+    if !table_exists(conn, "NavBlocked")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT X, Y
+  FROM NavBlocked
+")?;
+    let result = stmt.query_map(&[], |r| (r.get::<&str,i32>("X"), r.get::<&str,i32>("Y")))?;
+    let mut cells = Vec::new();
+    for r in result {
+        cells.push(r?);
+    }
+    Ok(cells)
+}
+
+// Max hit points and speed multiplier, authored by hand; None for objects without a row.
+pub fn query_stats(conn: &Connection, object_id: &i32) -> RusqliteResult<Option<(f32, f32)>> {
+    if !table_exists(conn, "Stats")? {
+        return Ok(None);
+    }
+    match conn.query_row("
+SELECT MaxHp, Speed
+  FROM Stats AS S
+WHERE S.ObjectId = ?1
+", &[object_id], |r| (r.get::<i32, f64>(0) as f32, r.get::<i32, f64>(1) as f32)) {
+        Ok(stats) => Ok(Some(stats)),
+        Err(RusqliteError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Group names an object's instances start with, authored by hand.
+pub fn query_object_tags(conn: &Connection, object_id: &i32) -> RusqliteResult<Vec<String>> {
+    if !table_exists(conn, "ObjectTag")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT Tag
+  FROM ObjectTag AS T
+WHERE T.ObjectId = ?1
+Order By Tag
+")?;
+    let result = stmt.query_map(&[object_id], |r| r.get::<i32, String>(0))?;
+    let mut tags = Vec::new();
+    for r in result {
+        tags.push(r?);
+    }
+    Ok(tags)
+}
+
+// Trigger volumes of a scene, authored by hand; scene 0 is the world as it starts.
+// Shape is sphere, with SizeX as the radius, or box, sized around the position.
+pub fn query_triggers(conn: &Connection, scene_id: i32) -> RusqliteResult<Vec<Trigger>> {
+    if !table_exists(conn, "Trigger")? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("
+SELECT
+    TriggerId, Shape,
+    X, Y, Z,
+    SizeX, SizeY, SizeZ,
+    Prompt, TargetScene
+  FROM Trigger AS T
+WHERE T.SceneId = ?1
+Order By TriggerId
+")?;
+    let result = stmt.query_map(&[&scene_id], |r| {
+        let f = |name: &str| r.get::<&str,f64>(name) as f32;
+        let center = Point3::new(f("X"), f("Y"), f("Z"));
+        let half = [f("SizeX") / 2.0, f("SizeY") / 2.0, f("SizeZ") / 2.0];
+        let id = r.get::<&str,i32>("TriggerId");
+        let shape = match r.get::<&str,String>("Shape").as_str() {
+            "sphere" => Some(TriggerShape::Sphere { center, radius: f("SizeX") }),
+            "box" => Some(TriggerShape::Box(Aabb {
+                min: Point3::new(center.x - half[0], center.y - half[1], center.z - half[2]),
+                max: Point3::new(center.x + half[0], center.y + half[1], center.z + half[2]),
+            })),
+            shape => {
+                warn!("trigger {} has an unknown shape: {}", id, shape);
+                None
+            },
+        };
+        shape.map(|shape| Trigger {
+            id,
+            shape,
+            prompt: r.get::<&str,Option<String>>("Prompt"),
+            scene: r.get::<&str,Option<i32>>("TargetScene"),
+        })
+    })?;
+    let mut triggers = Vec::new();
+    for r in result {
+        if let Some(trigger) = r? {
+            triggers.push(trigger);
+        }
+    }
+    Ok(triggers)
+}
+
+pub fn delete_camera_path(conn: &Connection, path_id: i32) -> RusqliteResult<()> {
+    if table_exists(conn, "CameraPath")? {
+        conn.execute("DELETE FROM CameraPath WHERE PathId = ?1", &[&path_id])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use cgmath::{InnerSpace, Vector3, Zero};
+
+use input::{InputState, Key};
+
+// How a key driven avator gets going and comes to a stop, in units per second
+// and units per second squared. max_speed is scaled by the avator's Stats speed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MovementModel {
+    pub acceleration: f32,
+    pub friction: f32,
+    pub max_speed: f32,
+}
+
+impl Default for MovementModel {
+    fn default() -> Self {
+        MovementModel {
+            acceleration: 20.0,
+            friction: 15.0,
+            max_speed: 3.0,
+        }
+    }
+}
+
+impl MovementModel {
+    // As written to the Setting table: "<acceleration> <friction> <max speed>".
+    pub fn from_setting(value: &str) -> Option<Self> {
+        let values: Vec<f32> = match value.split_whitespace().map(|v| v.parse()).collect() {
+            Ok(values) => values,
+            Err(_) => return None,
+        };
+        match values.as_slice() {
+            &[acceleration, friction, max_speed] if acceleration > 0.0 && friction > 0.0 && max_speed > 0.0 =>
+                Some(MovementModel { acceleration, friction, max_speed }),
+            _ => None,
+        }
+    }
+    pub fn to_setting(&self) -> String {
+        format!("{} {} {}", self.acceleration, self.friction, self.max_speed)
+    }
+}
+
+// vi keys, as the avator has always moved with
+const MOVE_KEYS: [(Key, [f32; 3]); 4] = [
+    (Key::L, [1.0, 0.0, 0.0]),
+    (Key::H, [-1.0, 0.0, 0.0]),
+    (Key::J, [0.0, -1.0, 0.0]),
+    (Key::K, [0.0, 1.0, 0.0]),
+];
+
+// The velocity the direction keys built up. Opposite keys cancel, and two at
+// right angles go no faster than one.
+pub struct Movement {
+    pub model: MovementModel,
+    velocity: Vector3<f32>,
+}
+
+impl Movement {
+    pub fn new(model: MovementModel) -> Self {
+        Movement {
+            model,
+            velocity: Vector3::zero(),
+        }
+    }
+    // Whether a direction key went down since the last tick.
+    pub fn started(input: &InputState) -> bool {
+        MOVE_KEYS.iter().any(|&(key, _)| input.just_pressed(key))
+    }
+    // Halts at once, as when a click takes over.
+    pub fn stop(&mut self) {
+        self.velocity = Vector3::zero();
+    }
+    // A key let go during the tick still steered part of it.
+    fn direction(input: &InputState) -> Vector3<f32> {
+        let sum = MOVE_KEYS.iter()
+            .filter(|&&(key, _)| input.is_held(key) || input.just_released(key))
+            .fold(Vector3::zero(), |sum: Vector3<f32>, &(_, v)| sum + Vector3::from(v));
+        if sum.magnitude2() > 0.0 { sum.normalize() } else { sum }
+    }
+    // The distance to move over dt, None once at rest. The velocity turns toward
+    // the held direction at the acceleration and slows by friction when nothing is held.
+    pub fn step(&mut self, input: &InputState, speed_scale: f32, dt: f32) -> Option<Vector3<f32>> {
+        let max_speed = self.model.max_speed * speed_scale.max(0.0);
+        let direction = Movement::direction(input);
+        if direction.magnitude2() > 0.0 {
+            let wanted = direction * max_speed;
+            self.velocity = approach(self.velocity, wanted, self.model.acceleration * dt);
+        } else {
+            self.velocity = approach(self.velocity, Vector3::zero(), self.model.friction * dt);
+        }
+        if self.velocity.magnitude() > max_speed {
+            self.velocity = self.velocity.normalize_to(max_speed);
+        }
+        if self.velocity.magnitude2() > 0.0 {
+            Some(self.velocity * dt)
+        } else {
+            None
+        }
+    }
+}
+
+// from toward to by at most max_delta
+fn approach(from: Vector3<f32>, to: Vector3<f32>, max_delta: f32) -> Vector3<f32> {
+    let delta = to - from;
+    let distance = delta.magnitude();
+    if distance <= max_delta || distance == 0.0 {
+        to
+    } else {
+        from + delta * (max_delta / distance)
+    }
+}
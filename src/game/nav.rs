@@ -0,0 +1,167 @@
+use std;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use cgmath::Point3;
+
+use debug_draw::Aabb;
+
+// Square cells on the ground, each walkable or not. Cell (0, 0) starts at origin
+// and x, y grow with world x, y.
+#[derive(Debug, Clone)]
+pub struct NavGrid {
+    pub origin: [f32; 2],
+    pub cell_size: f32,
+    pub width: usize,
+    pub height: usize,
+    blocked: Vec<bool>,
+}
+
+// cost of a diagonal step in cells
+const DIAGONAL: f32 = std::f32::consts::SQRT_2;
+
+#[derive(Copy, Clone, PartialEq)]
+struct Open {
+    estimate: f32,
+    cell: usize,
+}
+
+impl Eq for Open {}
+
+// BinaryHeap is a max-heap; the lowest estimate comes out first.
+impl Ord for Open {
+    fn cmp(&self, other: &Open) -> Ordering {
+        other.estimate.partial_cmp(&self.estimate).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Open) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    pub fn new(origin: [f32; 2], cell_size: f32, width: usize, height: usize) -> Self {
+        NavGrid {
+            origin,
+            cell_size,
+            width,
+            height,
+            blocked: vec!(false; width * height),
+        }
+    }
+    pub fn cell_of(&self, p: Point3<f32>) -> Option<(usize, usize)> {
+        let x = ((p.x - self.origin[0]) / self.cell_size).floor();
+        let y = ((p.y - self.origin[1]) / self.cell_size).floor();
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+        Some((x as usize, y as usize))
+    }
+    pub fn center(&self, cell: (usize, usize), z: f32) -> Point3<f32> {
+        Point3::new(
+            self.origin[0] + (cell.0 as f32 + 0.5) * self.cell_size,
+            self.origin[1] + (cell.1 as f32 + 0.5) * self.cell_size,
+            z,
+        )
+    }
+    // Cells off the grid are not walkable.
+    pub fn is_walkable(&self, x: isize, y: isize) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+            && !self.blocked[y as usize * self.width + x as usize]
+    }
+    pub fn set_blocked(&mut self, cell: (usize, usize), blocked: bool) {
+        if cell.0 < self.width && cell.1 < self.height {
+            self.blocked[cell.1 * self.width + cell.0] = blocked;
+        }
+    }
+    // Blocks every cell the box's footprint touches.
+    pub fn block_aabb(&mut self, aabb: &Aabb) {
+        let cell_size = self.cell_size;
+        let clamp = |v: f32, origin: f32, count: usize| {
+            (((v - origin) / cell_size).floor().max(0.0) as usize).min(count.saturating_sub(1))
+        };
+        let (x0, x1) = (clamp(aabb.min.x, self.origin[0], self.width), clamp(aabb.max.x, self.origin[0], self.width));
+        let (y0, y1) = (clamp(aabb.min.y, self.origin[1], self.height), clamp(aabb.max.y, self.origin[1], self.height));
+        for y in y0..y1 + 1 {
+            for x in x0..x1 + 1 {
+                self.set_blocked((x, y), true);
+            }
+        }
+    }
+    pub fn blocked_cells(&self) -> Vec<(usize, usize)> {
+        (0..self.blocked.len())
+            .filter(|&i| self.blocked[i])
+            .map(|i| (i % self.width, i / self.width))
+            .collect()
+    }
+
+    // A* over the eight neighbours; diagonals may not cut a blocked corner.
+    // Returns the points to walk through after from, ending exactly at to, on from's height.
+    // The start cell may be blocked, as when standing against an obstacle; the goal may not.
+    pub fn find_path(&self, from: Point3<f32>, to: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        let start = self.cell_of(from)?;
+        let goal = self.cell_of(to)?;
+        if !self.is_walkable(goal.0 as isize, goal.1 as isize) {
+            return None;
+        }
+        let index = |cell: (usize, usize)| cell.1 * self.width + cell.0;
+        let heuristic = |cell: (usize, usize)| {
+            let dx = (cell.0 as f32 - goal.0 as f32).abs();
+            let dy = (cell.1 as f32 - goal.1 as f32).abs();
+            // octile distance
+            dx.max(dy) + (DIAGONAL - 1.0) * dx.min(dy)
+        };
+
+        let mut cost = vec!(std::f32::INFINITY; self.blocked.len());
+        let mut came_from: Vec<Option<usize>> = vec!(None; self.blocked.len());
+        let mut open = BinaryHeap::new();
+        cost[index(start)] = 0.0;
+        open.push(Open { estimate: heuristic(start), cell: index(start) });
+
+        while let Some(Open { cell, .. }) = open.pop() {
+            let (x, y) = ((cell % self.width) as isize, (cell / self.width) as isize);
+            if (x as usize, y as usize) == goal {
+                return Some(self.walk_back(&came_from, cell, from, to));
+            }
+            for &(dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let (nx, ny) = (x + dx, y + dy);
+                if !self.is_walkable(nx, ny) {
+                    continue;
+                }
+                let diagonal = dx != 0 && dy != 0;
+                if diagonal && !(self.is_walkable(x + dx, y) && self.is_walkable(x, y + dy)) {
+                    continue;
+                }
+                let next = index((nx as usize, ny as usize));
+                let next_cost = cost[cell] + if diagonal { DIAGONAL } else { 1.0 };
+                if next_cost < cost[next] {
+                    cost[next] = next_cost;
+                    came_from[next] = Some(cell);
+                    open.push(Open { estimate: next_cost + heuristic((nx as usize, ny as usize)), cell: next });
+                }
+            }
+        }
+        None
+    }
+
+    // Cell centres from start to goal, keeping only the turns, with to in place of the last one.
+    fn walk_back(&self, came_from: &[Option<usize>], goal: usize, from: Point3<f32>, to: Point3<f32>) -> Vec<Point3<f32>> {
+        let mut cells = vec!(goal);
+        while let Some(previous) = came_from[*cells.last().unwrap()] {
+            cells.push(previous);
+        }
+        cells.reverse();
+        let cell_at = |i: usize| ((cells[i] % self.width) as isize, (cells[i] / self.width) as isize);
+        let mut points = Vec::new();
+        // skips the start cell, which the avator is already in
+        for i in 1..cells.len() - 1 {
+            let (a, b, c) = (cell_at(i - 1), cell_at(i), cell_at(i + 1));
+            if (b.0 - a.0, b.1 - a.1) != (c.0 - b.0, c.1 - b.1) {
+                points.push(self.center((b.0 as usize, b.1 as usize), from.z));
+            }
+        }
+        points.push(Point3::new(to.x, to.y, from.z));
+        points
+    }
+}
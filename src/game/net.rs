@@ -0,0 +1,178 @@
+use std;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use cgmath::Vector3;
+
+use AvatorCommand;
+
+// Both ends run the same crate, so the wire format is just a tag byte
+// followed by little-endian fields.
+const TAG_HELLO: u8 = 0;
+const TAG_MOVE: u8 = 1;
+// the listener's answer to hello, with the key of the avator the connecting end drives
+const TAG_WELCOME: u8 = 2;
+const MAX_PACKET: usize = 64;
+
+#[derive(Debug)]
+pub enum NetError {
+    IoError(io::Error),
+    UnknownTag(u8),
+    Truncated,
+    // the listener has no avator to give the peer
+    NoAvator,
+}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> NetError { NetError::IoError(e) }
+}
+
+pub enum NetRole {
+    Listen(String),
+    Connect(String),
+}
+
+// One peer over UDP. The listener learns the remote address from the first packet.
+// Both ends load the same scene, so the listener picks the avator the peer drives and
+// tells it in the welcome; the connecting end moves that key for the listener's player.
+pub struct Peer {
+    socket: UdpSocket,
+    remote: Option<SocketAddr>,
+    avator: Option<i32>,
+}
+
+enum Packet {
+    Hello,
+    Welcome(i32),
+    Command(AvatorCommand),
+}
+
+impl Peer {
+    // avator is what the listener gives the peer; connecting, it comes in the welcome.
+    pub fn open(role: &NetRole, avator: Option<i32>) -> Result<Peer, NetError> {
+        match *role {
+            NetRole::Listen(ref addr) => Peer::listen(addr, avator.ok_or(NetError::NoAvator)?),
+            NetRole::Connect(ref addr) => Peer::connect(addr),
+        }
+    }
+    pub fn listen(addr: &str, avator: i32) -> Result<Peer, NetError> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Peer {
+            socket,
+            remote: None,
+            avator: Some(avator),
+        })
+    }
+    pub fn connect(addr: &str) -> Result<Peer, NetError> {
+        let remote = addr.to_socket_addrs()?.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+        let local = if remote.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        let peer = Peer {
+            socket,
+            remote: Some(remote),
+            avator: None,
+        };
+        peer.send_packet(&[TAG_HELLO])?;
+        Ok(peer)
+    }
+    // The key of the avator the peer drives; None until the welcome arrives.
+    pub fn avator(&self) -> Option<i32> {
+        self.avator
+    }
+    pub(crate) fn send(&self, command: &AvatorCommand) -> Result<(), NetError> {
+        self.send_packet(&encode(command))
+    }
+    fn send_packet(&self, packet: &[u8]) -> Result<(), NetError> {
+        if let Some(remote) = self.remote {
+            self.socket.send_to(packet, remote)?;
+        }
+        Ok(())
+    }
+    // Drains every packet received since the last call, answering hellos on the way.
+    // Commands that come before the welcome have no avator to move and are dropped.
+    pub(crate) fn poll(&mut self) -> Result<Vec<AvatorCommand>, NetError> {
+        let mut commands = Vec::new();
+        let mut buf = [0u8; MAX_PACKET];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) => {
+                    self.remote = Some(from);
+                    match decode(&buf[..len]) {
+                        Ok(Packet::Hello) => match self.avator {
+                            Some(avator) => self.send_packet(&welcome(avator))?,
+                            None => warn!("hello from {}, which is listening too", from),
+                        },
+                        Ok(Packet::Welcome(avator)) => {
+                            info!("joined {}, driving avator {}", from, avator);
+                            self.avator = Some(avator);
+                        },
+                        Ok(Packet::Command(_)) if self.avator.is_none() => warn!("dropped a command from {} before the welcome", from),
+                        Ok(Packet::Command(command)) => commands.push(command),
+                        Err(e) => warn!("dropped packet from {}: {:?}", from, e),
+                    }
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(NetError::from(e)),
+            }
+        }
+        Ok(commands)
+    }
+}
+
+fn encode(command: &AvatorCommand) -> Vec<u8> {
+    match *command {
+        AvatorCommand::Move(v) => {
+            let mut packet = vec!(TAG_MOVE);
+            for f in &[v.x, v.y, v.z] {
+                let bits = f.to_bits();
+                for i in 0..4 {
+                    packet.push((bits >> (i * 8)) as u8);
+                }
+            }
+            packet
+        },
+    }
+}
+
+fn welcome(avator: i32) -> Vec<u8> {
+    let mut packet = vec!(TAG_WELCOME);
+    for i in 0..4 {
+        packet.push(((avator as u32) >> (i * 8)) as u8);
+    }
+    packet
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut bits = 0u32;
+    for i in 0..4 {
+        bits |= (bytes[i] as u32) << (i * 8);
+    }
+    bits
+}
+
+fn read_f32(bytes: &[u8]) -> f32 {
+    f32::from_bits(read_u32(bytes))
+}
+
+fn decode(packet: &[u8]) -> Result<Packet, NetError> {
+    match packet.first() {
+        Some(&TAG_HELLO) => Ok(Packet::Hello),
+        Some(&TAG_MOVE) => {
+            if packet.len() < 1 + 3 * std::mem::size_of::<f32>() {
+                return Err(NetError::Truncated);
+            }
+            let v = Vector3::new(read_f32(&packet[1..]), read_f32(&packet[5..]), read_f32(&packet[9..]));
+            Ok(Packet::Command(AvatorCommand::Move(v)))
+        },
+        Some(&TAG_WELCOME) => {
+            if packet.len() < 1 + std::mem::size_of::<i32>() {
+                return Err(NetError::Truncated);
+            }
+            Ok(Packet::Welcome(read_u32(&packet[1..]) as i32))
+        },
+        Some(&tag) => Err(NetError::UnknownTag(tag)),
+        None => Err(NetError::Truncated),
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use text_effect::{EffectText, TextEffect};
+use tween::TweenHandle;
+
+// seconds a toast takes to appear and to go
+const TOAST_FADE: f32 = 0.3;
+// older toasts beyond this are dropped early
+const MAX_TOASTS: usize = 5;
+
+// Queues toasts from wherever there is no World at hand: command targets, other threads.
+// What is sent shows up the next frame; see Notifications::receive.
+#[derive(Clone)]
+pub struct Notifier(Sender<(String, f32)>);
+
+impl Notifier {
+    pub fn notify<S: Into<String>>(&self, text: S, seconds: f32) {
+        // nobody is showing toasts once the World is gone
+        let _ = self.0.send((text.into(), seconds));
+    }
+}
+
+pub struct Toast {
+    pub text: EffectText,
+    pub seconds: f32,
+    // runs for the toast's whole life; the World drops the toast when it finishes
+    pub handle: TweenHandle,
+}
+
+impl Toast {
+    // Opacity from the lifetime tween's progress: fades in, holds, then fades out.
+    pub fn alpha(&self, progress: Option<f32>) -> f32 {
+        let t = progress.unwrap_or(1.0) * self.seconds;
+        let fade = TOAST_FADE.min(self.seconds / 2.0).max(0.001);
+        (t / fade).min((self.seconds - t) / fade).max(0.0).min(1.0)
+    }
+}
+
+// Toasts in the order they came, oldest first.
+pub struct Notifications {
+    pub toasts: VecDeque<Toast>,
+    sender: Sender<(String, f32)>,
+    received: Receiver<(String, f32)>,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        let (sender, received) = channel();
+        Notifications {
+            toasts: VecDeque::new(),
+            sender,
+            received,
+        }
+    }
+    pub fn notifier(&self) -> Notifier {
+        Notifier(self.sender.clone())
+    }
+    // start makes the lifetime tween. Returns the handles of toasts pushed out to make room.
+    pub fn push<F: FnOnce(f32) -> TweenHandle>(&mut self, text: &str, seconds: f32, start: F) -> Vec<TweenHandle> {
+        let seconds = seconds.max(0.0);
+        self.toasts.push_back(Toast {
+            text: EffectText::new(text, vec!(TextEffect::Typewriter { chars_per_second: 60.0 })),
+            seconds,
+            handle: start(seconds),
+        });
+        let mut dropped = Vec::new();
+        while self.toasts.len() > MAX_TOASTS {
+            if let Some(toast) = self.toasts.pop_front() {
+                dropped.push(toast.handle);
+            }
+        }
+        dropped
+    }
+    // What Notifiers sent since the last call.
+    pub fn receive(&self) -> Vec<(String, f32)> {
+        self.received.try_iter().collect()
+    }
+    pub fn expire(&mut self, handle: TweenHandle) {
+        self.toasts.retain(|toast| toast.handle != handle);
+    }
+    pub fn tick(&mut self, dt: f32) {
+        for toast in &mut self.toasts {
+            toast.text.tick(dt);
+        }
+    }
+}
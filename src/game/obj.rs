@@ -0,0 +1,291 @@
+use std;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use fnv::FnvHashMap as HashMap;
+use rusqlite::Connection;
+use rusqlite::Error as RusqliteError;
+use image;
+use cgmath::Vector3;
+
+use axes::{Axes, WORLD};
+use models::insert_mesh_indices;
+use deduplicate_vertices;
+
+#[derive(Debug)]
+pub enum ImportError {
+    IoError(std::io::Error),
+    RusqliteError(RusqliteError),
+    ImageError(image::ImageError),
+    Parse { line: usize, message: String },
+}
+
+impl From<std::io::Error> for ImportError {
+    fn from(e: std::io::Error) -> ImportError { ImportError::IoError(e) }
+}
+impl From<RusqliteError> for ImportError {
+    fn from(e: RusqliteError) -> ImportError { ImportError::RusqliteError(e) }
+}
+impl From<image::ImageError> for ImportError {
+    fn from(e: image::ImageError) -> ImportError { ImportError::ImageError(e) }
+}
+
+pub type ImportResult<T> = Result<T, ImportError>;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ObjVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+pub struct ObjMesh {
+    pub material: Option<String>,
+    // expanded triangle list
+    pub vertices: Vec<ObjVertex>,
+}
+
+pub struct Material {
+    pub diffuse: [f32; 3],
+    pub diffuse_map: Option<PathBuf>,
+}
+
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: HashMap<String, Material>,
+}
+
+// axes is the convention the file was authored in; positions and normals come out in the World's.
+pub fn load_obj(path: &Path, axes: Axes) -> ImportResult<ObjModel> {
+    let file = std::fs::File::open(path)?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    let to_world = axes.to_world();
+    let convert = |v: [f32; 3]| -> [f32; 3] { (to_world * Vector3::from(v)).into() };
+    let flip = axes.flips_winding(WORLD);
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut materials = HashMap::default();
+    let mut meshes = vec!(ObjMesh { material: None, vertices: Vec::new() });
+
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let line_no = i + 1;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => positions.push(convert(parse_floats3(words, line_no)?)),
+            Some("vn") => normals.push(convert(parse_floats3(words, line_no)?)),
+            Some("vt") => {
+                let uv: Vec<f32> = parse_floats(words, line_no)?;
+                if uv.len() < 2 {
+                    return Err(parse_error(line_no, "vt needs two components"));
+                }
+                uvs.push([uv[0], uv[1]]);
+            },
+            Some("f") => {
+                let corners = words
+                    .map(|w| parse_corner(w, &positions, &normals, &uvs, line_no))
+                    .collect::<ImportResult<Vec<ObjVertex>>>()?;
+                if corners.len() < 3 {
+                    return Err(parse_error(line_no, "face needs at least three vertices"));
+                }
+                let mesh = meshes.last_mut().unwrap();
+                // triangle fan, turned around when the conversion mirrors
+                for k in 1..corners.len() - 1 {
+                    let (b, c) = if flip { (corners[k + 1], corners[k]) } else { (corners[k], corners[k + 1]) };
+                    mesh.vertices.push(corners[0]);
+                    mesh.vertices.push(b);
+                    mesh.vertices.push(c);
+                }
+            },
+            Some("usemtl") => {
+                let name = words.next().map(|w| w.to_string());
+                if meshes.last().map(|m| m.vertices.is_empty()).unwrap_or(false) {
+                    meshes.last_mut().unwrap().material = name;
+                } else {
+                    meshes.push(ObjMesh { material: name, vertices: Vec::new() });
+                }
+            },
+            Some("mtllib") => {
+                if let Some(name) = words.next() {
+                    load_mtl(&base.join(name), &mut materials)?;
+                }
+            },
+            _ => { }
+        }
+    }
+    meshes.retain(|m| !m.vertices.is_empty());
+
+    Ok(ObjModel {
+        meshes,
+        materials,
+    })
+}
+
+fn load_mtl(path: &Path, materials: &mut HashMap<String, Material>) -> ImportResult<()> {
+    let file = std::fs::File::open(path)?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    let mut current: Option<String> = None;
+
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("newmtl") => {
+                let name = words.next().unwrap_or("").to_string();
+                materials.insert(name.clone(), Material { diffuse: [1.0; 3], diffuse_map: None });
+                current = Some(name);
+            },
+            Some("Kd") => {
+                let diffuse = parse_floats3(words, i + 1)?;
+                if let Some(m) = current.as_ref().and_then(|name| materials.get_mut(name)) {
+                    m.diffuse = diffuse;
+                }
+            },
+            Some("map_Kd") => {
+                // options such as -s are not supported; the file name comes last
+                let file_name = words.last().map(|w| base.join(w));
+                if let Some(m) = current.as_ref().and_then(|name| materials.get_mut(name)) {
+                    m.diffuse_map = file_name;
+                }
+            },
+            _ => { }
+        }
+    }
+    Ok(())
+}
+
+fn parse_error(line: usize, message: &str) -> ImportError {
+    ImportError::Parse { line, message: message.to_string() }
+}
+
+fn parse_floats<'a, I: Iterator<Item = &'a str>>(words: I, line: usize) -> ImportResult<Vec<f32>> {
+    words.map(|w| w.parse::<f32>().map_err(|_| parse_error(line, "invalid number"))).collect()
+}
+
+fn parse_floats3<'a, I: Iterator<Item = &'a str>>(words: I, line: usize) -> ImportResult<[f32; 3]> {
+    let v = parse_floats(words, line)?;
+    if v.len() < 3 {
+        return Err(parse_error(line, "expected three components"));
+    }
+    Ok([v[0], v[1], v[2]])
+}
+
+// OBJ indices are 1-based, negative values count back from the end.
+fn resolve_index(word: &str, len: usize, line: usize) -> ImportResult<usize> {
+    let i = word.parse::<i64>().map_err(|_| parse_error(line, "invalid index"))?;
+    let index = if i < 0 { len as i64 + i } else { i - 1 };
+    if index < 0 || index as usize >= len {
+        return Err(parse_error(line, "index out of range"));
+    }
+    Ok(index as usize)
+}
+
+fn parse_corner(word: &str, positions: &[[f32; 3]], normals: &[[f32; 3]], uvs: &[[f32; 2]], line: usize) -> ImportResult<ObjVertex> {
+    let mut parts = word.split('/');
+    let position = match parts.next() {
+        Some(p) => positions[resolve_index(p, positions.len(), line)?],
+        None => return Err(parse_error(line, "missing position index")),
+    };
+    let uv = match parts.next() {
+        Some(t) if !t.is_empty() => uvs[resolve_index(t, uvs.len(), line)?],
+        _ => [0.0, 0.0],
+    };
+    let normal = match parts.next() {
+        Some(n) if !n.is_empty() => normals[resolve_index(n, normals.len(), line)?],
+        _ => [0.0, 0.0, 1.0],
+    };
+    Ok(ObjVertex {
+        position,
+        normal,
+        uv,
+    })
+}
+
+fn vertex_key(v: &ObjVertex) -> [u32; 8] {
+    [ v.position[0].to_bits(), v.position[1].to_bits(), v.position[2].to_bits(),
+      v.normal[0].to_bits(), v.normal[1].to_bits(), v.normal[2].to_bits(),
+      v.uv[0].to_bits(), v.uv[1].to_bits() ]
+}
+
+fn load_texture(material: Option<&Material>) -> ImportResult<(u32, u32, Vec<u8>)> {
+    match material {
+        Some(&Material { diffuse_map: Some(ref path), .. }) => {
+            let img = image::open(path)?.to_rgba();
+            let (width, height) = img.dimensions();
+            Ok((width, height, img.into_raw()))
+        },
+        Some(m) => {
+            let c = |f: f32| (f.max(0.0).min(1.0) * 255.0) as u8;
+            Ok((1, 1, vec!(c(m.diffuse[0]), c(m.diffuse[1]), c(m.diffuse[2]), 255)))
+        },
+        None => Ok((1, 1, vec!(255, 255, 255, 255))),
+    }
+}
+
+// Replaces any existing rows of the object.
+pub fn insert_obj(conn: &mut Connection, model: &ObjModel, object_id: i32) -> ImportResult<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM MeshIndex WHERE ObjectId = ?1", &[&object_id])?;
+    tx.execute("DELETE FROM MeshVertex WHERE ObjectId = ?1", &[&object_id])?;
+    // the textures of an earlier import, unless another object's meshes use them too
+    tx.execute("
+DELETE FROM Texture
+WHERE TextureId IN (SELECT TextureId FROM Mesh WHERE ObjectId = ?1)
+  AND TextureId NOT IN (SELECT TextureId FROM Mesh WHERE ObjectId <> ?1)
+", &[&object_id])?;
+    tx.execute("DELETE FROM Mesh WHERE ObjectId = ?1", &[&object_id])?;
+    tx.execute("INSERT OR REPLACE INTO Object (ObjectId) VALUES (?1)", &[&object_id])?;
+
+    let mut texture_ids: HashMap<Option<String>, i32> = HashMap::default();
+    for (i, mesh) in model.meshes.iter().enumerate() {
+        let mesh_id = i as i32 + 1;
+        let texture_id = match texture_ids.get(&mesh.material).cloned() {
+            Some(id) => id,
+            None => {
+                let (width, height, data) = load_texture(mesh.material.as_ref().and_then(|name| model.materials.get(name)))?;
+                let id = tx.query_row("SELECT IFNULL(MAX(TextureId), 0) + 1 FROM Texture", &[], |r| r.get::<i32, i32>(0))?;
+                tx.execute("
+INSERT INTO Texture (TextureId, Width, Height, Data)
+VALUES (?1, ?2, ?3, ?4)
+", &[&id, &(width as i32), &(height as i32), &data])?;
+                texture_ids.insert(mesh.material.clone(), id);
+                id
+            }
+        };
+        tx.execute("
+INSERT INTO Mesh (ObjectId, MeshId, TextureId)
+VALUES (?1, ?2, ?3)
+", &[&object_id, &mesh_id, &texture_id])?;
+
+        let (vertices, indices) = deduplicate_vertices(&mesh.vertices, vertex_key);
+        insert_mesh_indices(&tx, object_id, mesh_id, &indices)?;
+
+        let mut stmt = tx.prepare("
+INSERT INTO MeshVertex (
+    ObjectId, MeshId, IndexNo,
+    PositionX, PositionY, PositionZ,
+    NormalX, NormalY, NormalZ,
+    U, V,
+    Joint1, Joint2, Joint3, Joint4,
+    JointWeight1, JointWeight2, JointWeight3, JointWeight4
+) VALUES (
+    ?1, ?2, ?3,
+    ?4, ?5, ?6,
+    ?7, ?8, ?9,
+    ?10, ?11,
+    0, 0, 0, 0,
+    1.0, 0.0, 0.0, 0.0
+)")?;
+        for (index_no, v) in vertices.iter().enumerate() {
+            stmt.execute(&[
+                &object_id, &mesh_id, &(index_no as i32),
+                &(v.position[0] as f64), &(v.position[1] as f64), &(v.position[2] as f64),
+                &(v.normal[0] as f64), &(v.normal[1] as f64), &(v.normal[2] as f64),
+                &(v.uv[0] as f64), &(v.uv[1] as f64),
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
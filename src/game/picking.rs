@@ -0,0 +1,194 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+
+use debug_draw::Aabb;
+use {Skinning, Vertex};
+
+// below this a ray counts as parallel to a slab or triangle
+const EPSILON: f32 = 1e-7;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    // not normalized; t is a fraction of it
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    // The line through a screen point, from the near plane at t = 0 to the far one at t = 1.
+    // ndc is the point in normalized device coordinates of the whole screen, which the
+    // projection maps to, and depth the NDC depth range it maps the view volume into.
+    pub fn from_screen(projection: &Matrix4<f32>, ndc: [f32; 2], depth: [f32; 2]) -> Option<Ray> {
+        let inverse = projection.invert()?;
+        let unproject = |depth: f32| {
+            let p = inverse * Vector4::new(ndc[0], ndc[1], depth, 1.0);
+            if p.w.abs() < 1e-6 { None } else { Some(Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)) }
+        };
+        let (near, far) = (unproject(depth[0])?, unproject(depth[1])?);
+        Some(Ray { origin: near, direction: far - near })
+    }
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction * t
+    }
+    // The same ray in the space of an object at position and scale. t is unchanged,
+    // so hits on different objects still compare.
+    pub fn to_model(&self, position: Point3<f32>, scale: f32) -> Ray {
+        Ray {
+            origin: Point3::from_vec((self.origin - position) / scale),
+            direction: self.direction / scale,
+        }
+    }
+    // Where the ray enters the box, or 0 when it starts inside.
+    pub fn hit_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, ::std::f32::INFINITY);
+        for axis in 0..3 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            let (min, max) = (aabb.min[axis], aabb.max[axis]);
+            if direction.abs() < EPSILON {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let (t1, t2) = ((min - origin) / direction, (max - origin) / direction);
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+    // t and the barycentric weights of b and c (Moller and Trumbore); either side is hit.
+    pub fn hit_triangle(&self, a: Point3<f32>, b: Point3<f32>, c: Point3<f32>) -> Option<(f32, f32, f32)> {
+        let (e1, e2) = (b - a, c - a);
+        let p = self.direction.cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < EPSILON {
+            return None;
+        }
+        let s = self.origin - a;
+        let u = s.dot(p) / det;
+        if u < 0.0 || u > 1.0 {
+            return None;
+        }
+        let q = s.cross(e1);
+        let v = self.direction.dot(q) / det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = e2.dot(q) / det;
+        if t < 0.0 { None } else { Some((t, u, v)) }
+    }
+}
+
+// How picking tests objects. Bounds is cheap but takes the whole box around a character;
+// Mesh poses the pick mesh with the object's palette and tests its triangles.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PickMode {
+    Bounds,
+    Mesh,
+}
+
+impl PickMode {
+    pub fn from_name(name: &str) -> Option<PickMode> {
+        match name {
+            "bounds" => Some(PickMode::Bounds),
+            "mesh" => Some(PickMode::Mesh),
+            _ => None,
+        }
+    }
+}
+
+// What a pick found: the object's key, the joint hit when picking by mesh,
+// and the point on the object in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PickHit {
+    pub key: i32,
+    pub joint_index: Option<i32>,
+    pub point: Point3<f32>,
+}
+
+struct PickVertex {
+    position: Vector4<f32>,
+    joints: [i32; 4],
+    weights: [f32; 4],
+}
+
+// The triangles a mesh pick tests, skinned on the CPU at every pick, so they
+// should come from a low-res collision mesh rather than what is drawn.
+pub struct PickMesh {
+    vertices: Vec<PickVertex>,
+    indices: Vec<u32>,
+}
+
+// t along the ray, and the palette slot weighing most where it hit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeshHit {
+    pub t: f32,
+    pub slot: usize,
+}
+
+impl PickMesh {
+    pub fn new<'a, I>(meshes: I) -> Self
+        where I: Iterator<Item = (&'a [Vertex], &'a [u32])>
+    {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (mesh_vertices, mesh_indices) in meshes {
+            let base = vertices.len() as u32;
+            vertices.extend(mesh_vertices.iter().map(|v| PickVertex {
+                position: Vector4::new(v.position[0], v.position[1], v.position[2], 1.0),
+                joints: v.joint_indices,
+                weights: v.joint_weights,
+            }));
+            indices.extend(mesh_indices.iter().map(|i| base + i));
+        }
+        PickMesh { vertices, indices }
+    }
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+    // The nearest hit on the mesh posed as the vertex shader poses it with palette.
+    pub fn cast(&self, ray: &Ray, palette: &[Skinning]) -> Option<MeshHit> {
+        let matrices: Vec<Matrix4<f32>> = palette.iter().map(|s| Matrix4::from(s.transform)).collect();
+        let posed: Vec<Point3<f32>> = self.vertices.iter().map(|v| skin(v, &matrices)).collect();
+        let mut nearest: Option<MeshHit> = None;
+        for triangle in self.indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+            let corners = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+            let (a, b, c) = match (posed.get(corners[0]), posed.get(corners[1]), posed.get(corners[2])) {
+                (Some(&a), Some(&b), Some(&c)) => (a, b, c),
+                _ => continue,
+            };
+            if let Some((t, u, v)) = ray.hit_triangle(a, b, c) {
+                if nearest.map_or(true, |hit| t < hit.t) {
+                    nearest = Some(MeshHit { t, slot: self.heaviest_slot(corners, [1.0 - u - v, u, v]) });
+                }
+            }
+        }
+        nearest
+    }
+    // The palette slot with the most weight at a point blended from three vertices.
+    fn heaviest_slot(&self, corners: [usize; 3], blend: [f32; 3]) -> usize {
+        let mut weights: Vec<(i32, f32)> = Vec::new();
+        for (&corner, &b) in corners.iter().zip(blend.iter()) {
+            let v = &self.vertices[corner];
+            for (&joint, &weight) in v.joints.iter().zip(v.weights.iter()) {
+                match weights.iter_mut().find(|w| w.0 == joint) {
+                    Some(w) => w.1 += weight * b,
+                    None => weights.push((joint, weight * b)),
+                }
+            }
+        }
+        weights.into_iter().fold((0, ::std::f32::MIN), |best, w| if w.1 > best.1 { w } else { best }).0.max(0) as usize
+    }
+}
+
+fn skin(v: &PickVertex, palette: &[Matrix4<f32>]) -> Point3<f32> {
+    let mut p = Vector4::new(0.0, 0.0, 0.0, 0.0);
+    for (&joint, &weight) in v.joints.iter().zip(v.weights.iter()) {
+        if let Some(m) = palette.get(joint as usize) {
+            p += (*m * v.position) * weight;
+        }
+    }
+    Point3::new(p.x, p.y, p.z)
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+use glutin;
+use gfx;
+use gfx_device_gl;
+use gfx_window_glutin;
+#[cfg(feature = "vulkan")]
+use gfx_device_vulkan;
+#[cfg(feature = "vulkan")]
+use gfx_window_vulkan;
+
+use input::*;
+
+// Everything App needs from a backend beyond the gfx traits:
+// creating the native window and the few window calls gfx does not cover.
+pub trait Platform {
+    type Backend: gfx::Backend;
+    type Window: gfx::WindowExt<Self::Backend>;
+
+    fn create_window(wb: glutin::WindowBuilder, vsync: bool, msaa: u16, events_loop: &glutin::EventsLoop) -> Self::Window;
+    fn set_cursor_state(window: &Self::Window, state: glutin::CursorState) -> Result<(), String>;
+    fn set_cursor_position(window: &Self::Window, x: i32, y: i32) -> Result<(), ()>;
+    // Lets the context follow the window before the swapchain is built again.
+    fn resize(window: &Self::Window, width: u32, height: u32);
+}
+
+pub struct GlPlatform;
+
+impl Platform for GlPlatform {
+    type Backend = gfx_device_gl::Backend;
+    type Window = gfx_window_glutin::Window;
+
+    fn create_window(wb: glutin::WindowBuilder, vsync: bool, msaa: u16, events_loop: &glutin::EventsLoop) -> Self::Window {
+        let gl_builder = glutin::ContextBuilder::new().with_vsync(vsync);
+        let gl_builder = if msaa > 1 { gl_builder.with_multisampling(msaa) } else { gl_builder };
+        let window = glutin::GlWindow::new(wb, gl_builder, events_loop).expect("failed to create window");
+        gfx_window_glutin::Window::new(window)
+    }
+    fn set_cursor_state(window: &Self::Window, state: glutin::CursorState) -> Result<(), String> {
+        window.raw().set_cursor_state(state)
+    }
+    fn set_cursor_position(window: &Self::Window, x: i32, y: i32) -> Result<(), ()> {
+        window.raw().set_cursor_position(x, y)
+    }
+    fn resize(window: &Self::Window, width: u32, height: u32) {
+        window.raw().resize(width, height)
+    }
+}
+
+// The world pipelines are still built from GLSL sources, so this backend needs
+// SPIR-V shader variants and swapchain rebuilding on resize before it can draw.
+// Until then it only lists adapters, and create_app turns it down.
+#[cfg(feature = "vulkan")]
+pub struct VulkanPlatform;
+
+#[cfg(feature = "vulkan")]
+impl Platform for VulkanPlatform {
+    type Backend = gfx_device_vulkan::Backend;
+    type Window = gfx_window_vulkan::Window;
+
+    // vsync and multisampling are chosen through the swapchain on vulkan
+    fn create_window(wb: glutin::WindowBuilder, _: bool, _: u16, events_loop: &glutin::EventsLoop) -> Self::Window {
+        let window = wb.build(events_loop).expect("failed to create window");
+        gfx_window_vulkan::Window(window)
+    }
+    fn set_cursor_state(window: &Self::Window, state: glutin::CursorState) -> Result<(), String> {
+        window.0.set_cursor_state(state)
+    }
+    fn set_cursor_position(window: &Self::Window, x: i32, y: i32) -> Result<(), ()> {
+        window.0.set_cursor_position(x, y)
+    }
+    // the surface takes the window's size when the swapchain is built
+    fn resize(_: &Self::Window, _: u32, _: u32) {}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BackendKind {
+    Gl,
+    #[cfg(feature = "vulkan")]
+    Vulkan,
+}
+
+#[derive(Debug)]
+pub enum BackendError {
+    Unknown(String),
+    // built in, but not able to run the World yet
+    Unsupported(BackendKind),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BackendError::Unknown(ref name) => write!(f, "unknown backend {}", name),
+            BackendError::Unsupported(kind) => write!(f, "the {:?} backend has no SPIR-V shaders yet", kind),
+        }
+    }
+}
+
+impl BackendKind {
+    // Whether create_app can open the World on it, rather than only list adapters.
+    pub fn check_drawable(self) -> Result<BackendKind, BackendError> {
+        match self {
+            BackendKind::Gl => Ok(self),
+            #[cfg(feature = "vulkan")]
+            BackendKind::Vulkan => Err(BackendError::Unsupported(self)),
+        }
+    }
+    pub fn from_name(name: &str) -> Result<BackendKind, BackendError> {
+        match name {
+            "gl" => Ok(BackendKind::Gl),
+            #[cfg(feature = "vulkan")]
+            "vulkan" => Ok(BackendKind::Vulkan),
+            _ => Err(BackendError::Unknown(name.to_string())),
+        }
+    }
+}
+
+impl Default for BackendKind {
+    fn default() -> Self {
+        BackendKind::Gl
+    }
+}
+
+// touchpads report pixels; this many make one wheel notch
+const PIXELS_PER_LINE: f32 = 20.0;
+
+pub fn translate_event(ev: &glutin::WindowEvent) -> Option<InputEvent> {
+    match *ev {
+        glutin::WindowEvent::KeyboardInput {
+            input: glutin::KeyboardInput {
+                state,
+                virtual_keycode: Some(key),
+                modifiers,
+                ..
+            }, ..
+        } => translate_key(key).map(|key| InputEvent::Key {
+            key,
+            state: translate_state(state),
+            modifiers: Modifiers {
+                shift: modifiers.shift,
+                ctrl: modifiers.ctrl,
+                alt: modifiers.alt,
+                logo: modifiers.logo,
+            },
+        }),
+        // This glutin reports no composition, only the characters an input method commits;
+        // Preedit and Commit come from whoever injects events.
+        glutin::WindowEvent::ReceivedCharacter(ch) => Some(InputEvent::Character(ch)),
+        glutin::WindowEvent::MouseInput { state, button, .. } => Some(InputEvent::MouseButton {
+            button: match button {
+                glutin::MouseButton::Left => MouseButton::Left,
+                glutin::MouseButton::Right => MouseButton::Right,
+                glutin::MouseButton::Middle => MouseButton::Middle,
+                glutin::MouseButton::Other(n) => MouseButton::Other(n),
+            },
+            state: translate_state(state),
+        }),
+        glutin::WindowEvent::MouseMoved { position, .. } => Some(InputEvent::CursorMoved {
+            x: position.0,
+            y: position.1,
+        }),
+        glutin::WindowEvent::AxisMotion { axis, value, .. } => Some(InputEvent::Axis {
+            axis,
+            value,
+        }),
+        glutin::WindowEvent::MouseWheel { delta, .. } => Some(InputEvent::Wheel {
+            delta: match delta {
+                glutin::MouseScrollDelta::LineDelta(_, y) => y,
+                glutin::MouseScrollDelta::PixelDelta(_, y) => y / PIXELS_PER_LINE,
+            },
+        }),
+        _ => None,
+    }
+}
+
+fn translate_state(state: glutin::ElementState) -> ButtonState {
+    match state {
+        glutin::ElementState::Pressed => ButtonState::Pressed,
+        glutin::ElementState::Released => ButtonState::Released,
+    }
+}
+
+fn translate_key(key: glutin::VirtualKeyCode) -> Option<Key> {
+    use glutin::VirtualKeyCode as V;
+    Some(match key {
+        V::A => Key::A, V::B => Key::B, V::C => Key::C, V::D => Key::D,
+        V::E => Key::E, V::F => Key::F, V::G => Key::G, V::H => Key::H,
+        V::I => Key::I, V::J => Key::J, V::K => Key::K, V::L => Key::L,
+        V::M => Key::M, V::N => Key::N, V::O => Key::O, V::P => Key::P,
+        V::Q => Key::Q, V::R => Key::R, V::S => Key::S, V::T => Key::T,
+        V::U => Key::U, V::V => Key::V, V::W => Key::W, V::X => Key::X,
+        V::Y => Key::Y, V::Z => Key::Z,
+        V::Key0 => Key::Key0, V::Key1 => Key::Key1, V::Key2 => Key::Key2,
+        V::Key3 => Key::Key3, V::Key4 => Key::Key4, V::Key5 => Key::Key5,
+        V::Key6 => Key::Key6, V::Key7 => Key::Key7, V::Key8 => Key::Key8,
+        V::Key9 => Key::Key9,
+        V::F1 => Key::F1, V::F2 => Key::F2, V::F3 => Key::F3, V::F4 => Key::F4,
+        V::F5 => Key::F5, V::F6 => Key::F6, V::F7 => Key::F7, V::F8 => Key::F8,
+        V::F9 => Key::F9, V::F10 => Key::F10, V::F11 => Key::F11, V::F12 => Key::F12,
+        V::Up => Key::Up, V::Down => Key::Down, V::Left => Key::Left, V::Right => Key::Right,
+        V::Home => Key::Home, V::End => Key::End, V::PageUp => Key::PageUp, V::PageDown => Key::PageDown,
+        V::Back => Key::Back, V::Delete => Key::Delete, V::Insert => Key::Insert,
+        V::Return => Key::Return, V::Escape => Key::Escape, V::Tab => Key::Tab,
+        V::Space => Key::Space, V::Grave => Key::Grave,
+        V::Comma => Key::Comma, V::Period => Key::Period,
+        V::LBracket => Key::LBracket, V::RBracket => Key::RBracket,
+        V::LShift => Key::LShift, V::RShift => Key::RShift,
+        V::LControl => Key::LControl, V::RControl => Key::RControl,
+        V::LAlt => Key::LAlt, V::RAlt => Key::RAlt,
+        _ => return None,
+    })
+}
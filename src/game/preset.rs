@@ -0,0 +1,17 @@
+use cgmath::Point3;
+
+use fog::Fog;
+
+// A reproducible starting point for scene reviews: the main camera
+// and what lights the scene, stored by name in the Preset table.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Preset {
+    pub position: Point3<f32>,
+    pub target: Point3<f32>,
+    // vertical field of view in radians
+    pub fovy: f32,
+    pub hour: f32,
+    // whether the day/night cycle stays at hour once loaded
+    pub time_frozen: bool,
+    pub fog: Fog,
+}
@@ -0,0 +1,64 @@
+use tween::TweenHandle;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransitionKind {
+    // swap with nothing in between
+    Cut,
+    // darken, swap while the screen is black, then lighten
+    FadeToBlack,
+    // the last frame of the outgoing scene fades out over the new one
+    CrossDissolve,
+}
+
+impl TransitionKind {
+    pub fn from_name(name: &str) -> Option<TransitionKind> {
+        match name {
+            "cut" => Some(TransitionKind::Cut),
+            "fade" => Some(TransitionKind::FadeToBlack),
+            "dissolve" => Some(TransitionKind::CrossDissolve),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransitionStage {
+    // the next frame renders the outgoing scene offscreen, then swaps
+    Capture,
+    // covering the outgoing scene
+    Out(TweenHandle),
+    // covered; the scene is swapped before the next frame draws
+    Swap,
+    // uncovering the new scene
+    In(TweenHandle),
+}
+
+impl TransitionStage {
+    pub fn handle(&self) -> Option<TweenHandle> {
+        match *self {
+            TransitionStage::Out(handle) | TransitionStage::In(handle) => Some(handle),
+            TransitionStage::Capture | TransitionStage::Swap => None,
+        }
+    }
+}
+
+// A scene change in progress, drawn by the Transition pass.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SceneTransition {
+    pub kind: TransitionKind,
+    pub scene_id: i32,
+    pub stage: TransitionStage,
+}
+
+impl SceneTransition {
+    // How opaque the cover (black, or the captured frame) is, given the stage tween's value.
+    // A finished tween has no value and counts as done.
+    pub fn cover(&self, value: Option<f32>) -> f32 {
+        match self.stage {
+            TransitionStage::Capture => 0.0,
+            TransitionStage::Out(_) => value.unwrap_or(1.0),
+            TransitionStage::Swap => 1.0,
+            TransitionStage::In(_) => 1.0 - value.unwrap_or(1.0),
+        }
+    }
+}
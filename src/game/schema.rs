@@ -0,0 +1,99 @@
+use rusqlite::Connection;
+use rusqlite::Result as RusqliteResult;
+
+use models::{column_exists, create_animation_event_table, create_game_tables, create_scene_table, table_exists};
+use AppError;
+
+// The version this build reads and writes. A database at a later one was upgraded
+// by a newer build, and its rows may mean what this one cannot tell.
+pub const SCHEMA_VERSION: i32 = 6;
+
+// Each step takes a database from the version before it to its own. Databases from
+// before SchemaVersion count as version 0 and may have some of a step done already
+// by hand or by the tools, so steps check before they add anything.
+const MIGRATIONS: &'static [(i32, &'static str, fn(&Connection) -> RusqliteResult<()>)] = &[
+    (1, "tables written by the game", create_game_tables),
+    (2, "optional asset columns", add_asset_columns),
+    (3, "mesh draw layers", add_mesh_layer_column),
+    (4, "mesh roughness", add_mesh_roughness_column),
+    (5, "animation events", create_animation_event_table),
+    (6, "scenes", create_scene_table),
+];
+
+// Columns the asset tools may leave out, with the defaults the queries assume when they do.
+const ASSET_COLUMNS: &'static [(&'static str, &'static str, &'static str)] = &[
+    ("Mesh", "Unlit", "INTEGER NOT NULL DEFAULT 0"),
+    ("Mesh", "Collision", "INTEGER NOT NULL DEFAULT 0"),
+    ("MeshVertex", "ColorR", "REAL NOT NULL DEFAULT 1.0"),
+    ("MeshVertex", "ColorG", "REAL NOT NULL DEFAULT 1.0"),
+    ("MeshVertex", "ColorB", "REAL NOT NULL DEFAULT 1.0"),
+    ("MeshVertex", "ColorA", "REAL NOT NULL DEFAULT 1.0"),
+    // NULL is raw RGBA8
+    ("Texture", "Format", "TEXT"),
+    ("Joint", "Name", "TEXT"),
+    // NULL for clips whose SampleTime is only an ordering
+    ("Animation", "Duration", "REAL"),
+];
+
+fn add_asset_columns(conn: &Connection) -> RusqliteResult<()> {
+    add_columns(conn, ASSET_COLUMNS)
+}
+
+// Layer names; NULL is the world layer.
+fn add_mesh_layer_column(conn: &Connection) -> RusqliteResult<()> {
+    add_columns(conn, &[("Mesh", "Layer", "TEXT")])
+}
+
+// 0 a mirror to 1 matte; NULL is DEFAULT_ROUGHNESS.
+fn add_mesh_roughness_column(conn: &Connection) -> RusqliteResult<()> {
+    add_columns(conn, &[("Mesh", "Roughness", "REAL")])
+}
+
+fn add_columns(conn: &Connection, columns: &[(&str, &str, &str)]) -> RusqliteResult<()> {
+    for &(table, column, definition) in columns {
+        // a table the tools never made stays missing; the queries handle that already
+        if table_exists(conn, table)? && !column_exists(conn, table, column)? {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), &[])?;
+        }
+    }
+    Ok(())
+}
+
+fn create_schema_version_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute("
+CREATE TABLE IF NOT EXISTS SchemaVersion (
+    Version   INTEGER NOT NULL PRIMARY KEY,
+    AppliedAt TEXT    NOT NULL DEFAULT CURRENT_TIMESTAMP
+)", &[])?;
+    Ok(())
+}
+
+fn query_schema_version(conn: &Connection) -> RusqliteResult<i32> {
+    if !table_exists(conn, "SchemaVersion")? {
+        return Ok(0);
+    }
+    conn.query_row("SELECT IFNULL(MAX(Version), 0) FROM SchemaVersion", &[], |r| r.get::<i32, i32>(0))
+}
+
+// Brings the database up to SCHEMA_VERSION, each step in a transaction of its own
+// so a failed one leaves the database at the version before it. Returns the version
+// it was at.
+pub fn migrate(conn: &mut Connection) -> Result<i32, AppError> {
+    let found = query_schema_version(conn)?;
+    if found > SCHEMA_VERSION {
+        return Err(AppError::SchemaVersion { found, supported: SCHEMA_VERSION });
+    }
+    for &(version, name, step) in MIGRATIONS.iter().filter(|m| m.0 > found) {
+        let tx = conn.transaction()?;
+        // another process may have got there first
+        if query_schema_version(&tx)? >= version {
+            continue;
+        }
+        info!("upgrading the database to version {}: {}", version, name);
+        create_schema_version_table(&tx)?;
+        step(&tx)?;
+        tx.execute("INSERT INTO SchemaVersion (Version) VALUES (?1)", &[&version])?;
+        tx.commit()?;
+    }
+    Ok(found)
+}
@@ -0,0 +1,62 @@
+use color::Color;
+use tween::TweenHandle;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScreenEffectKind {
+    // starts at its color and clears, as when the player is hit
+    Flash,
+    // covers the screen, and keeps it covered once done until a FadeIn
+    FadeOut,
+    // uncovers what a FadeOut left
+    FadeIn,
+}
+
+// A color laid over the whole screen for as long as its tween runs.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScreenEffect {
+    pub kind: ScreenEffectKind,
+    pub color: Color,
+    pub handle: TweenHandle,
+}
+
+impl ScreenEffect {
+    // How opaque it is given the tween's value, at most the color's alpha.
+    // A finished tween has no value and counts as done.
+    pub fn alpha(&self, value: Option<f32>) -> f32 {
+        let t = value.unwrap_or(1.0);
+        let cover = match self.kind {
+            ScreenEffectKind::Flash | ScreenEffectKind::FadeIn => 1.0 - t,
+            ScreenEffectKind::FadeOut => t,
+        };
+        cover * self.color.a
+    }
+}
+
+// Drawn in the order added, so a flash started while faded out is hidden under the fade.
+pub struct ScreenEffects {
+    effects: Vec<ScreenEffect>,
+}
+
+impl ScreenEffects {
+    pub fn new() -> Self {
+        ScreenEffects {
+            effects: Vec::new(),
+        }
+    }
+    pub fn add(&mut self, effect: ScreenEffect) {
+        self.effects.push(effect);
+    }
+    // Drops the effect the tween drove, unless it is a fade out, which stays covering.
+    pub fn finish(&mut self, handle: TweenHandle) {
+        self.effects.retain(|effect| effect.handle != handle || effect.kind == ScreenEffectKind::FadeOut);
+    }
+    // Takes off the fade outs, returning the color of the last, for a fade in to start from.
+    pub fn lift(&mut self) -> Option<ScreenEffect> {
+        let last = self.effects.iter().rev().find(|effect| effect.kind == ScreenEffectKind::FadeOut).cloned();
+        self.effects.retain(|effect| effect.kind != ScreenEffectKind::FadeOut);
+        last
+    }
+    pub fn iter(&self) -> std::slice::Iter<ScreenEffect> {
+        self.effects.iter()
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+use command_history::CommandHistory;
+use graphics_settings::QualityPreset;
+use models::{insert_command_history, insert_save, insert_setting};
+use {try_open_connection, AUTOSAVE_NAME, QUALITY_SETTING};
+
+struct SessionState {
+    positions: Vec<(i32, [f32; 3])>,
+    quality: QualityPreset,
+}
+
+// What shutting down writes to the database, kept current every frame so the panic
+// hook can write it as well. A panic on the update thread, or one that aborts, never
+// gets as far as dropping the App, and the World is out of reach from the hook.
+// The command history lives here rather than in the World, and is only read when
+// the session is written.
+#[derive(Clone)]
+pub struct SessionSave {
+    state: Arc<Mutex<SessionState>>,
+    history: Arc<Mutex<CommandHistory>>,
+}
+
+impl SessionSave {
+    pub fn new(quality: QualityPreset, history: CommandHistory) -> Self {
+        SessionSave {
+            state: Arc::new(Mutex::new(SessionState {
+                positions: Vec::new(),
+                quality,
+            })),
+            history: Arc::new(Mutex::new(history)),
+        }
+    }
+    pub fn refresh(&self, positions: Vec<(i32, [f32; 3])>, quality: QualityPreset) {
+        let mut state = self.lock();
+        state.positions = positions;
+        state.quality = quality;
+    }
+    pub fn history(&self) -> MutexGuard<CommandHistory> {
+        self.history.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    pub fn write(&self) {
+        write_state(&self.lock(), &self.history());
+    }
+    // For the panic hook, which must not wait on a lock the panicking thread may hold.
+    pub fn write_from_panic(&self) {
+        let state = match self.state.try_lock() {
+            Ok(state) => state,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                error!("the session is being updated; not saved");
+                return;
+            },
+        };
+        let history = match self.history.try_lock() {
+            Ok(history) => history,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                error!("the command history is being recorded; not saved");
+                return;
+            },
+        };
+        write_state(&state, &history);
+    }
+    // A panic while refreshing leaves the last state whole, so a poisoned lock is still used.
+    fn lock(&self) -> MutexGuard<SessionState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// Errors are logged rather than returned, as this runs while shutting down or panicking.
+fn write_state(state: &SessionState, history: &CommandHistory) {
+    let mut conn = match try_open_connection() {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("failed to open the database to save the session: {:?}", e);
+            return;
+        },
+    };
+    if let Err(e) = insert_save(&conn, AUTOSAVE_NAME, &state.positions) {
+        error!("failed to write {}: {}", AUTOSAVE_NAME, e);
+    }
+    if let Err(e) = insert_setting(&conn, QUALITY_SETTING, state.quality.name()) {
+        error!("failed to store the quality setting: {}", e);
+    }
+    if let Err(e) = insert_command_history(&mut conn, history.iter()) {
+        error!("failed to write the command history: {}", e);
+    }
+}
@@ -0,0 +1,788 @@
+// What the game simulates, apart from how it is drawn: cameras, bodies and their
+// poses, and the commands that move them. Nothing in here touches gfx, so it all
+// runs without a device; the render layer reads positions and matrices off it.
+use std;
+use std::sync::Arc;
+use fnv::FnvHashMap as HashMap;
+use fnv::FnvHashSet as HashSet;
+use cgmath::{self, Matrix4, One, Point3, Rad, Vector3};
+
+use animation::{AdditiveLayer, AnimationClip};
+use camera_path::PathPlayback;
+use layer::{self, LayerMask};
+use locomotion::Locomotion;
+use models::Joint;
+use notifications::Notifier;
+use stats::Stats;
+use validation::ROOT_PARENT;
+
+#[derive(Clone)]
+pub enum AvatorCommand {
+    Move (Vector3<f32>),
+}
+
+// An AvatorCommand for the body with this key, as the update thread queues them.
+#[derive(Clone)]
+pub struct BodyCommand {
+    pub key: i32,
+    pub command: AvatorCommand,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum StatsCommand {
+    Damage (f32),
+    Heal (f32),
+}
+
+pub trait Command<T> {
+    fn get_level(&self) -> Level;
+    fn execute(&self, &mut T);
+}
+
+pub struct Invoker<Cmd, T> {
+    commands: Vec<Cmd>,
+    pub target: T,
+    current_index: usize,
+}
+
+impl<Cmd, T> Invoker<Cmd, T> {
+    pub fn new(t: T) -> Self {
+        Invoker {
+            commands: Vec::new(),
+            target: t,
+            current_index: 0,
+        }
+    }
+}
+
+impl<Cmd, T> Invoker<Cmd, T>
+    where Cmd: Command<T> {
+    fn execute_command(&mut self) {
+        if self.commands.len() <= self.current_index {
+            return;
+        }
+        let c = &self.commands[self.current_index];
+        let t = &mut self.target;
+
+        c.execute(t);
+
+        self.current_index += 1;
+    }
+    pub fn execute_all_commands(&mut self) {
+        for _ in self.current_index..self.commands.len() {
+            self.execute_command();
+        }
+        self.commands.clear();
+        self.current_index = 0;
+    }
+    pub fn append_command(&mut self, c: Cmd) {
+        self.commands.push(c);
+    }
+    // Runs the queued commands of one level in the order they came; the rest stay queued.
+    // Returns the ones it ran.
+    pub fn execute_level(&mut self, level: Level) -> Vec<Cmd> {
+        let (run, keep): (Vec<Cmd>, Vec<Cmd>) = self.take_commands().into_iter()
+            .partition(|c| c.get_level() == level);
+        for c in &run {
+            c.execute(&mut self.target);
+        }
+        self.commands = keep;
+        run
+    }
+    pub fn discard_level(&mut self, level: Level) {
+        self.commands.retain(|c| c.get_level() != level);
+    }
+}
+
+impl<Cmd, T> Invoker<Cmd, T> {
+    // Hands the queued commands to whoever executes them elsewhere.
+    pub fn take_commands(&mut self) -> Vec<Cmd> {
+        let commands = std::mem::replace(&mut self.commands, Vec::new());
+        self.current_index = 0;
+        commands
+    }
+}
+
+impl Command<Body> for AvatorCommand {
+    fn get_level(&self) -> Level {
+        Level::Avator
+    }
+    fn execute(&self, c: &mut Body) {
+        match *self {
+            AvatorCommand::Move(v) => {
+                c.translate(v); 
+            },
+        }
+    }
+}
+impl Command<HashMap<i32, Body>> for BodyCommand {
+    fn get_level(&self) -> Level {
+        Command::<Body>::get_level(&self.command)
+    }
+    fn execute(&self, c: &mut HashMap<i32, Body>) {
+        if let Some(body) = c.get_mut(&self.key) {
+            self.command.execute(body);
+        }
+    }
+}
+impl Command<Stats> for StatsCommand {
+    fn get_level(&self) -> Level {
+        Level::World
+    }
+    fn execute(&self, c: &mut Stats) {
+        match *self {
+            StatsCommand::Damage(amount) => c.damage(amount),
+            StatsCommand::Heal(amount) => c.heal(amount),
+        }
+    }
+}
+
+// Declared lowest first; higher levels run first each frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Avator,
+    World,
+    System,
+}
+
+impl Level {
+    pub fn by_priority() -> [Level; 3] {
+        [Level::System, Level::World, Level::Avator]
+    }
+}
+
+// Which levels may run. Commands of a blocked level are dropped rather than held,
+// so nothing queued during a pause fires all at once on resume.
+pub struct Dispatcher {
+    blocked: HashSet<Level>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Dispatcher {
+            blocked: HashSet::default(),
+        }
+    }
+    pub fn set_blocked(&mut self, level: Level, blocked: bool) {
+        if blocked {
+            self.blocked.insert(level);
+        } else {
+            self.blocked.remove(&level);
+        }
+    }
+    pub fn allows(&self, level: Level) -> bool {
+        !self.blocked.contains(&level)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProjectionMode {
+    Perspective,
+    // parallel lines stay parallel, as in an editor's top view
+    Orthographic,
+}
+
+#[derive(Clone)]
+pub struct Camera<T> {
+    pub position: Point3<T>,
+    pub target: Point3<T>,
+    pub up: Vector3<T>,
+    pub fov: cgmath::PerspectiveFov<T>,
+    pub mode: ProjectionMode,
+    // world units seen from the bottom to the top of the view when orthographic;
+    // fov's aspect, near and far still apply
+    pub ortho_height: T,
+    // camera path being played back, which overrides position and target
+    pub playback: Option<PathPlayback>,
+    pub view: Matrix4<T>,
+    // perspective or orthographic, as mode says
+    pub perspective: Matrix4<T>,
+    pub projection: Matrix4<T>,
+    // moved since the matrices were last built
+    pub dirty: bool,
+    // counts rebuilds, so derived cameras can tell they are stale
+    pub generation: u64,
+    // identifies a viewport camera's matrices to the objects' TransformCache
+    pub snapshot: u64,
+    // tells the player what a command could not do
+    pub notifier: Option<Notifier>,
+    // the layers drawn through it
+    pub layers: LayerMask,
+    // whether perspective negates NDC depth, for a depth buffer cleared to 0
+    pub reversed_z: bool,
+}
+
+// look_at needs an up off the line of sight. Looking straight along up keeps last,
+// the up of the view before, and failing that takes any axis across the line.
+fn view_up<T: cgmath::BaseFloat>(direction: Vector3<T>, up: Vector3<T>, last: Vector3<T>) -> Vector3<T> {
+    use cgmath::InnerSpace;
+    let across = |up: Vector3<T>| direction.cross(up).magnitude2() > T::epsilon() * direction.magnitude2() * up.magnitude2();
+    if across(up) {
+        return up;
+    }
+    if across(last) {
+        return last;
+    }
+    [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()].iter().cloned()
+        .find(|&axis| across(axis))
+        .unwrap_or(up)
+}
+
+
+impl<T: cgmath::BaseFloat> Camera<T> {
+    // up is the world's, as in axes::WORLD.
+    pub fn new(position: Point3<T>, target: Point3<T>, up: Vector3<T>, perspective: cgmath::PerspectiveFov<T>) -> Camera<T> {
+        let view = Matrix4::look_at(position, target, view_up(target - position, up, up));
+        let fov = perspective;
+        let perspective = Matrix4::from(perspective);
+
+        Camera {
+            position,
+            target,
+            up,
+            fov,
+            mode: ProjectionMode::Perspective,
+            ortho_height: One::one(),
+            playback: None,
+            view,
+            perspective,
+            projection: perspective * view,
+            dirty: false,
+            generation: 0,
+            snapshot: 0,
+            notifier: None,
+            layers: layer::WORLD.union(layer::DEBUG),
+            reversed_z: false,
+        }
+    }
+    pub fn with_reversed_z(mut self, reversed_z: bool) -> Self {
+        self.reversed_z = reversed_z;
+        self.perspective = self.reverse_depth(self.projection_matrix(self.fov.aspect));
+        self.projection = self.perspective * self.view;
+        self
+    }
+    // Flips NDC depth last, so a viewport's depth range is turned around with the rest.
+    fn reverse_depth(&self, m: Matrix4<T>) -> Matrix4<T> {
+        if self.reversed_z {
+            Matrix4::from_nonuniform_scale(T::one(), T::one(), -T::one()) * m
+        } else {
+            m
+        }
+    }
+    pub fn set_clip(&mut self, near: T, far: T) {
+        self.fov.near = near;
+        self.fov.far = far;
+        self.dirty = true;
+    }
+    pub fn look_at(&mut self, target: Point3<T>) {
+        self.target = target;
+        self.dirty = true;
+    }
+    pub fn set_fovy(&mut self, fovy: Rad<T>) {
+        self.fov.fovy = fovy;
+        self.dirty = true;
+    }
+    pub fn set_up(&mut self, up: Vector3<T>) {
+        use cgmath::InnerSpace;
+        if up.magnitude2() > T::zero() {
+            self.up = up.normalize();
+            self.dirty = true;
+        }
+    }
+    // Turns up around the line of sight; the picture turns the other way.
+    pub fn roll(&mut self, angle: Rad<T>) {
+        use cgmath::InnerSpace;
+        let direction = self.direction();
+        if direction.magnitude2() == T::zero() {
+            return;
+        }
+        let axis = direction.normalize();
+        let (sin, cos) = (angle.0.sin(), angle.0.cos());
+        // Rodrigues' rotation formula
+        let up = self.up * cos + axis.cross(self.up) * sin + axis * (axis.dot(self.up) * (T::one() - cos));
+        self.set_up(up);
+    }
+    // Going orthographic keeps what is at the target the same size on screen.
+    pub fn set_mode(&mut self, mode: ProjectionMode) {
+        use cgmath::InnerSpace;
+        if mode == ProjectionMode::Orthographic && self.mode != mode {
+            let half_fovy = self.fov.fovy.0 / (T::one() + T::one());
+            self.ortho_height = self.direction().magnitude() * half_fovy.tan() * (T::one() + T::one());
+        }
+        self.mode = mode;
+        self.dirty = true;
+    }
+    pub fn set_ortho_height(&mut self, height: T) {
+        if height > T::zero() {
+            self.ortho_height = height;
+            self.dirty = true;
+        }
+    }
+    fn projection_matrix(&self, aspect: T) -> Matrix4<T> {
+        match self.mode {
+            ProjectionMode::Perspective => Matrix4::from(cgmath::PerspectiveFov { aspect, ..self.fov }),
+            ProjectionMode::Orthographic => {
+                let half_height = self.ortho_height / (T::one() + T::one());
+                let half_width = half_height * aspect;
+                cgmath::ortho(-half_width, half_width, -half_height, half_height, self.fov.near, self.fov.far)
+            },
+        }
+    }
+    // The camera's own up on screen, which differs from self.up unless looking level.
+    fn screen_up(&self) -> Vector3<T> {
+        Vector3::new(self.view.x.y, self.view.y.y, self.view.z.y)
+    }
+    pub fn direction(& self) -> Vector3<T> {
+        self.target - self.position
+    }
+    // Called once a frame after every move has been applied; does nothing when none happened.
+    pub fn update(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let up = view_up(self.direction(), self.up, self.screen_up());
+        self.view = Matrix4::look_at(self.position, self.target, up);
+        self.perspective = self.reverse_depth(self.projection_matrix(self.fov.aspect));
+        self.projection = self.perspective * self.view;
+        self.generation += 1;
+        self.dirty = false;
+    }
+    // A copy projecting into the given viewport; see Viewport::matrix.
+    pub fn with_viewport(&self, aspect: T, viewport: Matrix4<T>) -> Camera<T> {
+        let mut camera = self.clone();
+        camera.fov.aspect = aspect;
+        camera.perspective = camera.reverse_depth(viewport * camera.projection_matrix(aspect));
+        camera.projection = camera.perspective * camera.view;
+        camera
+    }
+}
+
+impl Camera<f32> {
+    // The NDC depths a viewport's near and far planes land on through this camera.
+    pub fn ndc_depth(&self, depth: [f32; 2]) -> [f32; 2] {
+        if self.reversed_z { [-depth[0], -depth[1]] } else { depth }
+    }
+    pub fn advance_path(&mut self, now: f64) {
+        let frame = match self.playback.as_mut() {
+            Some(playback) => playback.advance(now),
+            None => return,
+        };
+        match frame {
+            Some((position, target)) => {
+                self.position = position;
+                self.target = target;
+                self.dirty = true;
+            },
+            None => self.playback = None,
+        }
+    }
+    // Rotates the target around the position, keeping the distance between them.
+    // Yaw turns around the camera's up and pitch is clamped short of its poles, so a rolled
+    // camera turns about its own axes.
+    pub fn rotate(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        use cgmath::InnerSpace;
+        let direction = self.direction();
+        let distance = direction.magnitude();
+        if distance == 0.0 {
+            return;
+        }
+        let limit = 89.0f32.to_radians();
+        let up = self.up;
+        let forward = direction / distance;
+        let height = forward.dot(up).max(-1.0).min(1.0);
+        let current_pitch = height.asin();
+        // the heading on the plane across up; straight along up it is where the screen's up points
+        let level = forward - up * height;
+        let level = if level.magnitude2() > 1e-12 {
+            level.normalize()
+        } else {
+            self.screen_up() * -height.signum()
+        };
+
+        let new_level = level * yaw.0.cos() + up.cross(level) * yaw.0.sin();
+        let new_pitch = (current_pitch + pitch.0).max(-limit).min(limit);
+        self.dirty = true;
+
+        self.target = self.position + (new_level * new_pitch.cos() + up * new_pitch.sin()) * distance;
+    }
+}
+
+// Which pose GameObjects are skinned with.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AnimationClock {
+    Time(f64),
+    // a single sample, used by the timeline scrubber
+    Sample(usize),
+}
+
+// The part of a GameObject the update thread owns. Everything heavy is shared.
+#[derive(Clone)]
+pub struct Body {
+    pub position: Point3<f32>,
+    // front: Vector3<f32>,
+    pub joints: Arc<Vec<Joint>>,
+    // positions in joints, parents before children
+    pub joint_order: Arc<Vec<usize>>,
+    pub clip: Arc<AnimationClip>,
+    // playback speed multiplier; change it with set_speed
+    pub speed: f32,
+    // where on the looping clip it was at world time phase_time; it runs on at speed
+    // from there, so a new speed carries on from the current pose
+    pub phase: f64,
+    pub phase_time: f64,
+    // blends idle, walk and run by movement speed in place of clip when present
+    pub locomotion: Option<Locomotion>,
+    // world time clip started playing once, holding its last pose after; None loops it
+    pub once_from: Option<f64>,
+    // applied in order over clip or locomotion
+    pub layers: Vec<AdditiveLayer>,
+}
+
+pub trait Translate<T: cgmath::BaseFloat> {
+    fn translate(&mut self, v: Vector3<T>);
+}
+
+impl Translate<f32> for Body
+{
+    fn translate(&mut self, v: Vector3<f32>) {
+        use cgmath::InnerSpace;
+        self.position += v;
+        if let Some(ref mut locomotion) = self.locomotion {
+            locomotion.add_distance(v.magnitude());
+        }
+    }
+}
+impl<T: cgmath::BaseFloat> Translate<T> for Camera<T> 
+{
+    fn translate(&mut self, v: Vector3<T>) {
+        self.position += v;
+        self.dirty = true;
+    }
+}
+
+impl Body {
+    pub fn sample_count(&self) -> usize {
+        self.clip.sample_count()
+    }
+    pub fn sample_time(&self, index: usize) -> Option<f32> {
+        self.clip.tracks.iter()
+            .find(|v| !v.is_empty())
+            .map(|v| v[index % v.len()].0)
+    }
+    // How far the looping clip has played by a world time, unwrapped.
+    pub fn clip_position(&self, time: f64) -> f64 {
+        self.phase + (time - self.phase_time) * self.speed as f64
+    }
+    // Position on the looping clip for a world time.
+    fn clip_time(&self, time: f64) -> f32 {
+        if let Some(start) = self.once_from {
+            return ((time - start) * self.speed as f64).max(0.0).min(self.clip.end_time() as f64) as f32;
+        }
+        self.wrap(self.clip_position(time)) as f32
+    }
+    // A clip position onto the loop, [0, duration).
+    pub fn wrap(&self, position: f64) -> f64 {
+        let duration = self.clip.duration as f64;
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        let t = position % duration;
+        if t < 0.0 { t + duration } else { t }
+    }
+    // Moves the phase on to time, by the time passed since the last advance at speed.
+    pub fn advance_clip(&mut self, time: f64) {
+        self.phase = self.wrap(self.clip_position(time));
+        self.phase_time = time;
+    }
+    // The clip plays at the old speed up to now and at the new one after.
+    pub fn set_speed(&mut self, speed: f32, now: f64) {
+        self.advance_clip(now);
+        self.speed = speed;
+    }
+    // The sample get_skinning starts interpolating from at the given time.
+    pub fn sample_index_at(&self, time: f64) -> usize {
+        let t = self.clip_time(time);
+        match self.clip.tracks.iter().find(|v| !v.is_empty()) {
+            Some(v) => self.clip.sample_span(v, t).0,
+            None => 0,
+        }
+    }
+    // Runs before sampling a step, so the blend follows the moves made since the last one.
+    pub fn advance_locomotion(&mut self, clock: AnimationClock) {
+        if let AnimationClock::Time(time) = clock {
+            self.advance_clip(time);
+            if let Some(ref mut locomotion) = self.locomotion {
+                locomotion.advance(time, self.speed);
+            }
+        }
+    }
+    // S is what the renderer uploads a joint's matrix as.
+    pub fn skinning<S: From<Matrix4<f32>> + Clone>(&self, clock: AnimationClock) -> Vec<S> {
+        let mut palette = Vec::new();
+        self.skinning_into(clock, &mut Vec::new(), &mut palette);
+        palette
+    }
+    // Writes the pose at clock into palette, reusing the buffers' allocations.
+    pub fn skinning_into<S: From<Matrix4<f32>> + Clone>(&self, clock: AnimationClock, local: &mut Vec<Matrix4<f32>>, palette: &mut Vec<S>) {
+        match (clock, self.locomotion.as_ref()) {
+            (AnimationClock::Time(time), Some(locomotion)) => {
+                let weights = locomotion.weights();
+                self.evaluate_joints(|joint_index| self.layered(joint_index, locomotion.pose(joint_index, &weights), time), local, palette)
+            },
+            (AnimationClock::Time(time), None) => {
+                let t = self.clip_time(time);
+                self.evaluate_joints(|joint_index| self.layered(joint_index, self.clip.pose_at(joint_index, t), time), local, palette)
+            },
+            (AnimationClock::Sample(index), _) => self.evaluate_joints(|joint_index| match self.clip.tracks.get(joint_index) {
+                Some(v) if v.len() > 0 => Some(v[index % v.len()].1.pose),
+                _ => None,
+            }, local, palette),
+        }
+    }
+    // The pose below with each additive layer on top. Stepping through samples shows
+    // the base clip alone, so layers play only on the clock.
+    fn layered(&self, joint_index: usize, below: Option<Matrix4<f32>>, time: f64) -> Option<Matrix4<f32>> {
+        self.layers.iter().fold(below, |pose, layer| match layer.delta(joint_index, time) {
+            // a joint only layers move starts from its bind pose
+            Some(delta) => Some(pose.unwrap_or_else(|| {
+                self.joints.iter().find(|j| j.joint_index as usize == joint_index).map_or(One::one(), |j| j.bind)
+            }) * delta),
+            None => pose,
+        })
+    }
+    // Walks the hierarchy parents first; pose_of gives None for joints it does not animate.
+    fn evaluate_joints<S, F>(&self, pose_of: F, local: &mut Vec<Matrix4<f32>>, palette: &mut Vec<S>)
+        where S: From<Matrix4<f32>> + Clone, F: Fn(usize) -> Option<Matrix4<f32>>
+    {
+        let identity: Matrix4<f32> = One::one();
+        local.clear();
+        palette.clear();
+        if self.joints.is_empty() {
+            palette.push(S::from(identity));
+            return;
+        }
+        local.resize(self.joints.len(), identity);
+        palette.resize(self.joints.len(), S::from(identity));
+        for &i in self.joint_order.iter() {
+            let j = &self.joints[i];
+            let p = if j.parent == ROOT_PARENT {
+                identity
+            } else {
+                local[j.parent as usize]
+            };
+            let joint_index = j.joint_index as usize;
+            let (l, output) = match pose_of(joint_index) {
+                Some(pose) => (p * pose, p * (pose * j.inverse)),
+                None if joint_index < self.clip.tracks.len() => (j.bind, p * j.bind),
+                None => (j.bind, j.bind),
+            };
+            local[i] = l;
+            palette[i] = S::from(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use animation::AnimationClip;
+    use locomotion::{Locomotion, LocomotionClips};
+    use models::{Animation, Joint};
+
+    // A joint at the root moved along x, evenly through the samples' positions.
+    fn slide(xs: &[f32], duration: f32) -> AnimationClip {
+        AnimationClip {
+            tracks: vec!(xs.iter().enumerate().map(|(i, &x)| {
+                let time = i as f32;
+                (time, Animation {
+                    joint_index: 0,
+                    time,
+                    pose: Matrix4::from_translation(Vector3::new(x, 0.0, 0.0)),
+                })
+            }).collect()),
+            duration,
+            timestamped: false,
+        }
+    }
+
+    fn body(clip: AnimationClip) -> Body {
+        let identity: Matrix4<f32> = One::one();
+        Body {
+            position: Point3::new(0.0, 0.0, 0.0),
+            joints: Arc::new(vec!(Joint {
+                joint_index: 0,
+                global: identity,
+                bind: identity,
+                parent: ROOT_PARENT,
+                inverse: identity,
+            })),
+            joint_order: Arc::new(vec!(0)),
+            clip: Arc::new(clip),
+            speed: 1.0,
+            phase: 0.0,
+            phase_time: 0.0,
+            locomotion: None,
+            once_from: None,
+            layers: Vec::new(),
+        }
+    }
+
+    fn root_x(body: &Body, clock: AnimationClock) -> f32 {
+        body.skinning::<Matrix4<f32>>(clock)[0].w.x
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn translate_moves_the_body() {
+        let mut body = body(slide(&[0.0], 1.0));
+        body.translate(Vector3::new(1.0, 2.0, 0.0));
+        body.translate(Vector3::new(0.5, 0.0, -1.0));
+        assert_eq!(body.position, Point3::new(1.5, 2.0, -1.0));
+    }
+
+    #[test]
+    fn locomotion_blends_by_distance_moved() {
+        let clips = Arc::new(LocomotionClips {
+            idle: slide(&[0.0, 0.0], 1.0),
+            walk: slide(&[1.0, 1.0], 1.0),
+            run: None,
+        });
+        let mut body = body(slide(&[0.0], 1.0));
+        body.locomotion = Some(Locomotion::new(clips));
+        body.advance_locomotion(AnimationClock::Time(0.0));
+        // one unit in a second is half of walking speed
+        body.translate(Vector3::new(1.0, 0.0, 0.0));
+        body.advance_locomotion(AnimationClock::Time(1.0));
+        assert_eq!(body.locomotion.as_ref().map(|l| l.weights()), Some([0.5, 0.5, 0.0]));
+        assert!(close(root_x(&body, AnimationClock::Time(1.0)), 0.5));
+    }
+
+    #[test]
+    fn clip_blends_between_samples() {
+        // samples at 0 and 1 seconds
+        let body = body(slide(&[0.0, 2.0], 2.0));
+        assert!(close(root_x(&body, AnimationClock::Time(0.0)), 0.0));
+        assert!(close(root_x(&body, AnimationClock::Time(0.25)), 0.5));
+        assert!(close(root_x(&body, AnimationClock::Time(0.5)), 1.0));
+        // the scrubber shows samples as they are
+        assert!(close(root_x(&body, AnimationClock::Sample(1)), 2.0));
+    }
+
+    #[test]
+    fn clip_loops() {
+        let body = body(slide(&[0.0, 2.0], 2.0));
+        assert!(close(body.clip_time(2.5), 0.5));
+        assert!(close(body.clip_time(-0.5), 1.5));
+    }
+
+    #[test]
+    fn speed_change_continues_from_the_current_pose() {
+        let mut body = body(slide(&[0.0, 2.0], 2.0));
+        body.set_speed(2.0, 0.5);
+        assert!(close(body.clip_time(0.5), 0.5));
+        assert!(close(body.clip_time(1.0), 1.5));
+        // advancing rebases the phase without moving the pose
+        body.advance_clip(1.25);
+        assert!(close(body.clip_time(1.25), 0.0));
+        assert!(close(body.clip_time(1.5), 0.5));
+    }
+
+    #[test]
+    fn played_once_holds_the_last_pose() {
+        let mut body = body(slide(&[0.0, 2.0], 2.0));
+        body.once_from = Some(10.0);
+        assert!(close(body.clip_time(9.0), 0.0));
+        assert!(close(body.clip_time(10.5), 0.5));
+        assert!(close(body.clip_time(20.0), 1.0));
+    }
+
+    struct Push(Level, u32);
+
+    impl Command<Vec<u32>> for Push {
+        fn get_level(&self) -> Level {
+            self.0
+        }
+        fn execute(&self, ran: &mut Vec<u32>) {
+            ran.push(self.1);
+        }
+    }
+
+    #[test]
+    fn levels_run_by_priority_in_the_order_queued() {
+        let mut invoker = Invoker::new(Vec::<u32>::new());
+        invoker.append_command(Push(Level::Avator, 1));
+        invoker.append_command(Push(Level::World, 2));
+        invoker.append_command(Push(Level::Avator, 3));
+        invoker.append_command(Push(Level::System, 4));
+        invoker.append_command(Push(Level::World, 5));
+        for &level in Level::by_priority().iter() {
+            invoker.execute_level(level);
+        }
+        assert_eq!(invoker.target, vec!(4, 2, 5, 1, 3));
+    }
+
+    #[test]
+    fn execute_level_keeps_the_other_levels_queued() {
+        let mut invoker = Invoker::new(Vec::<u32>::new());
+        invoker.append_command(Push(Level::Avator, 1));
+        invoker.append_command(Push(Level::World, 2));
+        let ran: Vec<u32> = invoker.execute_level(Level::World).iter().map(|c| c.1).collect();
+        assert_eq!(ran, vec!(2));
+        invoker.execute_all_commands();
+        assert_eq!(invoker.target, vec!(2, 1));
+    }
+
+    #[test]
+    fn blocked_levels_are_discarded() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.set_blocked(Level::Avator, true);
+        let mut invoker = Invoker::new(Vec::<u32>::new());
+        invoker.append_command(Push(Level::Avator, 1));
+        invoker.append_command(Push(Level::World, 2));
+        for &level in Level::by_priority().iter() {
+            if dispatcher.allows(level) {
+                invoker.execute_level(level);
+            } else {
+                invoker.discard_level(level);
+            }
+        }
+        dispatcher.set_blocked(Level::Avator, false);
+        assert!(dispatcher.allows(Level::Avator));
+        invoker.execute_all_commands();
+        assert_eq!(invoker.target, vec!(2));
+    }
+
+    #[test]
+    fn body_commands_move_their_own_body() {
+        let mut bodies = HashMap::default();
+        bodies.insert(1, body(slide(&[0.0], 1.0)));
+        bodies.insert(2, body(slide(&[0.0], 1.0)));
+        let mut invoker = Invoker::new(bodies);
+        invoker.append_command(BodyCommand { key: 2, command: AvatorCommand::Move(Vector3::new(1.0, 0.0, 0.0)) });
+        invoker.append_command(BodyCommand { key: 1, command: AvatorCommand::Move(Vector3::new(0.0, 1.0, 0.0)) });
+        invoker.append_command(BodyCommand { key: 2, command: AvatorCommand::Move(Vector3::new(1.0, 0.0, 0.0)) });
+        // a key with no body is passed over
+        invoker.append_command(BodyCommand { key: 3, command: AvatorCommand::Move(Vector3::new(1.0, 0.0, 0.0)) });
+        invoker.execute_all_commands();
+        assert_eq!(invoker.target[&1].position, Point3::new(0.0, 1.0, 0.0));
+        assert_eq!(invoker.target[&2].position, Point3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn camera_translate_rebuilds_the_view_once() {
+        let mut camera = Camera::new(
+            Point3::new(0.0, -5.0, 0.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_z(),
+            cgmath::PerspectiveFov { fovy: Rad(1.0), aspect: 1.0, near: 0.5, far: 100.0 },
+        );
+        camera.translate(Vector3::new(1.0, 0.0, 0.0));
+        camera.update();
+        camera.update();
+        assert_eq!(camera.position, Point3::new(1.0, -5.0, 0.0));
+        assert_eq!(camera.generation, 1);
+    }
+}
@@ -0,0 +1,182 @@
+use std;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use fnv::FnvHashMap as HashMap;
+use cgmath::{Matrix4, Point3};
+use rayon::prelude::*;
+
+use animation::AnimationClip;
+use sim::{AnimationClock, Body, BodyCommand, Invoker};
+use Skinning;
+
+pub(crate) enum SimMessage {
+    Insert(i32, Body),
+    Remove(i32),
+    // key, speed and the world time it changes at
+    SetSpeed(i32, f32, f64),
+    // key, layer and its new weight
+    SetLayerWeight(i32, usize, f32),
+    // play the clip once from this world time and hold its last pose, as on death
+    PlayOnce(i32, Arc<AnimationClip>, f64),
+    // queued on the bodies' Invoker, in order, until the next step
+    Command(BodyCommand),
+    // run pending commands and publish the pose at this clock
+    Step(AnimationClock),
+    // a palette the render thread is done with, to be filled again
+    ReturnPalette(i32, Vec<Skinning>),
+}
+
+pub(crate) struct BodyState {
+    pub position: Point3<f32>,
+    pub palette: Vec<Skinning>,
+}
+
+pub(crate) struct FrameSnapshot {
+    pub bodies: HashMap<i32, BodyState>,
+}
+
+// Runs avator commands and samples poses off the render thread.
+// The render thread sends messages and draws with the latest snapshot,
+// which lags at most the frames the worker is behind.
+pub(crate) struct UpdateThread {
+    sender: Option<Sender<SimMessage>>,
+    snapshots: Receiver<FrameSnapshot>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl UpdateThread {
+    pub(crate) fn spawn() -> Self {
+        let (sender, messages) = channel();
+        let (publish, snapshots) = channel();
+        let handle = thread::Builder::new()
+            .name("update".to_string())
+            .spawn(move || run(messages, publish))
+            .expect("failed to start the update thread");
+        UpdateThread {
+            sender: Some(sender),
+            snapshots,
+            handle: Some(handle),
+        }
+    }
+    pub(crate) fn send(&self, message: SimMessage) {
+        if let Some(ref sender) = self.sender {
+            if sender.send(message).is_err() {
+                error!("update thread has stopped");
+            }
+        }
+    }
+    // Waits for the step in progress; later sends are dropped.
+    pub(crate) fn stop(&mut self) {
+        // closing the channel ends the worker loop
+        self.sender = None;
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                error!("update thread panicked");
+            }
+        }
+    }
+    // The newest snapshot published since the last call, if any.
+    // Palettes of the ones skipped over go straight back for reuse.
+    pub(crate) fn latest(&self) -> Option<FrameSnapshot> {
+        let mut latest = None;
+        for snapshot in self.snapshots.try_iter() {
+            if let Some(skipped) = std::mem::replace(&mut latest, Some(snapshot)) {
+                self.recycle(skipped);
+            }
+        }
+        latest
+    }
+    fn recycle(&self, snapshot: FrameSnapshot) {
+        for (key, state) in snapshot.bodies {
+            self.send(SimMessage::ReturnPalette(key, state.palette));
+        }
+    }
+}
+
+impl Drop for UpdateThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Per-body storage reused from step to step, so a steady frame allocates no palettes.
+#[derive(Default)]
+struct PoseBuffers {
+    local: Vec<Matrix4<f32>>,
+    // palettes handed back by the render thread
+    spare: Vec<Vec<Skinning>>,
+}
+
+// a snapshot in flight and the one being drawn, plus slack for a skipped one
+const MAX_SPARE_PALETTES: usize = 3;
+
+fn run(messages: Receiver<SimMessage>, publish: Sender<FrameSnapshot>) {
+    let mut bodies = Invoker::<BodyCommand, HashMap<i32, Body>>::new(HashMap::default());
+    let mut buffers = HashMap::<i32, PoseBuffers>::default();
+    while let Ok(first) = messages.recv() {
+        // when the render thread got ahead, only the newest step is sampled
+        let mut step = None;
+        for message in Some(first).into_iter().chain(messages.try_iter()) {
+            match message {
+                SimMessage::Insert(key, body) => { bodies.target.insert(key, body); },
+                SimMessage::Remove(key) => {
+                    bodies.target.remove(&key);
+                    buffers.remove(&key);
+                },
+                SimMessage::SetSpeed(key, speed, now) => if let Some(body) = bodies.target.get_mut(&key) {
+                    body.set_speed(speed, now);
+                },
+                SimMessage::SetLayerWeight(key, layer, weight) => if let Some(layer) = bodies.target.get_mut(&key).and_then(|body| body.layers.get_mut(layer)) {
+                    layer.weight = weight;
+                },
+                SimMessage::PlayOnce(key, clip, start) => if let Some(body) = bodies.target.get_mut(&key) {
+                    body.clip = clip;
+                    body.locomotion = None;
+                    body.once_from = Some(start);
+                },
+                SimMessage::Command(command) => bodies.append_command(command),
+                SimMessage::Step(clock) => {
+                    bodies.execute_all_commands();
+                    for body in bodies.target.values_mut() {
+                        body.advance_locomotion(clock);
+                    }
+                    step = Some(clock);
+                },
+                SimMessage::ReturnPalette(key, palette) => if bodies.target.contains_key(&key) {
+                    let spare = &mut buffers.entry(key).or_insert_with(PoseBuffers::default).spare;
+                    if spare.len() < MAX_SPARE_PALETTES {
+                        spare.push(palette);
+                    }
+                },
+            }
+        }
+        if let Some(clock) = step {
+            let snapshot = FrameSnapshot {
+                bodies: sample_poses(&bodies.target, &mut buffers, clock),
+            };
+            if publish.send(snapshot).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// Skins every body in parallel, each into its own buffers.
+fn sample_poses(bodies: &HashMap<i32, Body>, buffers: &mut HashMap<i32, PoseBuffers>, clock: AnimationClock) -> HashMap<i32, BodyState> {
+    let mut jobs: Vec<_> = bodies.iter().map(|(key, body)| {
+        let mut pose_buffers = buffers.remove(key).unwrap_or_default();
+        let palette = pose_buffers.spare.pop().unwrap_or_default();
+        (*key, body, pose_buffers, palette)
+    }).collect();
+    jobs.par_iter_mut().for_each(|&mut (_, body, ref mut pose_buffers, ref mut palette)| {
+        body.skinning_into(clock, &mut pose_buffers.local, palette);
+    });
+    jobs.into_iter().map(|(key, body, pose_buffers, palette)| {
+        buffers.insert(key, pose_buffers);
+        (key, BodyState {
+            position: body.position,
+            palette,
+        })
+    }).collect()
+}
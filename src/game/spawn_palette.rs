@@ -0,0 +1,46 @@
+// number keys 1 to 9
+pub const PALETTE_SLOTS: usize = 9;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpawnTemplate {
+    pub object_id: i32,
+    // uniform scale the object is spawned at
+    pub scale: f32,
+}
+
+// Object templates on the number keys, kept in the SpawnPalette table. Slots count from 1.
+pub struct SpawnPalette {
+    slots: [Option<SpawnTemplate>; PALETTE_SLOTS],
+}
+
+impl SpawnPalette {
+    pub fn new() -> Self {
+        SpawnPalette {
+            slots: [None; PALETTE_SLOTS],
+        }
+    }
+    // Rows are (slot, object id, scale); slots out of range are skipped.
+    pub fn from_rows(rows: Vec<(i32, i32, f32)>) -> Self {
+        let mut palette = SpawnPalette::new();
+        for (slot, object_id, scale) in rows {
+            if slot >= 1 {
+                palette.set(slot as usize, Some(SpawnTemplate { object_id, scale }));
+            }
+        }
+        palette
+    }
+    pub fn get(&self, slot: usize) -> Option<SpawnTemplate> {
+        if slot >= 1 && slot <= PALETTE_SLOTS { self.slots[slot - 1] } else { None }
+    }
+    pub fn set(&mut self, slot: usize, template: Option<SpawnTemplate>) {
+        if slot >= 1 && slot <= PALETTE_SLOTS {
+            self.slots[slot - 1] = template;
+        }
+    }
+    // Filled slots in order.
+    pub fn entries(&self) -> Vec<(usize, SpawnTemplate)> {
+        self.slots.iter().enumerate()
+            .filter_map(|(i, slot)| slot.map(|template| (i + 1, template)))
+            .collect()
+    }
+}
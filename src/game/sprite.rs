@@ -0,0 +1,75 @@
+use billboard::Billboard;
+
+// A texture cut into a grid of equally sized frames, read left to right, top to bottom.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpriteSheet {
+    pub texture_id: i32,
+    pub columns: u16,
+    pub rows: u16,
+    // frames in use; the last row may be partly empty
+    pub frames: u16,
+    pub fps: f32,
+}
+
+impl SpriteSheet {
+    pub fn new(texture_id: i32, columns: u16, rows: u16, fps: f32) -> Self {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        SpriteSheet {
+            texture_id,
+            columns,
+            rows,
+            frames: columns * rows,
+            fps,
+        }
+    }
+    // [left, top, right, bottom] in texture coordinates.
+    pub fn frame_uv(&self, frame: u16) -> [f32; 4] {
+        let column = (frame % self.columns) as f32;
+        let row = (frame / self.columns) as f32;
+        let (width, height) = (1.0 / self.columns as f32, 1.0 / self.rows as f32);
+        [column * width, row * height, (column + 1.0) * width, (row + 1.0) * height]
+    }
+}
+
+// Where a sprite is drawn.
+pub enum SpritePlacement {
+    // [left, bottom, right, top] in pixels, drawn with the HUD
+    Screen([f32; 4]),
+    // in every viewport; the billboard's color tints the frame
+    World(Billboard),
+}
+
+pub struct AnimatedSprite {
+    pub sheet: SpriteSheet,
+    pub placement: SpritePlacement,
+    pub looping: bool,
+    elapsed: f32,
+}
+
+impl AnimatedSprite {
+    pub fn new(sheet: SpriteSheet, placement: SpritePlacement) -> Self {
+        AnimatedSprite {
+            sheet,
+            placement,
+            looping: true,
+            elapsed: 0.0,
+        }
+    }
+    // Advanced with the fixed step, so sprites keep time with tweens rather than frame rate.
+    pub fn tick(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+    pub fn frame(&self) -> u16 {
+        let frames = self.sheet.frames.max(1) as u32;
+        let frame = (self.elapsed * self.sheet.fps.max(0.0)) as u32;
+        if self.looping {
+            (frame % frames) as u16
+        } else {
+            frame.min(frames - 1) as u16
+        }
+    }
+    pub fn uv_rect(&self) -> [f32; 4] {
+        self.sheet.frame_uv(self.frame())
+    }
+}
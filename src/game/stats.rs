@@ -0,0 +1,43 @@
+// Hit points and walking speed of a GameObject, from its Stats row.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stats {
+    pub hp: f32,
+    pub max_hp: f32,
+    // multiplies how fast the object walks
+    pub speed: f32,
+}
+
+// for objects without a Stats row
+const DEFAULT_MAX_HP: f32 = 100.0;
+
+impl Stats {
+    pub fn new(max_hp: f32, speed: f32) -> Self {
+        Stats {
+            hp: max_hp,
+            max_hp,
+            speed,
+        }
+    }
+    pub fn is_dead(&self) -> bool {
+        self.hp <= 0.0
+    }
+    // What is left of max_hp, in 0..1.
+    pub fn fraction(&self) -> f32 {
+        if self.max_hp > 0.0 { (self.hp / self.max_hp).max(0.0).min(1.0) } else { 0.0 }
+    }
+    // Hit points never go below zero or above max_hp, and the dead are not healed.
+    pub fn damage(&mut self, amount: f32) {
+        self.hp = (self.hp - amount.max(0.0)).max(0.0);
+    }
+    pub fn heal(&mut self, amount: f32) {
+        if !self.is_dead() {
+            self.hp = (self.hp + amount.max(0.0)).min(self.max_hp);
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats::new(DEFAULT_MAX_HP, 1.0)
+    }
+}
@@ -0,0 +1,113 @@
+use std::f32::consts::PI;
+
+use markup::parse_markup;
+
+// Per-glyph animation applied while text is laid out; see font_entry_runs.
+// Distances are in font pixels and follow the scale the text is drawn at.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextEffect {
+    // glyphs fade in one after another
+    Typewriter { chars_per_second: f32 },
+    // glyphs bob up and down; wavelength is in glyphs, speed in cycles per second
+    Wave { amplitude: f32, wavelength: f32, speed: f32 },
+    // glyphs jump to a new random offset rate times a second
+    Shake { magnitude: f32, rate: f32 },
+}
+
+impl TextEffect {
+    pub fn from_name(name: &str) -> Option<TextEffect> {
+        match name {
+            "typewriter" => Some(TextEffect::Typewriter { chars_per_second: 30.0 }),
+            "wave" => Some(TextEffect::Wave { amplitude: 4.0, wavelength: 8.0, speed: 1.0 }),
+            "shake" => Some(TextEffect::Shake { magnitude: 2.0, rate: 20.0 }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlyphStyle {
+    pub offset: [f32; 2],
+    // multiplies the glyph color's alpha
+    pub alpha: f32,
+}
+
+// index counts glyphs from the start of the text, line breaks excluded.
+pub fn glyph_style(effects: &[TextEffect], index: usize, time: f32) -> GlyphStyle {
+    let mut style = GlyphStyle { offset: [0.0, 0.0], alpha: 1.0 };
+    for effect in effects {
+        match *effect {
+            TextEffect::Typewriter { chars_per_second } => {
+                let shown = time * chars_per_second - index as f32;
+                style.alpha *= shown.max(0.0).min(1.0);
+            },
+            TextEffect::Wave { amplitude, wavelength, speed } => {
+                let phase = time * speed - index as f32 / wavelength.max(1.0);
+                style.offset[1] += amplitude * (2.0 * PI * phase).sin();
+            },
+            TextEffect::Shake { magnitude, rate } => {
+                let step = (time * rate) as u32;
+                style.offset[0] += magnitude * noise(index as u32, step, 0);
+                style.offset[1] += magnitude * noise(index as u32, step, 1);
+            },
+        }
+    }
+    style
+}
+
+// Seconds until every glyph is fully shown; None when nothing is being revealed.
+pub fn reveal_duration(effects: &[TextEffect], glyphs: usize) -> Option<f32> {
+    effects.iter()
+        .filter_map(|effect| match *effect {
+            TextEffect::Typewriter { chars_per_second } if chars_per_second > 0.0 => Some(glyphs as f32 / chars_per_second),
+            _ => None,
+        })
+        .fold(None, |longest, d| Some(longest.map_or(d, |l: f32| l.max(d))))
+}
+
+// Stable per glyph and step, so a shaken glyph holds still between steps. In -1..1.
+fn noise(index: u32, step: u32, axis: u32) -> f32 {
+    let mut h = index.wrapping_mul(0x9e37_79b9) ^ step.wrapping_mul(0x85eb_ca6b) ^ axis.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    (h & 0xffff) as f32 / 32767.5 - 1.0
+}
+
+// Text with effects and its own clock, advanced with the fixed step.
+#[derive(Debug, Clone)]
+pub struct EffectText {
+    // as for draw_markup
+    pub markup: String,
+    pub effects: Vec<TextEffect>,
+    pub time: f32,
+}
+
+impl EffectText {
+    pub fn new(markup: &str, effects: Vec<TextEffect>) -> Self {
+        EffectText {
+            markup: markup.to_string(),
+            effects,
+            time: 0.0,
+        }
+    }
+    pub fn tick(&mut self, dt: f32) {
+        self.time += dt;
+    }
+    // visible characters, as glyph_style indexes them
+    pub fn glyphs(&self) -> usize {
+        parse_markup(&self.markup).iter()
+            .map(|run| run.text.chars().filter(|&ch| ch != '\n').count())
+            .sum()
+    }
+    // Whether a typewriter, if any, has shown the whole text.
+    pub fn is_revealed(&self) -> bool {
+        reveal_duration(&self.effects, self.glyphs()).map_or(true, |d| self.time >= d)
+    }
+    // Jumps to the end of the reveal, as when a dialogue line is skipped.
+    pub fn reveal(&mut self) {
+        if let Some(d) = reveal_duration(&self.effects, self.glyphs()) {
+            self.time = self.time.max(d);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use font::Font;
+use input::*;
+use markup::TextRun;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextInputEvent {
+    Submit(String),
+    Cancel,
+}
+
+pub struct TextInput {
+    chars: Vec<char>,
+    // cursor and anchor are char indices, 0..=chars.len()
+    cursor: usize,
+    anchor: Option<usize>,
+    // An input method's uncommitted text, shown at the cursor but not part of text().
+    // The input method has the keys while it is there.
+    preedit: Vec<char>,
+    preedit_cursor: usize,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        TextInput {
+            chars: Vec::new(),
+            cursor: 0,
+            anchor: None,
+            preedit: Vec::new(),
+            preedit_cursor: 0,
+        }
+    }
+    pub fn text(&self) -> String {
+        self.chars.iter().cloned().collect()
+    }
+    pub fn set_text(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+        self.anchor = None;
+        self.preedit.clear();
+        self.preedit_cursor = 0;
+    }
+    pub fn clear(&mut self) {
+        self.set_text("");
+    }
+    pub fn is_composing(&self) -> bool {
+        !self.preedit.is_empty()
+    }
+    // The caret as a char index into the text as shown, preedit included.
+    pub fn caret(&self) -> usize {
+        self.cursor + self.preedit_cursor
+    }
+    // The text as shown, with the preedit underlined at the cursor.
+    pub fn runs(&self) -> Vec<TextRun> {
+        let before: String = self.chars[..self.cursor].iter().cloned().collect();
+        let preedit: String = self.preedit.iter().cloned().collect();
+        let after: String = self.chars[self.cursor..].iter().cloned().collect();
+        vec!(TextRun::plain(&before), TextRun::underlined(&preedit), TextRun::plain(&after))
+    }
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        use std::cmp::{min, max};
+        match self.anchor {
+            Some(a) if a != self.cursor => Some((min(a, self.cursor), max(a, self.cursor))),
+            _ => None,
+        }
+    }
+
+    pub fn insert_char(&mut self, ch: char) {
+        self.delete_selection();
+        self.chars.insert(self.cursor, ch);
+        self.cursor += 1;
+    }
+    pub fn backspace(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+    pub fn delete(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+    pub fn move_to(&mut self, index: usize, select: bool) {
+        if select {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.cursor);
+            }
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = std::cmp::min(index, self.chars.len());
+    }
+    pub fn move_left(&mut self, select: bool) {
+        let index = if self.cursor > 0 { self.cursor - 1 } else { 0 };
+        self.move_to(index, select);
+    }
+    pub fn move_right(&mut self, select: bool) {
+        let index = self.cursor + 1;
+        self.move_to(index, select);
+    }
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.chars.len();
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.chars.drain(start..end);
+                self.cursor = start;
+                self.anchor = None;
+                true
+            },
+            None => {
+                self.anchor = None;
+                false
+            }
+        }
+    }
+
+    // The preedit replaces the selection when it starts.
+    fn set_preedit(&mut self, text: &str, cursor: usize) {
+        if self.preedit.is_empty() && !text.is_empty() {
+            self.delete_selection();
+        }
+        self.preedit = text.chars().collect();
+        self.preedit_cursor = std::cmp::min(cursor, self.preedit.len());
+    }
+    fn commit(&mut self, text: &str) {
+        self.preedit.clear();
+        self.preedit_cursor = 0;
+        for ch in text.chars().filter(|ch| !ch.is_control()) {
+            self.insert_char(ch);
+        }
+    }
+
+    pub fn handle_input(&mut self, ev: &InputEvent) -> Option<TextInputEvent> {
+        match *ev {
+            InputEvent::Preedit { ref text, cursor } => {
+                self.set_preedit(text, cursor);
+                None
+            },
+            InputEvent::Commit(ref text) => {
+                self.commit(text);
+                None
+            },
+            // the input method picks candidates and confirms with these keys
+            InputEvent::Key { .. } | InputEvent::Character(_) if self.is_composing() => None,
+            InputEvent::Character(ch) => {
+                if !ch.is_control() {
+                    self.insert_char(ch);
+                }
+                None
+            },
+            InputEvent::Key {
+                key,
+                state: ButtonState::Pressed,
+                modifiers,
+            } => {
+                match key {
+                    Key::Back => self.backspace(),
+                    Key::Delete => self.delete(),
+                    Key::Left => self.move_left(modifiers.shift),
+                    Key::Right => self.move_right(modifiers.shift),
+                    Key::Home => self.move_to(0, modifiers.shift),
+                    Key::End => {
+                        let end = self.chars.len();
+                        self.move_to(end, modifiers.shift)
+                    },
+                    Key::A if modifiers.ctrl => self.select_all(),
+                    Key::Return => {
+                        let text = self.text();
+                        self.clear();
+                        return Some(TextInputEvent::Submit(text));
+                    },
+                    Key::Escape => return Some(TextInputEvent::Cancel),
+                    _ => { }
+                }
+                None
+            },
+            _ => None,
+        }
+    }
+
+    // x offset in font units of the given char index into the text as shown,
+    // matching font_entry's advance.
+    pub fn offset_of(&self, font: &Font, index: usize) -> f32 {
+        let (before, after) = self.chars.split_at(self.cursor);
+        before.iter().chain(self.preedit.iter()).chain(after.iter())
+            .take(index)
+            .filter_map(|ch| font.chars.get(ch))
+            .map(|info| info.x_advance as f32)
+            .sum()
+    }
+}
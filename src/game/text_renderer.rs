@@ -0,0 +1,78 @@
+use fnv::FnvHashMap as HashMap;
+
+use color::Color;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TextHandle(u32);
+
+// What a static text is drawn with besides its markup; where it goes is given per draw.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextStyle {
+    pub color: Color,
+    pub scale: f32,
+}
+
+struct StaticText<T> {
+    markup: String,
+    style: TextStyle,
+    // None until prepared, and again after a change
+    built: Option<T>,
+}
+
+// Texts laid out and uploaded once, then drawn until their markup or style changes,
+// for labels that would otherwise be built again every frame.
+// T is what a draw needs; the caller builds it, since it holds the font and the device.
+pub struct TextRenderer<T> {
+    texts: HashMap<TextHandle, StaticText<T>>,
+    next: u32,
+}
+
+impl<T> TextRenderer<T> {
+    pub fn new() -> Self {
+        TextRenderer {
+            texts: HashMap::default(),
+            next: 0,
+        }
+    }
+    pub fn create_static(&mut self, markup: &str, style: TextStyle) -> TextHandle {
+        let handle = TextHandle(self.next);
+        self.next += 1;
+        self.texts.insert(handle, StaticText {
+            markup: markup.to_string(),
+            style,
+            built: None,
+        });
+        handle
+    }
+    // Keeps what was built when neither changed, so this is cheap to call every frame.
+    pub fn set(&mut self, handle: TextHandle, markup: &str, style: TextStyle) {
+        if let Some(text) = self.texts.get_mut(&handle) {
+            if text.markup != markup || text.style != style {
+                text.markup = markup.to_string();
+                text.style = style;
+                text.built = None;
+            }
+        }
+    }
+    pub fn remove(&mut self, handle: TextHandle) {
+        self.texts.remove(&handle);
+    }
+    // Everything is built again, as when the font changes.
+    pub fn invalidate_all(&mut self) {
+        for text in self.texts.values_mut() {
+            text.built = None;
+        }
+    }
+    // Builds the texts created or changed since the last call.
+    pub fn prepare<F>(&mut self, mut build: F)
+        where F: FnMut(&str, &TextStyle) -> T
+    {
+        for text in self.texts.values_mut().filter(|text| text.built.is_none()) {
+            text.built = Some(build(&text.markup, &text.style));
+        }
+    }
+    // None for a removed handle or one not prepared yet.
+    pub fn get(&self, handle: TextHandle) -> Option<&T> {
+        self.texts.get(&handle).and_then(|text| text.built.as_ref())
+    }
+}
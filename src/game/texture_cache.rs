@@ -0,0 +1,60 @@
+use gfx;
+use fnv::FnvHashMap as HashMap;
+
+struct CachedTexture<R: gfx::Resources> {
+    view: gfx::handle::ShaderResourceView<R, [f32; 4]>,
+    refs: usize,
+    // created again on the next acquire, as after its rows changed
+    stale: bool,
+}
+
+// Textures shared between GameObjects, keyed by TextureId.
+// The GPU texture is released once the last user calls release and the queue is cleaned up.
+pub struct TextureCache<R: gfx::Resources> {
+    entries: HashMap<i32, CachedTexture<R>>,
+}
+
+impl<R: gfx::Resources> TextureCache<R> {
+    pub fn new() -> Self {
+        TextureCache {
+            entries: HashMap::default(),
+        }
+    }
+    // Returns the cached view, creating it on first use.
+    pub fn acquire<F, E>(&mut self, texture_id: i32, create: F) -> Result<gfx::handle::ShaderResourceView<R, [f32; 4]>, E>
+        where F: FnOnce() -> Result<gfx::handle::ShaderResourceView<R, [f32; 4]>, E>
+    {
+        if let Some(cached) = self.entries.get_mut(&texture_id) {
+            if cached.stale {
+                cached.view = create()?;
+                cached.stale = false;
+            }
+            cached.refs += 1;
+            return Ok(cached.view.clone());
+        }
+        let view = create()?;
+        self.entries.insert(texture_id, CachedTexture { view: view.clone(), refs: 1, stale: false });
+        Ok(view)
+    }
+    // Users keep the view they have; the next acquire reads the texture again.
+    pub fn invalidate(&mut self, texture_id: i32) {
+        if let Some(cached) = self.entries.get_mut(&texture_id) {
+            cached.stale = true;
+        }
+    }
+    pub fn release(&mut self, texture_id: i32) {
+        let unused = match self.entries.get_mut(&texture_id) {
+            Some(cached) => {
+                cached.refs -= 1;
+                cached.refs == 0
+            },
+            None => false,
+        };
+        if unused {
+            self.entries.remove(&texture_id);
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
@@ -0,0 +1,223 @@
+// Layouts the Texture.Format column may name. Everything is expanded to RGBA8 before upload:
+// gfx has no block-compressed surface types, so BCn data only saves space in the database.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextureDataFormat {
+    Rgba8,
+    Bgra8,
+    Gray8,
+    // DXT1, 4x4 blocks of 8 bytes, 1 bit alpha
+    Bc1,
+    // DXT5, 4x4 blocks of 16 bytes with interpolated alpha
+    Bc3,
+}
+
+#[derive(Debug)]
+pub enum TextureFormatError {
+    UnknownFormat(String),
+    SizeMismatch { format: TextureDataFormat, expected: usize, actual: usize },
+}
+
+impl TextureDataFormat {
+    // NULL or a missing column means the original raw RGBA8 layout.
+    pub fn from_name(name: Option<&str>) -> Result<Self, TextureFormatError> {
+        match name.map(|n| n.to_uppercase()) {
+            None => Ok(TextureDataFormat::Rgba8),
+            Some(name) => match name.as_str() {
+                "RGBA8" => Ok(TextureDataFormat::Rgba8),
+                "BGRA8" => Ok(TextureDataFormat::Bgra8),
+                "GRAY8" | "R8" => Ok(TextureDataFormat::Gray8),
+                "BC1" | "DXT1" => Ok(TextureDataFormat::Bc1),
+                "BC3" | "DXT5" => Ok(TextureDataFormat::Bc3),
+                _ => Err(TextureFormatError::UnknownFormat(name)),
+            },
+        }
+    }
+    fn data_len(&self, width: usize, height: usize) -> usize {
+        let blocks = ((width + 3) / 4) * ((height + 3) / 4);
+        match *self {
+            TextureDataFormat::Rgba8 | TextureDataFormat::Bgra8 => width * height * 4,
+            TextureDataFormat::Gray8 => width * height,
+            TextureDataFormat::Bc1 => blocks * 8,
+            TextureDataFormat::Bc3 => blocks * 16,
+        }
+    }
+}
+
+pub fn decode_texture(format: TextureDataFormat, width: u16, height: u16, data: Vec<u8>) -> Result<Vec<u8>, TextureFormatError> {
+    let (w, h) = (width as usize, height as usize);
+    let expected = format.data_len(w, h);
+    if data.len() != expected {
+        return Err(TextureFormatError::SizeMismatch { format, expected, actual: data.len() });
+    }
+    Ok(match format {
+        TextureDataFormat::Rgba8 => data,
+        TextureDataFormat::Bgra8 => {
+            let mut data = data;
+            for texel in data.chunks_mut(4) {
+                texel.swap(0, 2);
+            }
+            data
+        },
+        TextureDataFormat::Gray8 => data.iter().flat_map(|&v| vec!(v, v, v, 255)).collect(),
+        TextureDataFormat::Bc1 => decode_blocks(&data, w, h, 8, |block, texels| decode_color(block, true, texels)),
+        TextureDataFormat::Bc3 => decode_blocks(&data, w, h, 16, |block, texels| {
+            decode_color(&block[8..], false, texels);
+            decode_alpha(&block[..8], texels);
+        }),
+    })
+}
+
+// Runs decode on each block and copies the 16 texels it produces into place,
+// dropping the ones past the right or bottom edge.
+fn decode_blocks<F>(data: &[u8], width: usize, height: usize, block_size: usize, decode: F) -> Vec<u8>
+    where F: Fn(&[u8], &mut [[u8; 4]; 16])
+{
+    let mut out = vec!(0u8; width * height * 4);
+    let blocks_x = (width + 3) / 4;
+    for (i, block) in data.chunks(block_size).enumerate() {
+        let mut texels = [[0u8; 4]; 16];
+        decode(block, &mut texels);
+        let (bx, by) = ((i % blocks_x) * 4, (i / blocks_x) * 4);
+        for (j, texel) in texels.iter().enumerate() {
+            let (x, y) = (bx + j % 4, by + j / 4);
+            if x < width && y < height {
+                let at = (y * width + x) * 4;
+                out[at..at + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    out
+}
+
+fn rgb565(v: u16) -> [u32; 3] {
+    let (r, g, b) = ((v >> 11) as u32 & 0x1f, (v >> 5) as u32 & 0x3f, v as u32 & 0x1f);
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+// The 8 byte color half shared by BC1 and BC3. BC3 always uses the four color mode.
+fn decode_color(block: &[u8], allow_alpha: bool, texels: &mut [[u8; 4]; 16]) {
+    let c0 = block[0] as u16 | (block[1] as u16) << 8;
+    let c1 = block[2] as u16 | (block[3] as u16) << 8;
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u32, wb: u32| {
+        let total = wa + wb;
+        [
+            ((a[0] * wa + b[0] * wb) / total) as u8,
+            ((a[1] * wa + b[1] * wb) / total) as u8,
+            ((a[2] * wa + b[2] * wb) / total) as u8,
+            255,
+        ]
+    };
+    let palette = if c0 > c1 || !allow_alpha {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0, 0, 0, 0]]
+    };
+    let bits = block[4] as u32 | (block[5] as u32) << 8 | (block[6] as u32) << 16 | (block[7] as u32) << 24;
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((bits >> (i * 2)) & 0x3) as usize];
+    }
+}
+
+fn decode_alpha(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i) as u32 * a0 + i as u32 * a1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i) as u32 * a0 + i as u32 * a1) / 5;
+        }
+    }
+    let mut bits = 0u64;
+    for i in 0..6 {
+        bits |= (block[2 + i] as u64) << (i * 8);
+    }
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = palette[((bits >> (i * 3)) & 0x7) as usize] as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: u16 = 0xf800;
+    const BLUE: u16 = 0x001f;
+
+    // A BC1 block of two endpoint colors, with the 2 bit indices of its texels in order.
+    fn bc1_block(c0: u16, c1: u16, indices: &[u32]) -> Vec<u8> {
+        let bits = indices.iter().enumerate().fold(0u32, |bits, (i, &index)| bits | index << (i * 2));
+        vec!(c0 as u8, (c0 >> 8) as u8, c1 as u8, (c1 >> 8) as u8,
+             bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8)
+    }
+
+    fn texel(data: &[u8], width: usize, x: usize, y: usize) -> &[u8] {
+        let at = (y * width + x) * 4;
+        &data[at..at + 4]
+    }
+
+    #[test]
+    fn bc1_four_color_block_interpolates_thirds() {
+        let data = decode_texture(TextureDataFormat::Bc1, 4, 4, bc1_block(RED, BLUE, &[0, 1, 2, 3])).unwrap();
+        assert_eq!(texel(&data, 4, 0, 0), &[255, 0, 0, 255]);
+        assert_eq!(texel(&data, 4, 1, 0), &[0, 0, 255, 255]);
+        assert_eq!(texel(&data, 4, 2, 0), &[170, 0, 85, 255]);
+        assert_eq!(texel(&data, 4, 3, 0), &[85, 0, 170, 255]);
+        // indices left at 0 are the first endpoint
+        assert_eq!(texel(&data, 4, 3, 3), &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc1_three_color_block_has_transparent_black() {
+        let data = decode_texture(TextureDataFormat::Bc1, 4, 4, bc1_block(BLUE, RED, &[0, 1, 2, 3])).unwrap();
+        assert_eq!(texel(&data, 4, 0, 0), &[0, 0, 255, 255]);
+        assert_eq!(texel(&data, 4, 1, 0), &[255, 0, 0, 255]);
+        assert_eq!(texel(&data, 4, 2, 0), &[127, 0, 127, 255]);
+        assert_eq!(texel(&data, 4, 3, 0), &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc3_interpolates_alpha_over_opaque_colors() {
+        // alpha endpoints 255 and 0, with indices 0, 1, 2 and 7 on the first row
+        let bits: u64 = 0 | 1 << 3 | 2 << 6 | 7 << 9;
+        let mut block = vec!(255, 0);
+        for i in 0..6 {
+            block.push((bits >> (i * 8)) as u8);
+        }
+        // equal endpoints still use the four color mode, so nothing turns transparent
+        block.extend(bc1_block(0xffff, 0xffff, &[3]));
+        let data = decode_texture(TextureDataFormat::Bc3, 4, 4, block).unwrap();
+        assert_eq!(texel(&data, 4, 0, 0), &[255, 255, 255, 255]);
+        assert_eq!(texel(&data, 4, 1, 0), &[255, 255, 255, 0]);
+        assert_eq!(texel(&data, 4, 2, 0), &[255, 255, 255, 218]);
+        assert_eq!(texel(&data, 4, 3, 0), &[255, 255, 255, 36]);
+    }
+
+    #[test]
+    fn partial_blocks_are_cropped_at_the_edges() {
+        // 5x3 takes two blocks across and one down
+        let mut data = bc1_block(RED, 0, &[]);
+        data.extend(bc1_block(BLUE, 0, &[]));
+        let data = decode_texture(TextureDataFormat::Bc1, 5, 3, data).unwrap();
+        assert_eq!(data.len(), 5 * 3 * 4);
+        assert_eq!(texel(&data, 5, 3, 0), &[255, 0, 0, 255]);
+        assert_eq!(texel(&data, 5, 4, 0), &[0, 0, 255, 255]);
+        assert_eq!(texel(&data, 5, 3, 2), &[255, 0, 0, 255]);
+        assert_eq!(texel(&data, 5, 4, 2), &[0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn block_data_is_sized_by_whole_blocks() {
+        match decode_texture(TextureDataFormat::Bc1, 5, 3, vec!(0; 8)) {
+            Err(TextureFormatError::SizeMismatch { expected: 16, actual: 8, .. }) => {},
+            other => panic!("{:?}", other),
+        }
+        match decode_texture(TextureDataFormat::Bc3, 1, 1, vec!(0; 16)) {
+            Ok(data) => assert_eq!(data.len(), 4),
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use fnv::FnvHashMap as HashMap;
+
+// side of a thumbnail's texture in pixels
+pub const THUMBNAIL_SIZE: u16 = 128;
+// thumbnails not shown for this many frames are dropped, freeing their targets
+const UNUSED_FRAMES: u32 = 120;
+
+struct Entry<T> {
+    // None when the object could not be made into one, so it is not tried every frame
+    built: Option<T>,
+    // the animation frame last rendered; None until rendered, and again once invalidated
+    frame: Option<usize>,
+    unused: u32,
+}
+
+// Objects rendered on their own into small textures for the UI, keyed by ObjectId.
+// Each is rendered again only when its animation frame moves on or it is invalidated.
+// T is what one needs, the object and its target; the caller makes and renders it,
+// since it holds the device.
+pub struct Thumbnails<T> {
+    entries: HashMap<i32, Entry<T>>,
+}
+
+impl<T> Thumbnails<T> {
+    pub fn new() -> Self {
+        Thumbnails {
+            entries: HashMap::default(),
+        }
+    }
+    pub fn contains(&self, object_id: i32) -> bool {
+        self.entries.contains_key(&object_id)
+    }
+    pub fn insert(&mut self, object_id: i32, built: Option<T>) {
+        self.entries.insert(object_id, Entry { built, frame: None, unused: 0 });
+    }
+    // The thumbnail whether rendered or not, to tell which frame it should show.
+    pub fn peek(&self, object_id: i32) -> Option<&T> {
+        self.entries.get(&object_id).and_then(|entry| entry.built.as_ref())
+    }
+    // Marks the thumbnail shown, and hands it out when frame is not what it holds.
+    // The caller renders it then; it counts as holding frame from here on.
+    pub fn stale_mut(&mut self, object_id: i32, frame: usize) -> Option<&mut T> {
+        let entry = self.entries.get_mut(&object_id)?;
+        entry.unused = 0;
+        if entry.frame == Some(frame) {
+            return None;
+        }
+        entry.frame = Some(frame);
+        entry.built.as_mut()
+    }
+    // Made again the next time it is shown, as after the object's rows changed;
+    // returns the old one so the caller can give back what it holds.
+    pub fn remove(&mut self, object_id: i32) -> Option<T> {
+        self.entries.remove(&object_id).and_then(|entry| entry.built)
+    }
+    // None for a thumbnail not rendered yet.
+    pub fn get(&self, object_id: i32) -> Option<&T> {
+        match self.entries.get(&object_id) {
+            Some(entry) if entry.frame.is_some() => entry.built.as_ref(),
+            _ => None,
+        }
+    }
+    // Call once a frame after the shown thumbnails were marked; returns the ones
+    // dropped, so the caller can give back what they hold.
+    pub fn collect_unused(&mut self) -> Vec<T> {
+        for entry in self.entries.values_mut() {
+            entry.unused += 1;
+        }
+        let unused: Vec<i32> = self.entries.iter()
+            .filter(|&(_, entry)| entry.unused > UNUSED_FRAMES)
+            .map(|(&object_id, _)| object_id)
+            .collect();
+        unused.into_iter()
+            .filter_map(|object_id| self.entries.remove(&object_id).and_then(|entry| entry.built))
+            .collect()
+    }
+}
@@ -0,0 +1,113 @@
+use std;
+use cgmath::{InnerSpace, Vector3};
+
+use color::Color;
+
+// Light and colors for one moment of the day.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Lighting {
+    // direction the light travels, as the shaders expect
+    pub light: [f32; 3],
+    pub ambient: Color,
+    // clear color until there is a skybox to tint
+    pub sky: Color,
+}
+
+const NIGHT_AMBIENT: Color = Color { r: 0.0, g: 0.0, b: 0.02, a: 1.0 };
+const DAY_AMBIENT: Color = Color { r: 0.01, g: 0.01, b: 0.01, a: 1.0 };
+const NIGHT_SKY: Color = Color { r: 0.01, g: 0.01, b: 0.05, a: 1.0 };
+const DUSK_SKY: Color = Color { r: 0.6, g: 0.3, b: 0.2, a: 1.0 };
+const DAY_SKY: Color = Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 };
+
+pub const HOURS_PER_DAY: f32 = 24.0;
+
+// Clock for the day/night cycle, read from the world's elapsed seconds.
+pub struct TimeOfDay {
+    // real seconds for one full day
+    cycle_length: f64,
+    // hour at elapsed time zero
+    offset: f32,
+    frozen: Option<f32>,
+}
+
+impl TimeOfDay {
+    pub fn new(cycle_length: f64, start_hour: f32) -> Self {
+        TimeOfDay {
+            cycle_length: cycle_length.max(1.0),
+            offset: start_hour,
+            frozen: None,
+        }
+    }
+    pub fn hour(&self, elapsed: f64) -> f32 {
+        match self.frozen {
+            Some(hour) => hour,
+            None => {
+                let days = elapsed / self.cycle_length;
+                wrap_hour(self.offset + (days.fract() * HOURS_PER_DAY as f64) as f32)
+            },
+        }
+    }
+    pub fn set_hour(&mut self, hour: f32, elapsed: f64) {
+        let hour = wrap_hour(hour);
+        match self.frozen {
+            Some(_) => self.frozen = Some(hour),
+            None => self.offset += hour - self.hour(elapsed),
+        }
+    }
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.is_some()
+    }
+    pub fn freeze(&mut self, elapsed: f64) {
+        if self.frozen.is_none() {
+            self.frozen = Some(self.hour(elapsed));
+        }
+    }
+    // Picks up from the frozen hour rather than jumping ahead.
+    pub fn resume(&mut self, elapsed: f64) {
+        if let Some(hour) = self.frozen.take() {
+            self.set_hour(hour, elapsed);
+        }
+    }
+    pub fn cycle_length(&self) -> f64 {
+        self.cycle_length
+    }
+    pub fn set_cycle_length(&mut self, seconds: f64, elapsed: f64) {
+        let hour = self.hour(elapsed);
+        self.cycle_length = seconds.max(1.0);
+        if self.frozen.is_none() {
+            self.set_hour(hour, elapsed);
+        }
+    }
+    pub fn lighting(&self, elapsed: f64) -> Lighting {
+        lighting_at(self.hour(elapsed))
+    }
+}
+
+fn wrap_hour(hour: f32) -> f32 {
+    let h = hour % HOURS_PER_DAY;
+    if h < 0.0 { h + HOURS_PER_DAY } else { h }
+}
+
+// The sun rises in +x at 6, is overhead at 12 and sets at 18;
+// the moon takes the opposite side of the sky at night.
+pub fn lighting_at(hour: f32) -> Lighting {
+    let angle = (hour - 6.0) / 12.0 * std::f32::consts::PI;
+    let sun = Vector3::new(angle.cos(), 0.3, angle.sin()).normalize();
+    let elevation = sun.z;
+    let daylight = smoothstep(-0.1, 0.2, elevation);
+    // strongest while the sun is close to the horizon
+    let dusk = 1.0 - smoothstep(0.0, 0.35, elevation.abs());
+
+    let source = if elevation >= 0.0 { sun } else { -sun };
+    let sky = NIGHT_SKY.lerp(DAY_SKY, daylight).lerp(DUSK_SKY, dusk * 0.6);
+    Lighting {
+        light: (-source).into(),
+        ambient: NIGHT_AMBIENT.lerp(DAY_AMBIENT, daylight),
+        sky,
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,84 @@
+use std;
+use std::time::{Duration, Instant};
+
+// sleep overshoots by up to a scheduler tick, so the end of the budget is spun out
+const SPIN_MARGIN: f64 = 0.002;
+
+// Sleeps away the rest of the frame budget when a cap is set.
+pub struct FrameLimiter {
+    pub fps_cap: Option<f32>,
+    last_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(fps_cap: Option<f32>) -> Self {
+        FrameLimiter {
+            fps_cap,
+            last_frame: Instant::now(),
+        }
+    }
+    pub fn wait(&mut self) {
+        self.wait_for(self.fps_cap);
+    }
+    pub fn wait_for(&mut self, fps_cap: Option<f32>) {
+        if let Some(fps) = fps_cap.and_then(|fps| if fps > 0.0 { Some(fps) } else { None }) {
+            let budget = duration_from_secs(1.0 / fps as f64);
+            let deadline = self.last_frame + budget;
+            let margin = duration_from_secs(SPIN_MARGIN);
+            let spent = self.last_frame.elapsed();
+            if spent + margin < budget {
+                std::thread::sleep(budget - spent - margin);
+            }
+            while Instant::now() < deadline {
+                std::thread::yield_now();
+            }
+            // a late frame starts the next budget from now rather than catching up
+            let now = Instant::now();
+            self.last_frame = if now - deadline < budget { deadline } else { now };
+            return;
+        }
+        self.last_frame = Instant::now();
+    }
+}
+
+pub fn duration_from_secs(secs: f64) -> Duration {
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PowerMode {
+    // poll events and render every frame
+    Continuous,
+    // block on the events loop while the app is idle
+    Wait,
+}
+
+// Turns variable frame times into a whole number of fixed steps.
+pub struct FixedStep {
+    pub step: f64,
+    accumulated: f64,
+    last: Instant,
+}
+
+// steps dropped after a long stall instead of being caught up
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
+impl FixedStep {
+    pub fn new(step: f64) -> Self {
+        FixedStep {
+            step,
+            accumulated: 0.0,
+            last: Instant::now(),
+        }
+    }
+    // Steps due since the last call.
+    pub fn advance(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+        self.accumulated += elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+        let steps = (self.accumulated / self.step).floor();
+        self.accumulated -= steps * self.step;
+        (steps as u32).min(MAX_STEPS_PER_FRAME)
+    }
+}
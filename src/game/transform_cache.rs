@@ -0,0 +1,41 @@
+use std::cell::Cell;
+use cgmath::{EuclideanSpace, Matrix4, Point3};
+
+// viewports drawn per frame at most; see ViewportLayout::viewports
+const SLOTS: usize = 2;
+
+#[derive(Copy, Clone)]
+struct Cached {
+    snapshot: u64,
+    position: Point3<f32>,
+    scale: f32,
+    model_view: Matrix4<f32>,
+    model_view_proj: Matrix4<f32>,
+}
+
+// Model-view and model-view-projection of one object, kept per camera snapshot.
+// They are only rebuilt when the object moved or the snapshot changed.
+pub struct TransformCache {
+    slots: [Cell<Option<Cached>>; SLOTS],
+}
+
+impl TransformCache {
+    pub fn new() -> Self {
+        TransformCache {
+            slots: [Cell::new(None), Cell::new(None)],
+        }
+    }
+    // snapshot identifies the camera's view and perspective; see World::refresh_view_cameras.
+    pub fn get(&self, snapshot: u64, position: Point3<f32>, scale: f32, view: &Matrix4<f32>, perspective: &Matrix4<f32>) -> (Matrix4<f32>, Matrix4<f32>) {
+        let slot = &self.slots[snapshot as usize % SLOTS];
+        if let Some(cached) = slot.get() {
+            if cached.snapshot == snapshot && cached.position == position && cached.scale == scale {
+                return (cached.model_view, cached.model_view_proj);
+            }
+        }
+        let model_view = *view * Matrix4::from_translation(position.to_vec()) * Matrix4::from_scale(scale);
+        let model_view_proj = *perspective * model_view;
+        slot.set(Some(Cached { snapshot, position, scale, model_view, model_view_proj }));
+        (model_view, model_view_proj)
+    }
+}
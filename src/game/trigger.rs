@@ -0,0 +1,87 @@
+use cgmath::{InnerSpace, Point3};
+
+use debug_draw::Aabb;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TriggerShape {
+    Sphere { center: Point3<f32>, radius: f32 },
+    Box(Aabb),
+}
+
+impl TriggerShape {
+    pub fn contains(&self, p: Point3<f32>) -> bool {
+        match *self {
+            TriggerShape::Sphere { center, radius } => (p - center).magnitude2() <= radius * radius,
+            TriggerShape::Box(ref aabb) => {
+                aabb.min.x <= p.x && p.x <= aabb.max.x
+                    && aabb.min.y <= p.y && p.y <= aabb.max.y
+                    && aabb.min.z <= p.z && p.z <= aabb.max.z
+            },
+        }
+    }
+}
+
+// A volume placed in a scene from the Trigger table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub id: i32,
+    pub shape: TriggerShape,
+    // markup shown while the avator is inside
+    pub prompt: Option<String>,
+    // where the interact key leads from inside
+    pub scene: Option<i32>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Crossing {
+    Enter(i32),
+    Exit(i32),
+}
+
+// The current scene's triggers and which of them the avator stands in.
+pub struct Triggers {
+    pub triggers: Vec<Trigger>,
+    // trigger ids in the order they were entered
+    inside: Vec<i32>,
+}
+
+impl Triggers {
+    pub fn new(triggers: Vec<Trigger>) -> Self {
+        Triggers {
+            triggers,
+            inside: Vec::new(),
+        }
+    }
+    // Exits for what the avator was inside, so the old scene's prompts close.
+    pub fn replace(&mut self, triggers: Vec<Trigger>) -> Vec<Crossing> {
+        let exits = self.inside.drain(..).map(Crossing::Exit).collect();
+        self.triggers = triggers;
+        exits
+    }
+    // Crossings since the last position, exits first. None, as with no avator, is outside everything.
+    pub fn update(&mut self, position: Option<Point3<f32>>) -> Vec<Crossing> {
+        let now: Vec<i32> = match position {
+            Some(p) => self.triggers.iter().filter(|t| t.shape.contains(p)).map(|t| t.id).collect(),
+            None => Vec::new(),
+        };
+        let mut crossings: Vec<Crossing> = self.inside.iter()
+            .filter(|id| !now.contains(id))
+            .map(|&id| Crossing::Exit(id))
+            .collect();
+        self.inside.retain(|id| now.contains(id));
+        for id in now {
+            if !self.inside.contains(&id) {
+                self.inside.push(id);
+                crossings.push(Crossing::Enter(id));
+            }
+        }
+        crossings
+    }
+    pub fn get(&self, id: i32) -> Option<&Trigger> {
+        self.triggers.iter().find(|t| t.id == id)
+    }
+    // The trigger entered last among those the avator is still inside.
+    pub fn innermost(&self) -> Option<&Trigger> {
+        self.inside.last().and_then(|&id| self.get(id))
+    }
+}
@@ -0,0 +1,117 @@
+use std;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    // overshoots and settles, landing exactly on 1
+    Spring,
+}
+
+impl Easing {
+    // Maps linear progress in 0..1 to eased progress; 0 and 1 map to themselves.
+    pub fn apply(&self, t: f32) -> f32 {
+        if t <= 0.0 {
+            return 0.0;
+        }
+        if t >= 1.0 {
+            return 1.0;
+        }
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Spring => 1.0 - (-6.0 * t).exp() * (3.0 * std::f32::consts::PI * t).cos(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TweenHandle(u32);
+
+struct Tween<T> {
+    handle: TweenHandle,
+    payload: T,
+    duration: f32,
+    elapsed: f32,
+    easing: Easing,
+}
+
+impl<T> Tween<T> {
+    fn value(&self) -> f32 {
+        let t = if self.duration > 0.0 { self.elapsed / self.duration } else { 1.0 };
+        self.easing.apply(t)
+    }
+}
+
+// What one tween did during a step.
+pub struct TweenStep<T> {
+    pub handle: TweenHandle,
+    pub payload: T,
+    // change of the eased value; the deltas of a tween sum to 1
+    pub delta: f32,
+    pub finished: bool,
+}
+
+// Running tweens and timers, advanced by whoever owns them with a fixed dt.
+// A timer is a tween whose owner only acts on the finished step.
+pub struct Tweens<T> {
+    next_handle: u32,
+    active: Vec<Tween<T>>,
+}
+
+impl<T: Clone> Tweens<T> {
+    pub fn new() -> Self {
+        Tweens {
+            next_handle: 0,
+            active: Vec::new(),
+        }
+    }
+    pub fn start(&mut self, payload: T, duration: f32, easing: Easing) -> TweenHandle {
+        let handle = TweenHandle(self.next_handle);
+        self.next_handle = self.next_handle.wrapping_add(1);
+        self.active.push(Tween {
+            handle,
+            payload,
+            duration,
+            elapsed: 0.0,
+            easing,
+        });
+        handle
+    }
+    pub fn after(&mut self, delay: f32, payload: T) -> TweenHandle {
+        self.start(payload, delay, Easing::Linear)
+    }
+    // Stops the tween where it is. Returns false when it already finished.
+    pub fn cancel(&mut self, handle: TweenHandle) -> bool {
+        let before = self.active.len();
+        self.active.retain(|tween| tween.handle != handle);
+        self.active.len() != before
+    }
+    pub fn is_active(&self, handle: TweenHandle) -> bool {
+        self.active.iter().any(|tween| tween.handle == handle)
+    }
+    // Current eased value, for things that read a tween rather than react to its steps.
+    pub fn value(&self, handle: TweenHandle) -> Option<f32> {
+        self.active.iter().find(|tween| tween.handle == handle).map(|tween| tween.value())
+    }
+    pub fn step(&mut self, dt: f32) -> Vec<TweenStep<T>> {
+        let mut steps = Vec::with_capacity(self.active.len());
+        for tween in &mut self.active {
+            // a zero length tween still reports its whole delta once
+            let before = if tween.elapsed > 0.0 { tween.value() } else { 0.0 };
+            tween.elapsed = (tween.elapsed + dt).min(tween.duration);
+            steps.push(TweenStep {
+                handle: tween.handle,
+                payload: tween.payload.clone(),
+                delta: tween.value() - before,
+                finished: tween.elapsed >= tween.duration,
+            });
+        }
+        self.active.retain(|tween| tween.elapsed < tween.duration);
+        steps
+    }
+}
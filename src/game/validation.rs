@@ -0,0 +1,136 @@
+use models::{Animation, Image, Joint};
+use Vertex;
+
+// parent index of root joints
+pub const ROOT_PARENT: i32 = 255;
+const WEIGHT_TOLERANCE: f32 = 0.01;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetProblem {
+    // joints are expected to be numbered 0..n without gaps
+    JointIndexGap { position: usize, joint_index: i32 },
+    MissingParent { joint_index: i32, parent: i32 },
+    JointCycle { joint_index: i32 },
+    JointIndexOutOfRange { mesh: usize, vertex: usize, joint_index: i32 },
+    WeightsNotNormalized { mesh: usize, vertex: usize, sum: f32 },
+    VertexIndexOutOfRange { mesh: usize, index: u32, vertices: usize },
+    AnimatedJointMissing { joint_index: usize },
+    TextureSizeMismatch { texture_id: i32, width: u16, height: u16, bytes: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetValidationError {
+    pub object_id: i32,
+    pub problems: Vec<AssetProblem>,
+}
+
+// The first index past the end of a mesh's vertices, if any.
+pub fn vertex_index_problem(mesh: usize, indices: &[u32], vertices: usize) -> Option<AssetProblem> {
+    indices.iter()
+        .find(|&&index| index as usize >= vertices)
+        .map(|&index| AssetProblem::VertexIndexOutOfRange { mesh, index, vertices })
+}
+
+// Checks everything get_skinning and the renderer rely on, collecting every problem found.
+pub fn validate_object(
+    object_id: i32,
+    joints: &[Joint],
+    meshes: &[(Vec<Vertex>, Vec<u32>, i32)],
+    tracks: &[Vec<(f32, Animation)>],
+) -> Result<(), AssetValidationError> {
+    let mut problems = Vec::new();
+
+    for (position, joint) in joints.iter().enumerate() {
+        if joint.joint_index != position as i32 {
+            problems.push(AssetProblem::JointIndexGap { position, joint_index: joint.joint_index });
+        }
+        if joint.parent == ROOT_PARENT {
+            continue;
+        }
+        if joint.parent < 0 || joint.parent as usize >= joints.len() {
+            problems.push(AssetProblem::MissingParent { joint_index: joint.joint_index, parent: joint.parent });
+        }
+    }
+    if problems.is_empty() {
+        if let Err(joint_index) = joint_order(joints) {
+            problems.push(AssetProblem::JointCycle { joint_index });
+        }
+    }
+
+    // objects without a skeleton are skinned with a single identity matrix
+    let palette = joints.len().max(1);
+    for (mesh, &(ref vertices, ref indices, _)) in meshes.iter().enumerate() {
+        for (vertex, v) in vertices.iter().enumerate() {
+            let mut sum = 0.0;
+            for (&joint_index, &weight) in v.joint_indices.iter().zip(v.joint_weights.iter()) {
+                if weight > 0.0 && (joint_index < 0 || joint_index as usize >= palette) {
+                    problems.push(AssetProblem::JointIndexOutOfRange { mesh, vertex, joint_index });
+                }
+                sum += weight;
+            }
+            if (sum - 1.0).abs() > WEIGHT_TOLERANCE {
+                problems.push(AssetProblem::WeightsNotNormalized { mesh, vertex, sum });
+            }
+        }
+        problems.extend(vertex_index_problem(mesh, indices, vertices.len()));
+    }
+
+    for (joint_index, track) in tracks.iter().enumerate() {
+        if !track.is_empty() && joint_index >= joints.len() {
+            problems.push(AssetProblem::AnimatedJointMissing { joint_index });
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(AssetValidationError { object_id, problems })
+    }
+}
+
+// RGBA8 data must cover the whole image.
+pub fn validate_texture<T>(object_id: i32, texture_id: i32, img: &Image<T>) -> Result<(), AssetValidationError> {
+    let expected = img.width as usize * img.height as usize * 4;
+    if img.data.len() == expected && expected > 0 {
+        return Ok(());
+    }
+    Err(AssetValidationError {
+        object_id,
+        problems: vec!(AssetProblem::TextureSizeMismatch {
+            texture_id,
+            width: img.width,
+            height: img.height,
+            bytes: img.data.len(),
+        }),
+    })
+}
+
+// Positions in `joints` ordered so that every parent comes before its children,
+// whatever order the importer stored them in. Expects parents to be in range;
+// fails with a joint on a cycle.
+pub fn joint_order(joints: &[Joint]) -> Result<Vec<usize>, i32> {
+    let mut children = vec!(Vec::new(); joints.len());
+    let mut order = Vec::with_capacity(joints.len());
+    for (position, joint) in joints.iter().enumerate() {
+        if joint.parent == ROOT_PARENT {
+            order.push(position);
+        } else {
+            children[joint.parent as usize].push(position);
+        }
+    }
+    let mut next = 0;
+    while next < order.len() {
+        order.extend_from_slice(&children[order[next]]);
+        next += 1;
+    }
+    if order.len() < joints.len() {
+        // whatever was never reached hangs off a loop
+        let mut reached = vec!(false; joints.len());
+        for &position in &order {
+            reached[position] = true;
+        }
+        let position = reached.iter().position(|r| !r).unwrap_or(0);
+        return Err(joints[position].joint_index);
+    }
+    Ok(order)
+}
@@ -0,0 +1,111 @@
+use gfx;
+use cgmath::{Matrix4, Vector3};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ViewCamera {
+    Main,
+    Observer,
+}
+
+// A sub-rectangle of the render target, normalized to 0..1 from the bottom-left.
+// gfx has no per-draw viewport, so the remap is folded into the projection
+// and the scissor keeps fragments inside the rectangle.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    // NDC depth range; keeps an inset from being hidden behind the view it overlaps
+    pub depth: [f32; 2],
+}
+
+impl Viewport {
+    pub fn full() -> Self {
+        Viewport::new(0.0, 0.0, 1.0, 1.0)
+    }
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+            depth: [-1.0, 1.0],
+        }
+    }
+    pub fn with_depth(self, near: f32, far: f32) -> Self {
+        Viewport {
+            depth: [near, far],
+            ..self
+        }
+    }
+    pub fn aspect(&self, screen_width: u16, screen_height: u16) -> f32 {
+        (self.width * screen_width as f32) / (self.height * screen_height as f32).max(1.0)
+    }
+    // Maps the full NDC cube onto this viewport.
+    pub fn matrix(&self) -> Matrix4<f32> {
+        let depth_scale = (self.depth[1] - self.depth[0]) / 2.0;
+        let translation = Vector3::new(
+            2.0 * self.x + self.width - 1.0,
+            2.0 * self.y + self.height - 1.0,
+            self.depth[0] + depth_scale,
+        );
+        Matrix4::from_translation(translation) * Matrix4::from_nonuniform_scale(self.width, self.height, depth_scale)
+    }
+    // x and y as fractions of the screen from the bottom-left, like the viewport.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+    pub fn rect(&self, screen_width: u16, screen_height: u16) -> gfx::Rect {
+        let (w, h) = (screen_width as f32, screen_height as f32);
+        gfx::Rect {
+            x: (self.x * w) as u16,
+            y: (self.y * h) as u16,
+            w: (self.width * w) as u16,
+            h: (self.height * h) as u16,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ViewportLayout {
+    Single,
+    // main camera on the left, observer on the right
+    SplitVertical,
+    // observer in an inset at the top-right corner
+    PictureInPicture,
+}
+
+impl ViewportLayout {
+    pub fn next(&self) -> Self {
+        match *self {
+            ViewportLayout::Single => ViewportLayout::SplitVertical,
+            ViewportLayout::SplitVertical => ViewportLayout::PictureInPicture,
+            ViewportLayout::PictureInPicture => ViewportLayout::Single,
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "single" => Some(ViewportLayout::Single),
+            "split" => Some(ViewportLayout::SplitVertical),
+            "pip" => Some(ViewportLayout::PictureInPicture),
+            _ => None,
+        }
+    }
+    // In drawing order.
+    pub fn viewports(&self) -> Vec<(ViewCamera, Viewport)> {
+        match *self {
+            ViewportLayout::Single => vec!(
+                (ViewCamera::Main, Viewport::full()),
+            ),
+            ViewportLayout::SplitVertical => vec!(
+                (ViewCamera::Main, Viewport::new(0.0, 0.0, 0.5, 1.0)),
+                (ViewCamera::Observer, Viewport::new(0.5, 0.0, 0.5, 1.0)),
+            ),
+            ViewportLayout::PictureInPicture => vec!(
+                (ViewCamera::Main, Viewport::full().with_depth(-0.8, 1.0)),
+                (ViewCamera::Observer, Viewport::new(0.68, 0.68, 0.3, 0.3).with_depth(-1.0, -0.8)),
+            ),
+        }
+    }
+}
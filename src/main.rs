@@ -1,43 +1,303 @@
 extern crate glutin;
 extern crate parti_game as game;
+#[macro_use]
+extern crate log;
+
+fn import_obj(args: &[String]) -> bool {
+    let path = match parse_arg(args, "--import-obj", "a path", any_value) {
+        Some(path) => path,
+        None => return false,
+    };
+    let id = match arg_value(args, "--id").map(|id| id.parse::<i32>()) {
+        Some(Ok(id)) => id,
+        _ => usage_error("--import-obj requires --id <object id>"),
+    };
+    // as authored; the World's own convention by default, which leaves the file as is
+    let axes = parse_arg(args, "--axes", "z-up, y-up, z-up-left or y-up-left", game::Axes::from_name)
+        .unwrap_or_default();
+
+    match game::import_obj(&path, id, axes) {
+        Ok(_) => println!("imported {} as object {}", path, id),
+        Err(e) => eprintln!("failed to import {}: {:?}", path, e),
+    }
+    true
+}
+
+// A bad command line: says what was wrong, then how it is used.
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n", message);
+    eprint!("{}", USAGE);
+    std::process::exit(1);
+}
+
+fn arg_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).cloned()
+}
+
+// The value after flag, parsed; None when the flag is not given. A missing or bad value
+// is a usage error, expected saying what the flag takes.
+fn parse_arg<T, F>(args: &[String], flag: &str, expected: &str, parse: F) -> Option<T>
+    where F: FnOnce(&str) -> Option<T>
+{
+    if !args.iter().any(|a| a == flag) {
+        return None;
+    }
+    match arg_value(args, flag).and_then(|value| parse(&value)) {
+        Some(value) => Some(value),
+        None => usage_error(&format!("{} takes {}", flag, expected)),
+    }
+}
+
+// For parse_arg, when any value will do and only a missing one is wrong.
+fn any_value(value: &str) -> Option<String> {
+    Some(value.to_string())
+}
+
+fn positive(value: &str) -> Option<f32> {
+    match value.parse::<f32>() {
+        Ok(v) if v > 0.0 => Some(v),
+        _ => None,
+    }
+}
+
+// As in 1280x720.
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let mut parts = size.splitn(2, 'x');
+    let width = parts.next()?.parse::<u32>().ok()?;
+    let height = parts.next()?.parse::<u32>().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+// Console commands to run once the app is up, one per line; blank lines and # comments are skipped.
+fn read_replay(path: &str) -> Vec<String> {
+    match std::fs::read_to_string(path) {
+        Ok(script) => script.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect(),
+        Err(e) => {
+            error!("failed to read {}: {}", path, e);
+            Vec::new()
+        },
+    }
+}
+
+const USAGE: &'static str = "\
+usage: parti [options]
+
+  --help                        print this and exit
+  --import-obj <path> --id <object id> [--axes <axes>]
+                                import a Wavefront OBJ file into the database and exit
+  --axes z-up|y-up|z-up-left|y-up-left
+                                the convention the imported file was authored in
+  --db <path>                   the sqlite database to use (file.db, or $PARTI_DB)
+  --scene <scene id>            load this scene on startup
+  --replay <path>               run the console commands in a file on startup
+  --size <width>x<height>       window size (1024x768)
+  --fullscreen | --windowed     fullscreen on the primary monitor, or a window (the default)
+  --backend gl|vulkan           graphics backend (vulkan only with --list-gpus, for now)
+  --list-gpus                   list the adapters and exit
+  --bench                       time skinning, text layout and mesh loading and exit
+  --gpu <index or name>         the adapter to use
+  --quality low|medium|high     graphics preset (the one last used)
+  --no-mipmaps
+  --depth-prepass | --no-depth-prepass
+  --anisotropy <samples>
+  --vsync on|off|adaptive
+  --fps <frames per second>     frame rate cap
+  --near <distance> --far <distance>
+                                the cameras' clip planes (0.5 and 1000)
+  --reversed-z                  depth from 1 at the near plane to 0 at the far one
+  --wait                        sleep until input while idle
+  --listen <address> | --connect <address>
+                                play over the network
+  --log <level>[,<module>=<level>...]
+                                log filter (info, or $PARTI_LOG)
+  --log-file <path>             also write the log to a file
+";
 
 pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+    // before anything opens the database, the import included
+    if let Some(db) = parse_arg(&args, "--db", "a path", any_value) {
+        std::env::set_var(game::DATABASE_VAR, db);
+    }
+    if let Err(e) = game::check_database() {
+        eprintln!("cannot use the database: {:?}", e);
+        std::process::exit(1);
+    }
+    if import_obj(&args) {
+        return;
+    }
+    if args.iter().any(|a| a == "--bench") {
+        game::run_benchmarks();
+        return;
+    }
 
-    let width = 1024;
-    let height = 768;
+    let (width, height) = parse_arg(&args, "--size", "<width>x<height>", parse_size).unwrap_or((1024, 768));
+    let fullscreen = args.iter().any(|a| a == "--fullscreen") && !args.iter().any(|a| a == "--windowed");
+
+    let backend = match parse_arg(&args, "--backend", "gl or vulkan", any_value).map(|name| game::BackendKind::from_name(&name)) {
+        Some(Ok(backend)) => backend,
+        Some(Err(e)) => usage_error(&format!("--backend: {}", e)),
+        None => game::BackendKind::default(),
+    };
 
     let mut events_loop = glutin::EventsLoop::new();
 
-    let window = {
-        let wb = glutin::WindowBuilder::new()
-            .with_title("PARTI")
-            .with_dimensions(width, height);
-        let gl_builder = glutin::ContextBuilder::new().with_vsync(true);
+    if args.iter().any(|a| a == "--list-gpus") {
+        for adapter in game::list_adapters(backend, &events_loop) {
+            println!("{}", adapter);
+        }
+        return;
+    }
+    if let Err(e) = backend.check_drawable() {
+        usage_error(&format!("--backend: {}", e));
+    }
+    let gpu = parse_arg(&args, "--gpu", "an index or a name", any_value).map(|gpu| game::AdapterChoice::parse(&gpu));
 
-        glutin::GlWindow::new(wb, gl_builder, &events_loop).expect("new fa")
+    let wb = glutin::WindowBuilder::new()
+        .with_title("PARTI");
+    let (wb, width, height) = if fullscreen {
+        let monitor = events_loop.get_primary_monitor();
+        let (width, height) = monitor.get_dimensions();
+        (wb.with_fullscreen(monitor), width, height)
+    } else {
+        (wb.with_dimensions(width, height), width, height)
     };
 
-    let mut app = game::App::new(
-        window, width, height
-    );
+    // installed before the window so its warnings reach the log page too
+    let log_spec = parse_arg(&args, "--log", "<level>[,<module>=<level>...]", any_value)
+        .or_else(|| std::env::var("PARTI_LOG").ok())
+        .unwrap_or_else(|| "info".to_string());
+    let log_filters = match game::LogFilters::parse(&log_spec) {
+        Ok(filters) => filters,
+        Err(e) => usage_error(&format!("--log takes <level>[,<module>=<level>...]: {}", e)),
+    };
+    let log_file = parse_arg(&args, "--log-file", "a path", any_value);
+    let log = match game::init_logging(log_filters, log_file.as_ref().map(std::path::Path::new)) {
+        Ok(log) => Some(log),
+        Err(e) => {
+            eprintln!("failed to set up logging: {:?}", e);
+            None
+        },
+    };
+    let panic_actions = game::install_panic_hook(log.clone());
+
+    let mut settings = game::GraphicsSettings::default();
+    let quality = parse_arg(&args, "--quality", "low, medium or high", game::QualityPreset::from_name)
+        .or_else(game::saved_quality);
+    if let Some(quality) = quality {
+        settings = settings.with_quality(quality);
+    }
+    if args.iter().any(|a| a == "--no-mipmaps") {
+        settings.mipmaps = false;
+    }
+    if args.iter().any(|a| a == "--depth-prepass") {
+        settings.depth_prepass = true;
+    }
+    if args.iter().any(|a| a == "--no-depth-prepass") {
+        settings.depth_prepass = false;
+    }
+    if let Some(anisotropy) = parse_arg(&args, "--anisotropy", "a sample count", |n| n.parse::<u8>().ok()) {
+        settings.anisotropy = anisotropy;
+    }
+    if let Some(vsync) = parse_arg(&args, "--vsync", "on, off or adaptive", game::VsyncMode::from_name) {
+        settings.vsync = vsync;
+    }
+    settings.fps_cap = parse_arg(&args, "--fps", "frames per second", positive);
+    if let Some(near) = parse_arg(&args, "--near", "a distance", positive) {
+        settings.near = near;
+    }
+    if let Some(far) = parse_arg(&args, "--far", "a distance", positive) {
+        settings.far = far;
+    }
+    if settings.far <= settings.near {
+        usage_error("--near and --far need 0 < near < far");
+    }
+    if args.iter().any(|a| a == "--reversed-z") {
+        settings.reversed_z = true;
+    }
+    let scene = parse_arg(&args, "--scene", "a scene id", |id| id.parse::<i32>().ok());
+    let replay = parse_arg(&args, "--replay", "a path", any_value);
+    let role = match (parse_arg(&args, "--listen", "an address", any_value), parse_arg(&args, "--connect", "an address", any_value)) {
+        (Some(addr), _) => Some(game::NetRole::Listen(addr)),
+        (None, Some(addr)) => Some(game::NetRole::Connect(addr)),
+        (None, None) => None,
+    };
+
+    let mut app = match game::create_app(backend, wb, &events_loop, width, height, settings, gpu) {
+        Ok(app) => app,
+        Err(e) => {
+            error!("cannot start: {}", e);
+            std::process::exit(1);
+        },
+    };
+    if let Some(log) = log {
+        app.attach_log(log);
+    }
+    app.attach_panic_actions(&panic_actions);
+    if let Some(scene_id) = scene {
+        app.load_scene(scene_id);
+    }
+    if let Some(path) = replay {
+        for line in read_replay(&path) {
+            if let Err(e) = app.run_command(&line) {
+                error!("{}: {}", path, e);
+            }
+        }
+    }
+
+    let power_mode = if args.iter().any(|a| a == "--wait") {
+        game::PowerMode::Wait
+    } else {
+        game::PowerMode::Continuous
+    };
+    if let Some(role) = role {
+        if let Err(e) = app.open_network(&role) {
+            error!("failed to open network: {:?}", e);
+        }
+    }
 
     let mut running = true;
     while running {
-        events_loop.poll_events(|event| {
-            if let glutin::Event::WindowEvent { event, .. } = event {
-                match event {
-                    glutin::WindowEvent::Closed | 
-                    glutin::WindowEvent::KeyboardInput {
-                        input: glutin::KeyboardInput {
-                            state: glutin::ElementState::Pressed,
-                            virtual_keycode: Some(glutin::VirtualKeyCode::Escape), ..
-                        }, ..
-                    } => running = false,
-                    _ => app.handle_input(event) 
+        {
+            let wait = power_mode == game::PowerMode::Wait && app.is_idle();
+            let mut handle_event = |event: glutin::Event| {
+                if let glutin::Event::WindowEvent { event, .. } = event {
+                    match event {
+                        glutin::WindowEvent::Closed => running = false,
+                        glutin::WindowEvent::KeyboardInput {
+                            input: glutin::KeyboardInput {
+                                state: glutin::ElementState::Pressed,
+                                virtual_keycode: Some(glutin::VirtualKeyCode::Escape), ..
+                            }, ..
+                        } if !app.is_text_input_active() => running = false,
+                        _ => app.handle_input(event) 
+                    }
                 }
+            };
+            if wait {
+                // block until something happens, then redraw once
+                events_loop.run_forever(|event| {
+                    handle_event(event);
+                    glutin::ControlFlow::Break
+                });
+                events_loop.poll_events(|event| handle_event(event));
+            } else {
+                events_loop.poll_events(|event| handle_event(event));
             }
-        });
+        }
         app.render();
     }
+    app.shutdown();
 }
 